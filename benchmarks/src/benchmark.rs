@@ -10,28 +10,37 @@
 //!     - Create a type that implements `BenchmarkControl`,
 //!     - Add the type's name as a variant `Benchmark`.
 
-use std::collections::HashMap;
-use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
-use database_utils::{DatabaseConnection, DatabaseType, DatabaseURL};
+use database_utils::{DatabaseConnection, DatabaseConnectionPool, DatabaseType};
 use enum_dispatch::enum_dispatch;
 use hdrhistogram::Histogram;
 use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
 use crate::cache_hit_benchmark::CacheHitBenchmark;
 use crate::eviction_benchmark::EvictionBenchmark;
+use crate::eviction_throughput_benchmark::EvictionThroughputBenchmark;
 use crate::graph::ArgOverride;
+use crate::many_queries_benchmark::ManyQueriesBenchmark;
 use crate::migration_benchmark::MigrationBenchmark;
+use crate::parameter_sweep_benchmark::ParameterSweepBenchmark;
 use crate::query_benchmark::QueryBenchmark;
 use crate::read_write_benchmark::ReadWriteBenchmark;
+use crate::read_your_writes_benchmark::ReadYourWritesBenchmark;
+use crate::recovery_benchmark::RecoveryBenchmark;
 use crate::scale_connections::ScaleConnections;
 use crate::scale_views::ScaleViews;
 use crate::single_query_benchmark::SingleQueryBenchmark;
 use crate::template::Template;
 use crate::utils::generate::DataGenerator;
 use crate::utils::prometheus::{ForwardPrometheusMetrics, PrometheusEndpoint};
+use crate::utils::readyset_version;
+use crate::utils::tls::TlsParameters;
 use crate::workload_emulator::WorkloadEmulator;
 use crate::write_benchmark::WriteBenchmark;
 use crate::write_latency_benchmark::WriteLatencyBenchmark;
@@ -45,6 +54,7 @@ pub enum Benchmark {
     QueryBenchmark,
     WriteBenchmark,
     CacheHitBenchmark,
+    ManyQueriesBenchmark,
     ScaleViews,
     ScaleConnections,
     /// Measures time required to propagate table writes into Noria views
@@ -54,6 +64,16 @@ pub enum Benchmark {
     ReadWriteBenchmark,
     SingleQueryBenchmark,
     WorkloadEmulator,
+    /// Measures time for a write to become visible in a cached query which depends on it
+    ReadYourWritesBenchmark,
+    /// Measures how quickly keys can be evicted from a partial materialization, and how much
+    /// evictions slow down concurrent reads
+    EvictionThroughputBenchmark,
+    /// Measures how long it takes for a deployment to serve cached queries again after a restart
+    RecoveryBenchmark,
+    /// Measures how query latency changes as one of the query's parameters is swept across a
+    /// range of values
+    ParameterSweepBenchmark,
 }
 
 impl Benchmark {
@@ -63,6 +83,7 @@ impl Benchmark {
             Self::QueryBenchmark(_) => "query_benchmark",
             Self::WriteBenchmark(_) => "write_benchmark",
             Self::CacheHitBenchmark(_) => "cache_hit_benchmark",
+            Self::ManyQueriesBenchmark(_) => "many_queries_benchmark",
             Self::ScaleViews(_) => "scale_views",
             Self::ScaleConnections(_) => "scale_connections",
             Self::WriteLatencyBenchmark(_) => "write_latency",
@@ -71,6 +92,10 @@ impl Benchmark {
             Self::ReadWriteBenchmark(_) => "read_write_benchmark",
             Self::SingleQueryBenchmark(_) => "single_query_benchmark",
             Self::WorkloadEmulator(_) => "workload_emulator",
+            Self::ReadYourWritesBenchmark(_) => "read_your_writes",
+            Self::EvictionThroughputBenchmark(_) => "eviction_throughput",
+            Self::RecoveryBenchmark(_) => "recovery",
+            Self::ParameterSweepBenchmark(_) => "parameter_sweep",
         }
     }
 
@@ -81,6 +106,7 @@ impl Benchmark {
                 Benchmark::QueryBenchmark(x) => x.update_from(itr),
                 Benchmark::WriteBenchmark(x) => x.update_from(itr),
                 Benchmark::CacheHitBenchmark(x) => x.update_from(itr),
+                Benchmark::ManyQueriesBenchmark(x) => x.update_from(itr),
                 Benchmark::ScaleViews(x) => x.update_from(itr),
                 Benchmark::ScaleConnections(x) => x.update_from(itr),
                 Benchmark::WriteLatencyBenchmark(x) => x.update_from(itr),
@@ -89,6 +115,10 @@ impl Benchmark {
                 Benchmark::ReadWriteBenchmark(x) => x.update_from(itr),
                 Benchmark::SingleQueryBenchmark(x) => x.update_from(itr),
                 Benchmark::WorkloadEmulator(x) => x.update_from(itr),
+                Benchmark::ReadYourWritesBenchmark(x) => x.update_from(itr),
+                Benchmark::EvictionThroughputBenchmark(x) => x.update_from(itr),
+                Benchmark::RecoveryBenchmark(x) => x.update_from(itr),
+                Benchmark::ParameterSweepBenchmark(x) => x.update_from(itr),
             },
             ArgOverride::Json(json) => self.update_data_generator_from(json)?,
         }
@@ -126,24 +156,183 @@ pub struct DeploymentParameters {
 
     #[arg(long, default_value = "test")]
     pub database_name: String,
+
+    /// Number of shards the ReadySet deployment is configured with. Only used to label benchmark
+    /// results.
+    #[arg(long)]
+    #[serde(default)]
+    pub shards: Option<usize>,
+
+    /// Memory limit, in bytes, the ReadySet deployment is configured with. Only used to label
+    /// benchmark results.
+    #[arg(long)]
+    #[serde(default)]
+    pub memory_limit: Option<usize>,
+
+    /// Options for connecting to deployments which require TLS
+    #[command(flatten)]
+    #[serde(default)]
+    pub tls: TlsParameters,
+
+    /// How long, in seconds, to keep retrying a failed connection to the deployment before giving
+    /// up. Useful when the benchmark is launched alongside a deployment that may not be accepting
+    /// connections yet. Defaults to 0, which fails on the first error.
+    #[arg(
+        long,
+        env = "CONNECT_RETRY_TIMEOUT",
+        default_value = "0",
+        value_parser = crate::utils::seconds_as_str_to_duration
+    )]
+    #[serde(default)]
+    pub connect_retry_timeout: Duration,
+
+    /// Connection string for a second target deployment, to compare against the one at
+    /// `--target-conn-str` by running the identical workload against both. The two deployments
+    /// are expected to share the same upstream database (`--setup-conn-str`), and would typically
+    /// differ only in their configuration. Only supported by the cache hit benchmark, which
+    /// ignores it otherwise.
+    #[arg(long, env = "TARGET_B_CONN_STR")]
+    #[serde(default)]
+    pub target_b_conn_str: Option<String>,
+
+    /// A name for the configuration of the deployment at `--target-conn-str`, to label results
+    /// with when comparing it against `--target-b-conn-str`.
+    #[arg(long, default_value = "a")]
+    #[serde(default = "default_variant_a")]
+    pub variant_a: String,
+
+    /// A name for the configuration of the deployment at `--target-b-conn-str`, to label results
+    /// with.
+    #[arg(long, default_value = "b")]
+    #[serde(default = "default_variant_b")]
+    pub variant_b: String,
+}
+
+fn default_variant_a() -> String {
+    "a".to_owned()
+}
+
+fn default_variant_b() -> String {
+    "b".to_owned()
+}
+
+/// Delay before the first connection retry; doubled after every subsequent failure.
+const CONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Upper bound on the delay between two connection attempts.
+const CONNECT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Returns how long to wait after the `attempt`th (zero-indexed) failed connection attempt.
+fn connect_backoff(attempt: u32) -> Duration {
+    CONNECT_INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(CONNECT_MAX_BACKOFF)
 }
 
 impl DeploymentParameters {
     pub async fn connect_to_target(&self) -> Result<DatabaseConnection> {
-        Ok(DatabaseURL::from_str(&self.target_conn_str)?
-            .connect(None)
-            .await?)
+        Ok(self.connect_with_retry(&self.target_conn_str).await?.0)
     }
 
     pub async fn connect_to_setup(&self) -> Result<DatabaseConnection> {
-        Ok(DatabaseURL::from_str(&self.setup_conn_str)?
-            .connect(None)
-            .await?)
+        Ok(self.connect_with_retry(&self.setup_conn_str).await?.0)
+    }
+
+    /// Wait until the target database accepts connections, retrying for up to
+    /// `connect_retry_timeout`. Returns how long we waited.
+    pub async fn wait_for_target(&self) -> Result<Duration> {
+        let (_, waited) = self.connect_with_retry(&self.target_conn_str).await?;
+        Ok(waited)
+    }
+
+    /// Connect to `conn_str`, retrying with exponential backoff until `connect_retry_timeout` has
+    /// elapsed. Returns the connection along with the time spent waiting for it.
+    async fn connect_with_retry(&self, conn_str: &str) -> Result<(DatabaseConnection, Duration)> {
+        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            match self.tls.connect(conn_str).await {
+                Ok(conn) => {
+                    let waited = start.elapsed();
+                    if attempt > 0 {
+                        info!(?waited, attempts = attempt + 1, "Connected to deployment");
+                    }
+                    return Ok((conn, waited));
+                }
+                Err(error) => {
+                    let backoff = connect_backoff(attempt);
+                    if start.elapsed() + backoff > self.connect_retry_timeout {
+                        return Err(error);
+                    }
+                    warn!(%error, ?backoff, "Failed to connect to deployment, retrying");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the parameters for the second target deployment (variant B), if one was given with
+    /// `--target-b-conn-str`. These are the same as these parameters, but with the second
+    /// deployment as the target.
+    pub fn target_b(&self) -> Option<DeploymentParameters> {
+        Some(DeploymentParameters {
+            target_conn_str: self.target_b_conn_str.clone()?,
+            target_b_conn_str: None,
+            ..self.clone()
+        })
+    }
+
+    /// Build a pool of up to `max_connections` connections to the target database
+    pub fn target_pool(&self, max_connections: usize) -> Result<DatabaseConnectionPool> {
+        self.tls.pool(&self.target_conn_str, max_connections)
+    }
+
+    /// Labels describing this deployment, to attach to all benchmark results so that results from
+    /// different builds or configurations of ReadySet aren't mixed together when aggregated.
+    ///
+    /// This includes the version and commit ID of the target ReadySet deployment, if they can be
+    /// queried.
+    pub async fn labels(&self) -> HashMap<String, String> {
+        let mut labels =
+            HashMap::from([("database_type".to_owned(), self.database_type.to_string())]);
+        if let Some(shards) = self.shards {
+            labels.insert("shards".to_owned(), shards.to_string());
+        }
+        if let Some(memory_limit) = self.memory_limit {
+            labels.insert("memory_limit".to_owned(), memory_limit.to_string());
+        }
+
+        match readyset_version(&self.target_conn_str, &self.tls).await {
+            Ok(version) => labels.extend(version),
+            Err(error) => warn!(
+                %error,
+                "Could not query the version of the target ReadySet deployment"
+            ),
+        }
+
+        if let Some(target_b) = &self.target_b_conn_str {
+            labels.insert("variant_a".to_owned(), self.variant_a.clone());
+            labels.insert("variant_b".to_owned(), self.variant_b.clone());
+            match readyset_version(target_b, &self.tls).await {
+                Ok(version) => labels.extend(
+                    version
+                        .into_iter()
+                        .map(|(label, value)| (format!("variant_b_{label}"), value)),
+                ),
+                Err(error) => warn!(
+                    %error,
+                    "Could not query the version of the second target ReadySet deployment"
+                ),
+            }
+        }
+
+        labels
     }
 }
 
 /// Indicates whether increasing or decreasing is the more desirable property for a metric
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MetricGoal {
     Increasing,
     Decreasing,
@@ -236,6 +425,163 @@ impl BenchmarkResults {
         }
         BenchmarkResults { results }
     }
+
+    /// Summarize these results as a versioned [`ResultsDocument`] for the benchmark with the given
+    /// name and labels.
+    pub fn to_document(
+        &self,
+        benchmark: &str,
+        labels: &HashMap<String, String>,
+    ) -> ResultsDocument {
+        ResultsDocument {
+            schema_version: RESULTS_SCHEMA_VERSION,
+            benchmark: benchmark.to_owned(),
+            labels: labels.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            metrics: self
+                .results
+                .iter()
+                .filter_map(|(metric, data)| Some((metric.clone(), MetricSummary::new(data)?)))
+                .collect(),
+        }
+    }
+
+    /// Write these results to `writer` as a versioned JSON [`ResultsDocument`], suitable for long
+    /// term storage and for comparing results across versions with
+    /// [`ResultsDocument::regressions`].
+    pub fn write_json<W: Write>(
+        &self,
+        benchmark: &str,
+        labels: &HashMap<String, String>,
+        writer: W,
+    ) -> Result<()> {
+        serde_json::to_writer_pretty(writer, &self.to_document(benchmark, labels))?;
+        Ok(())
+    }
+}
+
+/// The version of the schema of [`ResultsDocument`]. This must be incremented whenever the
+/// document changes in a way that older readers can't handle.
+pub const RESULTS_SCHEMA_VERSION: u32 = 1;
+
+/// Aggregates of all the values recorded for a single metric in a benchmark run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub unit: String,
+    pub goal: MetricGoal,
+    pub samples: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl MetricSummary {
+    /// Summarize all the values in `data`, or return `None` if there are no values
+    fn new(data: &BenchmarkData) -> Option<Self> {
+        if data.values.is_empty() {
+            return None;
+        }
+        let mut values = data.values.clone();
+        values.sort_unstable_by(|a, b| {
+            // We shouldn't have any NaNs/infs
+            a.partial_cmp(b).unwrap()
+        });
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+
+        Some(Self {
+            unit: data.unit.clone(),
+            goal: data.desired_action,
+            samples: values.len(),
+            min: values[0],
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+        })
+    }
+}
+
+/// The results of a single benchmark run, in a stable, versioned format for storage and for
+/// comparison across versions. See [`BenchmarkResults::write_json`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultsDocument {
+    /// The version of the document's schema; always [`RESULTS_SCHEMA_VERSION`] when written
+    pub schema_version: u32,
+    /// The name of the benchmark which was run
+    pub benchmark: String,
+    /// The labels describing the benchmark run
+    pub labels: BTreeMap<String, String>,
+    /// Aggregates of the values recorded for each metric, by metric name
+    pub metrics: BTreeMap<String, MetricSummary>,
+}
+
+/// A metric whose p50 or p99 in one benchmark run is worse than in a baseline run by more than
+/// the tolerated amount, as returned by [`ResultsDocument::regressions`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub metric: String,
+    pub percentile: &'static str,
+    pub baseline: f64,
+    pub value: f64,
+}
+
+impl std::fmt::Display for Regression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} regressed from {} to {}",
+            self.metric, self.percentile, self.baseline, self.value
+        )
+    }
+}
+
+impl ResultsDocument {
+    /// Read a document previously written by [`BenchmarkResults::write_json`], returning an error
+    /// if it was written with an unsupported version of the schema.
+    pub fn read_json<R: Read>(reader: R) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        match value.get("schema_version").and_then(|v| v.as_u64()) {
+            Some(v) if v == RESULTS_SCHEMA_VERSION as u64 => Ok(serde_json::from_value(value)?),
+            Some(v) => bail!(
+                "Unsupported benchmark results schema version {v} (expected \
+                 {RESULTS_SCHEMA_VERSION})"
+            ),
+            None => bail!("Benchmark results are missing a schema version"),
+        }
+    }
+
+    /// Compare the p50 and p99 of every metric in this document against the same metric in
+    /// `baseline`, returning all those which are worse than the baseline by more than the given
+    /// relative `tolerance`. Metrics missing from either document are ignored.
+    pub fn regressions(&self, baseline: &ResultsDocument, tolerance: f64) -> Vec<Regression> {
+        let mut regressions = vec![];
+        for (metric, summary) in &self.metrics {
+            let Some(base) = baseline.metrics.get(metric) else {
+                continue;
+            };
+            for (percentile, value, baseline) in [
+                ("p50", summary.p50, base.p50),
+                ("p99", summary.p99, base.p99),
+            ] {
+                let regressed = match summary.goal {
+                    MetricGoal::Decreasing => value > baseline * (1.0 + tolerance),
+                    MetricGoal::Increasing => value < baseline * (1.0 - tolerance),
+                };
+                if regressed {
+                    regressions.push(Regression {
+                        metric: metric.clone(),
+                        percentile,
+                        baseline,
+                        value,
+                    });
+                }
+            }
+        }
+        regressions
+    }
 }
 
 /// The formatted benchmark parameters and results for serialization
@@ -278,6 +624,13 @@ pub trait BenchmarkControl {
     /// one iteration.
     async fn reset(&self, deployment: &DeploymentParameters) -> Result<()>;
 
+    /// Check that `reset` left the deployment in a clean state, with no caches created by the
+    /// benchmark remaining that could pollute subsequent runs. Benchmarks which don't know what
+    /// they leave behind are assumed to be clean.
+    async fn verify_clean(&self, _deployment: &DeploymentParameters) -> Result<bool> {
+        Ok(true)
+    }
+
     /// Perform actual benchmarking, writing results to prometheus.
     async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults>;
 
@@ -305,3 +658,71 @@ pub trait BenchmarkControl {
     /// The [`DataGenerator`] used by this benchmark, if any.
     fn data_generator(&mut self) -> Option<&mut DataGenerator>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(values: impl IntoIterator<Item = f64>) -> BenchmarkResults {
+        let mut results = BenchmarkResults::new();
+        for value in values {
+            results.push(
+                "duration",
+                metrics::Unit::Microseconds,
+                MetricGoal::Decreasing,
+                value,
+            );
+        }
+        results
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let labels = HashMap::from([("query".to_owned(), "SELECT 1".to_owned())]);
+        let mut out = vec![];
+        results((1..=100).map(|v| v as f64))
+            .write_json("test_benchmark", &labels, &mut out)
+            .unwrap();
+
+        let doc = ResultsDocument::read_json(out.as_slice()).unwrap();
+        assert_eq!(doc.schema_version, RESULTS_SCHEMA_VERSION);
+        assert_eq!(doc.benchmark, "test_benchmark");
+        assert_eq!(doc.labels["query"], "SELECT 1");
+        let duration = &doc.metrics["duration"];
+        assert_eq!(duration.samples, 100);
+        assert_eq!(duration.min, 1.0);
+        assert_eq!(duration.max, 100.0);
+        assert_eq!(duration.p99, 99.0);
+    }
+
+    #[test]
+    fn connect_backoff_is_exponential_and_capped() {
+        assert_eq!(connect_backoff(0), Duration::from_millis(100));
+        assert_eq!(connect_backoff(1), Duration::from_millis(200));
+        assert_eq!(connect_backoff(3), Duration::from_millis(800));
+        assert_eq!(connect_backoff(10), CONNECT_MAX_BACKOFF);
+        assert_eq!(connect_backoff(u32::MAX), CONNECT_MAX_BACKOFF);
+    }
+
+    #[test]
+    fn reject_unknown_schema_version() {
+        let doc = r#"{"schema_version": 999, "benchmark": "", "labels": {}, "metrics": {}}"#;
+        assert!(ResultsDocument::read_json(doc.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn regressions_against_baseline() {
+        let labels = HashMap::new();
+        let baseline = results((1..=100).map(|v| v as f64)).to_document("b", &labels);
+        let similar = results((1..=100).map(|v| v as f64 * 1.05)).to_document("b", &labels);
+        let slower = results((1..=100).map(|v| v as f64 * 2.0)).to_document("b", &labels);
+
+        assert!(similar.regressions(&baseline, 0.1).is_empty());
+        let regressions = slower.regressions(&baseline, 0.1);
+        assert_eq!(
+            regressions.iter().map(|r| r.percentile).collect::<Vec<_>>(),
+            vec!["p50", "p99"]
+        );
+        assert!(baseline.regressions(&slower, 0.1).is_empty());
+    }
+}
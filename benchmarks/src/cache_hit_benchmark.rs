@@ -1,21 +1,62 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::str::FromStr;
-use std::time::Instant;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use clap::Parser;
-use database_utils::{DatabaseConnection, DatabaseURL, QueryableConnection};
+use anyhow::{bail, Result};
+use clap::{Parser, ValueEnum, ValueHint};
+use database_utils::{
+    DatabaseConnection, DatabaseConnectionPool, DatabaseStatement, QueryableConnection,
+};
+use hdrhistogram::Histogram;
 use metrics::Unit;
 use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
-use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark::{
+    BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal, ResultsDocument,
+};
 use crate::benchmark_histogram;
+use crate::utils::clock::{Clock, MonotonicClock};
 use crate::utils::generate::DataGenerator;
 use crate::utils::prometheus::ForwardPrometheusMetrics;
-use crate::utils::query::{ArbitraryQueryParameters, CachingQueryGenerator};
+use crate::utils::query::{
+    served_from_cache, ArbitraryQueryParameters, CachingQueryGenerator, Query,
+};
+
+/// How to send queries to the database
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum ExecutionMode {
+    /// Prepare the query once, then execute the prepared statement with each set of parameters
+    #[default]
+    Prepared,
+    /// Render each set of parameters into the text of the query, and send it as a simple query
+    /// over the text protocol, without preparing it
+    Text,
+}
+
+impl ExecutionMode {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Prepared => "prepared",
+            Self::Text => "text",
+        }
+    }
+}
 
 /// Measure query execution time for both cache hits and cache misses of a single query
+///
+/// If a second target deployment is given with `--target-b-conn-str`, exactly the same queries
+/// are then run against it, and its latencies are reported as `b_misses` and `b_hits` alongside
+/// the relative difference from the first deployment at each of p50, p90, and p99.
+///
+/// The wall-clock time spent installing the schema, generating data, and migrating the query is
+/// reported as `install_schema_duration`, `generate_data_duration`, and `migrate_duration` (and
+/// `b_migrate_duration` for the second target deployment), so that slow setup can be told apart
+/// from slow queries.
 #[derive(Parser, Clone, Serialize, Deserialize)]
 pub struct CacheHitBenchmark {
     /// Parameters to handle generating parameters for arbitrary queries.
@@ -26,50 +67,578 @@ pub struct CacheHitBenchmark {
     #[command(flatten)]
     data_generator: DataGenerator,
 
-    /// Number of cache hits to perform
+    /// Number of cache hits to perform. With `--until-p99-stable`, the maximum number of cache
+    /// hits to perform.
     #[arg(long, default_value = "1000")]
     num_cache_hits: u32,
 
-    /// Number of cache misses to perform
+    /// Number of cache misses to perform. With `--until-p99-stable`, the maximum number of cache
+    /// misses to perform.
     #[arg(long, default_value = "1000")]
     num_cache_misses: u32,
+
+    /// Rather than always performing exactly `--num-cache-hits` and `--num-cache-misses`
+    /// queries, stop as soon as the estimated p99 latency stabilizes to within
+    /// `--p99-tolerance`, or `--max-phase-duration` elapses.
+    #[arg(long)]
+    #[serde(default)]
+    until_p99_stable: bool,
+
+    /// The maximum relative change in the estimated p99 latency between successive checks for
+    /// the estimate to be considered stable, with `--until-p99-stable`.
+    #[arg(long, default_value = "0.01")]
+    #[serde(default = "default_p99_tolerance")]
+    p99_tolerance: f64,
+
+    /// The maximum number of seconds to spend on each of the cache miss and cache hit phases
+    /// with `--until-p99-stable`.
+    #[arg(long, value_parser = crate::utils::seconds_as_str_to_duration)]
+    #[serde(default)]
+    max_phase_duration: Option<Duration>,
+
+    /// Whether to execute queries as prepared statements, or as simple queries with their
+    /// parameters rendered into the SQL. With `--compare-upstream`, queries are sent to the
+    /// upstream database the same way.
+    #[arg(long, value_enum, default_value_t)]
+    #[serde(default)]
+    execution_mode: ExecutionMode,
+
+    /// Maximum number of connections kept in the pool that is shared across benchmark phases. If
+    /// set to 0, every phase opens a fresh connection instead.
+    #[arg(long, default_value = "1")]
+    #[serde(default = "default_pool_size")]
+    pool_size: usize,
+
+    /// If set, write every individual query latency sample to this file as CSV, with a
+    /// `query_type,latency_micros` header row. Samples are streamed to the file as they're
+    /// recorded, rather than buffered in memory.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    dump_samples: Option<PathBuf>,
+
+    /// After benchmarking ReadySet, run the exact same queries directly against the upstream
+    /// database (the setup connection string), and report the upstream latencies along with
+    /// ReadySet's speedup over upstream at each of p50, p90, and p99.
+    #[arg(long)]
+    #[serde(default)]
+    compare_upstream: bool,
+
+    /// If set, bucket the latencies of the queries run against ReadySet into windows of
+    /// `--latency-series-window` seconds, and write the p50 and p99 latency of each window to this
+    /// file as CSV, so that latency can be plotted over the course of the run.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    latency_series: Option<PathBuf>,
+
+    /// The length, in seconds, of each window of time with `--latency-series`.
+    #[arg(
+        long,
+        default_value = "1",
+        value_parser = crate::utils::seconds_as_str_to_duration
+    )]
+    #[serde(default = "default_latency_series_window")]
+    latency_series_window: Duration,
+
+    /// If set, write the results of the benchmark to this file as a versioned JSON document,
+    /// which can later be passed to `--baseline` to check for regressions.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    output_json: Option<PathBuf>,
+
+    /// If set, compare the results of the benchmark against a JSON document previously written
+    /// with `--output-json`, and fail if the p50 or p99 of any metric is worse than in the
+    /// baseline by more than `--baseline-tolerance`.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[serde(default)]
+    baseline: Option<PathBuf>,
+
+    /// The maximum relative amount by which any metric may be worse than in `--baseline` before
+    /// it's considered a regression.
+    #[arg(long, default_value = "0.1")]
+    #[serde(default = "default_baseline_tolerance")]
+    baseline_tolerance: f64,
+
+    /// Don't fail if the query isn't served from ReadySet's cache after migrating it (for example
+    /// because it's unsupported and is proxied to the upstream database instead). Without this,
+    /// the benchmark checks that the query is cached before running, so that it never silently
+    /// reports the latency of the upstream database as that of cache hits.
+    #[arg(long)]
+    #[serde(default)]
+    allow_proxied: bool,
+
+    #[arg(skip)]
+    #[serde(skip)]
+    pool: Arc<Mutex<Option<DatabaseConnectionPool>>>,
+
+    /// The wall-clock time spent in each phase which has run since the last call to `benchmark`,
+    /// to be reported in its results
+    #[arg(skip)]
+    #[serde(skip)]
+    phase_timings: Arc<Mutex<Vec<(&'static str, Duration)>>>,
+}
+
+fn default_pool_size() -> usize {
+    1
+}
+
+fn default_p99_tolerance() -> f64 {
+    0.01
+}
+
+fn default_latency_series_window() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_baseline_tolerance() -> f64 {
+    0.1
+}
+
+/// Minimum number of samples to take before checking whether the p99 estimate has stabilized
+const P99_MIN_SAMPLES: u64 = 200;
+
+/// Number of samples to take between checks of whether the p99 estimate has stabilized
+const P99_CHECK_INTERVAL: u64 = 100;
+
+/// Number of consecutive checks for which the p99 estimate must be stable for it to be
+/// considered converged
+const P99_STABLE_CHECKS: usize = 3;
+
+/// Percentiles at which to compare ReadySet's latency to the upstream database's with
+/// `--compare-upstream`, along with their names
+const COMPARISON_PERCENTILES: [(&str, f64); 3] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+/// Returns the speedup of ReadySet over the upstream database at each of
+/// [`COMPARISON_PERCENTILES`], as the ratio of the upstream latency to the ReadySet latency
+fn speedups(readyset: &Histogram<u64>, upstream: &Histogram<u64>) -> Vec<(&'static str, f64)> {
+    COMPARISON_PERCENTILES
+        .iter()
+        .map(|&(name, quantile)| {
+            let readyset = readyset.value_at_quantile(quantile).max(1);
+            (
+                name,
+                upstream.value_at_quantile(quantile) as f64 / readyset as f64,
+            )
+        })
+        .collect()
+}
+
+/// Returns the relative difference between the latency of variant B and that of variant A at each
+/// of [`COMPARISON_PERCENTILES`], as a percentage of the latency of variant A. Positive values mean
+/// variant B is slower.
+fn deltas(a: &Histogram<u64>, b: &Histogram<u64>) -> Vec<(&'static str, f64)> {
+    COMPARISON_PERCENTILES
+        .iter()
+        .map(|&(name, quantile)| {
+            let a_latency = a.value_at_quantile(quantile).max(1) as f64;
+            let b_latency = b.value_at_quantile(quantile) as f64;
+            (name, (b_latency - a_latency) / a_latency * 100.0)
+        })
+        .collect()
+}
+
+/// Buckets query latencies into fixed-size windows of time since the start of each benchmark phase,
+/// and writes the number of samples and p50 and p99 latency of each window as CSV with
+/// `--latency-series`.
+///
+/// Windows in which no queries completed are written with no percentiles, so that stalls show up
+/// as gaps in the series.
+struct LatencySeries<W: Write> {
+    out: W,
+    window: Duration,
+    query_type: &'static str,
+    /// The index of the current window since the start of the phase
+    current: u32,
+    hist: Histogram<u64>,
+}
+
+impl<W: Write> LatencySeries<W> {
+    fn new(mut out: W, window: Duration) -> Result<Self> {
+        writeln!(
+            out,
+            "query_type,window_start_secs,samples,p50_micros,p99_micros"
+        )?;
+        Ok(Self {
+            out,
+            window,
+            query_type: "",
+            current: 0,
+            hist: Histogram::<u64>::new(3).unwrap(),
+        })
+    }
+
+    fn start_phase(&mut self, query_type: &'static str) {
+        self.query_type = query_type;
+        self.current = 0;
+        self.hist.reset();
+    }
+
+    /// Record the latency of a query which started `since_phase_start` after the start of the
+    /// phase
+    fn record(&mut self, since_phase_start: Duration, latency_micros: u64) -> Result<()> {
+        let window = (since_phase_start.as_secs_f64() / self.window.as_secs_f64()) as u32;
+        while self.current < window {
+            self.write_window()?;
+            self.current += 1;
+        }
+        self.hist.record(latency_micros).unwrap();
+        Ok(())
+    }
+
+    fn finish_phase(&mut self) -> Result<()> {
+        if !self.hist.is_empty() {
+            self.write_window()?;
+        }
+        Ok(())
+    }
+
+    fn write_window(&mut self) -> Result<()> {
+        let start = self.window.as_secs_f64() * self.current as f64;
+        if self.hist.is_empty() {
+            writeln!(self.out, "{},{start},0,,", self.query_type)?;
+        } else {
+            writeln!(
+                self.out,
+                "{},{start},{},{},{}",
+                self.query_type,
+                self.hist.len(),
+                self.hist.value_at_quantile(0.5),
+                self.hist.value_at_quantile(0.99)
+            )?;
+        }
+        self.hist.reset();
+        Ok(())
+    }
+}
+
+/// Records the latencies of the queries run in one phase of the benchmark, as timed by a [`Clock`],
+/// into the results of the benchmark and any `--dump-samples` and `--latency-series` output
+struct PhaseRecorder<'a, C, W: Write> {
+    clock: &'a C,
+    query_type: &'static str,
+    phase_start: Duration,
+    hist: Histogram<u64>,
+    results_data: &'a mut Vec<f64>,
+    samples: Option<&'a mut W>,
+    series: Option<&'a mut LatencySeries<W>>,
+}
+
+impl<'a, C: Clock, W: Write> PhaseRecorder<'a, C, W> {
+    fn new(
+        clock: &'a C,
+        query_type: &'static str,
+        results_data: &'a mut Vec<f64>,
+        samples: Option<&'a mut W>,
+        mut series: Option<&'a mut LatencySeries<W>>,
+    ) -> Self {
+        if let Some(series) = series.as_mut() {
+            series.start_phase(query_type);
+        }
+        Self {
+            clock,
+            query_type,
+            phase_start: clock.now(),
+            hist: Histogram::<u64>::new(3).unwrap(),
+            results_data,
+            samples,
+            series,
+        }
+    }
+
+    /// Returns the start time of a query, to pass to [`finish_query`](Self::finish_query) once the
+    /// query has completed
+    fn start_query(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Record the latency of a query which started at `start`, returning that latency
+    fn finish_query(&mut self, start: Duration) -> Result<Duration> {
+        let elapsed = self.clock.elapsed_since(start);
+        let micros = u64::try_from(elapsed.as_micros()).unwrap();
+        self.results_data.push(elapsed.as_millis() as f64);
+        self.hist.record(micros).unwrap();
+        if let Some(samples) = self.samples.as_mut() {
+            writeln!(samples, "{},{}", self.query_type, micros)?;
+        }
+        if let Some(series) = self.series.as_mut() {
+            series.record(start.saturating_sub(self.phase_start), micros)?;
+        }
+        Ok(elapsed)
+    }
+
+    /// Returns the amount of time since the start of the phase
+    fn phase_elapsed(&self) -> Duration {
+        self.clock.elapsed_since(self.phase_start)
+    }
+
+    /// Finish the phase, returning a histogram of all the latencies recorded during it, in
+    /// microseconds
+    fn finish(self) -> Result<Histogram<u64>> {
+        if let Some(series) = self.series {
+            series.finish_phase()?;
+        }
+        Ok(self.hist)
+    }
+}
+
+/// Tracks whether the p99 estimate of a running latency histogram has stabilized
+struct P99Convergence {
+    tolerance: f64,
+    last_p99: Option<u64>,
+    stable_checks: usize,
+}
+
+impl P99Convergence {
+    fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            last_p99: None,
+            stable_checks: 0,
+        }
+    }
+
+    /// Returns true if the p99 estimate of `hist` has been stable to within `tolerance` for the
+    /// last [`P99_STABLE_CHECKS`] checks. Should be called after every sample is recorded.
+    fn converged(&mut self, hist: &Histogram<u64>) -> bool {
+        let samples = hist.len();
+        if samples < P99_MIN_SAMPLES || samples % P99_CHECK_INTERVAL != 0 {
+            return false;
+        }
+
+        let p99 = hist.value_at_quantile(0.99);
+        let stable = self.last_p99.map_or(false, |last| {
+            p99.abs_diff(last) as f64 <= self.tolerance * last as f64
+        });
+        self.last_p99 = Some(p99);
+        self.stable_checks = if stable { self.stable_checks + 1 } else { 0 };
+        self.stable_checks >= P99_STABLE_CHECKS
+    }
 }
 
 impl BenchmarkControl for CacheHitBenchmark {
     async fn setup(&self, deployment: &DeploymentParameters) -> Result<()> {
+        let start = Instant::now();
         self.data_generator
             .install(&deployment.setup_conn_str)
             .await?;
+        self.record_phase("install_schema", start.elapsed());
+
+        let start = Instant::now();
         self.data_generator
             .generate(&deployment.setup_conn_str)
             .await?;
+        self.record_phase("generate_data", start.elapsed());
         Ok(())
     }
 
     async fn reset(&self, deployment: &DeploymentParameters) -> Result<()> {
-        let mut conn = DatabaseURL::from_str(&deployment.target_conn_str)?
-            .connect(None)
-            .await?;
-        let _ = self.query.unmigrate(&mut conn).await;
+        // Always use a fresh connection to reset, so that no state left over on a pooled
+        // connection from the previous iteration can leak into the next one.
+        for target in [Some(deployment.clone()), deployment.target_b()]
+            .into_iter()
+            .flatten()
+        {
+            let mut conn = target.connect_to_target().await?;
+            let _ = self.query.unmigrate(&mut conn).await;
+        }
         Ok(())
     }
 
+    async fn verify_clean(&self, deployment: &DeploymentParameters) -> Result<bool> {
+        for target in [Some(deployment.clone()), deployment.target_b()]
+            .into_iter()
+            .flatten()
+        {
+            let mut conn = target.connect_to_target().await?;
+            if self.query.is_migrated_as(&mut conn, "q").await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults> {
         // Explicitly migrate the query before benchmarking.
-        let mut conn = DatabaseURL::from_str(&deployment.target_conn_str)?
-            .connect(None)
-            .await?;
+        let mut conn = self.pooled_connection(deployment).await?;
+        let start = Instant::now();
         self.query.migrate(&mut conn).await?;
+        self.record_phase("migrate", start.elapsed());
 
         let mut gen = CachingQueryGenerator::from(self.query.prepared_statement(&mut conn).await?);
+        if !self.allow_proxied {
+            self.verify_cached(&mut conn, &mut gen).await?;
+        }
         let mut results = BenchmarkResults::new();
+        self.report_phase_timings(&mut results);
+        let clock = MonotonicClock::default();
+
+        let mut samples = self
+            .dump_samples
+            .as_ref()
+            .map(|path| -> Result<_> {
+                let mut samples = BufWriter::new(File::create(path)?);
+                writeln!(samples, "query_type,latency_micros")?;
+                Ok(samples)
+            })
+            .transpose()?;
+
+        let mut series = self
+            .latency_series
+            .as_ref()
+            .map(|path| {
+                LatencySeries::new(
+                    BufWriter::new(File::create(path)?),
+                    self.latency_series_window,
+                )
+            })
+            .transpose()?;
+
+        // The queries run against ReadySet are only kept if they need to be run again somewhere
+        // else
+        let target_b = deployment.target_b();
+        let keep_queries = self.compare_upstream || target_b.is_some();
 
         // Generate the cache misses.
-        self.run_queries(&mut conn, &mut gen, true, &mut results)
+        let mut misses = vec![];
+        let miss_hist = self
+            .run_queries(
+                &mut conn,
+                &mut gen,
+                true,
+                &clock,
+                &mut results,
+                samples.as_mut(),
+                series.as_mut(),
+                keep_queries.then_some(&mut misses),
+            )
             .await?;
         // Generate the cache hits.
-        self.run_queries(&mut conn, &mut gen, false, &mut results)
+        let mut hits = vec![];
+        let hit_hist = self
+            .run_queries(
+                &mut conn,
+                &mut gen,
+                false,
+                &clock,
+                &mut results,
+                samples.as_mut(),
+                series.as_mut(),
+                keep_queries.then_some(&mut hits),
+            )
             .await?;
+        if let Some(mut series) = series {
+            series.out.flush()?;
+        }
+
+        if self.compare_upstream {
+            // Prepare the same query against upstream, and replay exactly the queries that were
+            // run against ReadySet, so that both query sets are identical
+            let mut upstream = deployment.connect_to_setup().await?;
+            let statement = self
+                .query
+                .prepared_statement(&mut upstream)
+                .await?
+                .statement;
+            for (query_type, readyset_hist, queries) in
+                [("misses", &miss_hist, &misses), ("hits", &hit_hist, &hits)]
+            {
+                let upstream_hist = replay_queries(
+                    &mut upstream,
+                    &statement,
+                    self.execution_mode,
+                    &clock,
+                    &format!("upstream_{query_type}"),
+                    queries,
+                    &mut results,
+                    samples.as_mut(),
+                )
+                .await?;
+                for (percentile, speedup) in speedups(readyset_hist, &upstream_hist) {
+                    info!(
+                        query_type,
+                        "ReadySet is {speedup:.2}x faster than upstream at {percentile}"
+                    );
+                    results.push(
+                        &format!("{query_type}_speedup_{percentile}"),
+                        Unit::Count,
+                        MetricGoal::Increasing,
+                        speedup,
+                    );
+                }
+            }
+        }
+
+        if let Some(target_b) = target_b {
+            // Replay exactly the queries that were run against variant A, in the same order, so
+            // that the misses and hits are the same for both variants
+            let mut conn = target_b.connect_to_target().await?;
+            let start = Instant::now();
+            self.query.migrate(&mut conn).await?;
+            self.record_phase("b_migrate", start.elapsed());
+            self.report_phase_timings(&mut results);
+            let statement = self.query.prepared_statement(&mut conn).await?.statement;
+            for (query_type, a_hist, queries) in
+                [("misses", &miss_hist, &misses), ("hits", &hit_hist, &hits)]
+            {
+                let b_hist = replay_queries(
+                    &mut conn,
+                    &statement,
+                    self.execution_mode,
+                    &clock,
+                    &format!("b_{query_type}"),
+                    queries,
+                    &mut results,
+                    samples.as_mut(),
+                )
+                .await?;
+                for (percentile, delta) in deltas(a_hist, &b_hist) {
+                    info!(
+                        query_type,
+                        variant_a = %deployment.variant_a,
+                        variant_b = %deployment.variant_b,
+                        "Variant B latency differs from variant A by {delta:+.1}% at {percentile}"
+                    );
+                    results.push(
+                        &format!("{query_type}_b_delta_{percentile}"),
+                        Unit::Percent,
+                        MetricGoal::Decreasing,
+                        delta,
+                    );
+                }
+            }
+            if !self.allow_proxied && !served_from_cache(&mut conn).await? {
+                bail!(
+                    "Queries were proxied to the upstream database by the second target \
+                     deployment, so its latencies are those of the upstream database. Check that \
+                     the query is supported there, or pass --allow-proxied to compare anyway"
+                );
+            }
+        }
+
+        if let Some(mut samples) = samples {
+            samples.flush()?;
+        }
+
+        if let Some(path) = &self.output_json {
+            let mut out = BufWriter::new(File::create(path)?);
+            results.write_json(self.name(), &self.labels(), &mut out)?;
+            out.flush()?;
+        }
+
+        if let Some(path) = &self.baseline {
+            let baseline = ResultsDocument::read_json(BufReader::new(File::open(path)?))?;
+            let regressions = results
+                .to_document(self.name(), &self.labels())
+                .regressions(&baseline, self.baseline_tolerance);
+            for regression in &regressions {
+                warn!(%regression, "Regression against baseline");
+            }
+            if !regressions.is_empty() {
+                bail!(
+                    "{} metric(s) regressed against the baseline in {}",
+                    regressions.len(),
+                    path.display()
+                );
+            }
+        }
 
         Ok(results)
     }
@@ -78,6 +647,10 @@ impl BenchmarkControl for CacheHitBenchmark {
         let mut labels = HashMap::new();
         labels.extend(self.query.labels());
         labels.extend(self.data_generator.labels());
+        labels.insert(
+            "execution_mode".to_string(),
+            self.execution_mode.label().to_string(),
+        );
         labels
     }
 
@@ -95,33 +668,128 @@ impl BenchmarkControl for CacheHitBenchmark {
 }
 
 impl CacheHitBenchmark {
+    /// Check out a connection to the target database from the pool shared across benchmark
+    /// phases, building the pool on first use. Opens a fresh connection if `pool_size` is 0.
+    async fn pooled_connection(
+        &self,
+        deployment: &DeploymentParameters,
+    ) -> Result<DatabaseConnection> {
+        if self.pool_size == 0 {
+            return deployment.connect_to_target().await;
+        }
+
+        let pool = {
+            let mut pool = self.pool.lock().unwrap();
+            match &*pool {
+                Some(pool) => pool.clone(),
+                None => {
+                    let new_pool = deployment.target_pool(self.pool_size)?;
+                    *pool = Some(new_pool.clone());
+                    new_pool
+                }
+            }
+        };
+
+        Ok(pool.get_conn().await?)
+    }
+
+    /// Record that the phase named `phase` took `elapsed` wall-clock time, to be reported by the
+    /// next call to [`report_phase_timings`](Self::report_phase_timings)
+    fn record_phase(&self, phase: &'static str, elapsed: Duration) {
+        info!(
+            phase,
+            elapsed_ms = elapsed.as_millis(),
+            "Finished benchmark phase"
+        );
+        self.phase_timings.lock().unwrap().push((phase, elapsed));
+    }
+
+    /// Add the time taken by every phase recorded since this was last called to `results`, as
+    /// `{phase}_duration` in milliseconds
+    fn report_phase_timings(&self, results: &mut BenchmarkResults) {
+        for (phase, elapsed) in self.phase_timings.lock().unwrap().drain(..) {
+            results.push(
+                &format!("{phase}_duration"),
+                Unit::Milliseconds,
+                MetricGoal::Decreasing,
+                elapsed.as_secs_f64() * 1000.0,
+            );
+        }
+    }
+
+    /// Execute `query` against ReadySet, according to the execution mode
+    async fn execute(&self, conn: &mut DatabaseConnection, query: Query) -> Result<()> {
+        match self.execution_mode {
+            ExecutionMode::Prepared => {
+                conn.execute(&query.prep, query.params).await?;
+            }
+            ExecutionMode::Text => {
+                conn.simple_query(query.text).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute one instance of the query, and return an error if it wasn't served from ReadySet.
+    ///
+    /// This uses up one cache miss from `gen`, so that the misses measured afterwards are still
+    /// all actual misses.
+    async fn verify_cached(
+        &self,
+        conn: &mut DatabaseConnection,
+        gen: &mut CachingQueryGenerator,
+    ) -> Result<()> {
+        self.execute(conn, gen.generate_cache_miss()?).await?;
+        if !served_from_cache(conn).await? {
+            bail!(
+                "Query was proxied to the upstream database rather than served from ReadySet's \
+                 cache, so the benchmark would measure the upstream database. Check that the \
+                 query is supported, or pass --allow-proxied to benchmark it anyway"
+            );
+        }
+        Ok(())
+    }
+
+    /// Run one phase of the benchmark against ReadySet, returning a histogram of the latencies of
+    /// the queries, in microseconds. If `executed` is given, every query that was run is also
+    /// appended to it.
+    #[allow(clippy::too_many_arguments)]
     async fn run_queries(
         &self,
         conn: &mut DatabaseConnection,
         gen: &mut CachingQueryGenerator,
         cache_miss: bool,
+        clock: &impl Clock,
         results: &mut BenchmarkResults,
-    ) -> Result<()> {
-        // Generates 1000 cache misses.
-        let mut hist = hdrhistogram::Histogram::<u64>::new(3).unwrap();
+        samples: Option<&mut BufWriter<File>>,
+        series: Option<&mut LatencySeries<BufWriter<File>>>,
+        mut executed: Option<&mut Vec<Query>>,
+    ) -> Result<Histogram<u64>> {
         let count = match cache_miss {
             true => self.num_cache_misses,
             false => self.num_cache_hits,
         };
         let query_type = if cache_miss { "misses" } else { "hits" };
-        let results_data = results.entry(query_type, Unit::Milliseconds, MetricGoal::Decreasing);
+        let mut convergence = P99Convergence::new(self.p99_tolerance);
+        let mut phase = PhaseRecorder::new(
+            clock,
+            query_type,
+            results.entry(query_type, Unit::Milliseconds, MetricGoal::Decreasing),
+            samples,
+            series,
+        );
         for _ in 0..count {
             let query = if cache_miss {
                 gen.generate_cache_miss()?
             } else {
                 gen.generate_cache_hit()?
             };
-            let start = Instant::now();
-            conn.execute(&query.prep, query.params).await?;
-            let elapsed = start.elapsed();
-            results_data.push(elapsed.as_millis() as f64);
-            hist.record(u64::try_from(elapsed.as_micros()).unwrap())
-                .unwrap();
+            if let Some(executed) = executed.as_mut() {
+                executed.push(query.clone());
+            }
+            let start = phase.start_query();
+            self.execute(conn, query).await?;
+            let elapsed = phase.finish_query(start)?;
 
             let histogram_name = format!(
                 "cache_hit_benchmark.{}_duration",
@@ -133,8 +801,203 @@ impl CacheHitBenchmark {
                 "Duration of queries executed".into(),
                 elapsed.as_micros() as f64
             );
+
+            if self.until_p99_stable
+                && (convergence.converged(&phase.hist)
+                    || self
+                        .max_phase_duration
+                        .map_or(false, |max| phase.phase_elapsed() >= max))
+            {
+                break;
+            }
         }
 
-        Ok(())
+        let hist = phase.finish()?;
+        if self.until_p99_stable {
+            info!(samples = hist.len(), query_type, "Finished sampling");
+            results.push(
+                &format!("{query_type}_samples"),
+                Unit::Count,
+                MetricGoal::Decreasing,
+                hist.len() as f64,
+            );
+        }
+
+        Ok(hist)
+    }
+}
+
+/// Run each of the given queries, which were previously run against ReadySet, again against
+/// another database (the upstream database, or a second ReadySet deployment), either by executing
+/// `statement` with its parameters or as a simple query depending on `execution_mode`. The
+/// latencies are recorded as `query_type`, and a histogram of them is returned, in microseconds
+#[allow(clippy::too_many_arguments)]
+async fn replay_queries(
+    conn: &mut DatabaseConnection,
+    statement: &DatabaseStatement,
+    execution_mode: ExecutionMode,
+    clock: &impl Clock,
+    query_type: &str,
+    queries: &[Query],
+    results: &mut BenchmarkResults,
+    mut samples: Option<&mut BufWriter<File>>,
+) -> Result<Histogram<u64>> {
+    let mut hist = Histogram::<u64>::new(3).unwrap();
+    let results_data = results.entry(query_type, Unit::Milliseconds, MetricGoal::Decreasing);
+    for query in queries {
+        let start = clock.now();
+        match execution_mode {
+            ExecutionMode::Prepared => {
+                conn.execute(statement, query.params.clone()).await?;
+            }
+            ExecutionMode::Text => {
+                conn.simple_query(query.text.as_str()).await?;
+            }
+        }
+        let elapsed = clock.elapsed_since(start);
+        results_data.push(elapsed.as_millis() as f64);
+        hist.record(u64::try_from(elapsed.as_micros()).unwrap())
+            .unwrap();
+        if let Some(samples) = samples.as_mut() {
+            writeln!(samples, "{},{}", query_type, elapsed.as_micros())?;
+        }
+    }
+
+    Ok(hist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::MockClock;
+
+    #[test]
+    fn speedup_per_percentile() {
+        let mut readyset = Histogram::<u64>::new(3).unwrap();
+        let mut upstream = Histogram::<u64>::new(3).unwrap();
+        for latency in 1..=100 {
+            readyset.record(latency * 10).unwrap();
+            upstream.record(latency * 100).unwrap();
+        }
+
+        let speedups = speedups(&readyset, &upstream);
+        assert_eq!(
+            speedups.iter().map(|(p, _)| *p).collect::<Vec<_>>(),
+            vec!["p50", "p90", "p99"]
+        );
+        for (_, speedup) in speedups {
+            assert!((speedup - 10.0).abs() < 0.1, "{speedup}");
+        }
+    }
+
+    #[test]
+    fn delta_per_percentile() {
+        let mut a = Histogram::<u64>::new(3).unwrap();
+        let mut b = Histogram::<u64>::new(3).unwrap();
+        for latency in 1..=100 {
+            a.record(latency * 100).unwrap();
+            b.record(latency * 150).unwrap();
+        }
+
+        let deltas = deltas(&a, &b);
+        assert_eq!(
+            deltas.iter().map(|(p, _)| *p).collect::<Vec<_>>(),
+            vec!["p50", "p90", "p99"]
+        );
+        for (_, delta) in deltas {
+            assert!((delta - 50.0).abs() < 1.0, "{delta}");
+        }
+    }
+
+    #[test]
+    fn latency_series_windows() {
+        let mut series = LatencySeries::new(vec![], Duration::from_secs(1)).unwrap();
+        series.start_phase("misses");
+        series.record(Duration::from_millis(100), 10).unwrap();
+        series.record(Duration::from_millis(900), 20).unwrap();
+        // nothing completes in the second window
+        series.record(Duration::from_millis(2500), 30).unwrap();
+        series.finish_phase().unwrap();
+        series.start_phase("hits");
+        series.record(Duration::from_millis(0), 5).unwrap();
+        series.finish_phase().unwrap();
+
+        assert_eq!(
+            String::from_utf8(series.out).unwrap(),
+            "query_type,window_start_secs,samples,p50_micros,p99_micros\n\
+             misses,0,2,10,20\n\
+             misses,1,0,,\n\
+             misses,2,1,30,30\n\
+             hits,0,1,5,5\n"
+        );
+    }
+
+    #[test]
+    fn phase_recorder_aggregates_latencies() {
+        let clock = MockClock::default();
+        let mut results = BenchmarkResults::new();
+        let mut samples = vec![];
+        let mut series = LatencySeries::new(vec![], Duration::from_secs(1)).unwrap();
+        let mut phase = PhaseRecorder::new(
+            &clock,
+            "hits",
+            results.entry("hits", Unit::Milliseconds, MetricGoal::Decreasing),
+            Some(&mut samples),
+            Some(&mut series),
+        );
+
+        for latency_ms in 1..=100 {
+            let start = phase.start_query();
+            clock.advance(Duration::from_millis(latency_ms));
+            assert_eq!(
+                phase.finish_query(start).unwrap(),
+                Duration::from_millis(latency_ms)
+            );
+        }
+        assert_eq!(phase.phase_elapsed(), Duration::from_millis(5050));
+
+        let hist = phase.finish().unwrap();
+        assert_eq!(hist.len(), 100);
+        assert_eq!(hist.value_at_quantile(0.5) / 1000, 50);
+        assert_eq!(hist.value_at_quantile(0.99) / 1000, 99);
+        assert_eq!(
+            results.entry("hits", Unit::Milliseconds, MetricGoal::Decreasing)[..3],
+            [1.0, 2.0, 3.0]
+        );
+        assert!(String::from_utf8(samples)
+            .unwrap()
+            .starts_with("hits,1000\nhits,2000\n"));
+        // A header, then one line for each of the first 5 seconds of the phase, in which all the
+        // queries started
+        assert_eq!(
+            String::from_utf8(series.out).unwrap().lines().count(),
+            1 + 5
+        );
+    }
+
+    #[test]
+    fn p99_converges_for_constant_latency() {
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        let mut convergence = P99Convergence::new(0.01);
+        let mut samples = 0;
+        while !convergence.converged(&hist) {
+            hist.record(1000).unwrap();
+            samples += 1;
+            assert!(samples <= 10_000, "p99 never converged");
+        }
+        assert_eq!(
+            samples,
+            P99_MIN_SAMPLES + P99_CHECK_INTERVAL * P99_STABLE_CHECKS as u64
+        );
+    }
+
+    #[test]
+    fn p99_does_not_converge_for_increasing_latency() {
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        let mut convergence = P99Convergence::new(0.01);
+        for latency in 0..2000 {
+            hist.record(latency * 100).unwrap();
+            assert!(!convergence.converged(&hist));
+        }
     }
 }
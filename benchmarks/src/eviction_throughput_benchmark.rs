@@ -0,0 +1,280 @@
+//! Measures how quickly keys can be evicted from a partial materialization, and how much those
+//! evictions slow down concurrent reads.
+//!
+//! The benchmark first fills the partial materialization for the query by reading `--fill-keys`
+//! distinct keys, then reads those same keys again for `--phase-duration` to measure the baseline
+//! read latency. Finally, it keeps reading the same keys for another `--phase-duration` while
+//! issuing single-key eviction requests to the controller at `--evictions-per-second`, and reports
+//! the eviction throughput along with the read latency penalty relative to the baseline.
+//!
+//! Each eviction request evicts a random key from a random partial index in the deployment, so this
+//! should be run against a deployment with no partially materialized queries other than the one
+//! being benchmarked.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use clap::Parser;
+use database_utils::{DatabaseConnection, QueryableConnection};
+use hdrhistogram::Histogram;
+use metrics::Unit;
+use rand::seq::SliceRandom;
+use readyset_client::consensus::AuthorityType;
+use readyset_client::ReadySetHandle;
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tracing::info;
+
+use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark_histogram;
+use crate::utils::generate::DataGenerator;
+use crate::utils::prometheus::ForwardPrometheusMetrics;
+use crate::utils::query::{ArbitraryQueryParameters, CachingQueryGenerator, Query};
+
+#[derive(Parser, Clone, Serialize, Deserialize)]
+pub struct EvictionThroughputBenchmark {
+    /// Parameters to handle generating parameters for arbitrary queries.
+    #[command(flatten)]
+    query: ArbitraryQueryParameters,
+
+    /// Install and generate from an arbitrary schema.
+    #[command(flatten)]
+    data_generator: DataGenerator,
+
+    /// Number of distinct keys to read to fill the partial materialization before evicting.
+    #[arg(long, default_value = "10000")]
+    fill_keys: u32,
+
+    /// Number of single-key eviction requests to issue per second while evicting.
+    #[arg(long, default_value = "100")]
+    evictions_per_second: u64,
+
+    /// Number of seconds to read for, both to measure the baseline read latency and while
+    /// evicting.
+    #[arg(long, default_value = "30", value_parser = crate::utils::seconds_as_str_to_duration)]
+    phase_duration: Duration,
+
+    /// The type of authority used by the ReadySet deployment, for issuing eviction requests to
+    /// the controller.
+    #[arg(
+        long,
+        env = "AUTHORITY",
+        default_value = "consul",
+        value_parser = ["consul", "standalone", "local"]
+    )]
+    authority: String,
+
+    /// Address of the authority used by the ReadySet deployment.
+    #[arg(long, env = "AUTHORITY_ADDRESS", default_value = "127.0.0.1:8500")]
+    authority_address: String,
+
+    /// Name of the ReadySet deployment.
+    #[arg(long, env = "DEPLOYMENT")]
+    deployment: String,
+}
+
+impl BenchmarkControl for EvictionThroughputBenchmark {
+    async fn setup(&self, deployment: &DeploymentParameters) -> Result<()> {
+        self.data_generator
+            .install(&deployment.setup_conn_str)
+            .await?;
+        self.data_generator
+            .generate(&deployment.setup_conn_str)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset(&self, _: &DeploymentParameters) -> Result<()> {
+        Err(anyhow::anyhow!("reset unsupported"))
+    }
+
+    async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults> {
+        let authority = AuthorityType::from_str(&self.authority)?
+            .to_authority(&self.authority_address, &self.deployment);
+        let mut handle = ReadySetHandle::new(authority).await;
+        handle.ready().await?;
+
+        let mut conn = deployment.connect_to_target().await?;
+        self.query.migrate(&mut conn).await?;
+        let mut gen = CachingQueryGenerator::from(self.query.prepared_statement(&mut conn).await?);
+
+        // Fill the partial materialization, keeping track of the size of the state we added so we
+        // can estimate how many bytes each eviction frees
+        let bytes_before_fill = materialized_bytes(&mut handle).await?;
+        let mut keys = Vec::with_capacity(self.fill_keys as usize);
+        for _ in 0..self.fill_keys {
+            let query = gen.generate_cache_miss()?;
+            conn.execute(&query.prep, query.params.clone()).await?;
+            keys.push(query);
+        }
+        let bytes_per_key = materialized_bytes(&mut handle)
+            .await?
+            .saturating_sub(bytes_before_fill) as f64
+            / keys.len().max(1) as f64;
+        info!(bytes_per_key, "Filled {} keys", keys.len());
+
+        let mut results = BenchmarkResults::new();
+        let baseline = read_keys(
+            &mut conn,
+            &keys,
+            self.phase_duration,
+            "baseline",
+            &mut results,
+        )
+        .await?;
+
+        let evictions = tokio::spawn(evict_at_rate(
+            handle,
+            self.evictions_per_second,
+            self.phase_duration,
+        ));
+        let during_eviction = read_keys(
+            &mut conn,
+            &keys,
+            self.phase_duration,
+            "during_eviction",
+            &mut results,
+        )
+        .await?;
+        let evicted_keys = evictions.await??;
+
+        let evicted_keys_per_sec = evicted_keys as f64 / self.phase_duration.as_secs_f64();
+        let evicted_bytes_per_sec = evicted_keys_per_sec * bytes_per_key;
+        results.push(
+            "evicted_keys_per_sec",
+            Unit::CountPerSecond,
+            MetricGoal::Increasing,
+            evicted_keys_per_sec,
+        );
+        results.push(
+            "estimated_evicted_bytes_per_sec",
+            Unit::BytesPerSecond,
+            MetricGoal::Increasing,
+            evicted_bytes_per_sec,
+        );
+        for (percentile, quantile) in [("p50", 0.5), ("p99", 0.99)] {
+            let penalty = during_eviction.value_at_quantile(quantile) as f64
+                - baseline.value_at_quantile(quantile) as f64;
+            info!("Read latency penalty during eviction at {percentile}: {penalty}us");
+            results.push(
+                &format!("read_{percentile}_penalty"),
+                Unit::Microseconds,
+                MetricGoal::Decreasing,
+                penalty,
+            );
+        }
+        info!(
+            evicted_keys,
+            "Evicted {evicted_keys_per_sec:.1} keys/s (~{:.1} KiB/s)",
+            evicted_bytes_per_sec / 1024.
+        );
+
+        Ok(results)
+    }
+
+    fn labels(&self) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+        labels.extend(self.query.labels());
+        labels.extend(self.data_generator.labels());
+        labels.insert("fill_keys".to_string(), self.fill_keys.to_string());
+        labels.insert(
+            "evictions_per_second".to_string(),
+            self.evictions_per_second.to_string(),
+        );
+        labels
+    }
+
+    fn forward_metrics(&self, _: &DeploymentParameters) -> Vec<ForwardPrometheusMetrics> {
+        vec![]
+    }
+
+    fn name(&self) -> &'static str {
+        "eviction_throughput_benchmark"
+    }
+
+    fn data_generator(&mut self) -> Option<&mut DataGenerator> {
+        Some(&mut self.data_generator)
+    }
+}
+
+/// Returns the approximate total size in bytes of all materialized state in the deployment
+async fn materialized_bytes(handle: &mut ReadySetHandle) -> Result<usize> {
+    Ok(handle
+        .node_sizes()
+        .await?
+        .values()
+        .map(|size| size.bytes.0)
+        .sum())
+}
+
+/// Issue single-key eviction requests at up to `per_second` requests per second for `duration`,
+/// returning the number of keys that were actually evicted
+async fn evict_at_rate(
+    mut handle: ReadySetHandle,
+    per_second: u64,
+    duration: Duration,
+) -> Result<u64> {
+    let mut interval =
+        tokio::time::interval(Duration::from_secs_f64(1.0 / per_second.max(1) as f64));
+    // If eviction requests take longer than the interval, fall behind the requested rate rather
+    // than bursting to catch up
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let start = Instant::now();
+    let mut evicted = 0;
+    while start.elapsed() < duration {
+        interval.tick().await;
+        if handle.evict_single(None).await?.is_some() {
+            evicted += 1;
+        }
+    }
+    Ok(evicted)
+}
+
+/// Repeatedly read randomly selected keys out of `keys` for `duration`, recording the latencies of
+/// the reads as `{phase}_read_latency` and returning a histogram of them, in microseconds
+async fn read_keys(
+    conn: &mut DatabaseConnection,
+    keys: &[Query],
+    duration: Duration,
+    phase: &str,
+    results: &mut BenchmarkResults,
+) -> Result<Histogram<u64>> {
+    let mut hist = Histogram::<u64>::new(3).unwrap();
+    let results_data = results.entry(
+        &format!("{phase}_read_latency"),
+        Unit::Microseconds,
+        MetricGoal::Decreasing,
+    );
+    let histogram_name = format!("eviction_throughput_benchmark.{phase}_read_duration");
+    let phase_start = Instant::now();
+    while phase_start.elapsed() < duration {
+        let Some(query) = keys.choose(&mut rand::thread_rng()) else {
+            break;
+        };
+        let start = Instant::now();
+        conn.execute(&query.prep, query.params.clone()).await?;
+        let elapsed = start.elapsed();
+        results_data.push(elapsed.as_micros() as f64);
+        hist.record(u64::try_from(elapsed.as_micros()).unwrap())
+            .unwrap();
+        benchmark_histogram!(
+            &histogram_name,
+            Microseconds,
+            "Duration of reads".into(),
+            elapsed.as_micros() as f64
+        );
+    }
+
+    info!(
+        phase,
+        reads = hist.len(),
+        "Read latency: p50 {}us, p99 {}us",
+        hist.value_at_quantile(0.5),
+        hist.value_at_quantile(0.99)
+    );
+    Ok(hist)
+}
@@ -95,9 +95,14 @@ pub mod utils;
 // Benchmarks
 mod cache_hit_benchmark;
 mod eviction_benchmark;
+mod eviction_throughput_benchmark;
+mod many_queries_benchmark;
 mod migration_benchmark;
+mod parameter_sweep_benchmark;
 mod query_benchmark;
 mod read_write_benchmark;
+mod read_your_writes_benchmark;
+mod recovery_benchmark;
 mod scale_connections;
 mod scale_views;
 mod single_query_benchmark;
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -24,7 +25,7 @@ use readyset_util::shutdown::ShutdownSender;
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 use tokio_postgres::config::Host;
-use tracing::warn;
+use tracing::{info, warn};
 
 const PUSH_GATEWAY_PUSH_INTERVAL: Duration = Duration::from_secs(5);
 
@@ -115,6 +116,11 @@ struct BenchmarkRunner {
 
     #[command(flatten)]
     graph_params: GraphParams,
+
+    /// Labels describing the deployment, populated from the deployment parameters once they've
+    /// been initialized
+    #[arg(skip)]
+    deployment_labels: HashMap<String, String>,
 }
 
 fn make_prometheus_url(base: &str, benchmark_name_label: &str, instance_label: &str) -> String {
@@ -125,6 +131,14 @@ fn make_prometheus_url(base: &str, benchmark_name_label: &str, instance_label: &
 }
 
 impl BenchmarkRunner {
+    /// All the labels to attach to the results of this benchmark run: the labels for the
+    /// benchmark itself, along with the labels describing the deployment
+    fn labels(&self) -> HashMap<String, String> {
+        let mut labels = self.benchmark_cmd.as_ref().unwrap().labels();
+        labels.extend(self.deployment_labels.clone());
+        labels
+    }
+
     pub async fn init_prometheus(&mut self) -> anyhow::Result<Option<PrometheusHandle>> {
         // Append the full pushgateway config path to the user provided
         // address.
@@ -147,7 +161,7 @@ impl BenchmarkRunner {
                 None,
                 None,
             )?;
-            for (key, value) in &self.benchmark_cmd.as_ref().unwrap().labels() {
+            for (key, value) in &self.labels() {
                 builder = builder.add_global_label(key, value);
             }
             let (recorder, exporter) = builder.build()?;
@@ -176,7 +190,7 @@ impl BenchmarkRunner {
             return None;
         }
 
-        let global_labels = Arc::new(benchmark.labels().into_iter().collect::<Vec<_>>());
+        let global_labels = Arc::new(self.labels().into_iter().collect::<Vec<_>>());
 
         let (tx, mut rx) = oneshot::channel();
         let handle = tokio::spawn(async move {
@@ -382,6 +396,13 @@ impl BenchmarkRunner {
         // the user. These arguments need not be passed by the arguments in the flattened structs
         // directly, and instead may be passed via YAML or via arguments like `--local`.
         let handle = self.initialize_from_args().await?;
+        let connect_wait = self.deployment_params.wait_for_target().await?;
+        info!(?connect_wait, "Target deployment is accepting connections");
+        self.deployment_labels = self.deployment_params.labels().await;
+        self.deployment_labels.insert(
+            "connect_wait_ms".to_owned(),
+            connect_wait.as_millis().to_string(),
+        );
 
         let cmd_as_yaml = serde_yaml_ng::to_string(&self.benchmark_cmd.as_ref().unwrap())?;
         let deployment_as_yaml = serde_yaml_ng::to_string(&self.deployment_params)?;
@@ -409,7 +430,7 @@ impl BenchmarkRunner {
             "{}/{}",
             self.deployment_params.target_conn_str, self.deployment_params.database_name
         );
-        readyset_ready(&readyset_target).await?;
+        readyset_ready(&readyset_target, &self.deployment_params.tls).await?;
 
         let bench_start_time = std::time::SystemTime::now();
 
@@ -418,7 +439,13 @@ impl BenchmarkRunner {
             if self.iterations > 1 {
                 println!("Iteration: {} ---------------------------", i);
                 benchmark_cmd.reset(&self.deployment_params).await?;
-                readyset_ready(&readyset_target).await?;
+                if !benchmark_cmd.verify_clean(&self.deployment_params).await? {
+                    bail!(
+                        "{} left caches behind after being reset",
+                        benchmark_cmd.name()
+                    );
+                }
+                readyset_ready(&readyset_target, &self.deployment_params.tls).await?;
             }
             let start_time = Instant::now();
             let result = benchmark_cmd.benchmark(&self.deployment_params).await?;
@@ -476,6 +503,7 @@ impl BenchmarkRunner {
                 .open(f)?;
             file.write_all(&serde_yaml_ng::to_string(&self.benchmark_cmd)?.into_bytes())?;
             file.write_all(&serde_yaml_ng::to_string(&self.deployment_params)?.into_bytes())?;
+            file.write_all(&serde_yaml_ng::to_string(&self.labels())?.into_bytes())?;
             file.write_all(format!("{:?}", results).as_bytes())?;
         }
 
@@ -0,0 +1,162 @@
+//! Measures read latency while many distinct queries are cached at the same time.
+//!
+//! This benchmark migrates `--num-queries` distinct variants of `--query`, then round-robins
+//! `--num-reads` reads across all of them, reporting both the aggregate latency of all reads and
+//! the latency of reads against each individual query. This exercises having many
+//! materializations and readers in the same deployment, which single-query benchmarks don't.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use database_utils::QueryableConnection;
+use hdrhistogram::Histogram;
+use metrics::Unit;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark_histogram;
+use crate::utils::generate::DataGenerator;
+use crate::utils::prometheus::ForwardPrometheusMetrics;
+use crate::utils::query::ArbitraryQueryParameters;
+
+#[derive(Parser, Clone, Serialize, Deserialize)]
+pub struct ManyQueriesBenchmark {
+    /// Parameters for the query to generate distinct variants of. Each variant is parameterized
+    /// the same way as the original query.
+    #[command(flatten)]
+    query: ArbitraryQueryParameters,
+
+    /// Install and generate from an arbitrary schema.
+    #[command(flatten)]
+    data_generator: DataGenerator,
+
+    /// The number of distinct queries to cache.
+    #[arg(long, default_value = "100")]
+    num_queries: usize,
+
+    /// The total number of reads to perform, spread evenly across all of the queries.
+    #[arg(long, default_value = "10000")]
+    num_reads: usize,
+}
+
+/// The name of the cache for the query with the given index
+fn cache_name(query: usize) -> String {
+    format!("many_queries_q{query}")
+}
+
+impl BenchmarkControl for ManyQueriesBenchmark {
+    async fn setup(&self, deployment: &DeploymentParameters) -> Result<()> {
+        self.data_generator
+            .install(&deployment.setup_conn_str)
+            .await?;
+        self.data_generator
+            .generate(&deployment.setup_conn_str)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset(&self, deployment: &DeploymentParameters) -> Result<()> {
+        let mut conn = deployment.connect_to_target().await?;
+        for i in 0..self.num_queries {
+            let _ = self.query.unmigrate_as(&mut conn, &cache_name(i)).await;
+        }
+        Ok(())
+    }
+
+    async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults> {
+        if self.num_queries == 0 {
+            bail!("--num-queries must be at least 1");
+        }
+
+        let mut conn = deployment.connect_to_target().await?;
+        let mut statements = Vec::with_capacity(self.num_queries);
+        let start = Instant::now();
+        for (i, shape) in self
+            .query
+            .distinct_shapes(self.num_queries)?
+            .into_iter()
+            .enumerate()
+        {
+            shape.migrate_as(&mut conn, &cache_name(i)).await?;
+            statements.push(shape.prepared_statement(&mut conn).await?);
+        }
+        info!(
+            num_queries = self.num_queries,
+            elapsed_ms = start.elapsed().as_millis(),
+            "Migrated queries"
+        );
+
+        let mut results = BenchmarkResults::new();
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        let mut per_query = vec![hist.clone(); statements.len()];
+        for read in 0..self.num_reads {
+            let i = read % statements.len();
+            let (statement, params) = statements[i].generate_query();
+            let start = Instant::now();
+            conn.execute(statement, params).await?;
+            let elapsed = start.elapsed();
+
+            let micros = u64::try_from(elapsed.as_micros()).unwrap();
+            hist.record(micros).unwrap();
+            per_query[i].record(micros).unwrap();
+            results.push(
+                "duration",
+                Unit::Microseconds,
+                MetricGoal::Decreasing,
+                micros as f64,
+            );
+            results.push(
+                &format!("query_{i}_duration"),
+                Unit::Microseconds,
+                MetricGoal::Decreasing,
+                micros as f64,
+            );
+            benchmark_histogram!(
+                "many_queries_benchmark.read_duration",
+                Microseconds,
+                "Duration of reads against any of the cached queries".into(),
+                elapsed.as_micros() as f64
+            );
+        }
+
+        for (i, hist) in per_query.iter().enumerate() {
+            info!(
+                query = i,
+                p50_us = hist.value_at_quantile(0.5),
+                p99_us = hist.value_at_quantile(0.99),
+                "Query latency"
+            );
+        }
+        info!(
+            p50_us = hist.value_at_quantile(0.5),
+            p99_us = hist.value_at_quantile(0.99),
+            "Aggregate latency"
+        );
+
+        Ok(results)
+    }
+
+    fn labels(&self) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+        labels.extend(self.query.labels());
+        labels.extend(self.data_generator.labels());
+        labels.insert("num_queries".to_string(), self.num_queries.to_string());
+        labels.insert("num_reads".to_string(), self.num_reads.to_string());
+        labels
+    }
+
+    fn forward_metrics(&self, _: &DeploymentParameters) -> Vec<ForwardPrometheusMetrics> {
+        vec![]
+    }
+
+    fn name(&self) -> &'static str {
+        "many_queries_benchmark"
+    }
+
+    fn data_generator(&mut self) -> Option<&mut DataGenerator> {
+        Some(&mut self.data_generator)
+    }
+}
@@ -0,0 +1,237 @@
+//! Measures how query latency scales with a single query parameter.
+//!
+//! Fixed-parameter benchmarks report one latency distribution for a query, which hides how that
+//! latency depends on the parameters it's executed with: a parameter controlling the selectivity of
+//! a range predicate, for example, determines how many rows each lookup returns. This benchmark
+//! sweeps the parameter at `--sweep-parameter` across `--sweep-steps` evenly spaced values between
+//! `--sweep-start` and `--sweep-end`, generating all other parameters as usual, and records a
+//! latency histogram for each value. The resulting latency-vs-value curve is written as CSV to
+//! `--output-csv`, for plotting.
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use database_utils::{DatabaseConnection, QueryableConnection};
+use hdrhistogram::Histogram;
+use metrics::Unit;
+use readyset_data::DfValue;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark_histogram;
+use crate::utils::generate::DataGenerator;
+use crate::utils::prometheus::ForwardPrometheusMetrics;
+use crate::utils::query::{ArbitraryQueryParameters, PreparedStatement};
+
+/// The quantiles reported for each value in the CSV output
+const CSV_QUANTILES: &[(&str, f64)] = &[("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+#[derive(Parser, Clone, Serialize, Deserialize)]
+pub struct ParameterSweepBenchmark {
+    /// Parameters to handle generating parameters for arbitrary queries.
+    #[command(flatten)]
+    query: ArbitraryQueryParameters,
+
+    /// Install and generate from an arbitrary schema.
+    #[command(flatten)]
+    data_generator: DataGenerator,
+
+    /// The zero-based position of the query parameter to sweep.
+    #[arg(long, default_value = "0")]
+    sweep_parameter: usize,
+
+    /// The first value of the swept parameter.
+    #[arg(long)]
+    sweep_start: i64,
+
+    /// The last value of the swept parameter.
+    #[arg(long)]
+    sweep_end: i64,
+
+    /// The number of evenly spaced values between `--sweep-start` and `--sweep-end` (inclusive) to
+    /// measure latency at.
+    #[arg(long, default_value = "10")]
+    sweep_steps: usize,
+
+    /// The number of times to execute the query at each value. Each value is executed once more
+    /// beforehand, without being measured, so that the measured executions are cache hits.
+    #[arg(long, default_value = "1000")]
+    executions_per_value: u32,
+
+    /// File to write the latency at each value to, as CSV.
+    #[arg(long)]
+    output_csv: Option<PathBuf>,
+}
+
+/// Returns `steps` evenly spaced values from `start` to `end`, inclusive of both, rounded to the
+/// nearest integer and with duplicates removed
+fn sweep_values(start: i64, end: i64, steps: usize) -> Vec<i64> {
+    if steps <= 1 {
+        return vec![start];
+    }
+    let step = (end - start) as f64 / (steps - 1) as f64;
+    let mut values = (0..steps)
+        .map(|i| start + (step * i as f64).round() as i64)
+        .collect::<Vec<_>>();
+    values.dedup();
+    values
+}
+
+impl BenchmarkControl for ParameterSweepBenchmark {
+    async fn setup(&self, deployment: &DeploymentParameters) -> Result<()> {
+        self.data_generator
+            .install(&deployment.setup_conn_str)
+            .await?;
+        self.data_generator
+            .generate(&deployment.setup_conn_str)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset(&self, deployment: &DeploymentParameters) -> Result<()> {
+        let mut conn = deployment.connect_to_target().await?;
+        let _ = self.query.unmigrate(&mut conn).await;
+        Ok(())
+    }
+
+    async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults> {
+        let mut conn = deployment.connect_to_target().await?;
+        let _ = self.query.migrate(&mut conn).await;
+        let mut statement = self.query.prepared_statement(&mut conn).await?;
+        if self.sweep_parameter >= statement.params.len() {
+            bail!(
+                "Cannot sweep parameter {} of a query with {} parameters",
+                self.sweep_parameter,
+                statement.params.len()
+            );
+        }
+
+        let mut results = BenchmarkResults::new();
+        let mut curve = vec![];
+        for value in sweep_values(self.sweep_start, self.sweep_end, self.sweep_steps) {
+            let hist = self
+                .run_queries(&mut conn, &mut statement, value, &mut results)
+                .await?;
+            info!(
+                value,
+                p50_us = hist.value_at_quantile(0.5),
+                p99_us = hist.value_at_quantile(0.99),
+                "Measured latency"
+            );
+            curve.push((value, hist));
+        }
+
+        if let Some(path) = &self.output_csv {
+            let mut csv = csv::Writer::from_writer(File::create(path)?);
+            let mut header = vec!["value", "executions", "mean_us"];
+            header.extend(CSV_QUANTILES.iter().map(|(name, _)| *name));
+            header.push("max_us");
+            csv.write_record(&header)?;
+            for (value, hist) in &curve {
+                let mut row = vec![
+                    value.to_string(),
+                    hist.len().to_string(),
+                    format!("{:.1}", hist.mean()),
+                ];
+                row.extend(
+                    CSV_QUANTILES
+                        .iter()
+                        .map(|(_, q)| hist.value_at_quantile(*q).to_string()),
+                );
+                row.push(hist.max().to_string());
+                csv.write_record(&row)?;
+            }
+            csv.flush()?;
+        }
+
+        Ok(results)
+    }
+
+    fn labels(&self) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+        labels.extend(self.query.labels());
+        labels.extend(self.data_generator.labels());
+        labels.insert(
+            "sweep_parameter".to_string(),
+            self.sweep_parameter.to_string(),
+        );
+        labels.insert(
+            "sweep_range".to_string(),
+            format!(
+                "{}..={} ({} steps)",
+                self.sweep_start, self.sweep_end, self.sweep_steps
+            ),
+        );
+        labels
+    }
+
+    fn forward_metrics(&self, _: &DeploymentParameters) -> Vec<ForwardPrometheusMetrics> {
+        vec![]
+    }
+
+    fn name(&self) -> &'static str {
+        "parameter_sweep_benchmark"
+    }
+
+    fn data_generator(&mut self) -> Option<&mut DataGenerator> {
+        Some(&mut self.data_generator)
+    }
+}
+
+impl ParameterSweepBenchmark {
+    /// Execute the query `executions_per_value` times with the swept parameter set to `value`,
+    /// returning a histogram of the latencies in microseconds
+    async fn run_queries(
+        &self,
+        conn: &mut DatabaseConnection,
+        statement: &mut PreparedStatement,
+        value: i64,
+        results: &mut BenchmarkResults,
+    ) -> Result<Histogram<u64>> {
+        let mut params = statement.generate_parameters();
+        params[self.sweep_parameter] = DfValue::from(value);
+        conn.execute(&statement.statement, params).await?;
+
+        let mut hist = Histogram::<u64>::new(3)?;
+        let duration = results.entry(
+            &format!("duration_at_{value}"),
+            Unit::Microseconds,
+            MetricGoal::Decreasing,
+        );
+        for _ in 0..self.executions_per_value {
+            let mut params = statement.generate_parameters();
+            params[self.sweep_parameter] = DfValue::from(value);
+            let start = Instant::now();
+            conn.execute(&statement.statement, params).await?;
+            let elapsed = start.elapsed();
+
+            duration.push(elapsed.as_micros() as f64);
+            hist.record(elapsed.as_micros() as u64)?;
+            benchmark_histogram!(
+                "parameter_sweep_benchmark.duration",
+                Microseconds,
+                "Duration of queries executed".into(),
+                elapsed.as_micros() as f64
+            );
+        }
+
+        Ok(hist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_values_are_evenly_spaced_and_inclusive() {
+        assert_eq!(sweep_values(0, 100, 5), vec![0, 25, 50, 75, 100]);
+        assert_eq!(sweep_values(10, 0, 3), vec![10, 5, 0]);
+        assert_eq!(sweep_values(0, 2, 5), vec![0, 1, 2]);
+        assert_eq!(sweep_values(7, 100, 1), vec![7]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Measures the time it takes for a write to a base table to become visible in a cached query
+//! which depends on it.
+//!
+//! For each write, a new row is inserted into `--table` through the upstream database, and then a
+//! cached query looking up the row by `--key-column` is read from the target repeatedly until the
+//! row is returned. The time between the insert completing and the row becoming visible is
+//! reported as the `lag` metric.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use database_utils::QueryableConnection;
+use metrics::Unit;
+use nom_sql::{CacheInner, CreateCacheStatement, DialectDisplay, Expr};
+use query_generator::{ColumnName, TableName};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark_histogram;
+use crate::utils::generate::DataGenerator;
+use crate::utils::prometheus::{forward, ForwardPrometheusMetrics};
+use crate::write_benchmark::query_indexed_by_columns;
+
+#[derive(Parser, Clone, Serialize, Deserialize)]
+pub struct ReadYourWritesBenchmark {
+    #[command(flatten)]
+    data_generator: DataGenerator,
+
+    /// Table to insert rows into. Must be a table in `--schema`
+    #[arg(long)]
+    table: String,
+
+    /// Column of `--table` to look up newly inserted rows by in the cached query. Should be
+    /// unique for each generated row, such as the table's primary key
+    #[arg(long)]
+    key_column: ColumnName,
+
+    /// Number of writes to issue
+    #[arg(long, default_value = "1000")]
+    writes: u32,
+
+    /// Number of seconds to wait for each write to become visible before failing the benchmark
+    #[arg(long, default_value = "10", value_parser = crate::utils::seconds_as_str_to_duration)]
+    visibility_timeout: Duration,
+}
+
+impl BenchmarkControl for ReadYourWritesBenchmark {
+    async fn setup(&self, deployment: &DeploymentParameters) -> Result<()> {
+        self.data_generator
+            .install(&deployment.setup_conn_str)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset(&self, _: &DeploymentParameters) -> Result<()> {
+        Err(anyhow!("reset unsupported"))
+    }
+
+    async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults> {
+        let mut upstream = deployment.connect_to_setup().await?;
+        let mut db = deployment.connect_to_target().await?;
+        let dialect = db.dialect();
+
+        let mut data_spec = self
+            .data_generator
+            .generate(&deployment.setup_conn_str)
+            .await?;
+        info!("Rows inserted");
+
+        let table_name = TableName::from(self.table.as_str());
+        let table_spec = data_spec
+            .tables
+            .get_mut(&table_name)
+            .ok_or_else(|| anyhow!("--table {} not found in --schema", self.table))?;
+        if !table_spec.table.columns.contains_key(&self.key_column) {
+            bail!("--key-column {} not found in --table", self.key_column);
+        }
+
+        let query =
+            query_indexed_by_columns(&table_spec.table, vec![self.key_column.clone()], dialect);
+        let create_cache = CreateCacheStatement {
+            name: None,
+            inner: Ok(CacheInner::Statement(Box::new(query.clone()))),
+            always: false,
+            concurrently: false,
+            dry_run: false,
+            unparsed_create_cache_statement: None,
+        };
+        db.query_drop(create_cache.display(dialect).to_string())
+            .await?;
+        let select = db.prepare(query.display(dialect).to_string()).await?;
+        debug!("View created");
+
+        let columns = table_spec.table.columns.keys().cloned().collect::<Vec<_>>();
+        let mut hist = hdrhistogram::Histogram::<u64>::new(3).unwrap();
+        let mut results = BenchmarkResults::new();
+        let lag = results.entry("lag", Unit::Microseconds, MetricGoal::Decreasing);
+        for i in 0..self.writes {
+            // Generate rows past the end of the ones we've already written, so their keys don't
+            // collide with any existing row
+            let mut row = table_spec
+                .table
+                .generate_data_from_index(1, table_spec.num_rows + i as usize, false)
+                .remove(0);
+            let key = row[&self.key_column].clone();
+            let insert = nom_sql::InsertStatement {
+                table: table_name.clone().into(),
+                fields: Some(columns.iter().map(|c| c.clone().into()).collect()),
+                data: vec![columns
+                    .iter()
+                    .map(|c| Ok(Expr::Literal(row.remove(c).unwrap().try_into()?)))
+                    .collect::<Result<_>>()?],
+                ignore: false,
+                on_duplicate: None,
+            };
+            upstream
+                .query_drop(insert.display(dialect).to_string())
+                .await?;
+
+            let start = Instant::now();
+            while db.execute(&select, [key.clone()]).await?.is_empty() {
+                if start.elapsed() > self.visibility_timeout {
+                    bail!(
+                        "Write {i} did not become visible within {:?}",
+                        self.visibility_timeout
+                    );
+                }
+            }
+            let elapsed = start.elapsed();
+
+            lag.push(elapsed.as_micros() as f64);
+            hist.record(u64::try_from(elapsed.as_micros()).unwrap())
+                .unwrap();
+            benchmark_histogram!(
+                "read_your_writes.lag",
+                Microseconds,
+                "Time between a write completing upstream and it being visible in a cached query"
+                    .into(),
+                elapsed.as_micros() as f64
+            );
+        }
+
+        info!(
+            "Write-to-visible lag: p50 {}us, p90 {}us, p99 {}us, max {}us",
+            hist.value_at_quantile(0.5),
+            hist.value_at_quantile(0.9),
+            hist.value_at_quantile(0.99),
+            hist.max()
+        );
+
+        Ok(results)
+    }
+
+    fn labels(&self) -> HashMap<String, String> {
+        let mut labels = self.data_generator.labels();
+        labels.insert("table".to_string(), self.table.clone());
+        labels.insert("key_column".to_string(), self.key_column.to_string());
+        labels.insert("writes".to_string(), self.writes.to_string());
+        labels
+    }
+
+    fn forward_metrics(&self, deployment: &DeploymentParameters) -> Vec<ForwardPrometheusMetrics> {
+        vec![forward(
+            deployment.prometheus_endpoint.clone().unwrap(),
+            |metric| metric.name.starts_with("packet_write_propagation_time_us"),
+        )]
+    }
+
+    fn name(&self) -> &'static str {
+        "read_your_writes_benchmark"
+    }
+
+    fn data_generator(&mut self) -> Option<&mut DataGenerator> {
+        Some(&mut self.data_generator)
+    }
+}
@@ -0,0 +1,281 @@
+//! Measures how long it takes for a ReadySet deployment to recover after a restart.
+//!
+//! The controller doesn't persist which nodes are materialized and how: that state is rebuilt on
+//! startup, and full materializations are repopulated via replay. This benchmark caches an
+//! increasing number of distinct variants of `--query`, restarting the deployment with
+//! `--restart-command` each time it reaches one of the `--num-queries` counts. For each restart it
+//! reports how long it took until the controller was ready again, and until every cached query was
+//! served from ReadySet again, along with how many materializations had to be rebuilt.
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use database_utils::QueryableConnection;
+use metrics::Unit;
+use readyset_client::consensus::AuthorityType;
+use readyset_client::ReadySetHandle;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark_histogram;
+use crate::utils::generate::DataGenerator;
+use crate::utils::prometheus::ForwardPrometheusMetrics;
+use crate::utils::query::{served_from_cache, ArbitraryQueryParameters};
+
+/// How long to wait between checks for whether the deployment has recovered
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Parser, Clone, Serialize, Deserialize)]
+pub struct RecoveryBenchmark {
+    /// Parameters for the query to generate distinct variants of.
+    #[command(flatten)]
+    query: ArbitraryQueryParameters,
+
+    /// Install and generate from an arbitrary schema.
+    #[command(flatten)]
+    data_generator: DataGenerator,
+
+    /// Comma-separated list of the numbers of distinct queries to have cached when restarting the
+    /// deployment. The deployment is restarted once for each count, with queries added in between.
+    #[arg(long, value_delimiter = ',', default_value = "10,100")]
+    num_queries: Vec<usize>,
+
+    /// Shell command which restarts the ReadySet deployment, for example
+    /// `docker restart readyset`. Should return once the restart has been initiated.
+    #[arg(long)]
+    restart_command: String,
+
+    /// Maximum number of seconds to wait for the deployment to recover after each restart.
+    #[arg(long, default_value = "600", value_parser = crate::utils::seconds_as_str_to_duration)]
+    recovery_timeout: Duration,
+
+    /// The type of authority used by the ReadySet deployment, for checking when the controller
+    /// is ready and how many materializations it has.
+    #[arg(
+        long,
+        env = "AUTHORITY",
+        default_value = "consul",
+        value_parser = ["consul", "standalone", "local"]
+    )]
+    authority: String,
+
+    /// Address of the authority used by the ReadySet deployment.
+    #[arg(long, env = "AUTHORITY_ADDRESS", default_value = "127.0.0.1:8500")]
+    authority_address: String,
+
+    /// Name of the ReadySet deployment.
+    #[arg(long, env = "DEPLOYMENT")]
+    deployment: String,
+}
+
+/// The name of the cache for the query with the given index
+fn cache_name(query: usize) -> String {
+    format!("recovery_q{query}")
+}
+
+/// Returns the given query counts in the order to restart at, without duplicates
+fn restart_points(num_queries: &[usize]) -> Vec<usize> {
+    let mut points = num_queries.to_vec();
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+impl RecoveryBenchmark {
+    async fn handle(&self) -> Result<ReadySetHandle> {
+        let authority = AuthorityType::from_str(&self.authority)?
+            .to_authority(&self.authority_address, &self.deployment);
+        Ok(ReadySetHandle::new(authority).await)
+    }
+
+    async fn restart(&self) -> Result<()> {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.restart_command)
+            .status()
+            .await?;
+        if !status.success() {
+            bail!("Restart command exited with {status}");
+        }
+        Ok(())
+    }
+
+    /// Returns true if every one of the given queries can be executed, and is served from
+    /// ReadySet rather than proxied upstream
+    async fn all_served_from_cache(
+        &self,
+        deployment: &DeploymentParameters,
+        queries: &[ArbitraryQueryParameters],
+    ) -> Result<bool> {
+        let mut conn = deployment.connect_to_target().await?;
+        for query in queries {
+            let mut statement = query.prepared_statement(&mut conn).await?;
+            let (statement, params) = statement.generate_query();
+            conn.execute(statement, params).await?;
+            if !served_from_cache(&mut conn).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Wait until every one of the given queries is served from ReadySet, returning an error if
+    /// that takes longer than `--recovery-timeout` since `start`
+    async fn wait_until_served(
+        &self,
+        deployment: &DeploymentParameters,
+        queries: &[ArbitraryQueryParameters],
+        start: Instant,
+    ) -> Result<()> {
+        loop {
+            match self.all_served_from_cache(deployment, queries).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                // The adapter may not be accepting connections yet
+                Err(error) => debug!(%error, "Queries not yet served"),
+            }
+            if start.elapsed() > self.recovery_timeout {
+                bail!("Timed out waiting for all queries to be served from ReadySet");
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl BenchmarkControl for RecoveryBenchmark {
+    async fn setup(&self, deployment: &DeploymentParameters) -> Result<()> {
+        self.data_generator
+            .install(&deployment.setup_conn_str)
+            .await?;
+        self.data_generator
+            .generate(&deployment.setup_conn_str)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset(&self, deployment: &DeploymentParameters) -> Result<()> {
+        let max_queries = self.num_queries.iter().copied().max().unwrap_or(0);
+        let mut conn = deployment.connect_to_target().await?;
+        for i in 0..max_queries {
+            let _ = self.query.unmigrate_as(&mut conn, &cache_name(i)).await;
+        }
+        Ok(())
+    }
+
+    async fn benchmark(&self, deployment: &DeploymentParameters) -> Result<BenchmarkResults> {
+        let points = restart_points(&self.num_queries);
+        let Some(&max_queries) = points.last() else {
+            bail!("--num-queries must not be empty");
+        };
+        if max_queries == 0 {
+            bail!("--num-queries must be at least 1");
+        }
+
+        let shapes = self.query.distinct_shapes(max_queries)?;
+        let mut results = BenchmarkResults::new();
+        let mut migrated = 0;
+        for num_queries in points.into_iter().filter(|n| *n > 0) {
+            let mut conn = deployment.connect_to_target().await?;
+            for (i, shape) in shapes.iter().enumerate().take(num_queries).skip(migrated) {
+                shape.migrate_as(&mut conn, &cache_name(i)).await?;
+            }
+            migrated = num_queries;
+            let queries = &shapes[..num_queries];
+            self.wait_until_served(deployment, queries, Instant::now())
+                .await?;
+
+            let materializations = self.handle().await?.materialization_info().await?.len();
+            info!(num_queries, materializations, "Restarting deployment");
+            let start = Instant::now();
+            self.restart().await?;
+
+            let mut handle = self.handle().await?;
+            tokio::time::timeout(self.recovery_timeout, handle.ready())
+                .await
+                .map_err(|_| anyhow!("Timed out waiting for the controller to be ready"))??;
+            let controller_ready = start.elapsed();
+
+            self.wait_until_served(deployment, queries, start).await?;
+            let recovered = start.elapsed();
+
+            info!(
+                num_queries,
+                materializations,
+                controller_ready_ms = controller_ready.as_millis(),
+                recovered_ms = recovered.as_millis(),
+                "Recovered"
+            );
+            results.push(
+                &format!("controller_ready_{num_queries}_queries"),
+                Unit::Milliseconds,
+                MetricGoal::Decreasing,
+                controller_ready.as_millis() as f64,
+            );
+            results.push(
+                &format!("recovery_{num_queries}_queries"),
+                Unit::Milliseconds,
+                MetricGoal::Decreasing,
+                recovered.as_millis() as f64,
+            );
+            results.push(
+                &format!("materializations_{num_queries}_queries"),
+                Unit::Count,
+                MetricGoal::Decreasing,
+                materializations as f64,
+            );
+            benchmark_histogram!(
+                "recovery_benchmark.recovery_duration",
+                Milliseconds,
+                "Time from restarting the deployment until all cached queries are served from \
+                 ReadySet again"
+                    .into(),
+                recovered.as_millis() as f64
+            );
+        }
+
+        Ok(results)
+    }
+
+    fn labels(&self) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+        labels.extend(self.query.labels());
+        labels.extend(self.data_generator.labels());
+        labels.insert(
+            "num_queries".to_string(),
+            restart_points(&self.num_queries)
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        labels
+    }
+
+    fn forward_metrics(&self, _: &DeploymentParameters) -> Vec<ForwardPrometheusMetrics> {
+        vec![]
+    }
+
+    fn name(&self) -> &'static str {
+        "recovery_benchmark"
+    }
+
+    fn data_generator(&mut self) -> Option<&mut DataGenerator> {
+        Some(&mut self.data_generator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_points_sorted_and_deduplicated() {
+        assert_eq!(restart_points(&[100, 10, 100, 1000]), vec![10, 100, 1000]);
+        assert!(restart_points(&[]).is_empty());
+    }
+}
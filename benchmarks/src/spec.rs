@@ -272,6 +272,7 @@ impl WorkloadSpec {
                     unparsed_create_cache_statement: None,
                     always: false,
                     concurrently: false,
+                    dry_run: false,
                 };
 
                 let _ = conn
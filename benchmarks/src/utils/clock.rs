@@ -0,0 +1,90 @@
+//! Clocks for timing the queries run by benchmarks.
+//!
+//! Benchmarks take their timings from a [`Clock`] rather than calling [`Instant::now`] directly, so
+//! that logic which aggregates those timings can be tested deterministically with a [`MockClock`],
+//! without sleeping.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A source of monotonic time
+pub trait Clock {
+    /// Returns the amount of time that has elapsed since some fixed point in the past. Successive
+    /// calls must never go backwards.
+    fn now(&self) -> Duration;
+
+    /// Returns the amount of time that has elapsed since `earlier`, a value previously returned
+    /// from [`now`](Clock::now)
+    fn elapsed_since(&self, earlier: Duration) -> Duration {
+        self.now().saturating_sub(earlier)
+    }
+}
+
+/// A [`Clock`] which measures real time using [`Instant`]
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    origin: Instant,
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A [`Clock`] whose time only changes when it's explicitly [advanced](MockClock::advance)
+#[derive(Debug, Default)]
+pub struct MockClock {
+    nanos: AtomicU64,
+}
+
+impl MockClock {
+    /// Move the time returned by this clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(
+            u64::try_from(by.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_only_moves_when_advanced() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        assert_eq!(clock.elapsed_since(start), Duration::ZERO);
+
+        clock.advance(Duration::from_millis(5));
+        clock.advance(Duration::from_micros(250));
+        assert_eq!(clock.elapsed_since(start), Duration::from_micros(5250));
+    }
+
+    #[test]
+    fn monotonic_clock_never_goes_backwards() {
+        let clock = MonotonicClock::default();
+        let mut last = clock.now();
+        for _ in 0..1000 {
+            let now = clock.now();
+            assert!(now >= last);
+            last = now;
+        }
+    }
+}
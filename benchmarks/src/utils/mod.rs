@@ -1,21 +1,26 @@
+use std::collections::HashMap;
 use std::future::Future;
 use std::num::ParseIntError;
 use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::Result;
-use database_utils::{DatabaseURL, QueryableConnection};
+use database_utils::QueryableConnection;
 use nom_sql::DialectDisplay;
 use readyset_data::DfValue;
 use tracing::info;
 
+use self::tls::TlsParameters;
+
 pub mod backend;
+pub mod clock;
 pub mod generate;
 pub mod multi_thread;
 pub mod path;
 pub mod prometheus;
 pub mod query;
 pub mod spec;
+pub mod tls;
 
 pub fn us_to_ms(us: u64) -> f64 {
     us as f64 / 1000.
@@ -65,11 +70,11 @@ macro_rules! make_key {
 }
 
 /// Wait for replication to finish by lazy looping over "SHOW READYSET STATUS"
-pub async fn readyset_ready(target: &str) -> anyhow::Result<()> {
+pub async fn readyset_ready(target: &str, tls: &TlsParameters) -> anyhow::Result<()> {
     info!("Waiting for the target database to be ready...");
     // First attempt to connect to the readyset adapter at all
     let mut conn = loop {
-        match DatabaseURL::from_str(target)?.connect(None).await {
+        match tls.connect(target).await {
             Ok(conn) => break conn,
             _ => tokio::time::sleep(Duration::from_secs(1)).await,
         }
@@ -105,6 +110,39 @@ pub async fn readyset_ready(target: &str) -> anyhow::Result<()> {
     }
 }
 
+/// Query the release version and commit ID of the ReadySet deployment at `target` using "SHOW
+/// READYSET VERSION", returned as the `readyset_version` and `readyset_commit_id` labels
+pub async fn readyset_version(
+    target: &str,
+    tls: &TlsParameters,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut conn = tls.connect(target).await?;
+    // We have to use simple query here because ReadySet does not support preparing `SHOW` queries
+    let rows = Vec::<Vec<DfValue>>::try_from(
+        conn.simple_query(
+            nom_sql::ShowStatement::ReadySetVersion
+                .display(nom_sql::Dialect::MySQL)
+                .to_string(),
+        )
+        .await?,
+    )?;
+
+    rows.into_iter()
+        .filter_map(|row| {
+            let label = match <&str>::try_from(row.first()?).ok()? {
+                "release version" => "readyset_version",
+                "commit id" => "readyset_commit_id",
+                _ => return None,
+            };
+            Some(
+                String::try_from(row.get(1)?.clone())
+                    .map(|value| (label.to_owned(), value))
+                    .map_err(Into::into),
+            )
+        })
+        .collect()
+}
+
 #[macro_export]
 macro_rules! benchmark_gauge {
     ($name: expr, $unit: ident, $description: expr, $value: expr $(, $label_key: expr => $label_value: expr)*) => {
@@ -14,7 +14,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
 use data_generator::{ColumnGenerator, DistributionAnnotation};
 use database_utils::{DatabaseConnection, DatabaseStatement, QueryableConnection};
@@ -24,6 +24,17 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::utils::path::benchmark_path;
 
+/// Returns true if the last statement executed on `conn` was served from ReadySet, rather than
+/// proxied to the upstream database, according to `EXPLAIN LAST STATEMENT`
+pub async fn served_from_cache(conn: &mut DatabaseConnection) -> Result<bool> {
+    let rows = Vec::<Vec<DfValue>>::try_from(conn.simple_query("EXPLAIN LAST STATEMENT").await?)?;
+    let destination = rows
+        .first()
+        .and_then(|row| row.first())
+        .ok_or_else(|| anyhow!("EXPLAIN LAST STATEMENT returned no rows"))?;
+    Ok(*destination == "readyset".into())
+}
+
 /// The number of times we will try to generate a cache miss using the random
 /// generator before giving up. It is possible that we have generated cache hits
 /// on all values in the table, and as a result, will no longer be able to
@@ -200,9 +211,37 @@ impl ArbitraryQueryParameters {
         labels
     }
 
+    /// Returns `count` distinct variants of the query, each of which selects an additional literal
+    /// column identifying the variant, so that each variant is cached separately.
+    pub fn distinct_shapes(&self, count: usize) -> Result<Vec<ArbitraryQueryParameters>> {
+        let stmt = match nom_sql::parse_query(self.dialect, self.query.query()) {
+            Ok(nom_sql::SqlQuery::Select(stmt)) => stmt,
+            _ => bail!("Can only generate shapes from SELECT statements"),
+        };
+
+        Ok((0..count)
+            .map(|i| {
+                let mut shape = stmt.clone();
+                shape.fields.push(nom_sql::FieldDefinitionExpr::Expr {
+                    expr: nom_sql::Expr::Literal(Literal::from(i as u64)),
+                    alias: Some("shape".into()),
+                });
+                Self {
+                    query: QuerySpec::Query(shape.display(self.dialect).to_string()),
+                    ..self.clone()
+                }
+            })
+            .collect())
+    }
+
     pub async fn migrate(&self, conn: &mut DatabaseConnection) -> Result<()> {
-        // Remove any query q if it is exists before migration.
-        let _ = self.unmigrate(conn).await;
+        self.migrate_as(conn, "q").await
+    }
+
+    /// Migrate the query as a cache with the given name
+    pub async fn migrate_as(&self, conn: &mut DatabaseConnection, name: &str) -> Result<()> {
+        // Remove any query with this name if it is exists before migration.
+        let _ = self.unmigrate_as(conn, name).await;
 
         let stmt = match nom_sql::parse_query(nom_sql::Dialect::MySQL, self.query.query()) {
             Ok(nom_sql::SqlQuery::Select(stmt)) => stmt,
@@ -210,10 +249,11 @@ impl ArbitraryQueryParameters {
         };
 
         let create_cache_query = nom_sql::CreateCacheStatement {
-            name: Some("q".into()),
+            name: Some(name.into()),
             inner: Ok(nom_sql::CacheInner::Statement(Box::new(stmt))),
             always: false,
             concurrently: false,
+            dry_run: false,
             unparsed_create_cache_statement: None,
         };
 
@@ -224,10 +264,32 @@ impl ArbitraryQueryParameters {
     }
 
     pub async fn unmigrate(&self, conn: &mut DatabaseConnection) -> anyhow::Result<()> {
-        let stmt = "DROP CACHE q";
-        conn.query_drop(stmt).await?;
+        self.unmigrate_as(conn, "q").await
+    }
+
+    /// Drop the cache with the given name
+    pub async fn unmigrate_as(
+        &self,
+        conn: &mut DatabaseConnection,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        conn.query_drop(format!("DROP CACHE {name}")).await?;
         Ok(())
     }
+
+    /// Returns true if a cache with the given name exists, according to `SHOW CACHES`
+    pub async fn is_migrated_as(
+        &self,
+        conn: &mut DatabaseConnection,
+        name: &str,
+    ) -> anyhow::Result<bool> {
+        // ReadySet does not support preparing `SHOW` queries
+        let caches = Vec::<Vec<DfValue>>::try_from(conn.simple_query("SHOW CACHES").await?)?;
+        Ok(caches.iter().any(|row| {
+            row.get(1)
+                .map_or(false, |cache_name| *cache_name == name.into())
+        }))
+    }
 }
 
 /// Utility wrapper around Vec<DistributionAnnotation>. A list of DistributionAnnotation delimited
@@ -325,10 +387,16 @@ impl PreparedStatement {
 
     pub fn generate_ad_hoc_query(&mut self) -> String {
         let params = self.generate_parameters();
+        self.render(&params)
+    }
+
+    /// Returns the text of the query with each of its placeholders replaced by the corresponding
+    /// value in `params`, so that it can be run without being prepared
+    pub fn render(&self, params: &[DfValue]) -> String {
         let q = self
             .query
             .split('?')
-            .zip(&params)
+            .zip(params)
             .map(|(text, value)| {
                 // TODO(ethan): Eventually, we should replace the query construction in this method
                 // with a query builder library of some sort to ensure that all values are escaped
@@ -405,15 +473,19 @@ impl GeneratorSet {
 pub struct Query {
     pub prep: DatabaseStatement,
     pub params: Vec<String>,
+    /// The text of the query with the parameters substituted in, for executing it without
+    /// preparing it
+    pub text: String,
 }
 
-// Values cannot be hashed so we turn them into sql text before putting
-// them in the Query struct.
-impl From<(&DatabaseStatement, Vec<DfValue>)> for Query {
-    fn from(v: (&DatabaseStatement, Vec<DfValue>)) -> Query {
+impl Query {
+    // Values cannot be hashed so we turn them into sql text before putting
+    // them in the Query struct.
+    fn new(prepared_statement: &PreparedStatement, params: Vec<DfValue>) -> Query {
         Query {
-            prep: v.0.clone(),
-            params: v.1.into_iter().map(|s| s.to_string()).collect(),
+            prep: prepared_statement.statement.clone(),
+            text: prepared_statement.render(&params),
+            params: params.into_iter().map(|s| s.to_string()).collect(),
         }
     }
 }
@@ -441,7 +513,8 @@ impl CachingQueryGenerator {
     pub fn generate_cache_miss(&mut self) -> Result<Query> {
         let mut attempts = 0;
         while attempts < MAX_RANDOM_GENERATIONS {
-            let q = Query::from(self.prepared_statement.generate_query());
+            let params = self.prepared_statement.generate_parameters();
+            let q = Query::new(&self.prepared_statement, params);
             if !self.seen.contains(&q) {
                 self.seen.insert(q.clone());
                 return Ok(q);
@@ -470,6 +543,29 @@ impl CachingQueryGenerator {
 mod tests {
     use super::*;
 
+    #[test]
+    fn distinct_shapes_select_a_literal() {
+        let params = ArbitraryQueryParameters::new(
+            QuerySpec::Query("SELECT a FROM t WHERE b = ?".to_owned()),
+            None,
+            None,
+            Dialect::MySQL,
+        );
+        let shapes = params
+            .distinct_shapes(2)
+            .unwrap()
+            .into_iter()
+            .map(|shape| shape.query.query().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shapes,
+            vec![
+                "SELECT `a`, 0 AS `shape` FROM `t` WHERE (`b` = ?)",
+                "SELECT `a`, 1 AS `shape` FROM `t` WHERE (`b` = ?)",
+            ]
+        );
+    }
+
     #[test]
     fn parse_annotation_specs() {
         let q = "
@@ -110,7 +110,7 @@ fn columns_for_indices(num_indices: usize, table: &TableSpec) -> Result<Vec<Vec<
     Ok(res)
 }
 
-fn query_indexed_by_columns(
+pub(crate) fn query_indexed_by_columns(
     table: &TableSpec,
     cols: Vec<ColumnName>,
     dialect: Dialect,
@@ -156,6 +156,7 @@ async fn create_indices(
                 inner: Ok(CacheInner::Statement(Box::new(query))),
                 always: false,
                 concurrently: false,
+                dry_run: false,
                 unparsed_create_cache_statement: None,
             };
             conn.query_drop(create_cache.display(conn.dialect()).to_string())
@@ -10,6 +10,7 @@ use std::fmt::{self, Debug};
 use std::iter::FromIterator;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::vec;
 
 use ahash::RandomState;
@@ -18,6 +19,7 @@ use derive_more::From;
 use hashbag::HashBag;
 use itertools::Either;
 pub use partial_map::PartialMap;
+use rand::Rng;
 use readyset_client::debug::info::KeyCount;
 use readyset_client::internal::Index;
 use readyset_client::{KeyComparison, PersistencePoint};
@@ -34,6 +36,86 @@ pub use crate::persistent_state::{
     PersistentStateHandle, SnapshotMode,
 };
 
+/// How a [`MemoryState`] chooses which keys to evict when asked to free some number of bytes via
+/// [`State::evict_bytes`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvictionGranularity {
+    /// Evict individual keys chosen at random from a random index. This is the default, and suits
+    /// materializations which are looked up by point keys.
+    #[default]
+    Key,
+    /// Evict a run of adjacent keys from a BTree index, if the state has one. This suits
+    /// materializations which are looked up by range, since the evicted keys can then be filled
+    /// again by a single range replay rather than leaving holes throughout the index.
+    Range,
+}
+
+impl fmt::Display for EvictionGranularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key => write!(f, "key"),
+            Self::Range => write!(f, "range"),
+        }
+    }
+}
+
+/// A count of the lookups performed into a materialization, which can be incremented through a
+/// shared reference.
+///
+/// Since even a single relaxed atomic increment per lookup can be measurable on very hot
+/// materializations, lookups can also be [sampled](LookupCounter::set_sample_rate), in which case
+/// only one in every `sample_rate` recordings is counted, scaled up by the sample rate - making the
+/// count an estimate.
+///
+/// Cloning a counter yields a new counter starting from zero (with the same sample rate), since the
+/// clone counts lookups into a different copy of the materialization.
+#[derive(Debug, Default)]
+pub struct LookupCounter {
+    count: AtomicU64,
+    /// Only one in every `sample_rate` recordings is counted. Values of 0 and 1 both count every
+    /// recording.
+    sample_rate: AtomicU64,
+}
+
+impl Clone for LookupCounter {
+    fn clone(&self) -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            sample_rate: AtomicU64::new(self.sample_rate()),
+        }
+    }
+}
+
+impl LookupCounter {
+    /// Record that `lookups` lookups were performed
+    pub fn record(&self, lookups: u64) {
+        let sample_rate = self.sample_rate();
+        if sample_rate == 1 {
+            self.count.fetch_add(lookups, Ordering::Relaxed);
+        } else if rand::thread_rng().gen_range(0..sample_rate) == 0 {
+            self.count
+                .fetch_add(lookups.saturating_mul(sample_rate), Ordering::Relaxed);
+        }
+    }
+
+    /// Return the total number of lookups recorded so far. If lookups are being sampled, this is
+    /// an estimate.
+    pub fn get(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the effective sample rate, which is always at least 1
+    pub fn sample_rate(&self) -> u64 {
+        self.sample_rate.load(Ordering::Relaxed).max(1)
+    }
+
+    /// Only count one in every `sample_rate` recordings of lookups from now on. Lookups which were
+    /// already recorded are kept, since they've already been scaled by the sample rate at the time.
+    pub fn set_sample_rate(&self, sample_rate: u64) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+}
+
 /// Information about state evicted via a call to [`State::evict_bytes`]
 pub struct EvictBytesResult<'a> {
     /// The index that was evicted from
@@ -242,6 +324,23 @@ pub trait State: SizeOf + Send {
     /// state
     fn row_count(&self) -> usize;
 
+    /// Return the total number of lookups (of any kind) performed into this state since it was
+    /// created
+    fn lookup_count(&self) -> u64;
+
+    /// Only count one in every `sample_rate` lookups into this state from now on, scaling
+    /// [`lookup_count`](State::lookup_count) accordingly (see [`LookupCounter`])
+    fn set_lookup_sample_rate(&self, sample_rate: u64);
+
+    /// Return (a potentially inaccurate estimate of) the number of bytes used by each of the
+    /// indices in this state, which add up to its [`deep_size_of`](SizeOf::deep_size_of).
+    ///
+    /// Returns `None` if this state can only report its total size, and not how that size is split
+    /// between its indices.
+    fn index_sizes(&self) -> Option<Vec<(Index, u64)>> {
+        None
+    }
+
     /// Return a handle that allows streaming a consistent snapshot of all records within this
     /// state. Panics if the state is only partially materialized.
     fn all_records(&self) -> AllRecords;
@@ -467,6 +566,32 @@ impl State for MaterializedNodeState {
         }
     }
 
+    fn lookup_count(&self) -> u64 {
+        match self {
+            MaterializedNodeState::Memory(ms) => ms.lookup_count(),
+            MaterializedNodeState::Persistent(ps) => ps.lookup_count(),
+            MaterializedNodeState::PersistentReadHandle(rh) => rh.lookup_count(),
+        }
+    }
+
+    fn set_lookup_sample_rate(&self, sample_rate: u64) {
+        match self {
+            MaterializedNodeState::Memory(ms) => ms.set_lookup_sample_rate(sample_rate),
+            MaterializedNodeState::Persistent(ps) => ps.set_lookup_sample_rate(sample_rate),
+            MaterializedNodeState::PersistentReadHandle(rh) => {
+                rh.set_lookup_sample_rate(sample_rate)
+            }
+        }
+    }
+
+    fn index_sizes(&self) -> Option<Vec<(Index, u64)>> {
+        match self {
+            MaterializedNodeState::Memory(ms) => ms.index_sizes(),
+            MaterializedNodeState::Persistent(ps) => ps.index_sizes(),
+            MaterializedNodeState::PersistentReadHandle(rh) => rh.index_sizes(),
+        }
+    }
+
     fn all_records(&self) -> AllRecords {
         match self {
             MaterializedNodeState::Memory(ms) => ms.all_records(),
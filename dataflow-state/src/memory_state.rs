@@ -15,8 +15,9 @@ use tracing::trace;
 use crate::keyed_state::KeyedState;
 use crate::single_state::SingleState;
 use crate::{
-    AllRecords, EvictBytesResult, EvictKeysResult, EvictRandomResult, LookupResult,
-    PersistencePoint, PointKey, RangeKey, RangeLookupResult, RecordResult, Row, Rows, State,
+    AllRecords, EvictBytesResult, EvictKeysResult, EvictRandomResult, EvictionGranularity,
+    LookupCounter, LookupResult, PersistencePoint, PointKey, RangeKey, RangeLookupResult,
+    RecordResult, Row, Rows, State,
 };
 
 #[derive(Default)]
@@ -30,6 +31,17 @@ pub struct MemoryState {
     replication_offset: Option<ReplicationOffset>,
     /// If this state is fully materialized, has it received a complete full replay yet?
     pub(crate) replay_done: bool,
+    /// How to choose keys to evict in [`State::evict_bytes`]
+    eviction_granularity: EvictionGranularity,
+    /// The number of lookups performed into this state
+    lookups: LookupCounter,
+}
+
+impl MemoryState {
+    /// Set how this state chooses which keys to evict when asked to free some number of bytes
+    pub fn set_eviction_granularity(&mut self, eviction_granularity: EvictionGranularity) {
+        self.eviction_granularity = eviction_granularity;
+    }
 }
 
 impl SizeOf for MemoryState {
@@ -168,6 +180,37 @@ impl State for MemoryState {
         self.state.iter().map(SingleState::row_count).sum()
     }
 
+    fn lookup_count(&self) -> u64 {
+        self.lookups.get()
+    }
+
+    fn set_lookup_sample_rate(&self, sample_rate: u64) {
+        self.lookups.set_sample_rate(sample_rate)
+    }
+
+    /// Rows are shared between all the indices that contain them, so rather than being exact, the
+    /// total size of the state is split between its indices in proportion to the number of rows in
+    /// each
+    fn index_sizes(&self) -> Option<Vec<(Index, u64)>> {
+        let total_rows = self.row_count() as u128;
+        let mut sizes = self
+            .state
+            .iter()
+            .map(|s| {
+                let bytes = (self.mem_size as u128 * s.row_count() as u128)
+                    .checked_div(total_rows)
+                    .unwrap_or(0);
+                (s.index().clone(), bytes as u64)
+            })
+            .collect::<Vec<_>>();
+        // Give any bytes lost to rounding to the first index, so that the sizes add up to the total
+        let assigned = sizes.iter().map(|(_, bytes)| bytes).sum::<u64>();
+        if let Some((_, bytes)) = sizes.first_mut() {
+            *bytes += self.mem_size.saturating_sub(assigned);
+        }
+        Some(sizes)
+    }
+
     fn mark_filled(&mut self, key: KeyComparison, tag: Tag) {
         debug_assert!(!self.state.is_empty(), "filling uninitialized index");
         let index = self.by_tag[&tag];
@@ -186,6 +229,7 @@ impl State for MemoryState {
 
     fn lookup<'a>(&'a self, columns: &[usize], key: &PointKey) -> LookupResult<'a> {
         debug_assert!(!self.state.is_empty(), "lookup on uninitialized index");
+        self.lookups.record(1);
         let index = self
             .state_for(columns, IndexType::HashMap)
             .or_else(|| self.state_for(columns, IndexType::BTreeMap))
@@ -241,6 +285,7 @@ impl State for MemoryState {
             !self.state.is_empty(),
             "lookup_range on uninitialized index"
         );
+        self.lookups.record(1);
         let index = self
             .state_for(columns, IndexType::BTreeMap)
             .expect("lookup on non-indexed column set");
@@ -262,12 +307,27 @@ impl State for MemoryState {
     /// `weak_indices`.
     fn evict_bytes(&mut self, bytes: usize) -> Option<EvictBytesResult> {
         let mut rng = rand::thread_rng();
-        let state_index = rng.gen_range(0..self.state.len());
+        let btree_indices = match self.eviction_granularity {
+            EvictionGranularity::Key => vec![],
+            EvictionGranularity::Range => (0..self.state.len())
+                .filter(|&i| self.state[i].index().index_type == IndexType::BTreeMap)
+                .collect(),
+        };
+        let (state_index, run_start) = if btree_indices.is_empty() {
+            (rng.gen_range(0..self.state.len()), None)
+        } else {
+            let state_index = btree_indices[rng.gen_range(0..btree_indices.len())];
+            let key_count = self.state[state_index].key_count();
+            (state_index, Some(rng.gen_range(0..key_count.max(1))))
+        };
         let mut bytes_freed = 0u64;
         let mut keys_evicted = Vec::new();
 
         while bytes_freed < bytes as u64 {
-            let evicted = self.state[state_index].evict_random(&mut rng);
+            let evicted = match run_start {
+                Some(position) => self.state[state_index].evict_at(position),
+                None => self.state[state_index].evict_random(&mut rng),
+            };
 
             if evicted.is_none() {
                 // There are no more keys in this state.
@@ -401,6 +461,7 @@ impl State for MemoryState {
     }
 
     fn lookup_weak<'a>(&'a self, columns: &[usize], key: &PointKey) -> Option<RecordResult<'a>> {
+        self.lookups.record(1);
         self.weak_indices[columns].lookup(key).map(From::from)
     }
 
@@ -526,6 +587,91 @@ mod tests {
         assert_eq!(3, state.row_count());
     }
 
+    #[test]
+    fn memory_state_lookup_count() {
+        let mut state = MemoryState::default();
+        state.add_index(Index::hash_map(vec![0]), None);
+        state.add_index(Index::btree_map(vec![1]), None);
+        insert(&mut state, vec![1.into(), 10.into()]);
+        assert_eq!(state.lookup_count(), 0);
+
+        state.lookup(&[0], &PointKey::Single(1.into()));
+        state.lookup(&[0], &PointKey::Single(2.into()));
+        state.lookup_range(
+            &[1],
+            &RangeKey::from(&vec1![DfValue::from(20)].range_to_inclusive()),
+        );
+        assert_eq!(state.lookup_count(), 3);
+    }
+
+    #[test]
+    fn memory_state_sampled_lookup_count() {
+        let mut state = MemoryState::default();
+        state.add_index(Index::hash_map(vec![0]), None);
+        state.lookup(&[0], &PointKey::Single(1.into()));
+
+        state.set_lookup_sample_rate(10);
+        // lookups recorded before sampling started are kept as-is
+        assert_eq!(state.lookup_count(), 1);
+
+        for _ in 0..10_000 {
+            state.lookup(&[0], &PointKey::Single(1.into()));
+        }
+        let estimate = state.lookup_count() - 1;
+        // every sampled lookup is scaled up by the sample rate
+        assert_eq!(estimate % 10, 0);
+        assert!((5_000..=15_000).contains(&estimate), "{estimate}");
+    }
+
+    #[test]
+    fn memory_state_index_sizes() {
+        let mut state = MemoryState::default();
+        assert_eq!(state.index_sizes(), Some(vec![]));
+
+        state.add_index(Index::hash_map(vec![0]), None);
+        state.add_index(Index::btree_map(vec![1]), None);
+        insert(&mut state, vec![1.into(), 10.into()]);
+        insert(&mut state, vec![2.into(), 20.into()]);
+        insert(&mut state, vec![3.into(), 30.into()]);
+
+        let sizes = state.index_sizes().unwrap();
+        assert_eq!(
+            sizes.iter().map(|(index, _)| index).collect::<Vec<_>>(),
+            vec![&Index::hash_map(vec![0]), &Index::btree_map(vec![1])]
+        );
+        assert_eq!(
+            sizes.iter().map(|(_, bytes)| bytes).sum::<u64>(),
+            state.deep_size_of()
+        );
+        // both indices hold every row, so they're each attributed about half the state
+        assert!(sizes[0].1.abs_diff(sizes[1].1) <= 1);
+    }
+
+    #[test]
+    fn range_eviction_evicts_adjacent_keys() {
+        let mut state = MemoryState::default();
+        state.set_eviction_granularity(EvictionGranularity::Range);
+        state.add_index(Index::hash_map(vec![1]), None);
+        state.add_index(Index::btree_map(vec![0]), None);
+        for i in 0..100 {
+            insert(&mut state, vec![i.into(), (i * 10).into()]);
+        }
+
+        let evicted = state.evict_bytes(1000).unwrap();
+        assert_eq!(evicted.index, &Index::btree_map(vec![0]));
+        let mut keys = evicted
+            .keys_evicted
+            .iter()
+            .map(|key| i32::try_from(&key[0]).unwrap())
+            .collect::<Vec<_>>();
+        assert!(keys.len() > 1);
+        keys.sort();
+        assert!(
+            keys.windows(2).all(|w| w[1] == w[0] + 1),
+            "expected a run of adjacent keys, got {keys:?}"
+        );
+    }
+
     #[test]
     fn memory_state_process_records() {
         let mut state = MemoryState::default();
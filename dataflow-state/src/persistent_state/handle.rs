@@ -14,7 +14,7 @@ use rocksdb::{self, DB};
 use tracing::debug;
 
 use super::{deserialize_row, PersistentState, SharedState, PK_CF};
-use crate::{PointKey, RecordResult};
+use crate::{LookupCounter, PointKey, RecordResult};
 
 /// A handle that can cloned and shared between threads to safely read from the
 /// [`PersistentState`] concurrently.
@@ -28,6 +28,8 @@ pub struct PersistentStateHandle {
     shared_state: Arc<RwLock<SharedState>>,
     /// The handle to the RocksDB database we are reading from
     db: Arc<RwLock<DB>>,
+    /// The number of lookups performed through this handle
+    pub(super) lookups: LookupCounter,
 }
 
 pub(super) struct PersistentStateReadGuard<'a> {
@@ -59,6 +61,7 @@ impl PersistentStateHandle {
             shared_state: Arc::new(RwLock::new(shared_state)),
             db: Arc::new(RwLock::new(db)),
             replication_offset,
+            lookups: LookupCounter::default(),
         }
     }
 
@@ -98,6 +101,7 @@ impl PersistentStateHandle {
         if keys.is_empty() {
             return vec![];
         }
+        self.lookups.record(keys.len() as u64);
         let inner = self.inner();
 
         let index = inner.shared_state.index(IndexType::HashMap, columns);
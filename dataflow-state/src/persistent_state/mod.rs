@@ -798,6 +798,14 @@ impl State for PersistentState {
         self.db.row_count()
     }
 
+    fn lookup_count(&self) -> u64 {
+        self.db.lookup_count()
+    }
+
+    fn set_lookup_sample_rate(&self, sample_rate: u64) {
+        self.db.set_lookup_sample_rate(sample_rate)
+    }
+
     fn is_useful(&self) -> bool {
         self.db.is_useful()
     }
@@ -947,6 +955,7 @@ impl State for PersistentStateHandle {
     fn mark_hole(&mut self, _: &KeyComparison, _: Tag) {}
 
     fn lookup(&self, columns: &[usize], key: &PointKey) -> LookupResult {
+        self.lookups.record(1);
         match self.do_lookup(columns, key) {
             Some(result) => LookupResult::Some(result.into()),
             None => LookupResult::Missing,
@@ -954,6 +963,7 @@ impl State for PersistentStateHandle {
     }
 
     fn lookup_range<'a>(&'a self, columns: &[usize], key: &RangeKey) -> RangeLookupResult<'a> {
+        self.lookups.record(1);
         let inner = self.inner();
         if self.replication_offset < inner.shared_state.replication_offset {
             debug!("Consistency miss in PersistentStateHandle");
@@ -1083,6 +1093,14 @@ impl State for PersistentStateHandle {
             .unwrap() as usize
     }
 
+    fn lookup_count(&self) -> u64 {
+        self.lookups.get()
+    }
+
+    fn set_lookup_sample_rate(&self, sample_rate: u64) {
+        self.lookups.set_sample_rate(sample_rate)
+    }
+
     fn all_records(&self) -> crate::AllRecords {
         crate::AllRecords::Persistent(AllRecords(self.clone()))
     }
@@ -362,7 +362,18 @@ impl SingleState {
         &mut self,
         rng: &mut R,
     ) -> Option<(Vec<DfValue>, Rows)> {
-        self.state.evict_with_seed(rng.gen()).map(|(rows, key)| {
+        self.evict_with_seed(rng.gen())
+    }
+
+    /// Evict the key at `position` in this state, or the last key if there are fewer keys than
+    /// that, returning it along with the removed rows. For BTree indices, evicting at the same
+    /// position repeatedly evicts a single run of adjacent keys.
+    pub(super) fn evict_at(&mut self, position: usize) -> Option<(Vec<DfValue>, Rows)> {
+        self.evict_with_seed(position.min(self.key_count().saturating_sub(1)))
+    }
+
+    fn evict_with_seed(&mut self, seed: usize) -> Option<(Vec<DfValue>, Rows)> {
+        self.state.evict_with_seed(seed).map(|(rows, key)| {
             self.row_count = self.row_count.saturating_sub(rows.len());
             (key, rows)
         })
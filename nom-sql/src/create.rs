@@ -330,9 +330,10 @@ impl DialectDisplay for CacheInner {
 struct CreateCacheOptions {
     always: bool,
     concurrently: bool,
+    dry_run: bool,
 }
 
-/// `CREATE CACHE [CONCURRENTLY] [ALWAYS] [<name>] FROM ...`
+/// `CREATE CACHE [CONCURRENTLY] [ALWAYS] [DRY RUN] [<name>] FROM ...`
 ///
 /// This is a non-standard ReadySet specific extension to SQL
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, Arbitrary)]
@@ -354,6 +355,9 @@ pub struct CreateCacheStatement {
     pub always: bool,
     /// Whether the CREATE CACHE STATEMENT should block or run concurrently
     pub concurrently: bool,
+    /// If `dry_run` is true, the cache isn't created. Instead, the migration is planned and the
+    /// resulting materialization plan is returned to the client
+    pub dry_run: bool,
 }
 
 impl DialectDisplay for CreateCacheStatement {
@@ -366,6 +370,9 @@ impl DialectDisplay for CreateCacheStatement {
             if self.always {
                 write!(f, "ALWAYS ")?;
             }
+            if self.dry_run {
+                write!(f, "DRY RUN ")?;
+            }
             if let Some(name) = &self.name {
                 write!(f, "{} ", name.display(dialect))?;
             }
@@ -1070,6 +1077,7 @@ fn cached_query_options(mut i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Create
     enum Option {
         Always,
         Concurrently,
+        DryRun,
     }
 
     let mut opts = CreateCacheOptions::default();
@@ -1082,6 +1090,15 @@ fn cached_query_options(mut i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Create
         map(tuple((tag_no_case("concurrently"), whitespace1)), |_| {
             Option::Concurrently
         }),
+        map(
+            tuple((
+                tag_no_case("dry"),
+                whitespace1,
+                tag_no_case("run"),
+                whitespace1,
+            )),
+            |_| Option::DryRun,
+        ),
     ))(i)
     {
         // Error if the same option appears twice.
@@ -1096,6 +1113,11 @@ fn cached_query_options(mut i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Create
                     return Err(error(i));
                 }
             }
+            Option::DryRun => {
+                if std::mem::replace(&mut opts.dry_run, true) {
+                    return Err(error(i));
+                }
+            }
         }
         i = remaining;
     }
@@ -1139,6 +1161,7 @@ pub fn create_cached_query(
                 unparsed_create_cache_statement,
                 always: opts.always,
                 concurrently: opts.concurrently,
+                dry_run: opts.dry_run,
             },
         ))
     }
@@ -1949,6 +1972,33 @@ mod tests {
             }
         }
 
+        #[test]
+        fn create_cached_query_dry_run() {
+            let stmt = test_parse!(
+                create_cached_query(Dialect::MySQL),
+                b"CREATE CACHE ALWAYS DRY RUN foo FROM SELECT id FROM users WHERE name = ?"
+            );
+            assert!(stmt.dry_run);
+            assert!(stmt.always);
+            assert!(!stmt.concurrently);
+            assert_eq!(stmt.name, Some("foo".into()));
+            assert_eq!(
+                stmt.display(Dialect::MySQL).to_string(),
+                "CREATE CACHE ALWAYS DRY RUN `foo` FROM SELECT `id` FROM `users` WHERE (`name` = ?)"
+            );
+
+            let stmt = test_parse!(
+                create_cached_query(Dialect::MySQL),
+                b"CREATE CACHE dry FROM SELECT id FROM users WHERE name = ?"
+            );
+            assert!(!stmt.dry_run);
+            assert_eq!(stmt.name, Some("dry".into()));
+
+            let qstring = "CREATE CACHE DRY RUN DRY RUN FROM SELECT id FROM users";
+            let res = create_cached_query(Dialect::MySQL)(LocatedSpan::new(qstring.as_bytes()));
+            res.unwrap_err();
+        }
+
         #[test]
         fn display_create_query_cache() {
             let stmt = test_parse!(
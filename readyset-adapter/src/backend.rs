@@ -1982,6 +1982,37 @@ where
         Ok(noria_connector::QueryResult::Empty)
     }
 
+    /// Forwards a `CREATE CACHE ... DRY RUN` request to ReadySet, which plans the migration for
+    /// the query without committing it. If the query is unsupported, the reason is returned to the
+    /// client rather than an error, so that query authors can see why before trying to create the
+    /// cache for real.
+    #[instrument(skip(self))]
+    async fn create_cache_dry_run(
+        &mut self,
+        name: Option<&Relation>,
+        mut stmt: SelectStatement,
+        override_schema_search_path: Option<Vec<SqlIdentifier>>,
+    ) -> ReadySetResult<noria_connector::QueryResult<'static>> {
+        let res = match adapter_rewrites::process_query(&mut stmt, self.noria.rewrite_params()) {
+            Ok(_) => {
+                self.noria
+                    .handle_create_cache_dry_run(name, &stmt, override_schema_search_path)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match res {
+            Err(e) if e.caused_by_unsupported() => {
+                Ok(noria_connector::QueryResult::MetaVariables(vec![
+                    ("readyset supported", "no").into(),
+                    ("reason", e.to_string()).into(),
+                ]))
+            }
+            res => res,
+        }
+    }
+
     /// Forwards an `EXPLAIN CREATE CACHE` request to ReadySet. Where possible, this method performs
     /// the dry run in the request path so we can return a result to the client immediately. If we
     /// encounter an error we think might be transient or if the query is unsupported and we might
@@ -2373,6 +2404,7 @@ where
                 inner,
                 always,
                 concurrently,
+                dry_run,
                 unparsed_create_cache_statement,
             }) => {
                 if !self.allow_cache_ddl {
@@ -2399,6 +2431,14 @@ where
                     }),
                 }?;
 
+                // A dry run doesn't create anything, so there's no telemetry event to log or DDL
+                // request to store
+                if *dry_run {
+                    return self
+                        .create_cache_dry_run(name.as_ref(), stmt, search_path)
+                        .await;
+                }
+
                 // Log a telemetry event
                 if let Some(ref telemetry_sender) = self.telemetry_sender {
                     if let Err(e) = telemetry_sender.send_event(TelemetryEvent::CreateCache) {
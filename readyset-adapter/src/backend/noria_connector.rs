@@ -429,6 +429,7 @@ impl NoriaConnector {
             .graphviz(GraphvizOptions {
                 detailed: !simplified,
                 for_query,
+                ..Default::default()
             })
             .await?;
 
@@ -1053,6 +1054,106 @@ impl NoriaConnector {
         .map(|_| ())
     }
 
+    /// Plans the migration for a `CREATE CACHE ... DRY RUN` statement without committing it, and
+    /// returns the resulting materialization plan as a table of variables: one row for each node
+    /// which would be materialized, followed by the estimated memory use of the new
+    /// materializations and the existing nodes whose materialization would change.
+    pub async fn handle_create_cache_dry_run(
+        &mut self,
+        name: Option<&Relation>,
+        statement: &nom_sql::SelectStatement,
+        override_schema_search_path: Option<Vec<SqlIdentifier>>,
+    ) -> ReadySetResult<QueryResult<'static>> {
+        let name = name
+            .cloned()
+            .unwrap_or_else(|| QueryId::from_select(statement, self.schema_search_path()).into());
+        let schema_search_path =
+            override_schema_search_path.unwrap_or_else(|| self.schema_search_path.clone());
+        let changelist = ChangeList::from_change(
+            Change::create_cache(name, statement.clone(), false),
+            self.dialect,
+        )
+        .with_schema_search_path(schema_search_path);
+
+        let report = noria_await!(
+            self.inner.get_mut()?,
+            self.inner.get_mut()?.noria.dry_run_report(changelist)
+        )?;
+
+        let mut vars: Vec<MetaVariable> = vec![("readyset supported", "yes").into()];
+        vars.extend(report.new_materializations.iter().map(|m| {
+            (
+                "materialization",
+                format!(
+                    "{} ({}, {}) indexed by {}, ~{} bytes, ~{} records to fill",
+                    m.node_index.index(),
+                    m.node_name.display_unquoted(),
+                    if m.partial {
+                        format!("partial, chain depth {}", m.partial_chain_depth)
+                    } else {
+                        "full".to_owned()
+                    },
+                    m.indices
+                        .iter()
+                        .map(|idx| {
+                            format!("{:?}[{}]", idx.index_type, idx.columns.iter().join(", "))
+                        })
+                        .join(", "),
+                    m.estimated_bytes,
+                    m.estimated_replay_records
+                ),
+            )
+                .into()
+        }));
+        vars.push(("estimated bytes", report.estimated_bytes().to_string()).into());
+        vars.push(
+            (
+                "estimated records to fill",
+                report.estimated_replay_records().to_string(),
+            )
+                .into(),
+        );
+        vars.push(("replay paths added", report.added_replay_paths.to_string()).into());
+        vars.push(
+            (
+                "domains touched",
+                report
+                    .touched_domains
+                    .iter()
+                    .map(|domain| domain.index())
+                    .join(", "),
+            )
+                .into(),
+        );
+
+        let impact = &report.impact;
+        let affected = impact
+            .newly_materialized
+            .iter()
+            .chain(impact.added_indices.keys())
+            .chain(&impact.became_partial)
+            .chain(&impact.became_full)
+            .chain(&impact.rerouted)
+            .chain(&impact.duplicated)
+            .map(|ni| ni.index())
+            .sorted()
+            .dedup()
+            .join(", ");
+        vars.push(
+            (
+                "existing nodes affected",
+                if affected.is_empty() {
+                    "none".to_owned()
+                } else {
+                    affected
+                },
+            )
+                .into(),
+        );
+
+        Ok(QueryResult::MetaVariables(vars))
+    }
+
     /// Gets the view name for the given statement from the view name cache, querying the server
     /// for the name if it is not cached.
     pub(crate) async fn get_view_name_cached(
@@ -461,8 +461,7 @@ impl ConsulAuthority {
             }
         }
 
-        let new_val = rmp_serde::to_vec(&input)?;
-        let compressed = super::Compressor::compress(&new_val);
+        let compressed = super::Compressor::compress_serialized(&input)?;
 
         let r = kv::set(
             &self.consul,
@@ -554,8 +553,7 @@ impl ConsulAuthority {
     ) -> ReadySetResult<(StateValue, P)> {
         let my_session = Some(self.get_session()?);
 
-        let new_val = rmp_serde::to_vec(&controller_state)?;
-        let compressed = super::Compressor::compress(&new_val);
+        let compressed = super::Compressor::compress_serialized(&controller_state)?;
 
         gauge!(recorded::DATAFLOW_STATE_SERIALIZED).set(compressed.len() as f64);
 
@@ -4,6 +4,7 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Display};
+use std::io::{self, BufWriter, Write};
 use std::net::SocketAddr;
 use std::str::FromStr;
 
@@ -393,9 +394,56 @@ impl Compressor {
         )
     }
 
-    pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
-        let mut comp = Self::new();
-        comp.0.compress(data).expect("Can't fail");
-        comp.0.finish().expect("Can't fail")
+    /// Serialize `value` with MessagePack and compress the result.
+    ///
+    /// The serialized value is written to the compressor incrementally, rather than being
+    /// buffered in full before compression, so for very large values (such as the controller
+    /// state of a huge graph) only the compressed output is ever held in memory at once.
+    pub(crate) fn compress_serialized<T>(value: &T) -> ReadySetResult<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut writer = BufWriter::new(Self::new());
+        rmp_serde::encode::write(&mut writer, value)?;
+        let comp = writer.into_inner().map_err(|e| e.into_error())?;
+        comp.0
+            .finish()
+            .map_err(|e| ReadySetError::SerializationFailed(format!("Compression failed: {e}")))
+    }
+}
+
+impl Write for Compressor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.compress(buf).map_err(|e| {
+            io::Error::new(io::ErrorKind::Other, format!("Compression failed: {e}"))
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn compress_serialized_round_trip() {
+        // large enough that the serialized state is written to the compressor in many pieces
+        let state = (0..100_000u64)
+            .map(|i| (i, (format!("node_{i}"), vec![i; (i % 8) as usize])))
+            .collect::<BTreeMap<_, _>>();
+
+        let compressed = Compressor::compress_serialized(&state).unwrap();
+        let data = cloudflare_zlib::inflate(&compressed).unwrap();
+        // the encoding is the same as serializing the whole state up front
+        assert_eq!(data, rmp_serde::to_vec(&state).unwrap());
+        let round_tripped: BTreeMap<u64, (String, Vec<u64>)> =
+            rmp_serde::from_slice(&data).unwrap();
+        assert_eq!(round_tripped, state);
     }
 }
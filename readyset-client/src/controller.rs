@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
@@ -26,9 +27,13 @@ use tracing::{debug, trace};
 use url::Url;
 
 use crate::consensus::{Authority, AuthorityControl};
-use crate::debug::info::{GraphInfo, MaterializationInfo, NodeSize};
+use crate::debug::info::{
+    FrontierSimulation, GraphInfo, MaterializationAccessStats, MaterializationImpact,
+    MaterializationInfo, MaterializationReport, MaterializationTemplate, MaterializationViolation,
+    MigrationDryRunReport, NodeSize,
+};
 use crate::debug::stats;
-use crate::internal::{DomainIndex, ReplicaAddress};
+use crate::internal::{DomainIndex, Index, ReplicaAddress};
 use crate::metrics::MetricsDump;
 use crate::recipe::changelist::ChangeList;
 use crate::recipe::{CacheExpr, ExtendRecipeResult, ExtendRecipeSpec, MigrationStatus};
@@ -290,6 +295,31 @@ impl Service<ControllerRequest> for Controller {
     }
 }
 
+/// The [shape][] to draw nodes with in detailed graphviz visualizations of the dataflow graph.
+///
+/// [shape]: https://graphviz.org/doc/info/shapes.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GraphvizNodeShape {
+    /// Plain record shapes (this is the default)
+    #[default]
+    Record,
+    /// Record shapes with rounded corners
+    Mrecord,
+    /// No shape at all, for use with HTML-like labels. Note that node labels are still rendered
+    /// with record syntax, which isn't interpreted with this shape.
+    Plaintext,
+}
+
+impl fmt::Display for GraphvizNodeShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Record => write!(f, "record"),
+            Self::Mrecord => write!(f, "Mrecord"),
+            Self::Plaintext => write!(f, "plaintext"),
+        }
+    }
+}
+
 /// Options for generating graphviz [dot][] visualizations of the ReadySet dataflow graph.
 ///
 /// Used as the argument to [`ReadySetHandle::graphviz`].
@@ -301,6 +331,48 @@ pub struct GraphvizOptions {
     pub for_query: Option<Relation>,
     /// Generate a detailed representation of the graph, larger and with more information
     pub detailed: bool,
+    /// Render chains of consecutive non-materialized query-through nodes as a single labeled edge
+    /// between the surrounding nodes, rather than rendering each node in the chain
+    pub collapse_query_through: bool,
+    /// If set, attach a URL to each node in the graph for use by interactive viewers, built from
+    /// this template by replacing every occurrence of `{index}` with the index of the node
+    pub node_url: Option<String>,
+    /// Pair each full node that was created as a duplicate of a partial node while rerouting a
+    /// migration with the partial node it duplicates, and annotate it as a rerouting artifact
+    pub show_redundant_partial: bool,
+    /// If set, only render nodes whose name matches this regular expression, along with their
+    /// direct neighbors. Combined with `for_query`, only nodes in that query's graph which match
+    /// (or neighbor a match) are rendered
+    pub name_filter: Option<String>,
+    /// The shape to draw nodes with, if `detailed` is set
+    #[serde(default)]
+    pub node_shape: GraphvizNodeShape,
+    /// Label each node with a short hash of its description, so that a textual diff of two
+    /// renders of the graph shows which nodes changed, even if their layout differs
+    #[serde(default)]
+    pub show_description_hash: bool,
+    /// Replace the name of every node with an anonymized token derived from a hash of that name,
+    /// eg `view_0123456789abcdef`, name columns by their position, and describe operators only by
+    /// their kind, so that the structure of the graph can be shared without revealing the schema,
+    /// the names of queries, or any literals in them. The hashes are salted randomly for each
+    /// render, so tokens are consistent within a render but not across renders
+    #[serde(default)]
+    pub anonymize_names: bool,
+    /// If set, only render the nodes in this domain, along with stubs for their direct neighbors
+    /// in other domains
+    #[serde(default)]
+    pub only_domain: Option<DomainIndex>,
+    /// Outline each node in a color indicating whether it's currently ready, being filled by a
+    /// replay, or not yet ready, as reported by its domain, so that it's clear which nodes a
+    /// migration is still waiting on
+    #[serde(default)]
+    pub show_health: bool,
+    /// Describe the columns of each index on a materialized node by the name of the base table
+    /// column they originate from, eg `hash[users.id]`, rather than by the name of the column in
+    /// that node. Columns which don't come from a single base table column, such as computed
+    /// columns, are still described by their name in the node
+    #[serde(default)]
+    pub resolve_index_columns: bool,
 }
 
 impl Default for GraphvizOptions {
@@ -308,6 +380,16 @@ impl Default for GraphvizOptions {
         Self {
             for_query: None,
             detailed: true,
+            collapse_query_through: false,
+            node_url: None,
+            show_redundant_partial: false,
+            name_filter: None,
+            node_shape: GraphvizNodeShape::default(),
+            show_description_hash: false,
+            anonymize_names: false,
+            only_domain: None,
+            show_health: false,
+            resolve_index_columns: false,
         }
     }
 }
@@ -696,6 +778,35 @@ impl ReadySetHandle {
         self.rpc("dry_run", request, self.migration_timeout)
     }
 
+    /// Performs a dry-run migration with the given set of queries, and returns the
+    /// materialization plan it would have committed: which nodes would be materialized and how,
+    /// an estimate of how much memory they'd use, and the effect on existing materializations.
+    ///
+    /// `Self::poll_ready` must have returned `Async::Ready` before you call this method.
+    pub fn dry_run_report(
+        &mut self,
+        changes: ChangeList,
+    ) -> impl Future<Output = ReadySetResult<MigrationDryRunReport>> + '_ {
+        let request = ExtendRecipeSpec::from(changes);
+
+        self.rpc("dry_run_report", request, self.migration_timeout)
+    }
+
+    /// Report which already-existing nodes would have their materialization changed (by gaining
+    /// indices, changing between partial and full materialization, or being rerouted) if the
+    /// given set of queries were added. This performs a dry-run migration, and doesn't change the
+    /// graph.
+    ///
+    /// `Self::poll_ready` must have returned `Async::Ready` before you call this method.
+    pub fn migration_impact(
+        &mut self,
+        changes: ChangeList,
+    ) -> impl Future<Output = ReadySetResult<MaterializationImpact>> + '_ {
+        let request = ExtendRecipeSpec::from(changes);
+
+        self.rpc("migration_impact", request, self.migration_timeout)
+    }
+
     /// Extend the existing recipe with the given set of queries.
     ///
     /// `Self::poll_ready` must have returned `Async::Ready` before you call this method.
@@ -878,6 +989,80 @@ impl ReadySetHandle {
         materialization_info() -> Vec<MaterializationInfo>
     );
 
+    simple_request!(
+        /// Check all the materializations in the graph for invariant violations, returning a list
+        /// of any violations found. This doesn't change the graph.
+        check_materializations() -> Vec<MaterializationViolation>
+    );
+
+    simple_request!(
+        /// Get a summary of every materialization in the graph, including the number of each
+        /// kind of materialization, the memory used by each domain, orphaned materializations,
+        /// unpaired weak indices, and the `top_n` largest materializations. This doesn't change
+        /// the graph.
+        materialization_report(top_n: usize) -> MaterializationReport
+    );
+
+    simple_request!(
+        /// Freeze the materialization decisions (indices, partial or full materialization, and
+        /// placement of the materialization frontier) made for the given query into a template,
+        /// which can be passed to
+        /// [`add_materialization_template`](Self::add_materialization_template).
+        materialization_template(query: Relation) -> MaterializationTemplate
+    );
+
+    simple_request!(
+        /// Apply the materialization decisions in the given template to the new nodes of all
+        /// future migrations which have the same shape as it, skipping the analysis that would
+        /// otherwise be done to make those decisions. Migrations whose nodes don't match the
+        /// template, or for which its decisions are inconsistent with the existing
+        /// materializations, are analyzed from scratch as usual.
+        add_materialization_template(template: MaterializationTemplate) -> ()
+    );
+
+    simple_request!(
+        /// Get the number of lookups performed into each materialized node since it was
+        /// materialized, or since the last call to
+        /// [`reset_materialization_access_stats`](Self::reset_materialization_access_stats).
+        /// Lookups are counted by the materializations themselves, and include both reads from
+        /// readers and lookups performed within the dataflow graph (such as by joins or to
+        /// satisfy upqueries). If lookups are being sampled, the counts are estimates, and the
+        /// sample rate is returned alongside them.
+        materialization_access_stats() -> MaterializationAccessStats
+    );
+
+    simple_request!(
+        /// Get the set of nodes which are currently placed beyond the materialization frontier.
+        shallow_nodes() -> HashSet<NodeIndex>
+    );
+
+    simple_request!(
+        /// Get the replay paths which fill the indices of the given node, as tuples of the tag
+        /// identifying each path, the index it fills, and the nodes along it in replay order,
+        /// sorted by tag.
+        replay_paths_for(node: NodeIndex) -> Vec<(u32, Index, Vec<NodeIndex>)>
+    );
+
+    simple_request!(
+        /// Get the materialized nodes which the replay paths for the given node (usually a
+        /// reader) are sourced from, in sorted order. This answers whether a cache reuses existing
+        /// state or has to build new state of its own.
+        replay_sources_for(node: NodeIndex) -> Vec<NodeIndex>
+    );
+
+    simple_request!(
+        /// Preview which materialized nodes would be placed beyond the materialization frontier,
+        /// and hence be evictable, if the frontier were placed using the named frontier strategy
+        /// (`none`, `all-partial`, or `readers`), along with the total size of their current
+        /// state. This doesn't change the graph.
+        simulate_frontier(strategy: String) -> FrontierSimulation
+    );
+
+    simple_request!(
+        /// Reset the lookup counts for all materialized nodes to zero.
+        reset_materialization_access_stats() -> ()
+    );
+
     simple_request!(
         /// Get the url of the current noria controller.
         ///
@@ -922,6 +1107,14 @@ impl ReadySetHandle {
         node_sizes() -> HashMap<NodeIndex, NodeSize>
     );
 
+    simple_request!(
+        /// Return a map of (node index, index) pairs to the approximate size in bytes of that index.
+        ///
+        /// Nodes whose state only reports its total size have that size split evenly between
+        /// their indices.
+        index_sizes() -> HashMap<(NodeIndex, Index), u64>
+    );
+
     simple_request!(
         /// Return whether the leader is ready or not.
         leader_ready() -> bool
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Display};
 use std::ops::{AddAssign, Deref};
 
@@ -59,6 +59,20 @@ pub struct NodeSize {
     pub bytes: NodeMaterializedSize,
 }
 
+/// Whether a node is ready to process writes and serve lookups, as reported by its domain.
+///
+/// Ordered from healthiest to least healthy, so that the health of a node in a sharded domain can
+/// be taken as the maximum over all of its shards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum NodeHealth {
+    /// The node is ready
+    Ready,
+    /// The node's state is currently being filled by a full replay
+    Replaying,
+    /// The node has been added to its domain, but hasn't yet been made ready
+    NotReady,
+}
+
 /// Information about a single materialization (stateful node) in the graph
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MaterializationInfo {
@@ -72,10 +86,321 @@ pub struct MaterializationInfo {
     pub size: NodeSize,
     /// Is the materialization partial?
     pub partial: bool,
+    /// The primary reason the node is materialized, if known
+    #[serde(default)]
+    pub kind: Option<MaterializationKind>,
     /// Set of ways the materialization is indexed
     pub indexes: HashSet<Index>,
 }
 
+/// The projected effect of placing materializations beyond the materialization frontier with a
+/// particular frontier strategy, computed without changing the graph
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrontierSimulation {
+    /// The materialized nodes (including readers) which would be beyond the materialization
+    /// frontier, and hence evictable, in sorted order
+    pub purged_nodes: Vec<NodeIndex>,
+    /// The approximate total size in bytes of the state of all of `purged_nodes`, as of when the
+    /// simulation was run
+    pub evictable_bytes: usize,
+}
+
+/// The effect that a proposed migration would have on the materializations of nodes which already
+/// exist in the graph, computed without changing the graph.
+///
+/// All nodes and indices are listed in sorted order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterializationImpact {
+    /// Existing nodes which would become materialized
+    pub newly_materialized: Vec<NodeIndex>,
+    /// Indices which would be added to existing nodes
+    pub added_indices: BTreeMap<NodeIndex, Vec<Index>>,
+    /// Existing nodes which would go from being fully to partially materialized
+    pub became_partial: Vec<NodeIndex>,
+    /// Existing nodes which would go from being partially to fully materialized
+    pub became_full: Vec<NodeIndex>,
+    /// Existing nodes whose parents would change, for example because they'd be rerouted to read
+    /// from a fully materialized duplicate of a partially materialized parent
+    pub rerouted: Vec<NodeIndex>,
+    /// Existing partially materialized nodes which would get a new fully materialized duplicate,
+    /// to serve a new fully materialized node below them
+    pub duplicated: Vec<NodeIndex>,
+}
+
+impl MaterializationImpact {
+    /// Returns true if the migration wouldn't change the materialization of any existing node
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A node which a proposed migration would newly materialize, as reported in a
+/// [`MigrationDryRunReport`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedMaterialization {
+    /// The index the node would have in the graph
+    pub node_index: NodeIndex,
+    /// The node's name
+    pub node_name: Relation,
+    /// Would the materialization be partial?
+    pub partial: bool,
+    /// The indices the materialization would have, in sorted order
+    pub indices: Vec<Index>,
+    /// The estimated size in bytes of the materialization once it's been filled. Partial
+    /// materializations start out empty, so this is always 0 for them
+    pub estimated_bytes: u64,
+    /// The estimated number of upstream records which replays to fill the materialization would
+    /// scan. For partial materializations, this is an upper bound on the cost of filling every
+    /// key, rather than the cost of filling a single one
+    #[serde(default)]
+    pub estimated_replay_records: u64,
+    /// For partial materializations, the number of partial materializations (including this one)
+    /// on the longest chain between it and the nearest full materialization above it, which is
+    /// the most partial materializations a replay to fill a miss may have to pass through. Always
+    /// 0 for full materializations
+    #[serde(default)]
+    pub partial_chain_depth: usize,
+}
+
+/// The full materialization plan for a proposed migration, computed by running the migration
+/// planner without committing anything to the graph or to any domains
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationDryRunReport {
+    /// The nodes which would be newly materialized, in sorted order
+    pub new_materializations: Vec<PlannedMaterialization>,
+    /// The number of replay paths which would be added
+    pub added_replay_paths: usize,
+    /// The effect the migration would have on the materializations of existing nodes
+    pub impact: MaterializationImpact,
+    /// The domains which the migration would send messages to, to add new materializations or
+    /// indices or to set up replay paths through them, in sorted order. These are the domains
+    /// which would be busy while the migration is applied
+    #[serde(default)]
+    pub touched_domains: Vec<DomainIndex>,
+}
+
+impl MigrationDryRunReport {
+    /// The estimated total size in bytes of all the new materializations once they've been filled
+    pub fn estimated_bytes(&self) -> u64 {
+        self.new_materializations
+            .iter()
+            .map(|m| m.estimated_bytes)
+            .sum()
+    }
+
+    /// The estimated total number of upstream records which replays to fill all the new
+    /// materializations would scan
+    pub fn estimated_replay_records(&self) -> u64 {
+        self.new_materializations
+            .iter()
+            .map(|m| m.estimated_replay_records)
+            .sum()
+    }
+}
+
+/// A summary of every materialization in a deployment, combining the materialization counts,
+/// orphaned materializations, size accounting and weak index pairing audit into a single view of
+/// the deployment's overall health. Computed without changing the graph.
+///
+/// All sizes are approximate, as of when the report was computed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterializationReport {
+    /// The total number of nodes in the graph, not counting the source node or dropped nodes
+    pub total_nodes: usize,
+    /// The number of partially materialized nodes, not counting readers
+    pub partial: usize,
+    /// The number of fully materialized nodes, not counting readers
+    pub full: usize,
+    /// The number of readers
+    pub readers: usize,
+    /// The total number of indices across all materialized nodes, not counting readers
+    pub total_indices: usize,
+    /// The total number of replay paths set up across all nodes
+    pub total_replay_paths: usize,
+    /// The total size in bytes of the state of the nodes (including readers) in each domain
+    pub memory_by_domain: BTreeMap<DomainIndex, u64>,
+    /// Materialized nodes which no reader depends on, with the size in bytes of their state, in
+    /// sorted order
+    pub orphaned: Vec<(NodeIndex, u64)>,
+    /// Weak indices which aren't paired with a strict index of the same type on the same columns,
+    /// in sorted order
+    pub unpaired_weak_indices: Vec<(NodeIndex, Index)>,
+    /// The largest materializations (including readers), with their names and the size in bytes
+    /// of their state, largest first
+    pub largest: Vec<(NodeIndex, Relation, u64)>,
+}
+
+/// The materialization decisions made for a single node of a [`MaterializationTemplate`], along
+/// with the shape of the node that they apply to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateNode {
+    /// A short description of the node's operator, which doesn't include anything specific to the
+    /// query it belongs to (such as the indices of other nodes)
+    pub operator: String,
+    /// The number of columns in the node
+    pub columns: usize,
+    /// The parents of the node, in order of their node index
+    pub parents: Vec<TemplateParent>,
+    /// Whether the node is materialized. Readers are never considered materialized, since their
+    /// state isn't indexed the same way
+    pub materialized: bool,
+    /// The indices of the node's materialization, in sorted order
+    pub indices: Vec<Index>,
+    /// Whether the node (or reader) is partially materialized
+    pub partial: bool,
+    /// The primary reason the node is materialized, if known
+    pub kind: Option<MaterializationKind>,
+}
+
+/// A parent of a [`TemplateNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemplateParent {
+    /// A parent which is part of the template, by its position in the template
+    Template(usize),
+    /// A parent outside of the template, such as a base table, which nodes the template is applied
+    /// to must share
+    External(NodeIndex),
+}
+
+/// The materialization decisions made for every node of a query, frozen so that they can be
+/// applied to structurally identical queries created later without analyzing them from scratch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterializationTemplate {
+    /// The nodes of the query, in topological order
+    pub nodes: Vec<TemplateNode>,
+}
+
+/// The number of lookups performed into each materialized node in the graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterializationAccessStats {
+    /// Only one in every `sample_rate` lookups was counted, so if this is greater than 1 the
+    /// counts in `lookups` are estimates, scaled up by the sample rate
+    pub sample_rate: u64,
+    /// The (estimated) number of lookups into each materialized node
+    pub lookups: HashMap<NodeIndex, u64>,
+}
+
+/// A violation of one of the invariants that must hold for the materializations in the graph.
+///
+/// These are returned when checking the materializations of an already-running deployment, which
+/// doesn't change the graph but reports whether it's currently sound.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaterializationViolation {
+    /// A fully materialized node (or reader) is a descendant of a partially materialized node
+    FullBelowPartial {
+        /// The partially materialized ancestor
+        parent: NodeIndex,
+        /// The child of `parent` through which the fully materialized node is reached
+        child: NodeIndex,
+    },
+    /// A partially materialized node is partial over some, but not all, of the columns of an index
+    /// in one of its partially materialized ancestors
+    OverlappingPartialIndices {
+        /// The partially materialized ancestor
+        parent: NodeIndex,
+        /// The index in `parent` that partially overlaps `child_index`
+        parent_index: Index,
+        /// The partially materialized descendant
+        child: NodeIndex,
+        /// The index in `child` that partially overlaps `parent_index`
+        child_index: Index,
+    },
+    /// A node beyond the materialization frontier is an ancestor of a materialized node that isn't
+    PurgeAboveNonPurge {
+        /// The node beyond the materialization frontier
+        node: NodeIndex,
+    },
+    /// A subgraph is sharded by one column, and then merged by a duplicated copy of that column
+    AliasedShardingColumn {
+        /// The sharded parent of the shard merger
+        node: NodeIndex,
+        /// The column `node` is sharded by
+        column: usize,
+        /// The column of `node` that aliases `column`
+        alias: usize,
+    },
+    /// A node has a weak index without a strict index of the same type on the same columns, which
+    /// replays need since they can't look up into weak indices
+    UnpairedWeakIndex {
+        /// The node with the weak index
+        node: NodeIndex,
+        /// The weak index which has no corresponding strict index
+        index: Index,
+    },
+    /// A partial node has a range (BTree) index whose replay path passes through, or is sourced
+    /// from, a hash-only index, which can't serve the range lookups replays for that index need
+    HashIndexInRangeReplay {
+        /// The partial node with the range index
+        node: NodeIndex,
+        /// The range index
+        index: Index,
+        /// The node along the replay path with the hash index
+        through: NodeIndex,
+        /// The hash index on `through`
+        through_index: Index,
+    },
+}
+
+impl Display for MaterializationViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterializationViolation::FullBelowPartial { parent, child } => write!(
+                f,
+                "full materialization below partially materialized node {} (via {})",
+                parent.index(),
+                child.index()
+            ),
+            MaterializationViolation::OverlappingPartialIndices {
+                parent,
+                parent_index,
+                child,
+                child_index,
+            } => write!(
+                f,
+                "partially overlapping partial indices (parent {} index {}, child {} index {})",
+                parent.index(),
+                parent_index,
+                child.index(),
+                child_index
+            ),
+            MaterializationViolation::PurgeAboveNonPurge { node } => {
+                write!(f, "found purge node {} above non-purge node", node.index())
+            }
+            MaterializationViolation::AliasedShardingColumn {
+                node,
+                column,
+                alias,
+            } => write!(
+                f,
+                "node {} is sharded by column {}, which is aliased by column {}",
+                node.index(),
+                column,
+                alias
+            ),
+            MaterializationViolation::UnpairedWeakIndex { node, index } => write!(
+                f,
+                "node {} has weak index {} without a corresponding strict index",
+                node.index(),
+                index
+            ),
+            MaterializationViolation::HashIndexInRangeReplay {
+                node,
+                index,
+                through,
+                through_index,
+            } => write!(
+                f,
+                "range index {} on partial node {} replays through hash index {} on node {}, which \
+                 can't serve range lookups",
+                index,
+                node.index(),
+                through_index,
+                through.index()
+            ),
+        }
+    }
+}
+
 impl Display for KeyCount {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -101,6 +426,16 @@ impl Display for NodeMaterializedSize {
     }
 }
 
+impl Display for NodeHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeHealth::Ready => write!(f, "ready"),
+            NodeHealth::Replaying => write!(f, "replaying"),
+            NodeHealth::NotReady => write!(f, "not ready"),
+        }
+    }
+}
+
 impl AddAssign for NodeSize {
     /// Adds the node size for the rhs node size to ourselves.
     fn add_assign(&mut self, rhs: Self) {
@@ -150,6 +485,21 @@ mod tests {
         assert_eq!("~42", KeyCount::EstimatedRowCount(42).to_string());
     }
 
+    #[test]
+    fn materialization_violation_formatting() {
+        let violation = MaterializationViolation::OverlappingPartialIndices {
+            parent: NodeIndex::new(1),
+            parent_index: Index::hash_map(vec![0, 1]),
+            child: NodeIndex::new(2),
+            child_index: Index::hash_map(vec![1]),
+        };
+        assert_eq!(
+            violation.to_string(),
+            "partially overlapping partial indices (parent 1 index hash[0,1], child 2 index \
+             hash[1])"
+        );
+    }
+
     #[test]
     fn key_count_add_assign() {
         let mut kc = KeyCount::ExactKeyCount(1);
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use petgraph::graph::NodeIndex;
 use serde::{Deserialize, Serialize};
 
+use crate::debug::info::NodeSize;
 use crate::internal::*;
 use crate::MaterializationStatus;
 
@@ -63,6 +64,14 @@ pub struct PersistentStats {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GraphStats {
     pub domains: DomainMap,
+    /// Materialized nodes which aren't an ancestor of any reader, and so could be dropped to
+    /// reclaim memory, along with the size of each
+    pub unreachable_materializations: HashMap<NodeIndex, NodeSize>,
+    /// The estimated number of bytes used on behalf of each query, keyed by the index of the
+    /// query's reader node. Materializations shared between several queries are split evenly
+    /// between them, and base tables aren't attributed to any query.
+    #[serde(default)]
+    pub memory_by_query: HashMap<NodeIndex, u64>,
 }
 
 use std::ops::Deref;
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 /// Describe the materialization state of an operator.
@@ -12,3 +14,31 @@ pub enum MaterializationStatus {
         beyond_materialization_frontier: bool,
     },
 }
+
+/// Describe the primary reason an operator's state is materialized.
+///
+/// Variants are ordered from most to least inherent, so that when a node is materialized for
+/// several reasons the smallest one is taken as its primary reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MaterializationKind {
+    /// Operator is a base table, which is always materialized.
+    Base,
+    /// Operator asked for its own state to be materialized (including readers).
+    OwnState,
+    /// A downstream operator performs lookups into this operator's state.
+    Lookup,
+    /// This operator's state is needed as the source of a replay path for a downstream partial
+    /// materialization.
+    Replay,
+}
+
+impl fmt::Display for MaterializationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base => write!(f, "base"),
+            Self::OwnState => write!(f, "own state"),
+            Self::Lookup => write!(f, "lookup"),
+            Self::Replay => write!(f, "replay"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::fmt::{self, Display};
 
 use nom_sql::BinaryOperator;
 use serde::{Deserialize, Serialize};
@@ -39,6 +40,17 @@ impl PartialOrd for IndexType {
     }
 }
 
+/// Renders the index type in a compact form (`hash` or `btree`), suitable for logs and
+/// user-facing error messages.
+impl Display for IndexType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexType::HashMap => write!(f, "hash"),
+            IndexType::BTreeMap => write!(f, "btree"),
+        }
+    }
+}
+
 impl IndexType {
     /// Return the [`IndexType`] that is best able to satisfy lookups via the given operator, if any
     pub fn for_operator(operator: BinaryOperator) -> Option<Self> {
@@ -123,6 +135,20 @@ impl Index {
     }
 }
 
+/// Renders the index as its type followed by its columns, eg `hash[0,2]`
+impl Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[", self.index_type)?;
+        for (i, col) in self.columns.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{col}")?;
+        }
+        write!(f, "]")
+    }
+}
+
 impl std::ops::Index<usize> for Index {
     type Output = usize;
 
@@ -130,3 +156,15 @@ impl std::ops::Index<usize> for Index {
         self.columns.get(index).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_index() {
+        assert_eq!(Index::hash_map(vec![0, 2]).to_string(), "hash[0,2]");
+        assert_eq!(Index::btree_map(vec![1]).to_string(), "btree[1]");
+        assert_eq!(Index::hash_map(vec![]).to_string(), "hash[]");
+    }
+}
@@ -6,5 +6,5 @@ mod external;
 mod index;
 
 pub use self::addressing::{DomainIndex, LocalNodeIndex, ReplicaAddress};
-pub use self::external::MaterializationStatus;
+pub use self::external::{MaterializationKind, MaterializationStatus};
 pub use self::index::{Index, IndexType};
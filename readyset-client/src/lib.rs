@@ -349,7 +349,9 @@ impl<T> From<T> for Tagged<T> {
 use url::Url;
 
 pub use crate::consensus::WorkerDescriptor;
-pub use crate::controller::{ControllerDescriptor, GraphvizOptions, ReadySetHandle};
+pub use crate::controller::{
+    ControllerDescriptor, GraphvizNodeShape, GraphvizOptions, ReadySetHandle,
+};
 pub use crate::table::{
     Modification, Operation, PacketData, PacketPayload, PacketTrace, PersistencePoint, Table,
     TableOperation, TableReplicationStatus, TableRequest, TableStatus,
@@ -163,6 +163,43 @@ pub mod recorded {
     /// is in progress, 0 otherwise.
     pub const CONTROLLER_MIGRATION_IN_PROGRESS: &str = "readyset_controller.migration_in_progress";
 
+    /// Counter: The number of indices added to new nodes during a migration which were already
+    /// covered by an index in one of that node's ancestors. Incremented each time the controller
+    /// plans the materializations for a migration.
+    pub const CONTROLLER_REDUNDANT_INDICES: &str = "readyset_controller.redundant_indices";
+
+    /// Counter: The number of times a migration needed to make an existing, fully materialized or
+    /// unmaterialized node partially materialized but couldn't, either because the node was
+    /// already fully materialized (in which case it stays full), or because one of its descendants
+    /// was already materialized (in which case the migration fails).
+    pub const CONTROLLER_FULL_TO_PARTIAL_CONFLICTS: &str =
+        "readyset_controller.full_to_partial_conflicts";
+
+    /// Counter: The number of new partially materialized nodes found below a longer chain of
+    /// partial materializations than the configured maximum partial chain depth. Incremented each
+    /// time the controller plans the materializations for a migration.
+    pub const CONTROLLER_DEEP_PARTIAL_CHAINS: &str = "readyset_controller.deep_partial_chains";
+
+    /// Counter: The number of times a node placed beyond the materialization frontier had no
+    /// state of its own, so the frontier was moved to one of its materialized parents instead.
+    /// Incremented each time the controller places the frontier for a migration.
+    pub const CONTROLLER_PURGE_MOVED_TO_ANCESTOR: &str =
+        "readyset_controller.purge_moved_to_ancestor";
+
+    /// Counter: The number of shard mergers below a column aliasing the sharding column which were
+    /// allowed with a warning rather than rejected, because `--allow-aliased-sharding` is set.
+    /// Incremented each time the controller validates the materializations for a migration.
+    pub const CONTROLLER_ALIASED_SHARDING_ALLOWED: &str =
+        "readyset_controller.aliased_sharding_allowed";
+
+    /// Gauge: The total number of replay paths set up across all nodes in the graph. Updated each
+    /// time the controller commits the materializations for a migration.
+    pub const CONTROLLER_REPLAY_PATHS: &str = "readyset_controller.replay_paths";
+
+    /// Gauge: The total number of indices across all materialized nodes in the graph. Updated each
+    /// time the controller commits the materializations for a migration.
+    pub const CONTROLLER_INDICES: &str = "readyset_controller.indices";
+
     /// Counter: The number of evicitons performed at a worker. Incremented each
     /// time `do_eviction` is called at the worker.
     pub const EVICTION_WORKER_EVICTIONS_REQUESTED: &str =
@@ -112,6 +112,7 @@ impl From<CacheExpr> for CreateCacheStatement {
             // CacheExpr represents a migrated query, and the below fields are not relevant for an
             // already-migrated query
             concurrently: false,
+            dry_run: false,
             unparsed_create_cache_statement: None,
         }
     }
@@ -5,6 +5,7 @@ use std::sync::Arc;
 use ahash::RandomState;
 use common::SizeOf;
 use dataflow_expression::{PostLookup, ReaderProcessing};
+use dataflow_state::LookupCounter;
 use nom_sql::Relation;
 use reader_map::{EvictionQuantity, EvictionStrategy};
 use readyset_client::consistency::Timestamp;
@@ -153,6 +154,7 @@ fn new_inner(
     };
 
     let (notifier, receiver) = tokio::sync::broadcast::channel(1);
+    let lookups = Arc::new(LookupCounter::default());
     let partial = trigger.is_some();
     let w = WriteHandle {
         partial,
@@ -164,6 +166,7 @@ fn new_inner(
         mem_size: 0,
         notifier,
         eviction_epoch: 0,
+        lookups: Arc::clone(&lookups),
     };
 
     let r = SingleReadHandle {
@@ -173,6 +176,7 @@ fn new_inner(
         post_lookup: post_processing,
         receiver,
         eviction_epoch: 0,
+        lookups,
     };
 
     (r, w)
@@ -202,6 +206,8 @@ pub(crate) struct WriteHandle {
     notifier: ReaderUpdatedSender,
     /// How many eviction rounds this handle had
     eviction_epoch: usize,
+    /// The number of lookups performed through all the read handles for this reader
+    lookups: Arc<LookupCounter>,
 }
 
 type Key<'a> = Cow<'a, [DfValue]>;
@@ -456,6 +462,17 @@ impl WriteHandle {
     pub(crate) fn replay_done(&self) -> bool {
         self.replay_done
     }
+
+    /// The total number of lookups performed through all the read handles for this reader
+    pub(crate) fn lookup_count(&self) -> u64 {
+        self.lookups.get()
+    }
+
+    /// Only count one in every `sample_rate` lookups through the read handles for this reader from
+    /// now on, scaling [`lookup_count`](Self::lookup_count) accordingly
+    pub(crate) fn set_lookup_sample_rate(&self, sample_rate: u64) {
+        self.lookups.set_sample_rate(sample_rate)
+    }
 }
 
 impl SizeOf for WriteHandle {
@@ -490,6 +507,9 @@ pub struct SingleReadHandle {
     receiver: ReaderUpdatedNotifier,
     /// Caches the eviction epoch of the associated [`WriteHandle`]
     eviction_epoch: usize,
+    /// Shared with the [`WriteHandle`] and all other read handles for the same reader, so that the
+    /// domain can report how often the reader is read from
+    lookups: Arc<LookupCounter>,
 }
 
 impl Clone for SingleReadHandle {
@@ -501,6 +521,7 @@ impl Clone for SingleReadHandle {
             post_lookup: self.post_lookup.clone(),
             receiver: self.receiver.resubscribe(),
             eviction_epoch: self.eviction_epoch,
+            lookups: Arc::clone(&self.lookups),
         }
     }
 }
@@ -544,6 +565,7 @@ impl SingleReadHandle {
         &self,
         keys: &'a [KeyComparison],
     ) -> Result<SharedResults, LookupError<'a>> {
+        self.lookups.record(keys.len() as u64);
         match self.handle.get_multi(keys) {
             Err(e) if e.is_miss() && self.trigger.is_none() => Ok(SharedResults::default()),
             r => r,
@@ -556,6 +578,7 @@ impl SingleReadHandle {
         &self,
         keys: &'a [KeyComparison],
     ) -> Result<SharedResults, LookupError<'a, ReaderUpdatedNotifier>> {
+        self.lookups.record(keys.len() as u64);
         match self
             .handle
             .get_multi_and_map_error(keys, || self.receiver.resubscribe())
@@ -15,8 +15,8 @@ use std::{cell, cmp, mem, process, time};
 use ahash::RandomState;
 use backoff::ExponentialBackoffBuilder;
 use dataflow_state::{
-    BaseTableState, EvictBytesResult, EvictKeysResult, EvictRandomResult, MaterializedNodeState,
-    PersistenceType, PointKey, RangeKey, RangeLookupResult,
+    BaseTableState, EvictBytesResult, EvictKeysResult, EvictRandomResult, EvictionGranularity,
+    MaterializedNodeState, PersistenceType, PointKey, RangeKey, RangeLookupResult,
 };
 use failpoint_macros::failpoint;
 use futures_util::future::FutureExt;
@@ -28,7 +28,7 @@ use metrics::{counter, histogram};
 use nom_sql::Relation;
 use petgraph::graph::NodeIndex;
 use readyset_alloc::StdThreadBuildWrapper;
-use readyset_client::debug::info::KeyCount;
+use readyset_client::debug::info::{KeyCount, NodeHealth};
 use readyset_client::internal::{self, Index};
 use readyset_client::metrics::recorded;
 use readyset_client::{KeyComparison, PersistencePoint, ReaderAddress};
@@ -88,6 +88,12 @@ pub struct Config {
     /// Allow fully materialized nodes to be persisted to disk.
     #[serde(default)]
     pub materialization_persistence: bool,
+
+    /// Only count one in every `lookup_sample_rate` lookups into materializations, scaling the
+    /// counts back up when they're reported, to reduce the overhead of tracking accesses to very
+    /// hot nodes at the cost of accuracy. Values of 0 and 1 both count every lookup.
+    #[serde(default)]
+    pub lookup_sample_rate: u64,
 }
 
 const BATCH_SIZE: usize = 256;
@@ -505,6 +511,7 @@ impl DomainBuilder {
 
             init_state_tx,
             materialization_persistence: self.config.materialization_persistence,
+            lookup_sample_rate: self.config.lookup_sample_rate,
         }
     }
 }
@@ -723,6 +730,10 @@ pub struct Domain {
     init_state_tx: tokio::sync::mpsc::Sender<MaterializedState>,
 
     materialization_persistence: bool,
+
+    /// Only count one in every `lookup_sample_rate` lookups into the materializations in this
+    /// domain (see [`Config::lookup_sample_rate`])
+    lookup_sample_rate: u64,
 }
 
 /// Creates the materialized node state for the given node.
@@ -1495,6 +1506,15 @@ impl Domain {
         Ok(None)
     }
 
+    fn handle_remove_replay_paths(&mut self, tags: Vec<Tag>) -> ReadySetResult<Option<Vec<u8>>> {
+        for tag in tags {
+            if self.replay_paths.remove(tag).is_some() {
+                trace!(%tag, "replay path removed");
+            }
+        }
+        Ok(None)
+    }
+
     #[inline(always)]
     fn handle_add_base_column(
         &mut self,
@@ -1717,6 +1737,7 @@ impl Domain {
                         .insert(node, MaterializedNodeState::Memory(MemoryState::default()));
                 }
                 let state = self.state.get_mut(node).unwrap();
+                state.set_lookup_sample_rate(self.lookup_sample_rate);
                 let strict = strict_indices
                     .into_iter()
                     .map(|(i, t)| (i, Some(t)))
@@ -1749,6 +1770,7 @@ impl Domain {
                     }
                 }
                 let state = self.state.get_mut(node).unwrap();
+                state.set_lookup_sample_rate(self.lookup_sample_rate);
                 let strict = strict_indices.into_iter().map(|x| (x, None)).collect();
                 let weak = weak_indices.into_iter().collect();
                 state.add_index_multi(strict, weak);
@@ -1809,6 +1831,7 @@ impl Domain {
                     r.reader_processing().clone(),
                     node_index,
                 );
+                write.set_lookup_sample_rate(self.lookup_sample_rate);
 
                 let shard = *self.shard.as_ref().unwrap_or(&0);
                 // TODO(ENG-838): Don't recreate every single node on leader failure.
@@ -1863,6 +1886,7 @@ impl Domain {
                     r.reader_processing().clone(),
                     node_index,
                 );
+                write.set_lookup_sample_rate(self.lookup_sample_rate);
 
                 let shard = *self.shard.as_ref().unwrap_or(&0);
                 // TODO(ENG-838): Don't recreate every single node on leader failure.
@@ -2002,13 +2026,14 @@ impl Domain {
             return Ok(None);
         }
         use std::thread;
-        invariant_eq!(
-            self.replay_paths
+        let first = {
+            let replay_path = self
+                .replay_paths
                 .get(tag)
-                .ok_or_else(|| ReadySetError::NoSuchReplayPath(tag.into()))?
-                .source,
-            Some(from)
-        );
+                .ok_or_else(|| ReadySetError::NoSuchReplayPath(tag.into()))?;
+            invariant_eq!(replay_path.source, Some(from));
+            replay_path.path[0].node
+        };
 
         let start = time::Instant::now();
         self.total_replay_time.start();
@@ -2045,7 +2070,7 @@ impl Domain {
             "current state cloned for replay"
         );
 
-        let link = Link::new(from, self.replay_paths[tag].path[0].node);
+        let link = Link::new(from, first);
 
         // we're been given an entire state snapshot, but we need to digest it
         // piece by piece spawn off a thread to do that chunking. however, before
@@ -2193,6 +2218,7 @@ impl Domain {
         node_idx: LocalNodeIndex,
         purge: bool,
         index: HashSet<Index>,
+        eviction_granularity: EvictionGranularity,
     ) -> ReadySetResult<Option<Vec<u8>>> {
         invariant_eq!(self.mode, DomainMode::Forwarding);
 
@@ -2260,6 +2286,7 @@ impl Domain {
                 }
                 _ => {
                     let mut s = MaterializedNodeState::Memory(MemoryState::default());
+                    s.set_lookup_sample_rate(self.lookup_sample_rate);
                     for idx in index {
                         s.add_index(idx, None);
                     }
@@ -2273,6 +2300,12 @@ impl Domain {
             true
         };
 
+        // partial states are created before the node is readied, so this has to apply to existing
+        // state too
+        if let Some(MaterializedNodeState::Memory(state)) = self.state.get_mut(node_idx) {
+            state.set_eviction_granularity(eviction_granularity);
+        }
+
         if is_ready && self.not_ready.remove(&node_idx) {
             trace!(local = node_idx.id(), "readying empty node");
         }
@@ -2395,6 +2428,57 @@ impl Domain {
         Ok(Some(bincode::serialize(&res)?))
     }
 
+    fn handle_request_index_sizes(&mut self) -> ReadySetResult<Option<Vec<u8>>> {
+        let mut res = Vec::new();
+        for (local_index, node_ref) in self.nodes.iter() {
+            let node = node_ref.borrow();
+            if node.is_reader() {
+                continue;
+            }
+            if let Some(sizes) = self.state.get(local_index).and_then(|s| s.index_sizes()) {
+                res.push((node.global_addr(), sizes));
+            }
+        }
+        Ok(Some(bincode::serialize(&res)?))
+    }
+
+    fn handle_request_node_lookups(&mut self) -> ReadySetResult<Option<Vec<u8>>> {
+        let mut res = Vec::new();
+        for (local_index, node_ref) in self.nodes.iter() {
+            let node = node_ref.borrow();
+            if node.is_reader() {
+                if let Some(wh) = self.reader_write_handles.get(local_index) {
+                    res.push((node.global_addr(), wh.lookup_count()));
+                }
+            } else if let Some(state) = self.state.get(local_index) {
+                res.push((node.global_addr(), state.lookup_count()));
+            }
+        }
+        Ok(Some(bincode::serialize(&res)?))
+    }
+
+    fn handle_request_node_health(&self) -> ReadySetResult<Option<Vec<u8>>> {
+        let replaying_to = match self.mode {
+            DomainMode::Replaying { to, .. } => Some(to),
+            DomainMode::Forwarding => None,
+        };
+        let res = self
+            .nodes
+            .iter()
+            .map(|(local_index, node_ref)| {
+                let health = if self.not_ready.contains(&local_index) {
+                    NodeHealth::NotReady
+                } else if replaying_to == Some(local_index) {
+                    NodeHealth::Replaying
+                } else {
+                    NodeHealth::Ready
+                };
+                (node_ref.borrow().global_addr(), health)
+            })
+            .collect::<Vec<_>>();
+        Ok(Some(bincode::serialize(&res)?))
+    }
+
     #[inline(always)]
     fn handle_query_replay_done(
         &mut self,
@@ -2430,7 +2514,11 @@ impl Domain {
         // know what hole to fill when we've satisfied replays to those columns
         self.state
             .entry(node)
-            .or_insert_with(|| MaterializedNodeState::Memory(MemoryState::default()))
+            .or_insert_with(|| {
+                let state = MemoryState::default();
+                state.set_lookup_sample_rate(self.lookup_sample_rate);
+                MaterializedNodeState::Memory(state)
+            })
             .add_index(index, Some(vec![tag]));
         Ok(None)
     }
@@ -2477,6 +2565,7 @@ impl Domain {
         let ret = match req {
             DomainRequest::AddNode { node, parents } => self.handle_add_node(node, parents),
             DomainRequest::RemoveNodes { nodes } => self.handle_remove_nodes(nodes),
+            DomainRequest::RemoveReplayPaths { tags } => self.handle_remove_replay_paths(tags),
             DomainRequest::AddBaseColumn {
                 node,
                 column,
@@ -2556,7 +2645,8 @@ impl Domain {
                 node: node_idx,
                 purge,
                 index,
-            } => self.handle_ready(node_idx, purge, index),
+                eviction_granularity,
+            } => self.handle_ready(node_idx, purge, index, eviction_granularity),
             DomainRequest::GetStatistics => self.handle_get_statistics(),
             DomainRequest::RequestMinPersistedReplicationOffset => Ok(Some(bincode::serialize(
                 &self.min_persisted_replication_offset()?,
@@ -2568,6 +2658,9 @@ impl Domain {
                 Ok(Some(bincode::serialize(&self.snapshotting_base_nodes())?))
             }
             DomainRequest::RequestNodeSizes => self.handle_request_node_sizes(),
+            DomainRequest::RequestIndexSizes => self.handle_request_index_sizes(),
+            DomainRequest::RequestNodeLookups => self.handle_request_node_lookups(),
+            DomainRequest::RequestNodeHealth => self.handle_request_node_health(),
             DomainRequest::Packet(pkt) => {
                 self.handle_packet(pkt, executor)?;
                 Ok(None)
@@ -2937,7 +3030,11 @@ impl Domain {
 
         let keys: HashSet<KeyComparison> = keys.into_iter().collect();
 
-        let (source, index, path) = match &self.replay_paths[tag] {
+        let Some(replay_path) = self.replay_paths.get(tag) else {
+            debug!(%tag, "dropping replay request for removed replay path");
+            return Ok(());
+        };
+        let (source, index, path) = match replay_path {
             ReplayPath {
                 source: Some(source),
                 trigger: TriggerEndpoint::Start(index),
@@ -3059,10 +3156,12 @@ impl Domain {
         let tag = m
             .tag()
             .ok_or_else(|| internal_err!("handle_replay called on an invalid message"))?;
-        let path = self
-            .replay_paths
-            .get(tag)
-            .ok_or_else(|| internal_err!("Replay path not found with tag {tag:?}"))?;
+        // replay paths are removed from each domain along them independently, so pieces of a
+        // replay that were already in flight can still arrive after the path has been removed
+        let Some(path) = self.replay_paths.get(tag) else {
+            debug!(%tag, "dropping replay piece for removed replay path");
+            return Ok(());
+        };
 
         if self.nodes[path.last_segment().node].borrow().is_dropped() {
             return Ok(());
@@ -3672,7 +3771,11 @@ impl Domain {
                             // this is a node that we were doing lookups into as part of
                             // the replay -- make sure we evict any state we may have added
                             // there.
-                            evict_tags.retain(|tag| tag_match(&self.replay_paths[*tag], pn));
+                            evict_tags.retain(|tag| {
+                                self.replay_paths
+                                    .get(*tag)
+                                    .map_or(false, |rp| tag_match(rp, pn))
+                            });
 
                             let state = self.state.get_mut(pn).unwrap();
                             assert!(state.is_partial());
@@ -4046,7 +4149,11 @@ impl Domain {
                 internal!();
             }
 
-            if self.replay_paths[tag].notify_done {
+            let Some(replay_path) = self.replay_paths.get(tag) else {
+                debug!(%tag, "finished replay for removed replay path");
+                return Ok(());
+            };
+            if replay_path.notify_done {
                 // NOTE: this will only be Some for non-partial replays
                 debug!(node = node.id(), "noting replay completed");
                 self.state
@@ -4107,9 +4214,7 @@ impl Domain {
             )?;
             match path.trigger {
                 TriggerEndpoint::Local(_) => {
-                    let replay_path = &replay_paths[tag];
-
-                    let dest = replay_path.last_segment();
+                    let dest = path.last_segment();
 
                     if nodes[dest.node].borrow().is_reader() {
                         // already evicted from in walk_path
@@ -4130,7 +4235,7 @@ impl Domain {
                         local = %dest.node,
                         ?keys,
                         ?tag,
-                        target = ?(replay_path.target_node(), &replay_path.target_index),
+                        target = ?(path.target_node(), &path.target_index),
                         "Evicting keys"
                     );
                     if let Some(result) = state[dest.node].evict_keys(tag, &keys) {
@@ -4758,6 +4863,7 @@ impl Domain {
             if self.not_ready.remove(&local_idx) {
                 trace!(local = local_idx.id(), "readying empty node");
             }
+            state.set_lookup_sample_rate(self.lookup_sample_rate);
             assert!(self.state.insert(local_idx, state).is_none());
         } else {
             warn!(
@@ -4950,4 +5056,120 @@ mod test {
             out.send(fake_addr(), Packet::Spin);
         });
     }
+
+    fn empty_domain() -> Domain {
+        DomainBuilder {
+            index: DomainIndex::new(0),
+            shard: None,
+            replica: 0,
+            nshards: 1,
+            nodes: Default::default(),
+            persistence_parameters: Default::default(),
+            config: Config {
+                aggressively_update_state_sizes: false,
+                view_request_timeout: time::Duration::from_secs(5),
+                table_request_timeout: time::Duration::from_secs(5),
+                eviction_kind: Default::default(),
+                verbose_metrics: false,
+                materialization_persistence: false,
+                lookup_sample_rate: 1,
+            },
+        }
+        .build(
+            Default::default(),
+            Arc::new(ChannelCoordinator::new()),
+            Default::default(),
+            tokio::sync::mpsc::channel(1).0,
+        )
+    }
+
+    #[test]
+    fn packets_for_removed_replay_paths_are_dropped() {
+        let mut domain = empty_domain();
+        let tag = Tag::new(1);
+        let node = LocalNodeIndex::make(0);
+        domain
+            .replay_paths
+            .insert(ReplayPathSpec {
+                tag,
+                source: Some(node),
+                source_index: Some(Index::hash_map(vec![0])),
+                path: vec1![ReplayPathSegment {
+                    node,
+                    force_tag_to: None,
+                    partial_index: Some(Index::hash_map(vec![0])),
+                    is_target: true,
+                }],
+                partial_unicast_sharder: None,
+                notify_done: false,
+                trigger: TriggerEndpoint::Local(Index::hash_map(vec![0])),
+            })
+            .unwrap();
+        domain.handle_remove_replay_paths(vec![tag]).unwrap();
+        assert!(domain.replay_paths.get(tag).is_none());
+
+        // packets which were already in flight for the path when it was removed
+        let key = KeyComparison::Equal(vec1![DfValue::from(1)]);
+        let mut out = Outboxes::new();
+        for packet in [
+            Packet::ReplayPiece {
+                link: Link::new(node, node),
+                tag,
+                data: Default::default(),
+                context: ReplayPieceContext::Partial {
+                    for_keys: HashSet::from([key.clone()]),
+                    requesting_shard: 0,
+                    requesting_replica: 0,
+                    unishard: true,
+                },
+                cache_name: "q".into(),
+            },
+            Packet::RequestPartialReplay {
+                tag,
+                keys: vec![key.clone()],
+                unishard: true,
+                requesting_shard: 0,
+                requesting_replica: 0,
+                cache_name: "q".into(),
+            },
+            Packet::Evict {
+                req: EvictRequest::Keys {
+                    link: Link::new(node, node),
+                    tag,
+                    keys: vec![key],
+                },
+                done: None,
+                barrier: 0,
+                credits: 0,
+            },
+            Packet::Evict {
+                req: EvictRequest::SingleKey { tag, key: None },
+                done: None,
+                barrier: 0,
+                credits: 0,
+            },
+        ] {
+            domain.handle_packet(packet, &mut out).unwrap();
+        }
+
+        // a full replay which was still finishing when its path was removed
+        domain.mode = DomainMode::Replaying {
+            to: node,
+            buffered: VecDeque::new(),
+            passes: 0,
+        };
+        domain
+            .handle_packet(
+                Packet::Finish {
+                    tag,
+                    node,
+                    cache_name: "q".into(),
+                },
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(domain.mode, DomainMode::Forwarding);
+
+        assert!(out.take_messages().is_empty());
+    }
 }
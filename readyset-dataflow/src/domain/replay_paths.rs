@@ -332,6 +332,31 @@ impl ReplayPaths {
         Ok(())
     }
 
+    /// Remove the replay path with the given tag from this set of replay paths, along with any
+    /// record of it wanting to perform replays sourced at generated columns, and return it if it
+    /// existed
+    pub(super) fn remove(&mut self, tag: Tag) -> Option<ReplayPath> {
+        let path = self.by_tag.remove(&tag)?;
+
+        if let Some(targets) = self.by_dst.get_mut(path.last_segment().node) {
+            for indexes in targets.values_mut() {
+                for tags in indexes.values_mut() {
+                    tags.remove(&tag);
+                }
+                indexes.retain(|_, tags| !tags.is_empty());
+            }
+        }
+
+        for columns in self.generated_columns.values_mut() {
+            for generated in columns.values_mut() {
+                generated.retain(|g| g.downstream_tag != tag);
+            }
+            columns.retain(|_, generated| !generated.is_empty());
+        }
+
+        Some(path)
+    }
+
     /// Record that a given set of columns are generated by a node, and that a particular tag is
     /// going to want to perform replays sourced at those columns
     pub(super) fn insert_generated_columns(
@@ -402,6 +427,55 @@ mod tests {
         assert_eq!(resolved_tags, Some(&HashSet::from([Tag::new(1)])));
     }
 
+    #[test]
+    fn insert_then_remove() {
+        let mut paths = ReplayPaths::default();
+        for tag in [Tag::new(1), Tag::new(2)] {
+            paths
+                .insert(ReplayPathSpec {
+                    tag,
+                    source: Some(LocalNodeIndex::make(0)),
+                    source_index: Some(Index::hash_map(vec![0])),
+                    path: vec1![ReplayPathSegment {
+                        node: LocalNodeIndex::make(1),
+                        force_tag_to: None,
+                        partial_index: Some(Index::hash_map(vec![0])),
+                        is_target: true
+                    }],
+                    partial_unicast_sharder: None,
+                    notify_done: false,
+                    trigger: TriggerEndpoint::Local(Index::hash_map(vec![0])),
+                })
+                .unwrap();
+        }
+        paths.insert_generated_columns(LocalNodeIndex::make(0), vec![0], Tag::new(1));
+
+        assert!(paths.remove(Tag::new(1)).is_some());
+        assert!(paths.remove(Tag::new(1)).is_none());
+        assert!(paths.get(Tag::new(1)).is_none());
+        assert!(paths
+            .tags_for_generated_columns(LocalNodeIndex::make(0), &[0usize][..])
+            .is_none());
+        assert_eq!(
+            paths.tags_for_index(
+                Destination(LocalNodeIndex::make(1)),
+                Target(LocalNodeIndex::make(1)),
+                &Index::hash_map(vec![0]),
+            ),
+            Some(&HashSet::from([Tag::new(2)]))
+        );
+
+        assert!(paths.remove(Tag::new(2)).is_some());
+        assert_eq!(
+            paths.tags_for_index(
+                Destination(LocalNodeIndex::make(1)),
+                Target(LocalNodeIndex::make(1)),
+                &Index::hash_map(vec![0]),
+            ),
+            None
+        );
+    }
+
     #[test]
     fn insert_then_resolve_extended_path() {
         let mut paths = ReplayPaths::default();
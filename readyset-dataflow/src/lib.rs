@@ -39,7 +39,8 @@ pub use dataflow_expression::{
     PostLookupAggregateFunction, PostLookupAggregates, ReaderProcessing,
 };
 pub use dataflow_state::{
-    BaseTableState, DurabilityMode, MaterializedNodeState, PersistenceParameters, PersistentState,
+    BaseTableState, DurabilityMode, EvictionGranularity, MaterializedNodeState,
+    PersistenceParameters, PersistentState,
 };
 
 pub use crate::domain::channel::{ChannelCoordinator, DomainReceiver, DomainSender, DualTcpStream};
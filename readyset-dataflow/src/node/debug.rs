@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use itertools::Itertools;
@@ -35,14 +35,81 @@ where
 }
 
 impl Node {
+    /// Returns the name to show for column `col` of this node, which is a placeholder based on the
+    /// position of the column if `anonymize` is set
+    fn column_display_name(&self, col: usize, anonymize: bool) -> String {
+        match self.columns.get(col) {
+            Some(_) if anonymize => format!("col{col}"),
+            Some(c) => c.name.to_string(),
+            None => col.to_string(),
+        }
+    }
+
+    /// Describe the given indices into this node's state by their type and the names of their
+    /// columns, eg `hash[id], btree[created_at]`, in sorted order. Columns with an entry in
+    /// `column_labels` are described by that label instead of their name, and if `anonymize` is
+    /// set other columns are described by their position rather than their name.
+    fn describe_indices(
+        &self,
+        indices: &HashSet<Index>,
+        column_labels: &HashMap<usize, String>,
+        anonymize: bool,
+    ) -> String {
+        indices
+            .iter()
+            .sorted()
+            .map(|index| {
+                format!(
+                    "{}[{}]",
+                    index.index_type,
+                    index
+                        .columns
+                        .iter()
+                        .map(|&col| match column_labels.get(&col) {
+                            Some(label) => label.clone(),
+                            None => self.column_display_name(col, anonymize),
+                        })
+                        .join(",")
+                )
+            })
+            .join(", ")
+    }
+
+    /// Describe this node for rendering in a graphviz graph.
+    ///
+    /// If `detailed` is true and `indices` is given, the label for the node includes the type and
+    /// columns of each of the indices into its state, with any columns in `column_labels`
+    /// described by their label rather than their name.
+    ///
+    /// If `anonymized_name` is given, it's shown in place of the name of the node, and the
+    /// description leaves out anything else which could reveal the schema or the queries: columns
+    /// are named by their position, and operators are described only by their kind, without any
+    /// expressions or literals.
+    #[allow(clippy::too_many_arguments)]
     pub fn describe(
         &self,
         idx: NodeIndex,
         detailed: bool,
         node_sizes: &HashMap<NodeIndex, NodeSize>,
         materialization_status: MaterializationStatus,
+        indices: Option<&HashSet<Index>>,
+        column_labels: &HashMap<usize, String>,
+        anonymized_name: Option<&str>,
     ) -> String {
         let mut s = String::new();
+        let anonymize = anonymized_name.is_some();
+        let name = escape(
+            anonymized_name
+                .map_or_else(|| self.name().display_unquoted().to_string(), str::to_owned),
+        );
+        let column_name = |col| self.column_display_name(col, anonymize);
+        let operator = |i: &NodeOperator| {
+            if anonymize {
+                i.to_string()
+            } else {
+                i.description(detailed)
+            }
+        };
         let border = match self.sharded_by {
             Sharding::ByColumn(_, _) | Sharding::Random(_) => "filled,dashed",
             _ => "filled",
@@ -57,15 +124,12 @@ impl Node {
                     s.push_str("[shape=point]\n");
                 }
                 NodeType::Base(..) => {
-                    s.push_str(&format!(
-                        "[style=bold, shape=tab, label=\"{}\"]\n",
-                        escape(self.name().display_unquoted())
-                    ));
+                    s.push_str(&format!("[style=bold, shape=tab, label=\"{}\"]\n", name));
                 }
                 NodeType::Sharder(ref sharder) => {
                     s.push_str(&format!(
                         "[style=bold, shape=Msquare, label=\"shard by {}\"]\n",
-                        escape(&self.columns[sharder.sharded_by()].name),
+                        escape(column_name(sharder.sharded_by())),
                     ));
                 }
                 NodeType::Reader(_) => {
@@ -76,14 +140,11 @@ impl Node {
                         } else {
                             "#5CBFF9"
                         },
-                        escape(self.name().display_unquoted().to_string())
+                        name
                     ));
                 }
                 NodeType::Internal(ref i) => {
-                    s.push_str(&format!(
-                        "[label=\"{}\"]\n",
-                        escape(i.description(detailed))
-                    ));
+                    s.push_str(&format!("[label=\"{}\"]\n", escape(operator(i))));
 
                     match materialization_status {
                         MaterializationStatus::Not => {}
@@ -151,10 +212,19 @@ impl Node {
                 }
                 MaterializationStatus::Full => "| ●",
             };
+            let materialized = match indices {
+                Some(indices) if !indices.is_empty() => {
+                    format!(
+                        "{materialized} {}",
+                        escape(self.describe_indices(indices, column_labels, anonymize))
+                    )
+                }
+                _ => materialized.to_owned(),
+            };
 
             let sharding = match self.sharded_by {
                 Sharding::ByColumn(k, w) => {
-                    format!("shard ⚷: {} / {}-way", self.columns[k].name, w)
+                    format!("shard ⚷: {} / {}-way", column_name(k), w)
                 }
                 Sharding::Random(_) => "shard randomly".to_owned(),
                 Sharding::None => "unsharded".to_owned(),
@@ -179,14 +249,14 @@ impl Node {
                     s.push_str(&format!(
                         "{{ {{ {} / {} | {} {} {} }} | {} | {} }}",
                         addr,
-                        escape(self.name().display_unquoted()),
+                        name,
                         "B",
                         materialized,
                         key_count_str,
                         self.columns()
                             .iter()
                             .enumerate()
-                            .map(|(i, c)| format!("[{}] {} : {}", i, c.name, c.ty()))
+                            .map(|(i, c)| format!("[{}] {} : {}", i, column_name(i), c.ty()))
                             .join(", \\n"),
                         sharding
                     ));
@@ -201,7 +271,7 @@ impl Node {
                 NodeType::Sharder(ref sharder) => s.push_str(&format!(
                     "{{ {} | shard by {} | {} }}",
                     addr,
-                    self.columns[sharder.sharded_by()].name,
+                    column_name(sharder.sharded_by()),
                     sharding
                 )),
                 NodeType::Reader(ref r) => {
@@ -211,13 +281,7 @@ impl Node {
                     };
                     s.push_str(&format!(
                         "{{ {{ {} / {} {} {} {} }} | (reader / ⚷: {}) | {} }}",
-                        addr,
-                        escape(self.name().display_unquoted()),
-                        materialized,
-                        key_count_str,
-                        node_size_str,
-                        key,
-                        sharding,
+                        addr, name, materialized, key_count_str, node_size_str, key, sharding,
                     ))
                 }
                 NodeType::Internal(ref i) => {
@@ -227,8 +291,8 @@ impl Node {
                     s.push_str(&format!(
                         "{{ {} / {} | {} {} {} {} }}",
                         addr,
-                        escape(self.name().display_unquoted()),
-                        escape(i.description(detailed)),
+                        name,
+                        escape(operator(i)),
                         materialized,
                         key_count_str,
                         node_size_str,
@@ -240,7 +304,7 @@ impl Node {
                         self.columns()
                             .iter()
                             .enumerate()
-                            .map(|(i, c)| format!("[{}] {} : {}", i, c.name, c.ty()))
+                            .map(|(i, c)| format!("[{}] {} : {}", i, column_name(i), c.ty()))
                             .join(", \\n"),
                     ));
                     s.push_str(&format!(" | {}", sharding));
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 use std::fmt::{self, Display};
 
-use dataflow_state::MaterializedNodeState;
+use dataflow_state::{EvictionGranularity, MaterializedNodeState};
 use itertools::Itertools;
 use nom_sql::Relation;
 use readyset_client::{self, KeyComparison, PacketData, PacketTrace};
@@ -300,6 +300,11 @@ pub enum DomainRequest {
         nodes: Vec<LocalNodeIndex>,
     },
 
+    /// Direct domain to forget about some replay paths, because their targets have been removed.
+    RemoveReplayPaths {
+        tags: Vec<Tag>,
+    },
+
     /// Tell an egress node about its corresponding ingress node in the next domain
     AddEgressTx {
         /// The local index of the egress node we're informing about changes
@@ -402,6 +407,8 @@ pub enum DomainRequest {
         node: LocalNodeIndex,
         purge: bool,
         index: HashSet<Index>,
+        /// How the node's state should choose keys to evict, if it's materialized in memory
+        eviction_granularity: EvictionGranularity,
     },
 
     /// Each base table has an offset up to which data has been persisted to disk, and this
@@ -418,6 +425,19 @@ pub enum DomainRequest {
     /// bytes
     RequestNodeSizes,
 
+    /// Request a list of node indexes paired with the approximate size in bytes of each of their
+    /// indices, for all the non-reader nodes in the domain whose state can report that
+    RequestIndexSizes,
+
+    /// Request a list of node indexes paired with the total number of lookups performed into each
+    /// of their materializations, for all the nodes in the domain with materialized state
+    RequestNodeLookups,
+
+    /// Request a list of node indexes paired with the [`NodeHealth`] of each node in the domain
+    ///
+    /// [`NodeHealth`]: readyset_client::debug::info::NodeHealth
+    RequestNodeHealth,
+
     /// Process the packet, as per usual
     Packet(Packet),
 
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::fmt::{self, Display};
 use std::{iter, mem};
 
 use dataflow_state::PointKey;
@@ -349,7 +350,7 @@ impl<'a> ReplayContext<'a> {
 }
 
 /// A reference to one or more of a node's columns.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ColumnRef<N = NodeIndex> {
     /// The index of the referenced node.
     pub node: N,
@@ -465,6 +466,17 @@ impl LookupIndex {
     }
 }
 
+/// Renders the lookup index as its strictness followed by the underlying [`Index`], eg
+/// `strict hash[0,2]` or `weak btree[1]`
+impl Display for LookupIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupIndex::Strict(idx) => write!(f, "strict {idx}"),
+            LookupIndex::Weak(idx) => write!(f, "weak {idx}"),
+        }
+    }
+}
+
 impl std::ops::Index<usize> for LookupIndex {
     type Output = usize;
 
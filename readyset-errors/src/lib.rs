@@ -361,6 +361,25 @@ pub enum ReadySetError {
     #[error("Internal error: {0}")]
     Internal(String),
 
+    /// Materialization planning placed a node beyond the materialization frontier that must not
+    /// be beyond it, either because it is fully materialized or because it is upstream of a
+    /// materialization which is not beyond the frontier.
+    ///
+    /// Like [`Internal`], this always indicates a bug, but is kept separate so that violations of
+    /// this invariant can be specifically monitored for.
+    #[error(
+        "Internal error: {} {node} placed beyond materialization frontier: {reason}",
+        if *is_reader { "reader" } else { "node" }
+    )]
+    MaterializationFrontierViolation {
+        /// The index of the node which was placed beyond the frontier
+        node: usize,
+        /// Whether the node is a reader
+        is_reader: bool,
+        /// Why the node must not be placed beyond the frontier
+        reason: String,
+    },
+
     /// An error has occurred during an HTTP request
     #[error("{0}")]
     HttpError(String),
@@ -375,6 +394,66 @@ pub enum ReadySetError {
     #[error("Operation unsupported: {0}")]
     Unsupported(String),
 
+    /// A migration would have created a straddled join (a join with a partial key traced to
+    /// columns in both of its parents), but straddled joins are disabled.
+    #[error(
+        "Operation unsupported: straddled join at node {join}, with key traced to columns \
+         {left_columns:?} of node {left_parent} and {right_columns:?} of node {right_parent}. \
+         {remediation}"
+    )]
+    UnsupportedStraddledJoin {
+        /// The index of the join node
+        join: usize,
+        /// The index of the left parent of the join
+        left_parent: usize,
+        /// The columns in the left parent that the key is traced to
+        left_columns: Vec<usize>,
+        /// The index of the right parent of the join
+        right_parent: usize,
+        /// The columns in the right parent that the key is traced to
+        right_columns: Vec<usize>,
+        /// A description of how to avoid this error
+        remediation: String,
+    },
+
+    /// A migration would have had to make an existing node partially materialized, but one of the
+    /// node's existing descendants is already materialized, and would stop receiving updates for
+    /// keys missing from the node.
+    #[error(
+        "Operation unsupported: cannot make existing node {node} partially materialized, since \
+         its existing descendant {child} is already materialized. {remediation}"
+    )]
+    UnsupportedFullToPartial {
+        /// The index of the node which would have been made partial
+        node: usize,
+        /// The index of the already-materialized descendant of `node`
+        child: usize,
+        /// A description of how to avoid this error
+        remediation: String,
+    },
+
+    /// A lookup into a node resolves to a column of a base table which has since been dropped from
+    /// that table, most likely because the table's schema changed after the query the node belongs
+    /// to was created.
+    #[error(
+        "Schema mismatch: lookups into node {node} ({node_name}) by index {index} resolve to \
+         column {column} of base table {table}, which has been dropped. {remediation}"
+    )]
+    LookupOnDroppedColumn {
+        /// The index of the node being looked up into
+        node: usize,
+        /// The name of the node being looked up into
+        node_name: String,
+        /// The index of the lookup into `node`
+        index: String,
+        /// The name of the base table the column was dropped from
+        table: String,
+        /// The dropped column of the base table
+        column: usize,
+        /// A description of how to avoid this error
+        remediation: String,
+    },
+
     /// The query provided by the user could not be parsed by `nom-sql`.
     ///
     /// TODO(eta): extend nom-sql to be able to provide more granular parse failure information.
@@ -771,9 +850,15 @@ impl ReadySetError {
         self.any_cause(|e| e.is_unparseable_query())
     }
 
-    /// Returns `true` if the error is [`Unsupported`].
+    /// Returns `true` if the error is [`Unsupported`], [`UnsupportedStraddledJoin`], or
+    /// [`UnsupportedFullToPartial`].
     pub fn is_unsupported(&self) -> bool {
-        matches!(self, Self::Unsupported(..))
+        matches!(
+            self,
+            Self::Unsupported(..)
+                | Self::UnsupportedStraddledJoin { .. }
+                | Self::UnsupportedFullToPartial { .. }
+        )
     }
 
     /// Returns true if the error either *is* [`Unsupported`], or was *caused by*
@@ -782,6 +867,17 @@ impl ReadySetError {
         self.any_cause(|e| e.is_unsupported())
     }
 
+    /// Returns `true` if self is [`MaterializationFrontierViolation`].
+    pub fn is_materialization_frontier_violation(&self) -> bool {
+        matches!(self, Self::MaterializationFrontierViolation { .. })
+    }
+
+    /// Returns `true` if self either *is* [`MaterializationFrontierViolation`], or was *caused
+    /// by* [`MaterializationFrontierViolation`].
+    pub fn caused_by_materialization_frontier_violation(&self) -> bool {
+        self.any_cause(|e| e.is_materialization_frontier_violation())
+    }
+
     /// Returns `true` if self is ['ViewNotFound'] or ['ViewNotFoundForQuery'].
     pub fn is_view_not_found(&self) -> bool {
         matches!(
@@ -27,6 +27,10 @@ impl From<Error> for ps::Error {
                 ps::Error::MissingPreparedStatement(statement_id.to_string())
             }
             ReadySet(ReadySetError::Unsupported(s)) => ps::Error::Unsupported(s),
+            ReadySet(
+                e @ (ReadySetError::UnsupportedStraddledJoin { .. }
+                | ReadySetError::UnsupportedFullToPartial { .. }),
+            ) => ps::Error::Unsupported(e.to_string()),
             ReadySet(e) => ps::Error::Unknown(e.to_string()),
             PostgreSql(e) => e.into(),
         }
@@ -0,0 +1,5 @@
+use criterion::{criterion_group, criterion_main};
+use readyset_server::bench::{obligations_cache, replay_path_cache};
+
+criterion_group!(benches, obligations_cache, replay_path_cache);
+criterion_main!(benches);
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::future::Future;
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
@@ -5,6 +6,7 @@ use std::sync::Arc;
 use std::time::{self, Duration};
 
 use database_utils::{ReplicationServerId, UpstreamConfig};
+use dataflow::prelude::{Index, NodeIndex};
 use dataflow::PersistenceParameters;
 use readyset_client::consensus::{
     Authority, LocalAuthority, LocalAuthorityStore, NodeTypeSchedulingRestriction,
@@ -99,6 +101,18 @@ impl Builder {
         builder.set_replication_strategy(opts.domain_replication_options.into());
         builder.set_verbose_domain_metrics(opts.verbose_domain_metrics);
         builder.set_frontier_strategy(opts.materialization_frontier);
+        builder.set_strict_frontier(opts.strict_materialization_frontier);
+        builder.set_force_full(opts.force_full_materialization.into_iter().collect());
+        builder.set_materialization_access_sample_rate(opts.materialization_access_sample_rate);
+        builder.set_range_eviction(opts.range_eviction.into_iter().collect());
+        builder.set_replay_concurrency(opts.replay_concurrency);
+        builder.set_allow_aliased_sharding(opts.allow_aliased_sharding);
+        if let Some(path) = opts.record_migration_plan {
+            builder.set_record_plan_path(path);
+        }
+        if let Some(depth) = opts.max_partial_chain_depth {
+            builder.set_max_partial_chain_depth(depth);
+        }
 
         if let Some(volume_id) = opts.volume_id {
             builder.set_volume_id(volume_id);
@@ -157,6 +171,93 @@ impl Builder {
         self.config.materialization_config.frontier_strategy = f;
     }
 
+    /// Should migrations fail if a node is placed beyond the materialization frontier, but there's
+    /// no state which could be placed beyond the frontier for it?
+    pub fn set_strict_frontier(&mut self, strict_frontier: bool) {
+        self.config.materialization_config.strict_frontier = strict_frontier;
+    }
+
+    /// Should migrations stop materializing new nodes whose indices are all already covered by
+    /// indices in their ancestors, rather than just warning about them?
+    pub fn set_suppress_redundant_indices(&mut self, suppress_redundant_indices: bool) {
+        self.config
+            .materialization_config
+            .suppress_redundant_indices = suppress_redundant_indices;
+    }
+
+    /// Should migrations drop BTree indices on new, fully materialized nodes that are a prefix of
+    /// another BTree index on the same node, when nothing needs the shorter index exactly?
+    pub fn set_merge_prefix_indices(&mut self, merge_prefix_indices: bool) {
+        self.config.materialization_config.merge_prefix_indices = merge_prefix_indices;
+    }
+
+    /// Should replays be sourced from an existing BTree index on a fully materialized ancestor,
+    /// rather than adding a new hash index on the same columns?
+    pub fn set_prefer_existing_indices(&mut self, prefer_existing_indices: bool) {
+        self.config.materialization_config.prefer_existing_indices = prefer_existing_indices;
+    }
+
+    /// Set the maximum estimated total size, in bytes, of all materializations. Migrations which
+    /// would exceed it fail before being applied.
+    pub fn set_max_total_materialized_bytes(&mut self, bytes: u64) {
+        self.config
+            .materialization_config
+            .max_total_materialized_bytes = Some(bytes);
+    }
+
+    /// Set the index to place on base tables without a primary or unique key, when nothing else
+    /// needs an index on them
+    pub fn set_default_base_index(&mut self, index: Index) {
+        self.config.materialization_config.default_base_index = Some(index);
+    }
+
+    /// Always fully materialize nodes whose names start with any of the given prefixes, if full
+    /// materialization is allowed
+    pub fn set_force_full(&mut self, prefixes: HashSet<String>) {
+        self.config.materialization_config.force_full = prefixes;
+    }
+
+    /// Only count one in every `sample_rate` lookups into materializations when tracking how often
+    /// each materialization is accessed
+    pub fn set_materialization_access_sample_rate(&mut self, sample_rate: u64) {
+        self.config.domain_config.lookup_sample_rate = sample_rate;
+    }
+
+    /// Evict runs of adjacent keys, rather than individual keys, from the in-memory state of nodes
+    /// whose names start with any of the given prefixes
+    pub fn set_range_eviction(&mut self, prefixes: HashSet<String>) {
+        self.config.materialization_config.range_eviction = prefixes;
+    }
+
+    /// Reconstruct up to `concurrency` independent new materializations via full replay at once
+    /// when committing a migration
+    pub fn set_replay_concurrency(&mut self, concurrency: usize) {
+        self.config.materialization_config.replay_concurrency = concurrency;
+    }
+
+    /// Should migrations which merge shards below a column aliasing the sharding column be allowed
+    /// with a warning, rather than rejected?
+    pub fn set_allow_aliased_sharding(&mut self, allow_aliased_sharding: bool) {
+        self.config.materialization_config.allow_aliased_sharding = allow_aliased_sharding;
+    }
+
+    /// Warn about new partial nodes below a chain of more than `depth` partial materializations
+    pub fn set_max_partial_chain_depth(&mut self, depth: usize) {
+        self.config.materialization_config.max_partial_chain_depth = Some(depth);
+    }
+
+    /// Append every domain request enqueued while committing materializations to the file at
+    /// `path`, so that migrations can be replayed later
+    pub fn set_record_plan_path(&mut self, path: PathBuf) {
+        self.config.materialization_config.record_plan_path = Some(path);
+    }
+
+    /// Never place the given nodes, or any of their ancestors, beyond the materialization frontier,
+    /// regardless of the frontier strategy
+    pub fn set_pinned_nodes(&mut self, nodes: HashSet<NodeIndex>) {
+        self.config.materialization_config.pinned_nodes = nodes;
+    }
+
     /// Allow the creation of all fully materialized nodes.
     ///
     /// Unless this is called, any migrations that add fully materialized nodes will return
@@ -336,6 +437,9 @@ impl Builder {
     /// that field for more information.
     pub fn set_materialization_persistence(&mut self, value: bool) {
         self.config.domain_config.materialization_persistence = value;
+        self.config
+            .materialization_config
+            .persistent_full_materializations = value;
     }
 
     /// Sets the value of [`Config::domain_config::table_request_timeout`]. See documentation of
@@ -44,7 +44,7 @@ use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
-use crate::controller::state::{DfState, DfStateHandle};
+use crate::controller::state::{dot_to_svg, DfState, DfStateHandle};
 use crate::controller::{ControllerState, Worker, WorkerIdentifier};
 use crate::worker::WorkerRequestKind;
 
@@ -274,12 +274,30 @@ impl Leader {
         match (&method, path) {
             (&Method::GET, "/simple_graph") => {
                 let ds = self.dataflow_state_handle.read().await;
-                Ok(ds.graphviz(false, None).into_bytes())
+                let opts = GraphvizOptions {
+                    detailed: false,
+                    ..Default::default()
+                };
+                Ok(ds.graphviz(&opts, None, None)?.into_bytes())
             }
             (&Method::GET, "/graph") => {
                 let ds = self.dataflow_state_handle.read().await;
                 let node_sizes = ds.node_sizes().await?;
-                Ok(ds.graphviz(true, Some(node_sizes)).into_bytes())
+                Ok(ds
+                    .graphviz(&Default::default(), Some(node_sizes), None)?
+                    .into_bytes())
+            }
+            (&Method::GET, "/graph.svg") => {
+                let dot = {
+                    let ds = self.dataflow_state_handle.read().await;
+                    let node_sizes = ds.node_sizes().await?;
+                    ds.graphviz(&Default::default(), Some(node_sizes), None)?
+                };
+                // Laying out a large graph can take a while, so do it off the runtime, and without
+                // holding the dataflow state lock
+                tokio::task::spawn_blocking(move || dot_to_svg(&dot))
+                    .await
+                    .map_err(|e| internal_err!("SVG rendering task failed: {e}"))?
             }
             (&Method::GET, path) if path.starts_with("/graph/") => {
                 #[allow(clippy::unwrap_used)]
@@ -290,17 +308,22 @@ impl Leader {
                 let ds = self.dataflow_state_handle.read().await;
                 let node_sizes = ds.node_sizes().await?;
                 Ok(ds
-                    .graphviz_for_query(&query_name, true, Some(node_sizes))?
+                    .graphviz_for_query(&query_name, &Default::default(), Some(node_sizes), None)?
                     .into_bytes())
             }
             (&Method::POST, "/graphviz") => {
                 let opts: GraphvizOptions = bincode::deserialize(&body)?;
                 let ds = self.dataflow_state_handle.read().await;
                 let node_sizes = ds.node_sizes().await?;
+                let node_health = if opts.show_health {
+                    Some(ds.node_health().await?)
+                } else {
+                    None
+                };
                 return_serialized!(if let Some(query) = &opts.for_query {
-                    ds.graphviz_for_query(query, opts.detailed, Some(node_sizes))?
+                    ds.graphviz_for_query(query, &opts, Some(node_sizes), node_health)?
                 } else {
-                    ds.graphviz(opts.detailed, Some(node_sizes))
+                    ds.graphviz(&opts, Some(node_sizes), node_health)?
                 });
             }
             (&Method::GET | &Method::POST, "/get_statistics") => {
@@ -340,6 +363,66 @@ impl Leader {
                 let ds = self.dataflow_state_handle.read().await;
                 return_serialized!(ds.materialization_info().await?);
             }
+            (&Method::GET | &Method::POST, "/check_materializations") => {
+                let ds = self.dataflow_state_handle.read().await;
+                return_serialized!(ds.check_materializations()?);
+            }
+            (&Method::POST, "/materialization_report") => {
+                let top_n = bincode::deserialize(&body)?;
+                let res = {
+                    let ds = self.dataflow_state_handle.read().await;
+                    ds.materialization_report(top_n).await
+                }?;
+                return_serialized!(res);
+            }
+            (&Method::POST, "/materialization_template") => {
+                let query: Relation = bincode::deserialize(&body)?;
+                let ds = self.dataflow_state_handle.read().await;
+                return_serialized!(ds.materialization_template(&query)?);
+            }
+            (&Method::POST, "/add_materialization_template") => {
+                let template = bincode::deserialize(&body)?;
+                let mut writer = self.dataflow_state_handle.write().await;
+                writer.as_mut().add_materialization_template(template);
+                self.dataflow_state_handle.commit(writer, authority).await?;
+                return_serialized!(());
+            }
+            (&Method::GET | &Method::POST, "/materialization_access_stats") => {
+                let res = {
+                    let ds = self.dataflow_state_handle.read().await;
+                    ds.materialization_access_stats().await
+                }?;
+                return_serialized!(res);
+            }
+            (&Method::GET | &Method::POST, "/shallow_nodes") => {
+                let ds = self.dataflow_state_handle.read().await;
+                return_serialized!(ds.shallow_nodes());
+            }
+            (&Method::POST, "/replay_paths_for") => {
+                let node = bincode::deserialize(&body)?;
+                let ds = self.dataflow_state_handle.read().await;
+                return_serialized!(ds.replay_paths_for(node));
+            }
+            (&Method::POST, "/replay_sources_for") => {
+                let node = bincode::deserialize(&body)?;
+                let ds = self.dataflow_state_handle.read().await;
+                return_serialized!(ds.replay_sources_for(node));
+            }
+            (&Method::POST, "/simulate_frontier") => {
+                let strategy: String = bincode::deserialize(&body)?;
+                let res = {
+                    let ds = self.dataflow_state_handle.read().await;
+                    ds.simulate_frontier(&strategy).await
+                }?;
+                return_serialized!(res);
+            }
+            (&Method::POST, "/reset_materialization_access_stats") => {
+                {
+                    let ds = self.dataflow_state_handle.read().await;
+                    ds.reset_materialization_access_stats().await
+                }?;
+                return_serialized!(());
+            }
             (&Method::GET, "/allocated_bytes") => {
                 let alloc_bytes = tikv_jemalloc_ctl::epoch::mib()
                     .and_then(|m| m.advance())
@@ -502,6 +585,13 @@ impl Leader {
                 }?;
                 return_serialized!(res);
             }
+            (&Method::POST, "/index_sizes") => {
+                let res = {
+                    let ds = self.dataflow_state_handle.read().await;
+                    ds.index_sizes().await
+                }?;
+                return_serialized!(res);
+            }
             (&Method::POST, "/leader_ready") => {
                 return_serialized!(leader_ready);
             }
@@ -543,6 +633,28 @@ impl Leader {
                 state_copy.extend_recipe(body, true).await?;
                 return_serialized!(ExtendRecipeResult::Done);
             }
+            (&Method::POST, "/dry_run_report") => {
+                let body: ExtendRecipeSpec = bincode::deserialize(&body)?;
+                if body.require_leader_ready {
+                    require_leader_ready()?;
+                }
+                let mut state_copy: DfState = {
+                    let reader = self.dataflow_state_handle.read().await;
+                    reader.clone()
+                };
+                return_serialized!(state_copy.dry_run_report(body).await?);
+            }
+            (&Method::POST, "/migration_impact") => {
+                let body: ExtendRecipeSpec = bincode::deserialize(&body)?;
+                if body.require_leader_ready {
+                    require_leader_ready()?;
+                }
+                let mut state_copy: DfState = {
+                    let reader = self.dataflow_state_handle.read().await;
+                    reader.clone()
+                };
+                return_serialized!(state_copy.migration_impact(body).await?);
+            }
             (&Method::GET | &Method::POST, "/adapter_rewrite_params") => {
                 let ds = self.dataflow_state_handle.read().await;
                 let supports = ds.recipe.adapter_rewrite_params();
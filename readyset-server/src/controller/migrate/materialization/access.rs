@@ -0,0 +1,130 @@
+//! Tracking of how often each materialization in the graph is read from.
+//!
+//! These are intended to drive decisions about materializations that depend on how "hot" or "cold"
+//! those materializations are, such as which nodes to place beyond the materialization frontier.
+//!
+//! The lookups themselves are counted by the states and readers in the domains, which only ever
+//! count up from when the state was created and report their totals on request (see
+//! `DfState::node_lookups`). To allow the counts to be reset without reaching into every domain,
+//! the controller keeps the totals reported at the time of the last reset as a baseline for each
+//! node, and reports lookups relative to that.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use petgraph::graph::NodeIndex;
+
+/// Per-node baselines for the lookup counts reported by domains.
+///
+/// Baselines must be [registered](AccessCounters::register) for a node before lookups into that
+/// node are reported. Resetting only requires a shared reference, so that it can be done while
+/// holding a read lock on the dataflow state.
+#[derive(Debug, Default)]
+pub(super) struct AccessCounters {
+    baselines: HashMap<NodeIndex, AtomicU64>,
+}
+
+impl Clone for AccessCounters {
+    fn clone(&self) -> Self {
+        Self {
+            baselines: self
+                .baselines
+                .iter()
+                .map(|(ni, baseline)| (*ni, AtomicU64::new(baseline.load(Ordering::Relaxed))))
+                .collect(),
+        }
+    }
+}
+
+impl AccessCounters {
+    /// Start reporting lookups into the given node, if we aren't already
+    pub(super) fn register(&mut self, ni: NodeIndex) {
+        self.baselines.entry(ni).or_default();
+    }
+
+    /// Stop reporting lookups into all nodes for which `f` returns false
+    pub(super) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(NodeIndex) -> bool,
+    {
+        self.baselines.retain(|&ni, _| f(ni));
+    }
+
+    /// Reset the lookup count for all registered nodes to zero, given the total number of lookups
+    /// into each node currently reported by the domains
+    pub(super) fn reset(&self, reported: &HashMap<NodeIndex, u64>) {
+        for (ni, baseline) in &self.baselines {
+            baseline.store(
+                reported.get(ni).copied().unwrap_or_default(),
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    /// Return the number of lookups into each registered node since the last reset, given the
+    /// total number of lookups into each node currently reported by the domains.
+    ///
+    /// If a node reports fewer lookups than its baseline, its state must have been recreated (for
+    /// example because the worker it was running on restarted) since the last reset, so all of the
+    /// lookups it reports are counted.
+    pub(super) fn snapshot(&self, reported: &HashMap<NodeIndex, u64>) -> HashMap<NodeIndex, u64> {
+        self.baselines
+            .iter()
+            .map(|(ni, baseline)| {
+                let total = reported.get(ni).copied().unwrap_or_default();
+                let baseline = baseline.load(Ordering::Relaxed);
+                (*ni, total.checked_sub(baseline).unwrap_or(total))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_and_reset() {
+        let mut counters = AccessCounters::default();
+        let registered = NodeIndex::new(1);
+        let unregistered = NodeIndex::new(2);
+        counters.register(registered);
+
+        let reported = HashMap::from([(registered, 3), (unregistered, 5)]);
+        assert_eq!(
+            counters.snapshot(&reported),
+            HashMap::from([(registered, 3)])
+        );
+
+        let cloned = counters.clone();
+        counters.reset(&reported);
+        assert_eq!(
+            counters.snapshot(&reported),
+            HashMap::from([(registered, 0)])
+        );
+        assert_eq!(cloned.snapshot(&reported), HashMap::from([(registered, 3)]));
+
+        let reported = HashMap::from([(registered, 7)]);
+        assert_eq!(
+            counters.snapshot(&reported),
+            HashMap::from([(registered, 4)])
+        );
+
+        counters.retain(|ni| ni != registered);
+        assert!(counters.snapshot(&reported).is_empty());
+    }
+
+    #[test]
+    fn recreated_state() {
+        let mut counters = AccessCounters::default();
+        let ni = NodeIndex::new(1);
+        counters.register(ni);
+        counters.reset(&HashMap::from([(ni, 10)]));
+
+        // The node's state was recreated, and has had 2 lookups since
+        assert_eq!(
+            counters.snapshot(&HashMap::from([(ni, 2)])),
+            HashMap::from([(ni, 2)])
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Benchmarks for the parts of materialization planning which are cached within a migration.
+//!
+//! These operate directly on synthetic graphs rather than going through a full migration, so they
+//! only compare the cached and uncached lookups themselves. They don't say how much of a real
+//! migration's planning time the caches save.
+
+use std::hint::black_box;
+
+use criterion::Criterion;
+use dataflow::prelude::*;
+
+use super::paths::ReplayPathCache;
+use super::{index_obligations, join_chain, Config, Materializations};
+use crate::controller::keys;
+
+/// Compare computing the indexing obligations of every node in a large migration from scratch,
+/// as every call to `extend` used to, with reusing the obligations cached by an earlier call.
+pub fn obligations_cache(c: &mut Criterion) {
+    let (graph, new) = join_chain(1000);
+    let config = Config::default();
+    let mut group = c.benchmark_group("index_obligations");
+
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            for &ni in &new {
+                black_box(index_obligations(&graph[ni], ni, &config));
+            }
+        })
+    });
+
+    let mut m = Materializations::new();
+    for &ni in &new {
+        m.obligations_for(&graph, ni);
+    }
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            for &ni in &new {
+                black_box(m.obligations_for(&graph, ni));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Compare computing the replay paths for an index spanning both sides of every join in a long
+/// chain of joins, each of which breaks its paths off at the generated columns, with looking them
+/// up in a warm [`ReplayPathCache`].
+pub fn replay_path_cache(c: &mut Criterion) {
+    let (graph, nodes) = join_chain(100);
+    let colrefs = nodes
+        .iter()
+        .filter(|&&ni| graph[ni].is_internal())
+        .map(|&node| ColumnRef {
+            node,
+            columns: vec![0, 2],
+        })
+        .collect::<Vec<_>>();
+    let mut group = c.benchmark_group("replay_paths");
+
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            for colref in &colrefs {
+                black_box(
+                    keys::replay_paths_for_nonstop(&graph, colref.clone(), IndexType::HashMap)
+                        .unwrap(),
+                );
+            }
+        })
+    });
+
+    let cache = ReplayPathCache::default();
+    for colref in &colrefs {
+        cache
+            .paths_for(&graph, colref.clone(), IndexType::HashMap)
+            .unwrap();
+    }
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            for colref in &colrefs {
+                black_box(
+                    cache
+                        .paths_for(&graph, colref.clone(), IndexType::HashMap)
+                        .unwrap(),
+                );
+            }
+        })
+    });
+
+    group.finish();
+}
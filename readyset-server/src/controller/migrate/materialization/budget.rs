@@ -0,0 +1,196 @@
+//! Estimating the total size of all materializations after a migration, so that migrations which
+//! would exceed [`Config::max_total_materialized_bytes`] can be rejected before they're applied.
+//!
+//! [`Config::max_total_materialized_bytes`]: super::Config::max_total_materialized_bytes
+
+use std::collections::{HashMap, HashSet};
+
+use dataflow::prelude::*;
+use itertools::Itertools;
+
+use super::sizes::SizeEstimator;
+use super::Materializations;
+
+/// The number of nodes contributing the most to the estimated size of all materializations to
+/// name when a migration exceeds the budget
+const TOP_CONTRIBUTORS: usize = 5;
+
+impl Materializations {
+    /// Returns true if the node at `ni` has state which will be fully populated as soon as it's
+    /// created, rather than filled on demand
+    fn is_full(&self, graph: &Graph, ni: NodeIndex) -> bool {
+        let materialized =
+            self.have.contains_key(&ni) || graph[ni].as_reader().and_then(|r| r.key()).is_some();
+        materialized && !self.partial.contains(&ni)
+    }
+
+    /// Estimate the size in bytes of the state of the new node at `ni`, memoizing the estimates
+    /// for each node in `estimates`.
+    ///
+    /// Nodes which `node_sizes` has an estimate for are assumed to be exactly that large. Other new
+    /// fully materialized nodes are estimated to be as large as the state of their nearest
+    /// materialized ancestors combined (since they're populated by replaying all of that state),
+    /// and new partially materialized nodes start out empty.
+    fn estimate_new_node(
+        &self,
+        graph: &Graph,
+        ni: NodeIndex,
+        node_sizes: &dyn SizeEstimator,
+        estimates: &mut HashMap<NodeIndex, u64>,
+    ) -> u64 {
+        if let Some(size) = node_sizes.estimate(ni) {
+            return size.bytes.0 as u64;
+        }
+        if let Some(&estimate) = estimates.get(&ni) {
+            return estimate;
+        }
+
+        let estimate = if self.partial.contains(&ni) {
+            0
+        } else {
+            graph
+                .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+                .filter(|&pi| !graph[pi].is_source())
+                .map(|pi| self.estimate_new_node(graph, pi, node_sizes, estimates))
+                .sum()
+        };
+        estimates.insert(ni, estimate);
+        estimate
+    }
+
+    /// Estimate the size in bytes of the state of every materialized node once the nodes in `new`
+    /// are committed, given the estimated size of the nodes in `node_sizes`.
+    pub(super) fn estimate_materialized_bytes(
+        &self,
+        graph: &Graph,
+        new: &HashSet<NodeIndex>,
+        node_sizes: &dyn SizeEstimator,
+    ) -> HashMap<NodeIndex, u64> {
+        let mut estimates = HashMap::new();
+        let mut sizes = node_sizes
+            .estimate_all(graph)
+            .into_iter()
+            .map(|(ni, size)| (ni, size.bytes.0 as u64))
+            .collect::<HashMap<_, _>>();
+        for &ni in new {
+            if self.is_full(graph, ni) && !sizes.contains_key(&ni) {
+                let estimate = self.estimate_new_node(graph, ni, node_sizes, &mut estimates);
+                sizes.insert(ni, estimate);
+            }
+        }
+        sizes
+    }
+
+    /// If [`Config::max_total_materialized_bytes`] is set, return an error if committing the nodes
+    /// in `new` would make the estimated total size of all materializations exceed it, given the
+    /// estimated size of the nodes in `node_sizes`.
+    ///
+    /// [`Config::max_total_materialized_bytes`]: super::Config::max_total_materialized_bytes
+    pub(in crate::controller) fn check_budget(
+        &self,
+        graph: &Graph,
+        new: &HashSet<NodeIndex>,
+        node_sizes: &dyn SizeEstimator,
+    ) -> ReadySetResult<()> {
+        let Some(budget) = self.config.max_total_materialized_bytes else {
+            return Ok(());
+        };
+
+        let sizes = self.estimate_materialized_bytes(graph, new, node_sizes);
+        let total = sizes.values().sum::<u64>();
+        if total > budget {
+            let top = sizes
+                .iter()
+                .sorted_by(|(a_ni, a), (b_ni, b)| b.cmp(a).then(a_ni.cmp(b_ni)))
+                .take(TOP_CONTRIBUTORS)
+                .map(|(ni, bytes)| {
+                    format!(
+                        "{} / {} ({bytes} bytes)",
+                        ni.index(),
+                        graph[*ni].name().display_unquoted()
+                    )
+                })
+                .join(", ");
+            unsupported!(
+                "Migration would bring the estimated size of all materializations to {total} \
+                 bytes, which exceeds the budget of {budget} bytes (largest nodes: {top})"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use readyset_client::debug::info::{NodeMaterializedSize, NodeSize};
+
+    use super::super::tests::projection;
+    use super::*;
+
+    fn node_size(bytes: usize) -> NodeSize {
+        NodeSize {
+            bytes: NodeMaterializedSize(bytes),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn budget_for_new_full_materialization() {
+        let (g, base, project) = projection(&[0, 1]);
+        let new = HashSet::from([project]);
+        let node_sizes = HashMap::from([(base, node_size(1000))]);
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![1])]));
+
+        // fully materializing the projection replays all of the base table's state into it
+        assert_eq!(
+            m.estimate_materialized_bytes(&g, &new, &node_sizes),
+            HashMap::from([(base, 1000), (project, 1000)])
+        );
+
+        m.check_budget(&g, &new, &node_sizes).unwrap();
+        m.config.max_total_materialized_bytes = Some(3000);
+        m.check_budget(&g, &new, &node_sizes).unwrap();
+        m.config.max_total_materialized_bytes = Some(1500);
+        let err = m.check_budget(&g, &new, &node_sizes).unwrap_err();
+        assert!(err.is_unsupported());
+        assert!(err.to_string().contains("2000 bytes"), "{err}");
+
+        // but partial materializations start out empty
+        m.partial.insert(project);
+        m.check_budget(&g, &new, &node_sizes).unwrap();
+    }
+
+    #[test]
+    fn budget_with_estimates_for_new_nodes() {
+        /// Estimates every node as being the same size, whether or not it exists yet
+        struct Uniform(usize);
+        impl SizeEstimator for Uniform {
+            fn estimate(&self, _ni: NodeIndex) -> Option<NodeSize> {
+                Some(node_size(self.0))
+            }
+        }
+
+        let (g, base, project) = projection(&[0, 1]);
+        let new = HashSet::from([project]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![1])]));
+
+        // the estimate for the new node is used instead of the size of its ancestors
+        let sizes = m.estimate_materialized_bytes(&g, &new, &Uniform(10));
+        assert_eq!(sizes[&base], 10);
+        assert_eq!(sizes[&project], 10);
+
+        m.config.max_total_materialized_bytes = Some(1000);
+        m.check_budget(&g, &new, &Uniform(10)).unwrap();
+        m.check_budget(&g, &new, &Uniform(1000)).unwrap_err();
+    }
+}
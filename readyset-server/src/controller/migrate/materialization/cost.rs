@@ -0,0 +1,100 @@
+//! Estimating how much upstream state a replay into a prospective index would have to scan, so
+//! that operators can see how expensive an index will be to fill before adding it.
+
+use std::collections::{HashMap, HashSet};
+
+use dataflow::prelude::*;
+use readyset_client::debug::info::{KeyCount, NodeSize};
+use readyset_errors::ReadySetResult;
+
+use super::Materializations;
+use crate::controller::keys;
+
+/// Returns the number of records in the state of a node of the given size, or `None` if the node
+/// doesn't keep any state of its own
+fn records(size: &NodeSize) -> Option<u64> {
+    match size.key_count {
+        KeyCount::ExactKeyCount(n) | KeyCount::EstimatedRowCount(n) => Some(n as u64),
+        KeyCount::ExternalMaterialization => None,
+    }
+}
+
+impl Materializations {
+    /// Estimate the number of upstream records which a full replay into an index of type
+    /// `index_type` on `colref` would scan, given the current size of every existing node in
+    /// `node_sizes`.
+    ///
+    /// Each of the index's replay paths is sourced from the nearest materialized ancestor along it
+    /// whose size is known, and each such source is counted once no matter how many paths share
+    /// it. Materialized ancestors with no size yet (because they're being added in the same
+    /// migration) are skipped, since they'll be filled from the same records further up. For
+    /// partial indices this is an upper bound, since replays for individual keys only scan the
+    /// records matching that key.
+    pub(in crate::controller) fn estimate_replay_cost(
+        &self,
+        graph: &Graph,
+        colref: ColumnRef,
+        index_type: IndexType,
+        node_sizes: &HashMap<NodeIndex, NodeSize>,
+    ) -> ReadySetResult<u64> {
+        let target = colref.node;
+        let mut sources = HashSet::new();
+        for path in keys::replay_paths_for_nonstop(graph, colref, index_type)? {
+            let source = path
+                .segments()
+                .iter()
+                .rev()
+                .map(|segment| segment.node)
+                .filter(|&ni| ni != target)
+                .find(|ni| self.have.contains_key(ni) && node_sizes.contains_key(ni))
+                .unwrap_or(path.source().node);
+            sources.insert(source);
+        }
+
+        Ok(sources
+            .into_iter()
+            .filter_map(|ni| node_sizes.get(&ni).and_then(records))
+            .sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::projection;
+    use super::*;
+
+    fn node_size(key_count: KeyCount) -> NodeSize {
+        NodeSize {
+            key_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replay_cost_from_nearest_sized_ancestor() {
+        let (g, base, project) = projection(&[0, 1]);
+        let colref = || ColumnRef {
+            node: project,
+            columns: vec![0],
+        };
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        assert_eq!(
+            m.estimate_replay_cost(&g, colref(), IndexType::HashMap, &HashMap::new())
+                .unwrap(),
+            0
+        );
+
+        let node_sizes = HashMap::from([
+            (base, node_size(KeyCount::EstimatedRowCount(100))),
+            (project, node_size(KeyCount::ExactKeyCount(10))),
+        ]);
+        assert_eq!(
+            m.estimate_replay_cost(&g, colref(), IndexType::HashMap, &node_sizes)
+                .unwrap(),
+            100
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! Computing the difference between two sets of [`Materializations`], for previewing what a
+//! migration will change.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use dataflow::prelude::*;
+use readyset_client::debug::info::{
+    MaterializationImpact, MigrationDryRunReport, NodeSize, PlannedMaterialization,
+};
+use readyset_errors::ReadySetResult;
+use serde::Serialize;
+
+use super::Materializations;
+
+/// A single replay path, as stored in [`Materializations::paths`]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub(in crate::controller) struct ReplayPathSummary {
+    /// The tag identifying the replay path
+    pub(in crate::controller) tag: Tag,
+    /// The index in the target node that the replay path fills
+    pub(in crate::controller) index: Index,
+    /// The nodes along the replay path, in replay order
+    pub(in crate::controller) path: Vec<NodeIndex>,
+}
+
+/// The difference between two sets of [`Materializations`], as computed by
+/// [`Materializations::diff`].
+///
+/// All nodes, indices, and replay paths are listed in sorted order, so that the diff between the
+/// same two sets of materializations is always the same.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub(in crate::controller) struct MaterializationDiff {
+    /// Nodes which have become materialized
+    pub(in crate::controller) added_nodes: Vec<NodeIndex>,
+    /// Nodes which are no longer materialized
+    pub(in crate::controller) removed_nodes: Vec<NodeIndex>,
+    /// Indices which have been added to each node, including all the indices of newly
+    /// materialized nodes
+    pub(in crate::controller) added_indices: BTreeMap<NodeIndex, Vec<Index>>,
+    /// Indices which have been removed from each node, including all the indices of nodes which
+    /// are no longer materialized
+    pub(in crate::controller) removed_indices: BTreeMap<NodeIndex, Vec<Index>>,
+    /// Nodes which were fully materialized, and are now partially materialized
+    pub(in crate::controller) became_partial: Vec<NodeIndex>,
+    /// Nodes which were partially materialized, and are now fully materialized
+    pub(in crate::controller) became_full: Vec<NodeIndex>,
+    /// Replay paths which have been added, keyed by the target node of the path
+    pub(in crate::controller) added_paths: BTreeMap<NodeIndex, Vec<ReplayPathSummary>>,
+    /// Replay paths which have been removed, keyed by the target node of the path
+    pub(in crate::controller) removed_paths: BTreeMap<NodeIndex, Vec<ReplayPathSummary>>,
+}
+
+impl MaterializationDiff {
+    /// Returns true if there are no differences at all between the two sets of materializations
+    pub(in crate::controller) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the domains in `graph` which committing the diff would send messages to: the
+    /// domains of every node which gains indices, and of every node along each new replay path.
+    /// New readers, which don't appear in the diff since they keep their own index, need to be
+    /// passed in `new_readers`.
+    pub(in crate::controller) fn touched_domains(
+        &self,
+        graph: &Graph,
+        new_readers: impl IntoIterator<Item = NodeIndex>,
+    ) -> BTreeSet<DomainIndex> {
+        self.added_indices
+            .keys()
+            .copied()
+            .chain(new_readers)
+            .chain(
+                self.added_paths
+                    .values()
+                    .flatten()
+                    .flat_map(|path| path.path.iter().copied()),
+            )
+            .filter(|&ni| graph[ni].has_domain())
+            .map(|ni| graph[ni].domain())
+            .collect()
+    }
+}
+
+/// Returns the elements of `from` that aren't in `to`, in sorted order
+fn sorted_difference<T: Ord + Clone>(from: &BTreeSet<T>, to: &BTreeSet<T>) -> Vec<T> {
+    from.difference(to).cloned().collect()
+}
+
+/// Returns the replay paths stored in `m` for each node, in sorted order
+fn replay_paths(m: &Materializations) -> BTreeMap<NodeIndex, BTreeSet<ReplayPathSummary>> {
+    m.paths
+        .iter()
+        .map(|(ni, paths)| {
+            (
+                *ni,
+                paths
+                    .iter()
+                    .map(|(tag, (index, path))| ReplayPathSummary {
+                        tag: *tag,
+                        index: index.clone(),
+                        path: path.clone(),
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Returns, for each key in `from`, the elements of that key's value which aren't in the
+/// corresponding value in `to`, omitting keys for which there are no such elements.
+fn per_node_difference<T: Ord + Clone>(
+    from: &BTreeMap<NodeIndex, BTreeSet<T>>,
+    to: &BTreeMap<NodeIndex, BTreeSet<T>>,
+) -> BTreeMap<NodeIndex, Vec<T>> {
+    let empty = BTreeSet::new();
+    from.iter()
+        .filter_map(|(ni, from)| {
+            let diff = sorted_difference(from, to.get(ni).unwrap_or(&empty));
+            (!diff.is_empty()).then_some((*ni, diff))
+        })
+        .collect()
+}
+
+impl Materializations {
+    /// Compute the changes required to go from this set of materializations to `other`.
+    ///
+    /// This is intended to be used to preview what a migration will change, by comparing the
+    /// materializations from before the migration with those after it.
+    pub(in crate::controller) fn diff(&self, other: &Materializations) -> MaterializationDiff {
+        let indices = |m: &Materializations| -> BTreeMap<NodeIndex, BTreeSet<Index>> {
+            m.have
+                .iter()
+                .map(|(ni, indices)| (*ni, indices.iter().cloned().collect()))
+                .collect()
+        };
+        let (old_indices, new_indices) = (indices(self), indices(other));
+        let old_nodes = old_indices.keys().copied().collect::<BTreeSet<_>>();
+        let new_nodes = new_indices.keys().copied().collect::<BTreeSet<_>>();
+
+        let (old_paths, new_paths) = (replay_paths(self), replay_paths(other));
+
+        let (became_partial, became_full) = old_nodes
+            .intersection(&new_nodes)
+            .copied()
+            .filter(|ni| self.is_partial(*ni) != other.is_partial(*ni))
+            .partition(|ni| other.is_partial(*ni));
+
+        MaterializationDiff {
+            added_nodes: sorted_difference(&new_nodes, &old_nodes),
+            removed_nodes: sorted_difference(&old_nodes, &new_nodes),
+            added_indices: per_node_difference(&new_indices, &old_indices),
+            removed_indices: per_node_difference(&old_indices, &new_indices),
+            became_partial,
+            became_full,
+            added_paths: per_node_difference(&new_paths, &old_paths),
+            removed_paths: per_node_difference(&old_paths, &new_paths),
+        }
+    }
+
+    /// Compute the effect that going from this set of materializations over `old_graph` to
+    /// `other` over `new_graph` has on the nodes which already existed in `old_graph`.
+    ///
+    /// This is intended to be used to tell operators whether a proposed migration would change the
+    /// materialization of already-deployed queries, by comparing the state before the migration
+    /// with that of a dry run of it.
+    pub(in crate::controller) fn impact_on_existing(
+        &self,
+        old_graph: &Graph,
+        other: &Materializations,
+        new_graph: &Graph,
+    ) -> MaterializationImpact {
+        let existing = old_graph
+            .node_indices()
+            .filter(|&ni| !old_graph[ni].is_source() && !old_graph[ni].is_dropped())
+            .collect::<BTreeSet<_>>();
+        let diff = self.diff(other);
+
+        let parents = |graph: &Graph, ni| {
+            graph
+                .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+                .collect::<BTreeSet<_>>()
+        };
+        let rerouted = existing
+            .iter()
+            .copied()
+            .filter(|&ni| parents(old_graph, ni) != parents(new_graph, ni))
+            .collect();
+
+        let mut duplicated = other
+            .redundant_partial
+            .keys()
+            .copied()
+            .filter(|ni| existing.contains(ni) && !self.redundant_partial.contains_key(ni))
+            .collect::<Vec<_>>();
+        duplicated.sort();
+
+        MaterializationImpact {
+            newly_materialized: diff
+                .added_nodes
+                .into_iter()
+                .filter(|ni| existing.contains(ni))
+                .collect(),
+            added_indices: diff
+                .added_indices
+                .into_iter()
+                .filter(|(ni, _)| existing.contains(ni))
+                .collect(),
+            became_partial: diff.became_partial,
+            became_full: diff.became_full,
+            rerouted,
+            duplicated,
+        }
+    }
+
+    /// Build a report of the materialization plan for going from this set of materializations
+    /// over `old_graph` to `other` over `new_graph`, for showing the result of a dry run of a
+    /// migration to the user before they commit to it.
+    ///
+    /// `node_sizes` should be the current sizes of all existing nodes, which are used to estimate
+    /// how large each new full materialization will be once it's filled.
+    pub(in crate::controller) fn dry_run_report(
+        &self,
+        old_graph: &Graph,
+        other: &Materializations,
+        new_graph: &Graph,
+        node_sizes: &HashMap<NodeIndex, NodeSize>,
+    ) -> ReadySetResult<MigrationDryRunReport> {
+        let diff = self.diff(other);
+
+        // Readers keep their index themselves rather than in `have`, so new readers need to be
+        // found in the graph
+        let mut planned = diff
+            .added_nodes
+            .iter()
+            .map(|&ni| (ni, diff.added_indices.get(&ni).cloned().unwrap_or_default()))
+            .collect::<BTreeMap<_, _>>();
+        for ni in new_graph.node_indices() {
+            if old_graph.node_weight(ni).is_some() {
+                continue;
+            }
+            if let Some(index) = new_graph[ni].as_reader().and_then(|r| r.index()) {
+                planned.insert(ni, vec![index.clone()]);
+            }
+        }
+
+        let new = planned.keys().copied().collect::<HashSet<_>>();
+        let touched_domains = diff
+            .touched_domains(
+                new_graph,
+                new.iter().copied().filter(|&ni| new_graph[ni].is_reader()),
+            )
+            .into_iter()
+            .collect();
+        let sizes = other.estimate_materialized_bytes(new_graph, &new, node_sizes);
+        let depths = other.partial_chain_depths(new_graph);
+
+        let mut new_materializations = Vec::with_capacity(planned.len());
+        for (ni, indices) in planned {
+            // Every index of a node is filled from the same upstream records, so the most
+            // expensive index is the cost of filling the node
+            let mut estimated_replay_records = 0;
+            for index in &indices {
+                let colref = ColumnRef {
+                    node: ni,
+                    columns: index.columns.clone(),
+                };
+                estimated_replay_records = estimated_replay_records.max(
+                    other.estimate_replay_cost(new_graph, colref, index.index_type, node_sizes)?,
+                );
+            }
+
+            new_materializations.push(PlannedMaterialization {
+                node_index: ni,
+                node_name: new_graph[ni].name().clone(),
+                partial: other.is_partial(ni),
+                indices,
+                estimated_bytes: if other.is_partial(ni) {
+                    0
+                } else {
+                    sizes.get(&ni).copied().unwrap_or_default()
+                },
+                estimated_replay_records,
+                partial_chain_depth: depths.get(&ni).copied().unwrap_or_default(),
+            });
+        }
+
+        Ok(MigrationDryRunReport {
+            new_materializations,
+            added_replay_paths: diff.added_paths.values().map(Vec::len).sum(),
+            impact: self.impact_on_existing(old_graph, other, new_graph),
+            touched_domains,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bimap::BiHashMap;
+    use readyset_client::debug::info::{KeyCount, NodeMaterializedSize};
+
+    use super::super::tests::projection;
+    use super::*;
+
+    fn index(columns: Vec<usize>) -> Index {
+        Index::hash_map(columns)
+    }
+
+    #[test]
+    fn diff_materializations() {
+        let (a, b, c) = (NodeIndex::new(1), NodeIndex::new(2), NodeIndex::new(3));
+
+        let mut old = Materializations::new();
+        old.have.insert(a, HashSet::from([index(vec![0])]));
+        old.have
+            .insert(b, HashSet::from([index(vec![0]), index(vec![1])]));
+        old.partial.insert(b);
+        old.paths.insert(
+            b,
+            BiHashMap::from_iter([(Tag::new(0), (index(vec![0]), vec![a, b]))]),
+        );
+
+        let mut new = old.clone();
+        new.have.remove(&a);
+        new.have
+            .insert(b, HashSet::from([index(vec![1]), index(vec![2])]));
+        new.have.insert(c, HashSet::from([index(vec![0])]));
+        new.partial.remove(&b);
+        new.paths.insert(
+            b,
+            BiHashMap::from_iter([(Tag::new(1), (index(vec![2]), vec![c, b]))]),
+        );
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_nodes, vec![c]);
+        assert_eq!(diff.removed_nodes, vec![a]);
+        assert_eq!(
+            diff.added_indices,
+            BTreeMap::from([(b, vec![index(vec![2])]), (c, vec![index(vec![0])])])
+        );
+        assert_eq!(
+            diff.removed_indices,
+            BTreeMap::from([(a, vec![index(vec![0])]), (b, vec![index(vec![0])])])
+        );
+        assert!(diff.became_partial.is_empty());
+        assert_eq!(diff.became_full, vec![b]);
+        assert_eq!(
+            diff.added_paths,
+            BTreeMap::from([(
+                b,
+                vec![ReplayPathSummary {
+                    tag: Tag::new(1),
+                    index: index(vec![2]),
+                    path: vec![c, b],
+                }]
+            )])
+        );
+        assert_eq!(diff.removed_paths[&b][0].tag, Tag::new(0));
+
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn impact_on_existing_nodes() {
+        let (old_g, base, project) = projection(&[0]);
+        let mut new_g = old_g.clone();
+        // a new node below the existing projection, and another new node which the existing
+        // projection is rerouted to read from
+        let child = new_g.add_node(new_g[project].duplicate());
+        new_g.add_edge(project, child, ());
+        let duplicate = new_g.add_node(new_g[base].duplicate());
+        new_g.add_edge(duplicate, project, ());
+
+        let mut old = Materializations::new();
+        old.have.insert(base, HashSet::from([index(vec![0])]));
+
+        let mut new = old.clone();
+        new.have
+            .insert(base, HashSet::from([index(vec![0]), index(vec![1])]));
+        new.have.insert(project, HashSet::from([index(vec![0])]));
+        new.have.insert(child, HashSet::from([index(vec![0])]));
+        new.partial.insert(project);
+        new.redundant_partial.insert(base, duplicate);
+
+        let impact = old.impact_on_existing(&old_g, &new, &new_g);
+        assert_eq!(impact.newly_materialized, vec![project]);
+        assert_eq!(
+            impact.added_indices,
+            BTreeMap::from([
+                (base, vec![index(vec![1])]),
+                (project, vec![index(vec![0])])
+            ])
+        );
+        assert!(impact.became_partial.is_empty());
+        assert!(impact.became_full.is_empty());
+        assert_eq!(impact.rerouted, vec![project]);
+        assert_eq!(impact.duplicated, vec![base]);
+
+        assert!(new.impact_on_existing(&new_g, &new, &new_g).is_empty());
+    }
+
+    #[test]
+    fn dry_run_report_estimates_new_materializations() {
+        let (old_g, base, project) = projection(&[0, 1]);
+        let mut new_g = old_g.clone();
+        let full = new_g.add_node(new_g[project].duplicate());
+        new_g.add_edge(project, full, ());
+        let partial = new_g.add_node(new_g[project].duplicate());
+        new_g.add_edge(project, partial, ());
+        // the new full node lives in a domain of its own, which no replay path passes through
+        for (ni, domain) in [(base, 0), (project, 1), (full, 2), (partial, 1)] {
+            new_g[ni].add_to(DomainIndex::from(domain));
+        }
+
+        let mut old = Materializations::new();
+        old.have.insert(base, HashSet::from([index(vec![0])]));
+
+        let mut new = old.clone();
+        new.have.insert(full, HashSet::from([index(vec![1])]));
+        new.have.insert(partial, HashSet::from([index(vec![0])]));
+        new.partial.insert(partial);
+        new.paths.insert(
+            partial,
+            BiHashMap::from_iter([(Tag::new(0), (index(vec![0]), vec![base, project, partial]))]),
+        );
+
+        let node_sizes = HashMap::from([(
+            base,
+            NodeSize {
+                key_count: KeyCount::ExactKeyCount(50),
+                bytes: NodeMaterializedSize(1000),
+            },
+        )]);
+        let report = old
+            .dry_run_report(&old_g, &new, &new_g, &node_sizes)
+            .unwrap();
+
+        assert_eq!(
+            report
+                .new_materializations
+                .iter()
+                .map(|m| (m.node_index, m.partial, m.estimated_bytes))
+                .collect::<Vec<_>>(),
+            vec![(full, false, 1000), (partial, true, 0)]
+        );
+        assert_eq!(report.new_materializations[0].indices, vec![index(vec![1])]);
+        assert_eq!(report.estimated_bytes(), 1000);
+        assert_eq!(report.new_materializations[0].estimated_replay_records, 50);
+        assert_eq!(
+            report
+                .new_materializations
+                .iter()
+                .map(|m| m.partial_chain_depth)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(report.added_replay_paths, 1);
+        assert!(report.impact.is_empty());
+        assert_eq!(
+            report.touched_domains,
+            (0..3).map(DomainIndex::from).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Merging BTree indices on the same node where the columns of one are a prefix of the other's.
+//!
+//! Both indices store every row of the node, so dropping the shorter of the two saves the memory
+//! it would have used. This is only possible when nothing needs the shorter index exactly, since
+//! lookups into state (both by operators and when sourcing replays) must use an index on exactly
+//! the looked-up columns.
+
+use std::collections::{HashMap, HashSet};
+
+use dataflow::prelude::*;
+
+use super::Materializations;
+
+impl Materializations {
+    /// Remove every BTree index added to a new, fully materialized, non-base node in the current
+    /// migration whose columns are a strict prefix of the columns of another BTree index on the
+    /// same node, as long as it isn't in `exact` (the indices which operators look up into or
+    /// replay paths are sourced from for each node).
+    ///
+    /// Returns tuples of the node, the removed index, and the index it was merged into, in sorted
+    /// order.
+    pub(super) fn merge_prefix_indices(
+        &mut self,
+        graph: &Graph,
+        new: &HashSet<NodeIndex>,
+        exact: &HashMap<NodeIndex, HashSet<Index>>,
+    ) -> Vec<(NodeIndex, Index, Index)> {
+        let mut merged = vec![];
+        for (&ni, added) in &self.added {
+            if !new.contains(&ni) || self.partial.contains(&ni) || graph[ni].is_base() {
+                continue;
+            }
+            let Some(have) = self.have.get(&ni) else {
+                continue;
+            };
+
+            for index in added {
+                if index.index_type != IndexType::BTreeMap
+                    || exact.get(&ni).map_or(false, |exact| exact.contains(index))
+                    || self
+                        .added_weak
+                        .get(&ni)
+                        .map_or(false, |weak| weak.contains(index))
+                {
+                    continue;
+                }
+
+                // Merge into the longest index we're a prefix of, which can't itself be merged
+                // into anything else
+                if let Some(into) = have
+                    .iter()
+                    .filter(|other| {
+                        other.index_type == IndexType::BTreeMap
+                            && other.len() > index.len()
+                            && other.columns.starts_with(&index.columns)
+                    })
+                    .max_by(|a, b| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+                {
+                    merged.push((ni, index.clone(), into.clone()));
+                }
+            }
+        }
+        merged.sort();
+
+        for (ni, index, _) in &merged {
+            if let Some(have) = self.have.get_mut(ni) {
+                have.remove(index);
+            }
+            if let Some(added) = self.added.get_mut(ni) {
+                added.remove(index);
+            }
+        }
+
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::projection;
+    use super::*;
+
+    fn materialized(indices: &[Index]) -> (Graph, NodeIndex, Materializations) {
+        let (g, base, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        for node_indices in [&mut m.have, &mut m.added] {
+            node_indices.insert(project, indices.iter().cloned().collect());
+        }
+        (g, project, m)
+    }
+
+    #[test]
+    fn merge_btree_prefix() {
+        let (g, project, mut m) =
+            materialized(&[Index::btree_map(vec![0]), Index::btree_map(vec![0, 1])]);
+
+        let merged = m.merge_prefix_indices(&g, &HashSet::from([project]), &HashMap::new());
+        assert_eq!(
+            merged,
+            vec![(
+                project,
+                Index::btree_map(vec![0]),
+                Index::btree_map(vec![0, 1])
+            )]
+        );
+        assert_eq!(
+            m.have[&project],
+            HashSet::from([Index::btree_map(vec![0, 1])])
+        );
+        m.assert_invariants(&g);
+    }
+
+    #[test]
+    fn dont_merge_indices_needed_exactly() {
+        let indices = [
+            Index::btree_map(vec![0]),
+            Index::btree_map(vec![0, 1]),
+            Index::hash_map(vec![1]),
+            Index::hash_map(vec![1, 0]),
+        ];
+        let (g, project, mut m) = materialized(&indices);
+        let new = HashSet::from([project]);
+
+        // An operator looks up into (or a replay path is sourced from) the shorter index
+        let exact = HashMap::from([(project, HashSet::from([Index::btree_map(vec![0])]))]);
+        assert!(m.merge_prefix_indices(&g, &new, &exact).is_empty());
+
+        // Partial nodes need all their indices exactly, since replays target each one
+        m.partial.insert(project);
+        assert!(m.merge_prefix_indices(&g, &new, &HashMap::new()).is_empty());
+        assert_eq!(m.have[&project].len(), indices.len());
+    }
+}
@@ -5,22 +5,57 @@
 //! domains, but does not perform that copying itself (that is the role of the `augmentation`
 //! module).
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{self, Display};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Instant;
 
 use bimap::BiHashMap;
 use dataflow::prelude::*;
-use dataflow::{DomainRequest, LookupIndex};
+use dataflow::{DomainRequest, EvictionGranularity, LookupIndex};
+use itertools::Itertools;
+use metrics::{counter, gauge};
 use petgraph::graph::NodeIndex;
-use readyset_errors::{internal, internal_err, invariant, ReadySetError, ReadySetResult};
+use readyset_client::debug::info::{
+    FrontierSimulation, MaterializationAccessStats, MaterializationTemplate,
+    MaterializationViolation, NodeSize,
+};
+use readyset_client::internal::MaterializationKind;
+use readyset_client::metrics::recorded;
+use readyset_errors::{
+    bad_request_err, internal, internal_err, invariant, ReadySetError, ReadySetResult,
+};
+use readyset_util::redacted::Sensitive;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info_span, trace};
+use tracing::{debug, error, info, info_span, trace, warn};
 
+use self::access::AccessCounters;
+use self::paths::ReplayPathCache;
 use crate::controller::keys::{self, RawReplayPath};
-use crate::controller::migrate::DomainMigrationPlan;
+use crate::controller::migrate::{DomainMigrationPlan, StoredDomainRequest};
+use crate::controller::reachability::{reachable, reachable_from_any};
 use crate::controller::state::Graphviz;
 
+mod access;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod budget;
+mod cost;
+mod diff;
+mod merge;
+mod paths;
 mod plan;
+mod record;
+mod redundant;
+mod report;
+mod reuse;
+mod sizes;
+mod template;
+
+pub use self::record::read_recorded_messages;
+pub(in crate::controller) use self::sizes::SizeEstimator;
+pub(in crate::controller) use self::template::query_nodes;
 
 type Indices = HashSet<Index>;
 
@@ -33,7 +68,7 @@ pub(crate) struct InvalidEdge {
 /// materialization frontier.
 ///
 /// Note that no matter what this is set to, all nodes whose name starts with `SHALLOW_` will be
-/// placed beyond the frontier.
+/// placed beyond the frontier, unless they're [pinned][Config::pinned_nodes].
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum, Default)]
 pub enum FrontierStrategy {
     /// Place no nodes beyond the frontier (this is the default).
@@ -55,7 +90,23 @@ impl Display for FrontierStrategy {
     }
 }
 
-#[derive(Debug)]
+impl FromStr for FrontierStrategy {
+    type Err = ReadySetError;
+
+    /// Parse a frontier strategy from the same (case-insensitive) names it's displayed with
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "all-partial" => Ok(Self::AllPartial),
+            "readers" => Ok(Self::Readers),
+            _ => Err(bad_request_err(format!(
+                "Invalid frontier strategy {s:?}; expected one of none, all-partial, readers"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 enum IndexObligation {
     /// An obligation to index a particular set of columns with a particular index type in a node.
     ///
@@ -71,6 +122,69 @@ enum IndexObligation {
     Replay(Index),
 }
 
+/// Returns the index to use for the base table node `n` if nothing else needs an index on it.
+///
+/// This is the base table's primary key or first unique key if it has one, and otherwise
+/// [`Config::default_base_index`] if that's set and all its columns exist in the base table,
+/// falling back to a hash index on column 0.
+fn fallback_base_index(n: &Node, config: &Config) -> Index {
+    if let Some(key) = n
+        .get_base()
+        .and_then(|base| base.all_unique_keys().into_iter().next())
+    {
+        return Index::hash_map(key.into_vec());
+    }
+
+    match &config.default_base_index {
+        Some(index) if index.columns.iter().all(|&col| col < n.columns().len()) => index.clone(),
+        _ => Index::hash_map(vec![0]),
+    }
+}
+
+/// Compute the indexing obligations that the (new) node `n` at index `ni` creates, keyed by the
+/// node the obligation applies to.
+fn index_obligations(
+    n: &Node,
+    ni: NodeIndex,
+    config: &Config,
+) -> Vec<(NodeIndex, IndexObligation)> {
+    let mut indices: HashMap<NodeIndex, IndexObligation> = if let Some(r) = n.as_reader() {
+        if let Some(index) = r.index() {
+            // for a reader that will get lookups, we'd like to have an index above us
+            // somewhere on our key so that we can make the reader partial
+            HashMap::from([(ni, IndexObligation::Replay(index.clone()))])
+        } else {
+            // only streaming, no indexing needed
+            HashMap::new()
+        }
+    } else {
+        n.suggest_indexes(ni)
+            .into_iter()
+            .map(|(n, lookup_index)| (n, IndexObligation::Lookup(lookup_index)))
+            .collect()
+    };
+
+    if indices.is_empty() && n.is_base() {
+        // we must *always* materialize base nodes
+        // so, just make up some column to index on
+        indices.insert(
+            ni,
+            IndexObligation::Lookup(LookupIndex::Strict(fallback_base_index(n, config))),
+        );
+    }
+
+    indices.into_iter().collect()
+}
+
+impl Display for IndexObligation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Lookup(index) => write!(f, "lookup {index}"),
+            Self::Replay(index) => write!(f, "replay {index}"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Config {
     /// Whether the creation of [`PacketFilter`]s for egresses before readers is enabled.
@@ -104,6 +218,138 @@ pub struct Config {
     /// Defaults to [`FrontierStrategy::None`]
     pub frontier_strategy: FrontierStrategy,
 
+    /// Nodes which should never be placed beyond the materialization frontier, no matter what the
+    /// [`frontier_strategy`][Self::frontier_strategy] is. Pinning takes precedence over the
+    /// `SHALLOW_` name prefix, and also applies to all ancestors of the pinned nodes, since a
+    /// materialization which isn't beyond the frontier can never be below one that is.
+    ///
+    /// Defaults to the empty set
+    #[serde(default)]
+    pub pinned_nodes: HashSet<NodeIndex>,
+
+    /// Name prefixes for nodes which should always be fully materialized, no matter whether they
+    /// could be made partial. This only applies if
+    /// [`allow_full_materialization`][Self::allow_full_materialization] is also set; otherwise
+    /// matching nodes are planned as usual.
+    ///
+    /// This is in addition to the hardcoded `FULL_` prefix, which takes precedence over this
+    /// setting: any node with a descendant whose name starts with `FULL_` is always fully
+    /// materialized, whether or not full materialization is allowed.
+    ///
+    /// Defaults to the empty set
+    #[serde(default)]
+    pub force_full: HashSet<String>,
+
+    /// Name prefixes for nodes whose in-memory state should evict runs of adjacent keys from a
+    /// BTree index ([`EvictionGranularity::Range`]), rather than individual keys chosen at random
+    /// ([`EvictionGranularity::Key`], the default for every other node). Range granularity suits
+    /// views which are mostly looked up by range, since the evicted keys can be filled again by a
+    /// single range replay.
+    ///
+    /// This is in addition to the hardcoded `RANGE_EVICT_` prefix. Readers, and states which aren't
+    /// kept in memory, always evict with key granularity.
+    ///
+    /// Defaults to the empty set
+    #[serde(default)]
+    pub range_eviction: HashSet<String>,
+
+    /// Whether to return an error if a node is placed beyond the materialization frontier, but
+    /// neither it nor any of its parents have any state which could be placed beyond the frontier
+    /// in its place. If this is false, such nodes are ignored, and their state (if any) will never
+    /// be evicted.
+    ///
+    /// Defaults to false
+    #[serde(default)]
+    pub strict_frontier: bool,
+
+    /// Whether to stop materializing new, fully materialized nodes for which every index is
+    /// already covered by an index in a fully materialized ancestor that lookups could query
+    /// through to. If this is false, such indices are only reported with a warning.
+    ///
+    /// Defaults to false
+    #[serde(default)]
+    pub suppress_redundant_indices: bool,
+
+    /// Whether to drop BTree indices on new, fully materialized nodes whose columns are a prefix
+    /// of another BTree index on the same node, as long as no operator looks up into them and no
+    /// replay path is sourced from them.
+    ///
+    /// Defaults to false
+    #[serde(default)]
+    pub merge_prefix_indices: bool,
+
+    /// Whether to source replays from an existing BTree index on a fully materialized ancestor,
+    /// rather than adding a new hash index on the same columns to it. Point lookups into
+    /// in-memory state fall back to a BTree index on the looked-up columns, so this is always
+    /// correct, but it trades some lookup speed for fewer indices. Base tables, and full
+    /// materializations if [`persistent_full_materializations`] is set, are never reused this
+    /// way, since point lookups into persistent state need a hash index.
+    ///
+    /// Defaults to false
+    ///
+    /// [`persistent_full_materializations`]: Self::persistent_full_materializations
+    #[serde(default)]
+    pub prefer_existing_indices: bool,
+
+    /// Whether the domains keep the state of fully materialized nodes on disk, rather than in
+    /// memory. This mirrors the domains' own configuration, which decides where that state
+    /// actually lives.
+    ///
+    /// Defaults to false
+    #[serde(default)]
+    pub persistent_full_materializations: bool,
+
+    /// The index to place on base tables which have no primary or unique key, if nothing else
+    /// needs an index on them. Ignored for base tables which don't have all of the index's
+    /// columns.
+    ///
+    /// Defaults to `None`, meaning such base tables get a hash index on their first column
+    #[serde(default)]
+    pub default_base_index: Option<Index>,
+
+    /// The maximum estimated total size, in bytes, of all materializations. Migrations which
+    /// would bring the estimated total above this are rejected before they're applied.
+    ///
+    /// Defaults to `None`, meaning there's no limit
+    #[serde(default)]
+    pub max_total_materialized_bytes: Option<u64>,
+
+    /// The maximum number of new nodes to reconstruct via full replay at once when committing a
+    /// migration. Replays are only ever run at the same time for nodes which don't depend on each
+    /// other. Values of 0 and 1 both run one replay at a time.
+    ///
+    /// Defaults to 1
+    #[serde(default = "default_replay_concurrency")]
+    pub replay_concurrency: usize,
+
+    /// Whether to only warn about, rather than reject, migrations which merge shards of a subgraph
+    /// that's sharded by a column which is aliased by another column. That check is conservative,
+    /// and rejects some graphs which are actually fine (if every replay path through the shard
+    /// merger uses the same alias as the subgraph is sharded by), so this should only be set once
+    /// it's been confirmed that the graphs being rejected are false positives.
+    ///
+    /// Defaults to false
+    #[serde(default)]
+    pub allow_aliased_sharding: bool,
+
+    /// A file to append every domain request enqueued while committing materializations to, in
+    /// order, so that the exact sequence of messages a migration produced can be replayed later
+    /// (see [`read_recorded_messages`]).
+    ///
+    /// Defaults to `None`, meaning nothing is recorded
+    #[serde(default)]
+    pub record_plan_path: Option<PathBuf>,
+
+    /// The maximum number of partial materializations a replay into a new partial node may have
+    /// to pass through on its way from the nearest full materialization (see
+    /// [`Materializations::partial_chain_depths`]) before a warning is logged. Each of those
+    /// partial materializations adds a replay hop, so nodes beyond this depth will be slow to
+    /// fill on a miss, and could benefit from fully materializing one of the nodes in between.
+    ///
+    /// Defaults to `None`, meaning no warnings are logged
+    #[serde(default)]
+    pub max_partial_chain_depth: Option<usize>,
+
     /// Whether partial node creation is enabled at all.
     ///
     /// Defaults to true.
@@ -118,8 +364,277 @@ impl Default for Config {
             allow_straddled_joins: false,
             partial_enabled: true,
             frontier_strategy: FrontierStrategy::None,
+            pinned_nodes: HashSet::new(),
+            force_full: HashSet::new(),
+            range_eviction: HashSet::new(),
+            strict_frontier: false,
+            suppress_redundant_indices: false,
+            merge_prefix_indices: false,
+            prefer_existing_indices: false,
+            persistent_full_materializations: false,
+            default_base_index: None,
+            max_total_materialized_bytes: None,
+            replay_concurrency: default_replay_concurrency(),
+            allow_aliased_sharding: false,
+            record_plan_path: None,
+            max_partial_chain_depth: None,
+        }
+    }
+}
+
+fn default_replay_concurrency() -> usize {
+    1
+}
+
+/// Returns the index of the domain that `n` is assigned to, if it's been assigned one yet, for use
+/// as a field in log lines
+fn domain_of(n: &Node) -> Option<usize> {
+    n.has_domain().then(|| n.domain().index())
+}
+
+/// Construct a [`ReadySetError::MaterializationFrontierViolation`] for placing the node `ni`
+/// beyond the materialization frontier, for the given `reason`
+fn frontier_violation(graph: &Graph, ni: NodeIndex, reason: &str) -> ReadySetError {
+    ReadySetError::MaterializationFrontierViolation {
+        node: ni.index(),
+        is_reader: graph[ni].is_reader(),
+        reason: reason.to_owned(),
+    }
+}
+
+/// Description of how to avoid [`ReadySetError::UnsupportedFullToPartial`]. Also logged when an
+/// existing fully materialized node has to stay full, rather than being made partial for a new
+/// query below it.
+const FULL_TO_PARTIAL_REMEDIATION: &str =
+    "Drop and recreate the existing queries which read from this node, so that it can be planned \
+     from scratch together with the new query, or force the node to stay fully materialized with \
+     --force-full-materialization";
+
+/// Returns the first node in `new` below `ni`, searching depth-first, for reporting which new
+/// node caused an index to be added to `ni`
+fn new_descendant(graph: &Graph, ni: NodeIndex, new: &HashSet<NodeIndex>) -> Option<NodeIndex> {
+    let mut stack: Vec<_> = graph
+        .neighbors_directed(ni, petgraph::EdgeDirection::Outgoing)
+        .collect();
+    while let Some(child) = stack.pop() {
+        if new.contains(&child) {
+            return Some(child);
+        }
+        stack.extend(graph.neighbors_directed(child, petgraph::EdgeDirection::Outgoing));
+    }
+    None
+}
+
+/// Returns every (non-source, non-dropped) node in the graph in topological order, breaking ties
+/// between nodes whose parents have all already been visited by visiting the lowest
+/// [`NodeIndex`] first.
+///
+/// Unlike [`petgraph::visit::Topo`], which makes no guarantees about the relative order of such
+/// nodes, this is deterministic for a given graph, so the messages [`Materializations::commit`]
+/// emits for a migration are always sent in the same order.
+fn topo_order(graph: &Graph) -> Vec<NodeIndex> {
+    let mut in_degree: HashMap<NodeIndex, usize> = graph
+        .node_indices()
+        .map(|ni| {
+            let parents = graph
+                .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+                .count();
+            (ni, parents)
+        })
+        .collect();
+    let mut ready: BTreeSet<NodeIndex> = in_degree
+        .iter()
+        .filter(|(_, parents)| **parents == 0)
+        .map(|(ni, _)| *ni)
+        .collect();
+
+    let mut ordered = Vec::with_capacity(graph.node_count());
+    while let Some(ni) = ready.pop_first() {
+        for child in graph.neighbors_directed(ni, petgraph::EdgeDirection::Outgoing) {
+            #[allow(clippy::unwrap_used)] // every node in the graph has an entry
+            let parents = in_degree.get_mut(&child).unwrap();
+            *parents -= 1;
+            if *parents == 0 {
+                ready.insert(child);
+            }
+        }
+
+        if !graph[ni].is_source() && !graph[ni].is_dropped() {
+            ordered.push(ni);
         }
     }
+    ordered
+}
+
+/// If `parent_index` and `child_index` are of the same type and share some, but not all, of their
+/// columns, return one of the columns which only one of them has.
+///
+/// A partial node whose index partially overlaps an index in a partial ancestor is only safe if
+/// the ancestor also has an index on exactly the descendant's columns.
+fn partial_overlap(parent_index: &Index, child_index: &Index) -> Option<usize> {
+    if parent_index.index_type != child_index.index_type
+        || parent_index
+            .columns
+            .iter()
+            .all(|c| !child_index.columns.contains(c))
+    {
+        return None;
+    }
+
+    parent_index
+        .columns
+        .iter()
+        .copied()
+        .find(|c| !child_index.columns.contains(c))
+        .or_else(|| {
+            child_index
+                .columns
+                .iter()
+                .copied()
+                .find(|c| !parent_index.columns.contains(c))
+        })
+}
+
+/// Return an error describing every obligation left in `replay_obligations` once all nodes have
+/// been processed by [`Materializations::extend`], since those indices will never be added
+fn check_obligations_satisfied(
+    graph: &Graph,
+    replay_obligations: &HashMap<NodeIndex, Indices>,
+) -> ReadySetResult<()> {
+    if replay_obligations.is_empty() {
+        return Ok(());
+    }
+
+    let leftover = replay_obligations
+        .iter()
+        .sorted_by_key(|(ni, _)| **ni)
+        .map(|(ni, indices)| {
+            format!(
+                "{} / {}: {}",
+                ni.index(),
+                graph[*ni].name().display_unquoted(),
+                indices.iter().sorted().join(", ")
+            )
+        })
+        .join("; ");
+    internal!("Replay obligations left unsatisfied after processing all nodes: {leftover}")
+}
+
+/// Check that column `col` of `ni` was correctly resolved to column `parent_col` of `parent`, by
+/// making sure that column actually exists in the parent and has a compatible type.
+///
+/// This catches planner bugs where [`Node::parent_columns`] returns stale column indices, and is
+/// only performed in builds with debug assertions enabled.
+fn check_column_provenance(
+    graph: &Graph,
+    ni: NodeIndex,
+    col: usize,
+    parent: NodeIndex,
+    parent_col: usize,
+) -> ReadySetResult<()> {
+    if !cfg!(debug_assertions) {
+        return Ok(());
+    }
+
+    let parent_columns = graph[parent].columns();
+    let Some(parent_column) = parent_columns.get(parent_col) else {
+        internal!(
+            "column {} of node {} resolved to column {} of ancestor {}, which only has {} columns",
+            col,
+            ni.index(),
+            parent_col,
+            parent.index(),
+            parent_columns.len()
+        );
+    };
+    if let Some(column) = graph[ni].columns().get(col) {
+        let (ty, parent_ty) = (column.ty(), parent_column.ty());
+        invariant!(
+            ty == parent_ty || ty.is_unknown() || parent_ty.is_unknown(),
+            "column {} of node {} (of type {}) resolved to column {} of ancestor {}, which has \
+             incompatible type {}",
+            col,
+            ni.index(),
+            ty,
+            parent_col,
+            parent.index(),
+            parent_ty
+        );
+    }
+
+    Ok(())
+}
+
+/// Description of how to avoid [`ReadySetError::LookupOnDroppedColumn`]
+const DROPPED_COLUMN_REMEDIATION: &str =
+    "The query this node belongs to was likely created against an older schema of the table; drop \
+     and recreate it against the table's current schema";
+
+/// Map all the given lookup indices into the node `ni` to the corresponding columns in its
+/// `parent`, returning [`ReadySetError::LookupOnDroppedColumn`] if any of them resolve to a column
+/// which has been dropped from `parent`
+fn map_lookup_indices(
+    graph: &Graph,
+    ni: NodeIndex,
+    parent: NodeIndex,
+    indices: &HashSet<LookupIndex>,
+) -> ReadySetResult<HashSet<LookupIndex>> {
+    let n = &graph[ni];
+    indices
+        .iter()
+        .map(|lookup_index| {
+            let index = lookup_index.index();
+            let index = Index::new(
+                index.index_type,
+                index
+                    .columns
+                    .iter()
+                    .map(|&col| {
+                        if !n.is_internal() {
+                            if n.is_base() {
+                                internal!("map_indices called with base table");
+                            }
+                            return Ok(col);
+                        }
+
+                        let really = n.parent_columns(col);
+                        let really = really
+                            .into_iter()
+                            .find(|&(anc, _)| anc == parent)
+                            .and_then(|(_, col)| col)
+                            .ok_or_else(|| {
+                                internal_err!(
+                                    "could not resolve obligation past operator;\
+                                     node => {}, ancestor => {}, column => {}",
+                                    ni.index(),
+                                    parent.index(),
+                                    col
+                                )
+                            })?;
+                        if graph[parent]
+                            .get_base()
+                            .map_or(false, |base| base.get_dropped().contains_key(really))
+                        {
+                            return Err(ReadySetError::LookupOnDroppedColumn {
+                                node: ni.index(),
+                                node_name: n.name().display_unquoted().to_string(),
+                                index: index.to_string(),
+                                table: graph[parent].name().display_unquoted().to_string(),
+                                column: really,
+                                remediation: DROPPED_COLUMN_REMEDIATION.to_owned(),
+                            });
+                        }
+                        check_column_provenance(graph, ni, col, parent, really)?;
+                        Ok(really)
+                    })
+                    .collect::<ReadySetResult<Vec<usize>>>()?,
+            );
+            Ok(match lookup_index {
+                LookupIndex::Strict(_) => LookupIndex::Strict(index),
+                LookupIndex::Weak(_) => LookupIndex::Weak(index),
+            })
+        })
+        .collect()
 }
 
 /// Struct containing (authoritative!) information about which nodes in a graph are materialized
@@ -131,7 +646,7 @@ pub(in crate::controller) struct Materializations {
     // Skipping this field as we will rebuild the [`Materializations`] state
     // upon recovery.
     #[serde(skip)]
-    have: HashMap<NodeIndex, Indices>,
+    pub(in crate::controller) have: HashMap<NodeIndex, Indices>,
     /// Nodes that *were* (fully or partially) as of the last time we called [`commit`].
     ///
     /// Used to validate that we're not adding any materializations we shouldn't (eg newly
@@ -141,6 +656,13 @@ pub(in crate::controller) struct Materializations {
     /// [`commit`]: Materializations::commit
     #[serde(skip)]
     had: HashSet<NodeIndex>,
+    /// Nodes that became (fully or partially) materialized the last time we called [`commit`],
+    /// having not been materialized before - both new nodes and existing nodes which weren't
+    /// previously materialized.
+    ///
+    /// [`commit`]: Materializations::commit
+    #[serde(skip)]
+    newly_materialized: HashSet<NodeIndex>,
     /// Nodes materialized since the last time `commit()` was invoked.
     #[serde(skip)]
     added: HashMap<NodeIndex, Indices>,
@@ -149,10 +671,45 @@ pub(in crate::controller) struct Materializations {
     #[serde(skip)]
     added_weak: HashMap<NodeIndex, Indices>,
 
+    /// For each node which performs lookups into a weak index, the nodes it asked for those weak
+    /// indices on, before they were hoisted through any query-through nodes.
+    ///
+    /// Only used for visualizing weak lookups (see [`Self::looks_up_weakly`])
+    #[serde(skip)]
+    pub(in crate::controller) weak_lookups: HashMap<NodeIndex, HashSet<NodeIndex>>,
+
     /// Readers added since the last time `commit()` was invoked.
     #[serde(skip)]
     new_readers: HashSet<NodeIndex>,
 
+    /// Indexing obligations computed for new nodes since the last time `commit()` was invoked,
+    /// along with the (sorted) parents each node had when they were computed.
+    ///
+    /// `extend` may be called several times before `commit` within a single migration (once per
+    /// iteration of the planning loop), so we cache each node's obligations rather than asking it
+    /// to suggest indexes again every time. The planning loop rewires nodes in between calls to
+    /// `extend` (for example, when rerouting a fully materialized node from below a partial one),
+    /// so cached obligations are only used if the node still has the same parents - see
+    /// [`obligations_for`](Materializations::obligations_for).
+    #[serde(skip)]
+    obligations: HashMap<NodeIndex, (Vec<NodeIndex>, Vec<(NodeIndex, IndexObligation)>)>,
+
+    /// When the first call to `extend` since the last time `commit()` was invoked started, for
+    /// reporting how long planning the migration took
+    #[serde(skip)]
+    planning_started: Option<Instant>,
+
+    /// The domain requests enqueued by the last call to `commit()`, encoded to be appended to
+    /// [`Config::record_plan_path`] once the migration has actually been applied (see
+    /// [`finish_recording`](Self::finish_recording))
+    #[serde(skip)]
+    recorded_plan: Vec<u8>,
+
+    /// Replay paths computed since the start of the current call to `extend`, which are reused
+    /// by `validate` until the next one
+    #[serde(skip)]
+    path_cache: ReplayPathCache,
+
     /// A list of replay paths for each node, indexed by tag.
     #[serde(with = "serde_with::rust::hashmap_as_tuple_list")]
     pub(in crate::controller) paths: HashMap<NodeIndex, BiHashMap<Tag, (Index, Vec<NodeIndex>)>>,
@@ -167,8 +724,33 @@ pub(in crate::controller) struct Materializations {
     #[serde(skip)]
     partial: HashSet<NodeIndex>,
 
+    /// The primary reason each materialized node (including readers) is materialized
+    #[serde(skip)]
+    kinds: HashMap<NodeIndex, MaterializationKind>,
+
+    /// Baselines for the number of lookups reported into each materialized node (including
+    /// readers), relative to which lookups are reported by [`access_stats`](Self::access_stats)
+    #[serde(skip)]
+    access_counters: AccessCounters,
+
     pub(in crate::controller) tag_generator: usize,
 
+    /// The id of the most recent migration to commit materializations
+    #[serde(default)]
+    pub(in crate::controller) migration_generator: u32,
+
+    /// The id of the migration which created each replay path tag that's still in use, so that
+    /// replay logs can be correlated with the migration that set the paths up. Tags created before
+    /// this was recorded aren't present.
+    #[serde(default)]
+    pub(in crate::controller) tag_migrations: HashMap<Tag, u32>,
+
+    /// Materialization decisions frozen from earlier queries, to apply to the new nodes of
+    /// migrations with the same shape instead of analyzing them from scratch (see
+    /// [`template`](self::template))
+    #[serde(default)]
+    templates: Vec<MaterializationTemplate>,
+
     pub(crate) config: Config,
 }
 
@@ -178,10 +760,16 @@ impl Materializations {
         Materializations {
             have: HashMap::default(),
             had: HashSet::default(),
+            newly_materialized: HashSet::default(),
             added: HashMap::default(),
             new_readers: HashSet::default(),
+            obligations: HashMap::default(),
+            planning_started: None,
+            recorded_plan: Vec::new(),
+            path_cache: ReplayPathCache::default(),
 
             added_weak: HashMap::default(),
+            weak_lookups: HashMap::default(),
 
             paths: HashMap::default(),
 
@@ -189,7 +777,14 @@ impl Materializations {
 
             partial: HashSet::default(),
 
+            kinds: HashMap::default(),
+
+            access_counters: AccessCounters::default(),
+
             tag_generator: 0,
+            migration_generator: 0,
+            tag_migrations: HashMap::default(),
+            templates: Vec::new(),
 
             config: Default::default(),
         }
@@ -205,6 +800,37 @@ impl Materializations {
         self.redundant_partial.get(idx)
     }
 
+    /// Returns the indexing obligations created by the new node at `ni`, reusing the ones cached
+    /// by an earlier call to [`extend`] in the same migration unless the node's parents have
+    /// changed since.
+    ///
+    /// A node's obligations only depend on the node itself and which nodes its parents are, and
+    /// rewiring a node always changes its incoming edges, so comparing the parents is enough to
+    /// tell whether the cached obligations are stale.
+    ///
+    /// [`extend`]: Materializations::extend
+    fn obligations_for(
+        &mut self,
+        graph: &Graph,
+        ni: NodeIndex,
+    ) -> Vec<(NodeIndex, IndexObligation)> {
+        let mut parents = graph
+            .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+            .collect::<Vec<_>>();
+        parents.sort_unstable();
+
+        match self.obligations.get(&ni) {
+            Some((cached_parents, obligations)) if *cached_parents == parents => {
+                obligations.clone()
+            }
+            _ => {
+                let obligations = index_obligations(&graph[ni], ni, &self.config);
+                self.obligations.insert(ni, (parents, obligations.clone()));
+                obligations
+            }
+        }
+    }
+
     /// Add new duplicate nodes to the redundant_partial map
     pub(in crate::controller) fn extend_redundant_partial(
         &mut self,
@@ -212,16 +838,79 @@ impl Materializations {
     ) {
         self.redundant_partial.extend(new_duplicates);
     }
+
+    /// Forget about the replay paths targeting, and any redundant full duplicates of, nodes which
+    /// have been dropped from the graph. This must be called after nodes are removed, since
+    /// nothing else cleans up that state.
+    ///
+    /// Returns the tags of the removed replay paths, keyed by each of the domains that the paths
+    /// pass through, so that those domains can be told to tear them down.
+    pub(in crate::controller) fn remove_dropped(
+        &mut self,
+        graph: &Graph,
+    ) -> HashMap<DomainIndex, Vec<Tag>> {
+        let dropped = self
+            .paths
+            .keys()
+            .copied()
+            .filter(|&ni| graph[ni].is_dropped())
+            .collect::<Vec<_>>();
+
+        let mut tags_by_domain: HashMap<DomainIndex, BTreeSet<Tag>> = HashMap::new();
+        for ni in dropped {
+            let Some(paths) = self.paths.remove(&ni) else {
+                continue;
+            };
+            debug!(node = %ni.index(), paths = paths.len(), "removing replay paths for dropped node");
+            for (tag, (_, path)) in paths.iter() {
+                self.tag_migrations.remove(tag);
+                for &node in path {
+                    tags_by_domain
+                        .entry(graph[node].domain())
+                        .or_default()
+                        .insert(*tag);
+                }
+            }
+        }
+
+        self.weak_lookups
+            .retain(|&child, _| !graph[child].is_dropped());
+        self.access_counters.retain(|ni| !graph[ni].is_dropped());
+        self.kinds.retain(|&ni, _| !graph[ni].is_dropped());
+
+        self.redundant_partial.retain(|&original, &mut duplicate| {
+            !graph[original].is_dropped() && !graph[duplicate].is_dropped()
+        });
+
+        tags_by_domain
+            .into_iter()
+            .map(|(domain, tags)| (domain, tags.into_iter().collect()))
+            .collect()
+    }
 }
 
 impl Materializations {
     fn next_tag(&mut self) -> Tag {
         self.tag_generator += 1;
-        Tag::new(self.tag_generator as u32)
+        let tag = Tag::new(self.tag_generator as u32);
+        self.tag_migrations.insert(tag, self.migration_generator);
+        tag
     }
 
+    /// Returns the id of the migration which created `tag`, if known
+    pub(in crate::controller) fn tag_migration(&self, tag: Tag) -> Option<u32> {
+        self.tag_migrations.get(&tag).copied()
+    }
+
+    /// Return the tag to use for the replay path `path` for `index`.
+    ///
+    /// If we've already set up a replay path for the same index along the same nodes (for example
+    /// because the index was dropped from the node and then re-added by a later migration), we
+    /// reuse its tag, since the domains along the path already know about it. Otherwise, we
+    /// allocate a new tag.
     fn tag_for_path(&mut self, index: &Index, path: &RawReplayPath) -> Tag {
-        self.paths
+        let existing = self
+            .paths
             .get(&path.last_segment().node)
             .and_then(|paths_for_node| {
                 paths_for_node.get_by_right(&(
@@ -232,8 +921,49 @@ impl Materializations {
                         .collect::<Vec<_>>(),
                 ))
             })
-            .copied()
-            .unwrap_or_else(|| self.next_tag())
+            .copied();
+        match existing {
+            Some(tag) => {
+                debug!(
+                    node = %path.last_segment().node.index(),
+                    %index,
+                    ?tag,
+                    "reusing existing replay path"
+                );
+                tag
+            }
+            None => self.next_tag(),
+        }
+    }
+
+    /// Record the replay paths in `paths` for the node at `ni`.
+    ///
+    /// Returns an error if any of those paths is already recorded for `ni` under a different tag,
+    /// since silently replacing it would leave the old tag set up in the domains along the path but
+    /// no longer tracked here.
+    fn add_paths(
+        &mut self,
+        ni: NodeIndex,
+        paths: impl IntoIterator<Item = (Tag, (Index, Vec<NodeIndex>))>,
+    ) -> ReadySetResult<()> {
+        let paths_for_node = self.paths.entry(ni).or_default();
+        for (tag, path) in paths {
+            if let Some(existing) = paths_for_node.get_by_right(&path) {
+                if *existing != tag {
+                    internal!(
+                        "Replay path for {} along {:?} to node {} already exists with tag {:?}, \
+                         but was set up again with tag {:?}",
+                        path.0,
+                        path.1.iter().map(|n| n.index()).collect::<Vec<_>>(),
+                        ni.index(),
+                        existing,
+                        tag
+                    );
+                }
+            }
+            paths_for_node.insert(tag, path);
+        }
+        Ok(())
     }
 
     /// Return a references to the set of indexes for the given node in the graph.
@@ -243,6 +973,15 @@ impl Materializations {
         self.have.get(&ni)
     }
 
+    /// Return the set of nodes which became materialized the last time materializations were
+    /// [committed](Materializations::commit), having not been materialized before.
+    ///
+    /// This includes both nodes added by that migration and existing nodes which weren't previously
+    /// materialized, but not existing materializations which just gained new indices.
+    pub(in crate::controller) fn newly_materialized(&self) -> &HashSet<NodeIndex> {
+        &self.newly_materialized
+    }
+
     /// Is the given node partially materialized?
     ///
     /// Note that this method returns `false` if the node is fully materialized, *or* if it's not
@@ -251,6 +990,46 @@ impl Materializations {
         self.partial.contains(&node_index)
     }
 
+    /// Returns the number of lookups that have been performed into each materialized node since
+    /// the node was materialized, or since the last call to [`reset_access_stats`], given the
+    /// total number of lookups into each node reported by the domains and the rate at which the
+    /// domains sample those lookups.
+    ///
+    /// [`reset_access_stats`]: Materializations::reset_access_stats
+    pub(in crate::controller) fn access_stats(
+        &self,
+        node_lookups: &HashMap<NodeIndex, u64>,
+        sample_rate: u64,
+    ) -> MaterializationAccessStats {
+        MaterializationAccessStats {
+            sample_rate: sample_rate.max(1),
+            lookups: self.access_counters.snapshot(node_lookups),
+        }
+    }
+
+    /// Reset the lookup counts returned by [`access_stats`] for all nodes to zero, given the total
+    /// number of lookups into each node currently reported by the domains.
+    ///
+    /// [`access_stats`]: Materializations::access_stats
+    pub(in crate::controller) fn reset_access_stats(&self, node_lookups: &HashMap<NodeIndex, u64>) {
+        self.access_counters.reset(node_lookups);
+    }
+
+    /// Record that `ni` is materialized because of `kind`, keeping whichever of that and any
+    /// previously recorded reason is more inherent
+    fn record_kind(&mut self, ni: NodeIndex, kind: MaterializationKind) {
+        self.kinds
+            .entry(ni)
+            .and_modify(|k| *k = (*k).min(kind))
+            .or_insert(kind);
+    }
+
+    /// Returns the primary reason the given node is materialized, or None if we don't know of a
+    /// reason for it to be
+    pub(in crate::controller) fn get_kind(&self, ni: NodeIndex) -> Option<MaterializationKind> {
+        self.kinds.get(&ni).copied()
+    }
+
     /// Extend the current set of materializations with any additional materializations needed to
     /// satisfy indexing obligations in the given set of (new) nodes.
     #[allow(clippy::cognitive_complexity)]
@@ -262,6 +1041,14 @@ impl Materializations {
     ) -> ReadySetResult<()> {
         let span = info_span!("materializations:extend");
         let _g = span.enter();
+        self.planning_started.get_or_insert_with(Instant::now);
+        // the graph may have changed since the last time we computed any replay paths
+        self.path_cache.clear();
+
+        if !dmp.is_recovery() && self.apply_template(graph, new)? {
+            self.assert_invariants(graph);
+            return Ok(());
+        }
         // this code used to be a mess, and will likely be a mess this time around too.
         // but, let's try to start out in a principled way...
         //
@@ -269,8 +1056,9 @@ impl Materializations {
         // newly added, but not yet constructed, materializations (self.added). Everything in
         // self.added is also in self.have. We're now being asked to compute any indexing
         // obligations created by the nodes in `nodes`, some of which may be new (iff the boolean
-        // is true). `extend` will be called once per new domain, so it will be called several
-        // times before `commit` is ultimately called to create the new materializations.
+        // is true). `extend` may be called several times before `commit` is ultimately called to
+        // create the new materializations (once per iteration of the migration planning loop), so
+        // the obligations created by each new node are cached in `self.obligations` until then.
         //
         // There are multiple ways in which an indexing obligation can be created:
         //
@@ -300,40 +1088,25 @@ impl Materializations {
         // Holds all replay obligations. Keyed by the node whose *parent* should be materialized.
         let mut replay_obligations: HashMap<NodeIndex, Indices> = HashMap::new();
 
+        // Nodes which asked for lookup indices on their own state, rather than on an ancestor's
+        let mut own_state: HashSet<NodeIndex> = HashSet::new();
+
         // Find indices we need to add.
         for &ni in new {
             let n = &graph[ni];
 
-            let mut indices: HashMap<NodeIndex, IndexObligation> = if let Some(r) = n.as_reader() {
-                if let Some(index) = r.index() {
-                    // for a reader that will get lookups, we'd like to have an index above us
-                    // somewhere on our key so that we can make the reader partial
-                    self.new_readers.insert(ni);
-                    HashMap::from([(ni, IndexObligation::Replay(index.clone()))])
-                } else {
-                    // only streaming, no indexing needed
-                    continue;
-                }
-            } else {
-                n.suggest_indexes(ni)
-                    .into_iter()
-                    .map(|(n, lookup_index)| (n, IndexObligation::Lookup(lookup_index)))
-                    .collect()
-            };
-
-            if indices.is_empty() && n.is_base() {
-                // we must *always* materialize base nodes
-                // so, just make up some column to index on
-                indices.insert(
-                    ni,
-                    IndexObligation::Lookup(LookupIndex::Strict(Index::hash_map(vec![0]))),
-                );
+            if n.as_reader().and_then(|r| r.index()).is_some() {
+                self.new_readers.insert(ni);
+                self.record_kind(ni, MaterializationKind::OwnState);
             }
 
+            let indices = self.obligations_for(graph, ni);
+
+            let requester = ni;
             for (ni, obligation) in indices {
                 trace!(
                     node = %ni.index(),
-                    obligation = ?obligation,
+                    %obligation,
                     "new indexing obligation"
                 );
 
@@ -342,61 +1115,18 @@ impl Materializations {
                         replay_obligations.entry(ni).or_default().insert(index);
                     }
                     IndexObligation::Lookup(index) => {
+                        if ni == requester {
+                            own_state.insert(ni);
+                        }
+                        if index.is_weak() {
+                            self.weak_lookups.entry(requester).or_default().insert(ni);
+                        }
                         lookup_obligations.entry(ni).or_default().insert(index);
                     }
                 }
             }
         }
 
-        // map all the indices to the corresponding columns in the parent
-        fn map_lookup_indices(
-            n: &Node,
-            parent: NodeIndex,
-            indices: &HashSet<LookupIndex>,
-        ) -> ReadySetResult<HashSet<LookupIndex>> {
-            indices
-                .iter()
-                .map(|lookup_index| {
-                    let index = lookup_index.index();
-                    let index = Index::new(
-                        index.index_type,
-                        index
-                            .columns
-                            .iter()
-                            .map(|&col| {
-                                if !n.is_internal() {
-                                    if n.is_base() {
-                                        internal!("map_indices called with base table");
-                                    }
-                                    return Ok(col);
-                                }
-
-                                let really = n.parent_columns(col);
-                                let really = really
-                                    .into_iter()
-                                    .find(|&(anc, _)| anc == parent)
-                                    .and_then(|(_, col)| col);
-
-                                really.ok_or_else(|| {
-                                    internal_err!(
-                                        "could not resolve obligation past operator;\
-                                     node => {}, ancestor => {}, column => {}",
-                                        n.global_addr().index(),
-                                        parent.index(),
-                                        col
-                                    )
-                                })
-                            })
-                            .collect::<ReadySetResult<Vec<usize>>>()?,
-                    );
-                    Ok(match lookup_index {
-                        LookupIndex::Strict(_) => LookupIndex::Strict(index),
-                        LookupIndex::Weak(_) => LookupIndex::Weak(index),
-                    })
-                })
-                .collect()
-        }
-
         // lookup obligations are fairly rigid, in that they require a materialization, and can
         // only be pushed through query-through nodes, and never across domains. so, we deal with
         // those first.
@@ -406,6 +1136,11 @@ impl Materializations {
         // partial node may add indices to only a subset of the intermediate partial views between
         // it and the nearest full materialization (because the intermediate ones haven't been
         // marked as materialized yet).
+        //
+        // we also keep track of every index that needs to exist exactly as-is, because an
+        // operator looks up into it or a replay path is sourced from it, so that we know which
+        // indices are safe to merge later.
+        let mut exact_indices: HashMap<NodeIndex, HashSet<Index>> = HashMap::new();
         for (ni, mut indices) in lookup_obligations {
             // we want to find the closest materialization that allows lookups (i.e., counting
             // query-through operators).
@@ -434,17 +1169,32 @@ impl Materializations {
                     to_node  = %parent.index(),
                     "hoisting indexing obligations"
                 );
+                indices = map_lookup_indices(graph, mi, parent, &indices)?;
                 mi = parent;
-                indices = map_lookup_indices(m, mi, &indices)?;
                 m = &graph[mi];
             }
 
+            let kind = if m.is_base() {
+                MaterializationKind::Base
+            } else if mi == ni && own_state.contains(&ni) {
+                MaterializationKind::OwnState
+            } else {
+                MaterializationKind::Lookup
+            };
+            self.record_kind(mi, kind);
+
             for index in indices {
                 debug!(
                     node = %mi.index(),
-                    ?index,
+                    name = %Sensitive(&m.name().display_unquoted()),
+                    domain = domain_of(m),
+                    %index,
                     "adding lookup index to view"
                 );
+                exact_indices
+                    .entry(mi)
+                    .or_default()
+                    .insert(index.index().clone());
 
                 // Since lookups into weak indices are forbidden when processing replays, any weak
                 // index that we add needs to *also* have a corresponding strict index of the same
@@ -481,21 +1231,11 @@ impl Materializations {
         // this is surprisingly difficult to get right.
         //
         // the approach we are going to take is to require walking the graph bottom-up:
-        let mut ordered = Vec::with_capacity(graph.node_count());
-        let mut topo = petgraph::visit::Topo::new(graph as &Graph);
-        while let Some(node) = topo.next(graph as &Graph) {
-            if graph[node].is_source() {
-                continue;
-            }
-            if graph[node].is_dropped() {
-                continue;
-            }
-
-            // unfortunately, we may end up adding indexes to existing views, and we need to walk
-            // them *all* in reverse topological order.
-            ordered.push(node);
-        }
-        ordered.reverse();
+        let ordered = Self::extend_order(graph);
+        trace!(
+            ?ordered,
+            "computing replay obligations in reverse topological order"
+        );
         // for each node, we will check if it has any *new* indexes (i.e., in self.added).
         // if it does, see if the indexed columns resolve into its nearest ancestor
         // materializations. if they do, we mark this view as partial. if not, we, well, don't.
@@ -521,17 +1261,49 @@ impl Materializations {
             }
 
             if graph[ni].is_internal() && graph[ni].requires_full_materialization() {
-                debug!(node = %ni.index(), "full because required");
+                debug!(
+                    node = %ni.index(),
+                    name = %Sensitive(&graph[ni].name().display_unquoted()),
+                    domain = domain_of(&graph[ni]),
+                    "full because required"
+                );
+                able = false;
+            }
+
+            if self.forced_full(&graph[ni]) {
+                debug!(
+                    node = %ni.index(),
+                    name = %Sensitive(&graph[ni].name().display_unquoted()),
+                    domain = domain_of(&graph[ni]),
+                    "full because forced by name"
+                );
                 able = false;
             }
 
-            // we are already fully materialized, so can't be made partial
+            // we are already fully materialized, so can't be made partial. The new indices will
+            // be fully materialized too, which is correct but wastes memory if the node could
+            // have been partial had it been planned together with the new query, so tell the
+            // operator about it.
+            //
+            // TODO: we could instead split the graph at this point, similar to what we do for the
+            // full-below-partial case (see `validate`), and give the new query its own partial
+            // copy of the node
             if !new.contains(&ni)
                 && self.added.get(&ni).map(|i| i.len()).unwrap_or(0)
                     != self.have.get(&ni).map(|i| i.len()).unwrap_or(0)
                 && !self.partial.contains(&ni)
             {
-                debug!(node = %ni.index(), "cannot turn full into partial");
+                if able {
+                    counter!(recorded::CONTROLLER_FULL_TO_PARTIAL_CONFLICTS).increment(1);
+                    warn!(
+                        node = %ni.index(),
+                        name = %Sensitive(&graph[ni].name().display_unquoted()),
+                        domain = domain_of(&graph[ni]),
+                        child = ?new_descendant(graph, ni, new).map(|c| c.index()),
+                        remediation = FULL_TO_PARTIAL_REMEDIATION,
+                        "cannot turn full into partial; keeping new indices fully materialized"
+                    );
+                }
                 able = false;
             }
 
@@ -574,7 +1346,7 @@ impl Materializations {
             let mut paths = vec![];
             for index in &indexes {
                 #[allow(clippy::unwrap_used)] // index.columns cannot be empty
-                paths.extend(keys::replay_paths_for_nonstop(
+                paths.extend(self.path_cache.paths_for(
                     graph,
                     ColumnRef {
                         node: ni,
@@ -616,14 +1388,37 @@ impl Materializations {
                         Some(index) => {
                             if let Some(m) = self.have.get(node) {
                                 // We've found an already-materialized node along our path - we can
-                                // use that as the source of our eventual replay path
-                                if !m.contains(index) {
-                                    // we need to add an index to this materialization to make that
-                                    // happen
-                                    add.entry(*node)
-                                        .or_insert_with(HashSet::new)
-                                        .insert(index.clone());
+                                // use that as the source of our eventual replay path.
+                                //
+                                // If that node is partial, we always source from an index on
+                                // exactly the (traced) columns of our index, even if it already
+                                // has another index which shares some of those columns. This is
+                                // what guarantees that a multi-column partial index never only
+                                // partially overlaps the indices of a partial ancestor (see
+                                // `overlapping_partial_index_violations`).
+                                if m.contains(index) {
+                                    break;
                                 }
+                                if let Some(existing) =
+                                    self.compatible_existing_index(graph, *node, index)
+                                {
+                                    debug!(
+                                        node = %node.index(),
+                                        %index,
+                                        %existing,
+                                        "sourcing replay from existing compatible index"
+                                    );
+                                    exact_indices
+                                        .entry(*node)
+                                        .or_default()
+                                        .insert(existing.clone());
+                                    break;
+                                }
+                                // we need to add an index to this materialization to make that
+                                // happen
+                                add.entry(*node)
+                                    .or_insert_with(HashSet::new)
+                                    .insert(index.clone());
                                 break;
                             }
                             if i == path.len() - 1 && path.broken() {
@@ -631,6 +1426,7 @@ impl Materializations {
                                     debug!(node = %node.index(), "forcing materialization for node with generated columns");
                                     HashSet::new()
                                 });
+                                self.record_kind(*node, MaterializationKind::Replay);
 
                                 add.entry(*node)
                                     .or_insert_with(HashSet::new)
@@ -644,23 +1440,30 @@ impl Materializations {
             if able {
                 // we can do partial if we add all those indices!
                 self.partial.insert(ni);
-                debug!(node = %ni.index(), "using partial materialization");
+                debug!(
+                    node = %ni.index(),
+                    name = %Sensitive(&graph[ni].name().display_unquoted()),
+                    domain = domain_of(&graph[ni]),
+                    "using partial materialization"
+                );
                 for (mi, indices) in add {
+                    exact_indices
+                        .entry(mi)
+                        .or_default()
+                        .extend(indices.iter().cloned());
                     replay_obligations.entry(mi).or_default().extend(indices);
                 }
             } else if !graph[ni].is_base() && !self.config.allow_full_materialization {
                 unsupported!(
                     "Creation of fully materialized query is disabled \
-                     (node {} / {} / {}  would be fully materialized)",
+                     (node {} / {} / {}  would be fully materialized, indexed by {})",
                     ni.index(),
                     graph[ni].name().display_unquoted(),
                     graph[ni].description(true),
+                    indexes.iter().join(", "),
                 );
-            } else {
-                invariant!(
-                    !graph[ni].purge,
-                    "full materialization placed beyond materialization frontier"
-                );
+            } else if graph[ni].purge {
+                return Err(frontier_violation(graph, ni, "node is fully materialized"));
             }
 
             // no matter what happens, we're going to have to fulfill our replay obligations.
@@ -671,7 +1474,9 @@ impl Materializations {
                     if new_index {
                         debug!(
                           on = %ni.index(),
-                          columns = ?index,
+                          name = %Sensitive(&graph[ni].name().display_unquoted()),
+                          domain = domain_of(&graph[ni]),
+                          %index,
                           "adding index to view to enable partial"
                         );
                     }
@@ -685,32 +1490,279 @@ impl Materializations {
                 }
             }
         }
-        assert!(replay_obligations.is_empty());
+        check_obligations_satisfied(graph, &replay_obligations)?;
 
-        // Mark nodes as beyond the frontier as dictated by the strategy
-        for &ni in new {
-            #[allow(clippy::unwrap_used)] // graph must contain nodes in new
-            let n = graph.node_weight_mut(ni).unwrap();
+        if let Some(max_depth) = self.config.max_partial_chain_depth {
+            let depths = self.partial_chain_depths(graph);
+            for ni in new.iter().copied().sorted() {
+                let Some(&depth) = depths.get(&ni) else {
+                    continue;
+                };
+                if depth > max_depth {
+                    counter!(recorded::CONTROLLER_DEEP_PARTIAL_CHAINS).increment(1);
+                    warn!(
+                        node = %ni.index(),
+                        name = %Sensitive(&graph[ni].name().display_unquoted()),
+                        domain = domain_of(&graph[ni]),
+                        depth,
+                        max_depth,
+                        "partial node is below a long chain of partial materializations; \
+                         consider fully materializing a node in between"
+                    );
+                }
+            }
+        }
+
+        let redundant = self.redundant_indices(graph, new);
+        for r in &redundant {
+            warn!(
+                node = %r.node.index(),
+                name = %Sensitive(&graph[r.node].name().display_unquoted()),
+                domain = domain_of(&graph[r.node]),
+                "{r}; lookups could query through to the ancestor instead"
+            );
+        }
+        counter!(recorded::CONTROLLER_REDUNDANT_INDICES).increment(redundant.len() as u64);
+        if self.config.suppress_redundant_indices {
+            for ni in self.suppress_redundant_indices(&redundant, new) {
+                warn!(
+                    node = %ni.index(),
+                    name = %Sensitive(&graph[ni].name().display_unquoted()),
+                    "not materializing node, since all of its indices are redundant"
+                );
+            }
+        }
+
+        if self.config.merge_prefix_indices {
+            for (ni, index, into) in self.merge_prefix_indices(graph, new, &exact_indices) {
+                debug!(
+                    node = %ni.index(),
+                    name = %Sensitive(&graph[ni].name().display_unquoted()),
+                    %index,
+                    %into,
+                    "merging index into longer index with the same prefix"
+                );
+            }
+        }
+
+        self.place_frontier(graph, new)?;
+        self.assert_invariants(graph);
+        Ok(())
+    }
+
+    /// In debug builds, check the invariants between the sets of materialized nodes and indices
+    /// that the rest of materialization planning relies on:
+    ///
+    /// * every index in `added` is also in `have`
+    /// * every node in `added_weak` is also in `have`
+    /// * every partially materialized node other than a reader (whose state isn't tracked in
+    ///   `have`) is also in `have`
+    ///
+    /// # Panics
+    ///
+    /// If any of the invariants don't hold, in debug builds
+    pub(in crate::controller) fn assert_invariants(&self, graph: &Graph) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        for (ni, indices) in &self.added {
+            let have = self.have.get(ni);
+            for index in indices {
+                assert!(
+                    have.map_or(false, |have| have.contains(index)),
+                    "index {index} was added to node {}, but isn't in `have`",
+                    ni.index()
+                );
+            }
+        }
+
+        for ni in self.added_weak.keys() {
+            assert!(
+                self.have.contains_key(ni),
+                "weak index was added to node {}, but it isn't in `have`",
+                ni.index()
+            );
+        }
+
+        for ni in &self.partial {
+            assert!(
+                self.have.contains_key(ni) || graph.node_weight(*ni).map_or(false, Node::is_reader),
+                "node {} is partial, but isn't in `have`",
+                ni.index()
+            );
+        }
+    }
+
+    /// Returns true if `node` must be fully materialized because its name starts with one of the
+    /// prefixes in [`Config::force_full`], and full materialization is allowed
+    fn forced_full(&self, node: &Node) -> bool {
+        self.config.allow_full_materialization
+            && self
+                .config
+                .force_full
+                .iter()
+                .any(|prefix| node.name().name.starts_with(prefix.as_str()))
+    }
 
-            if (self.have.contains_key(&ni) || n.is_reader()) && !self.partial.contains(&ni) {
-                // full materializations cannot be beyond the frontier.
+    /// Returns an existing descendant of `ni` which was already materialized before this
+    /// migration, and so relies on `ni` forwarding it every update. Nodes in `new` (and everything
+    /// below them) are skipped, as are nodes which are only now becoming materialized, since those
+    /// start out empty.
+    fn previously_materialized_descendant(
+        &self,
+        graph: &Graph,
+        ni: NodeIndex,
+        new: &HashSet<NodeIndex>,
+    ) -> Option<NodeIndex> {
+        let mut stack: Vec<_> = graph
+            .neighbors_directed(ni, petgraph::EdgeDirection::Outgoing)
+            .collect();
+        while let Some(child) = stack.pop() {
+            if new.contains(&child) {
+                // NOTE: no need to check its children either
                 continue;
             }
 
-            if n.name().name.starts_with("SHALLOW_") {
-                n.purge = true;
+            if self.added.get(&child).map(|i| i.len()).unwrap_or(0)
+                != self.have.get(&child).map(|i| i.len()).unwrap_or(0)
+            {
+                // node was previously materialized!
+                return Some(child);
+            }
+
+            stack.extend(graph.neighbors_directed(child, petgraph::EdgeDirection::Outgoing));
+        }
+        None
+    }
+
+    /// Returns the eviction granularity hint for the state of `node`: [`EvictionGranularity::Range`]
+    /// if its name starts with `RANGE_EVICT_` or one of the prefixes in [`Config::range_eviction`],
+    /// and [`EvictionGranularity::Key`] otherwise
+    fn eviction_granularity(&self, node: &Node) -> EvictionGranularity {
+        let name = &node.name().name;
+        if name.starts_with("RANGE_EVICT_")
+            || self
+                .config
+                .range_eviction
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+        {
+            EvictionGranularity::Range
+        } else {
+            EvictionGranularity::Key
+        }
+    }
+
+    /// Returns the set of nodes which must never be placed beyond the materialization frontier,
+    /// because they are either in [`Config::pinned_nodes`] or an ancestor of one
+    fn pinned_nodes(&self, graph: &Graph) -> HashSet<NodeIndex> {
+        self.config
+            .pinned_nodes
+            .iter()
+            .flat_map(|&ni| reachable(graph, ni, petgraph::EdgeDirection::Incoming))
+            .collect()
+    }
+
+    /// Returns true if `child` performs lookups into a weak index on `parent`, which it isn't
+    /// allowed to do while processing replays
+    pub(in crate::controller) fn looks_up_weakly(
+        &self,
+        child: NodeIndex,
+        parent: NodeIndex,
+    ) -> bool {
+        self.weak_lookups
+            .get(&child)
+            .map_or(false, |parents| parents.contains(&parent))
+    }
+
+    /// Returns true if the node `n` at index `ni` should be placed beyond the materialization
+    /// frontier according to `strategy` and the `SHALLOW_` name prefix. Doesn't take
+    /// [pinned][Config::pinned_nodes] nodes into account.
+    fn beyond_frontier(&self, ni: NodeIndex, n: &Node, strategy: FrontierStrategy) -> bool {
+        if (self.have.contains_key(&ni) || n.is_reader()) && !self.partial.contains(&ni) {
+            // full materializations cannot be beyond the frontier.
+            return false;
+        }
+
+        if n.name().name.starts_with("SHALLOW_") {
+            return true;
+        }
+
+        // For all other strategies, we only want to deal with partial indices
+        if !self.partial.contains(&ni) {
+            return false;
+        }
+
+        match strategy {
+            FrontierStrategy::None => false,
+            FrontierStrategy::AllPartial => true,
+            FrontierStrategy::Readers => n.is_reader(),
+        }
+    }
+
+    /// Compute which materialized nodes would be beyond the materialization frontier if it were
+    /// placed according to `strategy` rather than the configured frontier strategy, and the total
+    /// size of their state according to `node_sizes`, without changing the graph.
+    ///
+    /// Nodes which are already beyond the frontier stay there, and as when placing the frontier
+    /// for real, nodes without state which would be beyond the frontier place their partially
+    /// materialized parents beyond it instead.
+    pub(in crate::controller) fn simulate_frontier(
+        &self,
+        graph: &Graph,
+        strategy: FrontierStrategy,
+        node_sizes: &HashMap<NodeIndex, NodeSize>,
+    ) -> FrontierSimulation {
+        let pinned = self.pinned_nodes(graph);
+        let has_state = |ni: NodeIndex| self.have.contains_key(&ni) || graph[ni].is_reader();
+
+        let mut purged = BTreeSet::new();
+        for ni in graph.node_indices() {
+            let n = &graph[ni];
+            if n.is_source() || n.is_dropped() {
+                continue;
+            }
+            if !n.purge && (pinned.contains(&ni) || !self.beyond_frontier(ni, n, strategy)) {
                 continue;
             }
 
-            // For all other strategies, we only want to deal with partial indices
-            if !self.partial.contains(&ni) {
+            if has_state(ni) {
+                purged.insert(ni);
+            } else {
+                purged.extend(
+                    graph
+                        .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+                        .filter(|pi| {
+                            self.have.contains_key(pi)
+                                && self.partial.contains(pi)
+                                && !pinned.contains(pi)
+                        }),
+                );
+            }
+        }
+
+        FrontierSimulation {
+            evictable_bytes: purged
+                .iter()
+                .filter_map(|ni| node_sizes.get(ni))
+                .map(|size| size.bytes.0)
+                .sum(),
+            purged_nodes: purged.into_iter().collect(),
+        }
+    }
+
+    /// Mark the nodes in `new` as beyond the materialization frontier as dictated by the frontier
+    /// strategy, except for any [pinned][Config::pinned_nodes] nodes
+    fn place_frontier(&self, graph: &mut Graph, new: &HashSet<NodeIndex>) -> ReadySetResult<()> {
+        let pinned = self.pinned_nodes(graph);
+        for &ni in new {
+            if pinned.contains(&ni) {
                 continue;
             }
 
-            if let FrontierStrategy::AllPartial = self.config.frontier_strategy {
-                n.purge = true;
-            } else if let FrontierStrategy::Readers = self.config.frontier_strategy {
-                n.purge = n.purge || n.is_reader();
+            if self.beyond_frontier(ni, &graph[ni], self.config.frontier_strategy) {
+                graph[ni].purge = true;
             }
         }
 
@@ -720,23 +1772,73 @@ impl Materializations {
             // materialization, so let's make sure the label gets correctly applied: specifically,
             // if a .prune node doesn't have state, we "move" that .prune to its ancestors.
             if graph[ni].purge && !(self.have.contains_key(&ni) || graph[ni].is_reader()) {
+                let mut moved = false;
                 let mut it = graph
                     .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
                     .detach();
                 while let Some((_, pi)) = it.next(&*graph) {
                     if !new.contains(&pi) {
+                        debug!(
+                            node = %ni.index(),
+                            parent = %pi.index(),
+                            "not moving purge from node without state to existing parent"
+                        );
                         continue;
                     }
                     if !self.have.contains_key(&pi) {
-                        debug!(node = %ni.index(), "no associated state with purged node");
+                        debug!(
+                            node = %ni.index(),
+                            parent = %pi.index(),
+                            "not moving purge from node without state to parent without state"
+                        );
+                        continue;
+                    }
+                    if pinned.contains(&pi) {
+                        debug!(
+                            node = %ni.index(),
+                            parent = %pi.index(),
+                            "not moving purge to pinned parent of purged node"
+                        );
+                        moved = true;
                         continue;
                     }
-                    invariant!(
-                        self.partial.contains(&pi),
-                        "attempting to place full materialization beyond materialization frontier"
+                    if !self.partial.contains(&pi) {
+                        return Err(frontier_violation(
+                            graph,
+                            pi,
+                            "node is fully materialized, and is the parent of a purged node \
+                             without state",
+                        ));
+                    }
+                    debug!(
+                        node = %ni.index(),
+                        node_name = %graph[ni].name().display_unquoted(),
+                        ancestor = %pi.index(),
+                        ancestor_name = %graph[pi].name().display_unquoted(),
+                        reason = "purged node has no state of its own",
+                        "moving purge to materialized parent"
                     );
+                    counter!(recorded::CONTROLLER_PURGE_MOVED_TO_ANCESTOR).increment(1);
                     // #[allow(clippy::unwrap_used)] // graph must contain pi
                     graph.node_weight_mut(pi).unwrap().purge = true;
+                    moved = true;
+                }
+
+                if !moved {
+                    debug!(
+                        node = %ni.index(),
+                        node_name = %graph[ni].name().display_unquoted(),
+                        "purged node has no state, and no parents to move its purge to"
+                    );
+                }
+                if !moved && self.config.strict_frontier {
+                    unsupported!(
+                        "Node {} / {} was placed beyond the materialization frontier, but neither \
+                         it nor any of its parents have state which could be placed beyond the \
+                         frontier instead",
+                        ni.index(),
+                        graph[ni].name().display_unquoted(),
+                    );
                 }
             }
         }
@@ -768,6 +1870,94 @@ impl Materializations {
         }
     }
 
+    /// Returns the set of nodes which are currently placed beyond the materialization frontier,
+    /// either because of the [`FrontierStrategy`] or because their name starts with `SHALLOW_`.
+    ///
+    /// Only partially materialized nodes (including readers) can be beyond the frontier, so this
+    /// ignores the `purge` flag on all other nodes, such as non-materialized nodes whose purge flag
+    /// was moved to their parents.
+    pub(in crate::controller) fn shallow_nodes(&self, graph: &Graph) -> HashSet<NodeIndex> {
+        self.partial
+            .iter()
+            .copied()
+            .filter(|&ni| {
+                graph.node_weight(ni).map_or(false, |n| {
+                    matches!(
+                        self.get_status(ni, n),
+                        MaterializationStatus::Partial {
+                            beyond_materialization_frontier: true
+                        }
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the materialized nodes which no reader can reach (that aren't an ancestor of any
+    /// reader), sorted by index.
+    ///
+    /// Materializations can become orphaned like this over many migrations, once every query that
+    /// read from them has been dropped, but still use memory - so they're candidates to be dropped.
+    /// Base tables are never included, since they hold the data for every other node.
+    pub(in crate::controller) fn unreachable(&self, graph: &Graph) -> Vec<NodeIndex> {
+        let readers = graph
+            .node_indices()
+            .filter(|&ni| graph[ni].is_reader() && !graph[ni].is_dropped());
+        let reachable = reachable_from_any(graph, readers, petgraph::EdgeDirection::Incoming);
+        self.have
+            .keys()
+            .copied()
+            .filter(|ni| {
+                !reachable.contains(ni) && !graph[*ni].is_base() && !graph[*ni].is_dropped()
+            })
+            .sorted()
+            .collect()
+    }
+
+    /// Returns the replay paths which fill the indices of the given node, as tuples of the tag
+    /// identifying each path, the index it fills, and the nodes along it in replay order.
+    ///
+    /// Paths are returned sorted by tag, so the result is the same every time for the same
+    /// materializations.
+    pub(in crate::controller) fn replay_paths_for(
+        &self,
+        ni: NodeIndex,
+    ) -> Vec<(Tag, Index, Vec<NodeIndex>)> {
+        self.paths
+            .get(&ni)
+            .into_iter()
+            .flat_map(|paths| paths.iter())
+            .map(|(tag, (index, path))| (*tag, index.clone(), path.clone()))
+            .sorted()
+            .collect()
+    }
+
+    /// Returns the total number of replay paths set up across all nodes
+    pub(in crate::controller) fn total_paths(&self) -> usize {
+        self.paths.values().map(|paths| paths.len()).sum()
+    }
+
+    /// Returns the total number of indices across all materialized nodes
+    pub(in crate::controller) fn index_count(&self) -> usize {
+        self.have.values().map(|indices| indices.len()).sum()
+    }
+
+    /// Return the materialized nodes which the replay paths for the node at `ni` are sourced
+    /// from, deduplicated and in sorted order.
+    ///
+    /// For a reader, this is the upstream state its replays (whether partial or full) are read
+    /// from.
+    pub(in crate::controller) fn replay_sources_for(&self, ni: NodeIndex) -> Vec<NodeIndex> {
+        self.paths
+            .get(&ni)
+            .into_iter()
+            .flat_map(|paths| paths.iter())
+            .filter_map(|(_, (_, path))| path.first().copied())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
     /// Construct an iterator over the indexes of non-reader nodes that are materialized.
     pub(in crate::controller) fn materialized_non_reader_nodes(
         &self,
@@ -781,6 +1971,8 @@ impl Materializations {
     /// * Checking to make sure no partially materialized nodes exist that are ancestors of fully
     ///   materialized nodes
     /// * Checking that no node is partial over a subset of the indices in its parent
+    /// * Checking that the replay paths for range (BTree) indices on partial nodes don't pass
+    ///   through, or get sourced from, hash-only indices
     /// * Checking that there are no cases where a subgraph is sharded by one column, and then has a
     ///   replay path on a duplicated copy of that column.
     ///
@@ -791,286 +1983,678 @@ impl Materializations {
         graph: &Graph,
         new: &HashSet<NodeIndex>,
     ) -> ReadySetResult<Option<InvalidEdge>> {
-        // check that we don't have fully materialized nodes downstream of partially materialized
-        // nodes.
-        // returns (parent_index, child_index) if two neighbors are found where parent is partially
-        // materialized and child is fully materialized.
+        if let Some(MaterializationViolation::FullBelowPartial { parent, child }) = self
+            .full_below_partial_violations(
+                graph,
+                self.added.keys().copied().chain(self.new_readers.clone()),
+            )
+            .into_iter()
+            .next()
         {
-            fn any_partial(
-                this: &Materializations,
-                graph: &Graph,
-                ni: NodeIndex,
-            ) -> (Option<NodeIndex>, Option<NodeIndex>) {
-                if this.partial.contains(&ni) {
-                    return (Some(ni), None);
-                }
-                for pi in graph.neighbors_directed(ni, petgraph::EdgeDirection::Incoming) {
-                    match any_partial(this, graph, pi) {
-                        (Some(pi), Some(ni)) => return (Some(pi), Some(ni)),
-                        (Some(pi), None) => return (Some(pi), Some(ni)),
-                        _ => {}
-                    }
-                }
-                (None, None)
+            return Ok(Some(InvalidEdge { parent, child }));
+        }
+
+        let mut aliased_sharding = self.aliased_sharding_violations(graph, new)?;
+        if self.config.allow_aliased_sharding && !aliased_sharding.is_empty() {
+            for violation in &aliased_sharding {
+                warn!(%violation, "allowing shard merger below aliased sharding column");
+            }
+            counter!(recorded::CONTROLLER_ALIASED_SHARDING_ALLOWED)
+                .increment(aliased_sharding.len() as u64);
+            aliased_sharding.clear();
+        }
+        let violations = self
+            .overlapping_partial_index_violations(graph, &self.added)?
+            .into_iter()
+            .chain(self.purge_violations(graph, new))
+            .chain(self.unpaired_weak_index_violations(graph, new)?)
+            .chain(self.hash_index_in_range_replay_violations(
+                graph,
+                &self.added,
+                self.new_readers.iter().copied(),
+            )?)
+            .chain(aliased_sharding);
+        if let Some(violation) = violations.into_iter().next() {
+            println!("{}", Graphviz::new(graph, self));
+            if let MaterializationViolation::PurgeAboveNonPurge { node } = violation {
+                return Err(frontier_violation(
+                    graph,
+                    node,
+                    "node is upstream of a materialization which is not beyond the frontier",
+                ));
             }
+            internal!("{}", violation);
+        }
+
+        let (hits, misses) = self.path_cache.hit_counts();
+        debug!(hits, misses, "replay path cache usage");
+
+        Ok(None)
+    }
+
+    /// Check all graph invariants for the materializations in `self` over the *entire* given
+    /// `graph`, rather than only the nodes added in the current migration, returning a list of any
+    /// violations that were found.
+    ///
+    /// This performs the same checks as [`validate`][Self::validate], but doesn't stop at the first
+    /// violation, and doesn't change the graph - it's intended as a diagnostic to tell whether an
+    /// already-running graph is currently sound.
+    pub(in crate::controller) fn check(
+        &self,
+        graph: &Graph,
+    ) -> ReadySetResult<Vec<MaterializationViolation>> {
+        let nodes = graph
+            .node_indices()
+            .filter(|&ni| !graph[ni].is_source() && !graph[ni].is_dropped())
+            .collect::<HashSet<_>>();
+        let materialized = self.have.keys().copied().chain(
+            nodes
+                .iter()
+                .copied()
+                .filter(|&ni| graph[ni].as_reader().and_then(|r| r.key()).is_some()),
+        );
 
-            for ni in self.added.keys().copied().chain(self.new_readers.clone()) {
-                if let (Some(pi), Some(ni)) = any_partial(self, graph, ni) {
-                    return Ok(Some(InvalidEdge {
-                        parent: pi,
-                        child: ni,
-                    }));
+        // the graph has likely changed since the paths in the cache were computed
+        self.path_cache.clear();
+        let mut violations = self.full_below_partial_violations(graph, materialized);
+        violations.extend(self.overlapping_partial_index_violations(graph, &self.have)?);
+        violations.extend(self.purge_violations(graph, &nodes));
+        violations.extend(self.unpaired_weak_index_violations(graph, &nodes)?);
+        violations.extend(self.hash_index_in_range_replay_violations(
+            graph,
+            &self.have,
+            nodes.iter().copied().filter(|&ni| graph[ni].is_reader()),
+        )?);
+        violations.extend(self.aliased_sharding_violations(graph, &nodes)?);
+        Ok(violations)
+    }
+
+    /// check that we don't have fully materialized nodes downstream of partially materialized
+    /// nodes, for each of the given (materialized) `nodes`.
+    fn full_below_partial_violations<I>(
+        &self,
+        graph: &Graph,
+        nodes: I,
+    ) -> Vec<MaterializationViolation>
+    where
+        I: IntoIterator<Item = NodeIndex>,
+    {
+        // returns (parent_index, child_index) if two neighbors are found where parent is partially
+        // materialized and child is fully materialized.
+        fn any_partial(
+            this: &Materializations,
+            graph: &Graph,
+            ni: NodeIndex,
+        ) -> (Option<NodeIndex>, Option<NodeIndex>) {
+            if this.partial.contains(&ni) {
+                return (Some(ni), None);
+            }
+            for pi in graph.neighbors_directed(ni, petgraph::EdgeDirection::Incoming) {
+                match any_partial(this, graph, pi) {
+                    (Some(pi), Some(ni)) => return (Some(pi), Some(ni)),
+                    (Some(pi), None) => return (Some(pi), Some(ni)),
+                    _ => {}
                 }
             }
+            (None, None)
         }
 
-        // check that no node is partial over a subset of the indices in its parent
-        {
-            for (&ni, added) in &self.added {
-                if !self.partial.contains(&ni) {
-                    continue;
+        nodes
+            .into_iter()
+            .filter_map(|ni| match any_partial(self, graph, ni) {
+                (Some(parent), Some(child)) => {
+                    Some(MaterializationViolation::FullBelowPartial { parent, child })
                 }
+                _ => None,
+            })
+            .collect()
+    }
 
-                for index in added {
-                    #[allow(clippy::unwrap_used)] // index.columns cannot be empty
-                    let paths = keys::replay_paths_for_nonstop(
-                        graph,
-                        ColumnRef {
-                            node: ni,
-                            columns: index.columns.clone(),
-                        },
-                        index.index_type,
-                    )?;
+    /// check that no node in `indices` is partial over a subset of the indices in its parent
+    fn overlapping_partial_index_violations(
+        &self,
+        graph: &Graph,
+        indices: &HashMap<NodeIndex, Indices>,
+    ) -> ReadySetResult<Vec<MaterializationViolation>> {
+        let mut violations = vec![];
+        for (&ni, added) in indices {
+            if !self.partial.contains(&ni) {
+                continue;
+            }
+
+            for index in added {
+                #[allow(clippy::unwrap_used)] // index.columns cannot be empty
+                let paths = self.path_cache.paths_for(
+                    graph,
+                    ColumnRef {
+                        node: ni,
+                        columns: index.columns.clone(),
+                    },
+                    index.index_type,
+                )?;
+
+                for path in paths {
+                    for IndexRef { node, index } in path.segments().iter().rev() {
+                        match index {
+                            None => break,
+                            Some(child_index) => {
+                                if self.partial.contains(node) {
+                                    // self.partial should be a subset of self.have
+                                    let parent_indices = &self.have[node];
+
+                                    // If the parent is also indexed on exactly the child's
+                                    // columns, then any partially overlapping indices are fine,
+                                    // since the overlapping index logic in `MemoryState::lookup`
+                                    // will save us. `extend` always adds that index when it makes
+                                    // the child partial, so this should always be the case.
+                                    if parent_indices.contains(child_index) {
+                                        continue;
+                                    }
 
-                    for path in paths {
-                        for IndexRef { node, index } in path.segments().iter().rev() {
-                            match index {
-                                None => break,
-                                Some(child_index) => {
-                                    if self.partial.contains(node) {
-                                        // self.partial should be a subset of self.have
-                                        'outer: for parent_index in &self.have[node] {
-                                            // is this node partial over some of the child's partial
-                                            // columns, but not others? if so, we run into really
-                                            // sad
-                                            // situations where the parent could miss in its state
-                                            // despite
-                                            // the child having state present for that key.
-
-                                            // Are the indexes the same type?
-                                            if parent_index.index_type != child_index.index_type {
-                                                continue;
-                                            }
-
-                                            // do we share a column?
-                                            if parent_index
-                                                .columns
-                                                .iter()
-                                                .all(|&c| !child_index.columns.contains(&c))
-                                            {
-                                                continue;
-                                            }
-
-                                            // is there a column we *don't* share?
-                                            let unshared =
-                                                parent_index
-                                                    .columns
-                                                    .iter()
-                                                    .cloned()
-                                                    .find(|&c| !child_index.columns.contains(&c))
-                                                    .or_else(|| {
-                                                        child_index.columns.iter().cloned().find(
-                                                            |c| !parent_index.columns.contains(c),
-                                                        )
-                                                    });
-                                            if let Some(not_shared) = unshared {
-                                                // This might be fine if we also have the child's
-                                                // index in
-                                                // the parent, since then the overlapping index
-                                                // logic in
-                                                // `MemoryState::lookup` will save us.
-
-                                                for other_idx in &self.have[node] {
-                                                    if other_idx == child_index {
-                                                        // Looks like we have the necessary index,
-                                                        // so we'll
-                                                        // be okay.
-                                                        continue 'outer;
-                                                    }
-                                                }
-                                                // If we get here, we've somehow managed to not
-                                                // index the
-                                                // parent by the same key as the child, which really
-                                                // should
-                                                // never happen.
-                                                // This code should probably just be taken out soon.
-                                                println!(
-                                                    "{}",
-                                                    Graphviz {
-                                                        graph,
-                                                        detailed: true,
-                                                        node_sizes: None,
-                                                        materializations: self,
-                                                        domain_nodes: None,
-                                                        reachable_from: None,
-                                                    }
-                                                );
-                                                error!(
-                                                    parent = %node.index(),
-                                                    parent_index = ?parent_index,
-                                                    child = %ni.index(),
-                                                    child_index = ?child_index,
-                                                    conflict = not_shared,
-                                                    "partially lapping partial indices"
-                                                );
-                                                internal!(
-                                                    "partially overlapping partial indices (parent {:?} cols {:?} all {:?}, child {:?} cols {:?})",
-                                                    node.index(), parent_index, &self.have[node], ni.index(), parent_index
-                                                );
-                                            }
+                                    // is this node partial over some of the child's partial
+                                    // columns, but not others? if so, we run into really sad
+                                    // situations where the parent could miss in its state despite
+                                    // the child having state present for that key.
+                                    for parent_index in parent_indices {
+                                        if let Some(not_shared) =
+                                            partial_overlap(parent_index, child_index)
+                                        {
+                                            error!(
+                                                parent = %node.index(),
+                                                %parent_index,
+                                                child = %ni.index(),
+                                                %child_index,
+                                                conflict = not_shared,
+                                                "partially lapping partial indices"
+                                            );
+                                            violations.push(
+                                                MaterializationViolation::OverlappingPartialIndices {
+                                                    parent: *node,
+                                                    parent_index: parent_index.clone(),
+                                                    child: ni,
+                                                    child_index: child_index.clone(),
+                                                },
+                                            );
                                         }
-                                    } else if self.have.contains_key(&ni) {
-                                        break;
                                     }
+                                } else if self.have.contains_key(&ni) {
+                                    break;
                                 }
                             }
                         }
                     }
                 }
             }
+        }
+
+        Ok(violations)
+    }
+
+    /// check that every range (BTree) index on a partial node in `indices`, or on a partial reader
+    /// in `readers`, can actually be replayed: every segment of its replay paths must be a range
+    /// index too, and the materialization the replays are sourced from must have that range index
+    /// rather than only a hash index on the same columns, since hash indices can't serve the range
+    /// lookups those replays perform.
+    fn hash_index_in_range_replay_violations<I>(
+        &self,
+        graph: &Graph,
+        indices: &HashMap<NodeIndex, Indices>,
+        readers: I,
+    ) -> ReadySetResult<Vec<MaterializationViolation>>
+    where
+        I: IntoIterator<Item = NodeIndex>,
+    {
+        let reader_indices = readers.into_iter().filter_map(|ni| {
+            let index = graph[ni].as_reader()?.index()?;
+            Some((ni, index.clone()))
+        });
+        let range_indices = indices
+            .iter()
+            .flat_map(|(&ni, indices)| indices.iter().map(move |index| (ni, index.clone())))
+            .chain(reader_indices)
+            .filter(|(ni, index)| {
+                index.index_type == IndexType::BTreeMap && self.partial.contains(ni)
+            });
+
+        let mut violations = vec![];
+        for (ni, index) in range_indices {
+            let paths = self.path_cache.paths_for(
+                graph,
+                ColumnRef {
+                    node: ni,
+                    columns: index.columns.clone(),
+                },
+                index.index_type,
+            )?;
 
-            // check that we never have non-purge below purge
-            let mut non_purge = Vec::new();
-            for &ni in new {
-                if (graph[ni].is_reader() || self.have.contains_key(&ni)) && !graph[ni].purge {
-                    for pi in graph.neighbors_directed(ni, petgraph::EdgeDirection::Incoming) {
-                        non_purge.push(pi);
+            for path in paths {
+                let n_to_skip = usize::from(path.target().node == ni);
+                for IndexRef {
+                    node,
+                    index: segment_index,
+                } in path.segments().iter().rev().skip(n_to_skip)
+                {
+                    let Some(segment_index) = segment_index else {
+                        break;
+                    };
+                    let through_index = if segment_index.index_type != IndexType::BTreeMap {
+                        Some(segment_index.clone())
+                    } else {
+                        // the replays are sourced from the first materialization along the path,
+                        // which a hash index on the same columns can't stand in for
+                        self.have
+                            .get(node)
+                            .filter(|have| !have.contains(segment_index))
+                            .and_then(|have| {
+                                have.iter()
+                                    .find(|i| {
+                                        i.index_type == IndexType::HashMap
+                                            && i.columns == segment_index.columns
+                                    })
+                                    .cloned()
+                            })
+                    };
+                    if let Some(through_index) = through_index {
+                        error!(
+                            node = %ni.index(),
+                            %index,
+                            through = %node.index(),
+                            %through_index,
+                            "range replay path through hash index"
+                        );
+                        violations.push(MaterializationViolation::HashIndexInRangeReplay {
+                            node: ni,
+                            index: index.clone(),
+                            through: *node,
+                            through_index,
+                        });
+                        break;
+                    }
+                    if self.have.contains_key(node) {
+                        break;
                     }
                 }
             }
-            while let Some(ni) = non_purge.pop() {
-                if graph[ni].purge {
-                    println!(
-                        "{}",
-                        Graphviz {
-                            graph,
-                            detailed: true,
-                            node_sizes: None,
-                            materializations: self,
-                            domain_nodes: None,
-                            reachable_from: None,
-                        }
-                    );
-                    internal!("found purge node {} above non-purge node", ni.index())
-                }
-                if self.have.contains_key(&ni) {
-                    // already shceduled to be checked
-                    // NOTE: no need to check for readers here, since they can't be parents
-                    continue;
-                }
-                for pi in graph.neighbors_directed(ni, petgraph::EdgeDirection::Incoming) {
-                    non_purge.push(pi);
-                }
-            }
-            drop(non_purge);
         }
 
-        // check that we don't have any cases where a subgraph is sharded by one column, and then
-        // has a replay path on a duplicated copy of that column. for example, a join with
-        // [B(0, 0), R(0)] where the join's subgraph is sharded by .0, but a downstream replay path
-        // looks up by .1. this causes terrible confusion where the target (correctly) queries only
-        // one shard, but the shard merger expects to have to wait for all shards (since the replay
-        // key and the sharding key do not match at the shard merger).
-        {
-            for &node in new {
-                let n = &graph[node];
-                if !n.is_shard_merger() {
+        Ok(violations)
+    }
+
+    /// check that every weak index that any of the given `nodes` looks up into is paired with a
+    /// strict index of the same type on the same columns, since lookups into weak indices are
+    /// forbidden when processing replays.
+    ///
+    /// Weak indices aren't tracked once they've been committed (and `weak_lookups` isn't persisted
+    /// across controller restarts), so they're derived from the indexing obligations of `nodes`
+    /// instead, the same way `extend` does when adding them.
+    fn unpaired_weak_index_violations(
+        &self,
+        graph: &Graph,
+        nodes: &HashSet<NodeIndex>,
+    ) -> ReadySetResult<Vec<MaterializationViolation>> {
+        let mut weak: HashMap<NodeIndex, HashSet<Index>> = HashMap::new();
+        for &ni in nodes {
+            for (mut target, obligation) in index_obligations(&graph[ni], ni, &self.config) {
+                let IndexObligation::Lookup(lookup_index) = obligation else {
+                    continue;
+                };
+                if !lookup_index.is_weak() {
                     continue;
                 }
 
-                // we don't actually store replay paths anywhere in Materializations (perhaps we
-                // should). however, we can check a proxy for the necessary property by making sure
-                // that our parent's sharding key is never aliased. this will lead to some false
-                // positives (all replay paths may use the same alias as we shard by), but we'll
-                // deal with that.
-                let parent = graph
-                    .neighbors_directed(node, petgraph::EdgeDirection::Incoming)
-                    .next()
-                    .ok_or_else(|| internal_err!("shard mergers must have a parent"))?;
-                let psharding = graph[parent].sharded_by();
-
-                if let Sharding::ByColumn(col, _) = psharding {
-                    // we want to resolve col all the way to its nearest materialized ancestor.
-                    // and then check whether any other cols of the parent alias that source column
-                    let columns: Vec<_> = (0..n.columns().len()).collect();
-                    for path in keys::provenance_of(graph, parent, &columns[..])? {
-                        let (mat_anc, cols) = path
-                            .into_iter()
-                            .find(|&(n, _)| self.have.contains_key(&n))
-                            .ok_or_else(|| {
-                                internal_err!(
-                                    "since bases are materialized, \
+                // follow the lookup up through any query-through nodes, the same way `extend`
+                // hoists lookup obligations
+                let mut indices = HashSet::from([lookup_index]);
+                while !self.have.contains_key(&target)
+                    && graph[target].is_internal()
+                    && graph[target].can_query_through()
+                {
+                    let Some(parent) = graph
+                        .neighbors_directed(target, petgraph::EdgeDirection::Incoming)
+                        .next()
+                    else {
+                        break;
+                    };
+                    indices = map_lookup_indices(graph, target, parent, &indices)?;
+                    target = parent;
+                }
+
+                if self.have.contains_key(&target) {
+                    weak.entry(target)
+                        .or_default()
+                        .extend(indices.into_iter().map(LookupIndex::into_index));
+                }
+            }
+        }
+
+        Ok(weak
+            .into_iter()
+            .sorted_by_key(|(ni, _)| *ni)
+            .flat_map(|(node, indices)| {
+                let strict = self.have.get(&node);
+                indices
+                    .into_iter()
+                    .filter(move |index| !strict.map_or(false, |strict| strict.contains(index)))
+                    .sorted()
+                    .map(move |index| MaterializationViolation::UnpairedWeakIndex { node, index })
+            })
+            .collect())
+    }
+
+    /// check that we never have non-purge below purge, for any of the given `nodes`
+    fn purge_violations(
+        &self,
+        graph: &Graph,
+        nodes: &HashSet<NodeIndex>,
+    ) -> Vec<MaterializationViolation> {
+        self.purge_above_non_purge(graph, nodes)
+            .into_iter()
+            .map(|(node, _)| MaterializationViolation::PurgeAboveNonPurge { node })
+            .collect()
+    }
+
+    /// Returns a `(purged, materialized)` pair for every node `purged` beyond the materialization
+    /// frontier which is upstream of a materialized node or reader `materialized` among the given
+    /// `nodes` that isn't beyond the frontier. Each purged node is only reported once, along with
+    /// the first such materialization found below it.
+    fn purge_above_non_purge(
+        &self,
+        graph: &Graph,
+        nodes: &HashSet<NodeIndex>,
+    ) -> Vec<(NodeIndex, NodeIndex)> {
+        let mut pairs = vec![];
+        let mut non_purge = Vec::new();
+        for &ni in nodes {
+            if (graph[ni].is_reader() || self.have.contains_key(&ni)) && !graph[ni].purge {
+                for pi in graph.neighbors_directed(ni, petgraph::EdgeDirection::Incoming) {
+                    non_purge.push((pi, ni));
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        while let Some((ni, materialized)) = non_purge.pop() {
+            if !seen.insert(ni) {
+                continue;
+            }
+            if graph[ni].purge {
+                pairs.push((ni, materialized));
+            }
+            if self.have.contains_key(&ni) {
+                // already shceduled to be checked
+                // NOTE: no need to check for readers here, since they can't be parents
+                continue;
+            }
+            for pi in graph.neighbors_directed(ni, petgraph::EdgeDirection::Incoming) {
+                non_purge.push((pi, materialized));
+            }
+        }
+        pairs
+    }
+
+    /// Check that no node anywhere in `graph` is beyond the materialization frontier while being
+    /// upstream of a materialized node or reader which isn't, returning the `(purged,
+    /// materialized)` pair with the lowest indices if there is one.
+    ///
+    /// [`validate`][Self::validate] only looks upwards from the nodes in the current migration,
+    /// whereas this covers the whole graph, so it also catches inconsistencies in the `purge` flags
+    /// that built up over multiple migrations.
+    pub(in crate::controller) fn purge_inconsistency(
+        &self,
+        graph: &Graph,
+    ) -> Option<(NodeIndex, NodeIndex)> {
+        let nodes = graph
+            .node_indices()
+            .filter(|&ni| !graph[ni].is_source() && !graph[ni].is_dropped())
+            .collect::<HashSet<_>>();
+        self.purge_above_non_purge(graph, &nodes).into_iter().min()
+    }
+
+    /// Returns the length of the longest chain of partial materializations ending at each
+    /// partially materialized node in the graph, counting the node itself and stopping at the
+    /// nearest full materializations (including bases) above it. A miss in a partial node with a
+    /// chain depth of `n` may have to be filled by a replay which misses in `n - 1` partial
+    /// ancestors on its way down.
+    ///
+    /// Nodes which aren't partially materialized don't have an entry.
+    pub(in crate::controller) fn partial_chain_depths(
+        &self,
+        graph: &Graph,
+    ) -> HashMap<NodeIndex, usize> {
+        // The depth of the chain above each node, whether or not it's materialized. Fully
+        // materialized nodes and readers end the chains above them.
+        let mut above: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut depths = HashMap::new();
+        for ni in topo_order(graph) {
+            let parents = graph
+                .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+                .filter_map(|pi| above.get(&pi).copied())
+                .max()
+                .unwrap_or(0);
+            let depth = if self.partial.contains(&ni) {
+                depths.insert(ni, parents + 1);
+                parents + 1
+            } else if self.have.contains_key(&ni) || graph[ni].is_base() || graph[ni].is_reader() {
+                0
+            } else {
+                parents
+            };
+            above.insert(ni, depth);
+        }
+        depths
+    }
+
+    /// check that we don't have any cases where a subgraph is sharded by one column, and then
+    /// has a replay path on a duplicated copy of that column, for any shard mergers in `nodes`.
+    ///
+    /// for example, a join with [B(0, 0), R(0)] where the join's subgraph is sharded by .0, but a
+    /// downstream replay path looks up by .1. this causes terrible confusion where the target
+    /// (correctly) queries only one shard, but the shard merger expects to have to wait for all
+    /// shards (since the replay key and the sharding key do not match at the shard merger).
+    fn aliased_sharding_violations(
+        &self,
+        graph: &Graph,
+        nodes: &HashSet<NodeIndex>,
+    ) -> ReadySetResult<Vec<MaterializationViolation>> {
+        let mut violations = vec![];
+        for &node in nodes {
+            let n = &graph[node];
+            if !n.is_shard_merger() {
+                continue;
+            }
+
+            // we don't actually store replay paths anywhere in Materializations (perhaps we
+            // should). however, we can check a proxy for the necessary property by making sure
+            // that our parent's sharding key is never aliased. this will lead to some false
+            // positives (all replay paths may use the same alias as we shard by), but we'll
+            // deal with that.
+            let parent = graph
+                .neighbors_directed(node, petgraph::EdgeDirection::Incoming)
+                .next()
+                .ok_or_else(|| internal_err!("shard mergers must have a parent"))?;
+            let psharding = graph[parent].sharded_by();
+
+            if let Sharding::ByColumn(col, _) = psharding {
+                // we want to resolve col all the way to its nearest materialized ancestor.
+                // and then check whether any other cols of the parent alias that source column
+                let columns: Vec<_> = (0..n.columns().len()).collect();
+                for path in keys::provenance_of(graph, parent, &columns[..])? {
+                    let (mat_anc, cols) = path
+                        .into_iter()
+                        .find(|&(n, _)| self.have.contains_key(&n))
+                        .ok_or_else(|| {
+                            internal_err!(
+                                "since bases are materialized, \
                                  every path must eventually have a materialized node",
-                                )
-                            })?;
-                        let src = cols[col];
-                        if src.is_none() {
-                            continue;
-                        }
+                            )
+                        })?;
+                    let src = cols[col];
+                    if src.is_none() {
+                        continue;
+                    }
 
-                        if let Some((c, res)) = cols
-                            .iter()
-                            .enumerate()
-                            .find(|&(c, res)| c != col && res == &src)
-                        {
-                            // another column in the merger's parent resolved to the source column!
-                            //println!("{}", graphviz(graph, &self));
-                            error!(
-                                parent = %mat_anc.index(),
-                                aliased = ?res,
-                                sharded = %parent.index(),
-                                alias = c,
-                                shard = col,
-                                "attempting to merge sharding by aliased column"
-                            );
-                            internal!("attempting to merge sharding by aliased column (parent {:?}, aliased {:?}, sharded {:?}, alias {:?}, shard {:?})", mat_anc.index(), res, parent.index(), c, col)
-                        }
+                    if let Some((c, res)) = cols
+                        .iter()
+                        .enumerate()
+                        .find(|&(c, res)| c != col && res == &src)
+                    {
+                        // another column in the merger's parent resolved to the source column!
+                        warn!(
+                            parent = %mat_anc.index(),
+                            aliased = ?res,
+                            sharded = %parent.index(),
+                            alias = c,
+                            shard = col,
+                            "attempting to merge sharding by aliased column"
+                        );
+                        violations.push(MaterializationViolation::AliasedShardingColumn {
+                            node: parent,
+                            column: col,
+                            alias: c,
+                        });
                     }
                 }
             }
         }
 
-        Ok(None)
+        Ok(violations)
+    }
+
+    /// Returns the order in which [`extend`] walks the nodes in the graph when computing replay
+    /// obligations: every (non-source, non-dropped) node in the graph, in reverse topological
+    /// order.
+    ///
+    /// [`extend`]: Materializations::extend
+    pub(in crate::controller) fn extend_order(graph: &Graph) -> Vec<NodeIndex> {
+        // unfortunately, we may end up adding indexes to existing views, and we need to walk
+        // them *all* in reverse topological order.
+        let mut ordered = topo_order(graph);
+        ordered.reverse();
+        ordered
+    }
+
+    /// Returns the orders in which [`commit`] would process nodes, given the set of `new` nodes
+    /// in the migration: first the list of new nodes to make, then the list of existing nodes to
+    /// add new indices to, both in topological order, with ties broken by [`NodeIndex`].
+    ///
+    /// Existing nodes only need new indices once [`extend`] has been called, so before that the
+    /// second list will always be empty.
+    ///
+    /// [`commit`]: Materializations::commit
+    /// [`extend`]: Materializations::extend
+    pub(in crate::controller) fn commit_order(
+        &self,
+        graph: &Graph,
+        new: &HashSet<NodeIndex>,
+    ) -> (Vec<NodeIndex>, Vec<NodeIndex>) {
+        let mut reindex = Vec::with_capacity(new.len());
+        let mut make = Vec::with_capacity(new.len());
+        for node in topo_order(graph) {
+            if new.contains(&node) {
+                make.push(node);
+            } else if self.added.contains_key(&node) {
+                reindex.push(node);
+            }
+        }
+        (make, reindex)
     }
 
     /// Commit to all materialization decisions since the last time `commit` was called.
     ///
     /// This includes setting up replay paths, adding new indices to existing materializations, and
-    /// populating new materializations.
-    #[allow(clippy::cognitive_complexity)]
+    /// populating new materializations. The messages required to do so are enqueued in `dmp`, to
+    /// be sent when it's applied.
+    ///
+    /// Once committed, a summary of the migration is logged. If `node_sizes` is provided, that
+    /// summary includes an estimate of the memory used by the new materializations.
     pub(in crate::controller) fn commit(
         &mut self,
         graph: &mut Graph,
         new: &HashSet<NodeIndex>,
         dmp: &mut DomainMigrationPlan,
+        node_sizes: Option<&HashMap<NodeIndex, NodeSize>>,
     ) -> Result<(), ReadySetError> {
-        let mut reindex = Vec::with_capacity(new.len());
-        let mut make = Vec::with_capacity(new.len());
-        let mut topo = petgraph::visit::Topo::new(&*graph);
-        while let Some(node) = topo.next(&*graph) {
-            if graph[node].is_source() {
-                continue;
-            }
-            if graph[node].is_dropped() {
-                continue;
-            }
+        let planning_started = self.planning_started.take().unwrap_or_else(Instant::now);
+        // no need to hold on to the paths between migrations
+        self.path_cache.clear();
+        let paths_before = self.total_paths();
+        let (made_partial, made_full): (Vec<_>, Vec<_>) = self
+            .added
+            .keys()
+            .filter(|ni| !self.had.contains(ni))
+            .partition(|ni| self.partial.contains(ni));
+        let (made_partial, made_full) = (made_partial.len(), made_full.len());
+        let new_readers = self.new_readers.len();
+        let estimated_bytes_added = node_sizes.map(|node_sizes| {
+            let sizes = self.estimate_materialized_bytes(graph, new, node_sizes);
+            new.iter().filter_map(|ni| sizes.get(ni)).sum::<u64>()
+        });
 
-            if new.contains(&node) {
-                make.push(node);
-            } else if self.added.contains_key(&node) {
-                reindex.push(node);
+        let messages = self.commit_messages(graph, new, dmp)?;
+        self.recorded_plan.clear();
+        if self.config.record_plan_path.is_some() {
+            match record::encode_messages(&messages) {
+                Ok(encoded) => self.recorded_plan = encoded,
+                Err(error) => warn!(%error, "Failed to record migration plan"),
             }
         }
+        dmp.add_messages(messages);
+
+        info!(
+            migration = self.migration_generator,
+            new_nodes = new.len(),
+            made_partial,
+            made_full,
+            new_readers,
+            new_replay_paths = self.total_paths().saturating_sub(paths_before),
+            planning_ms = planning_started.elapsed().as_millis(),
+            estimated_bytes_added,
+            "committed materializations"
+        );
+        Ok(())
+    }
+
+    /// Finish recording the domain requests enqueued by the last call to [`commit`] to
+    /// [`Config::record_plan_path`]. They're only written if `applied` is true, meaning the
+    /// migration they belong to was actually applied, rather than failing or being a dry run.
+    ///
+    /// [`commit`]: Materializations::commit
+    pub(in crate::controller) fn finish_recording(&mut self, applied: bool) {
+        let recorded = std::mem::take(&mut self.recorded_plan);
+        let Some(path) = &self.config.record_plan_path else {
+            return;
+        };
+        if !applied || recorded.is_empty() {
+            return;
+        }
+        if let Err(error) = record::append_messages(path, &recorded) {
+            warn!(%error, path = %path.display(), "Failed to record migration plan");
+        }
+    }
+
+    /// Like [`commit`], but rather than enqueueing messages in `dmp`, return the ordered list of
+    /// messages that committing would send to domains.
+    ///
+    /// Note that this still updates the materialization state (and the graph) as though the
+    /// messages had been sent.
+    ///
+    /// [`commit`]: Materializations::commit
+    #[allow(clippy::cognitive_complexity)]
+    pub(in crate::controller) fn commit_messages(
+        &mut self,
+        graph: &mut Graph,
+        new: &HashSet<NodeIndex>,
+        dmp: &DomainMigrationPlan,
+    ) -> ReadySetResult<Vec<StoredDomainRequest>> {
+        let mut scratch = dmp.scratch();
+        let dmp = &mut scratch;
+        self.migration_generator = self.migration_generator.wrapping_add(1);
+        let (make, reindex) = self.commit_order(graph, new);
+        trace!(
+            migration = self.migration_generator,
+            ?make,
+            ?reindex,
+            "committing materializations"
+        );
 
         // Track a set of nodes which we haven't already waited to be ready
         let mut non_ready_nodes = make
@@ -1107,46 +2691,27 @@ impl Materializations {
                     // correct in an elegant way and also creates smaller graphs with fewer
                     // materializations, but there might be some weirdness I'm not thinking of. But
                     // this also might just be impossible anyway, which makes this all moot.
-                    let mut stack: Vec<_> = graph
-                        .neighbors_directed(node, petgraph::EdgeDirection::Outgoing)
-                        .collect();
-                    while let Some(child) = stack.pop() {
-                        if new.contains(&child) {
-                            // NOTE: no need to check its children either
-                            continue;
-                        }
-
-                        if self.added.get(&child).map(|i| i.len()).unwrap_or(0)
-                            != self.have.get(&child).map(|i| i.len()).unwrap_or(0)
-                        {
-                            // node was previously materialized!
-                            eprintln!(
-                                "{}",
-                                Graphviz {
-                                    graph,
-                                    detailed: true,
-                                    node_sizes: None,
-                                    materializations: self,
-                                    domain_nodes: None,
-                                    reachable_from: None,
-                                }
-                            );
-                            error!(
-                                node = %node.index(),
-                                child = %child.index(),
-                                "attempting to make old non-materialized node with children partial"
-                            );
-                            internal!("attempting to make old non-materialized node ({:?}) with child ({:?}) partial", node.index(), child.index());
-                        }
-
-                        stack.extend(
-                            graph.neighbors_directed(child, petgraph::EdgeDirection::Outgoing),
+                    if let Some(child) = self.previously_materialized_descendant(graph, node, new) {
+                        eprintln!("{}", Graphviz::new(graph, self));
+                        error!(
+                            node = %node.index(),
+                            child = %child.index(),
+                            remediation = FULL_TO_PARTIAL_REMEDIATION,
+                            "attempting to make old non-materialized node with children partial"
                         );
+                        counter!(recorded::CONTROLLER_FULL_TO_PARTIAL_CONFLICTS).increment(1);
+                        return Err(ReadySetError::UnsupportedFullToPartial {
+                            node: node.index(),
+                            child: child.index(),
+                            remediation: FULL_TO_PARTIAL_REMEDIATION.to_owned(),
+                        });
                     }
                 }
 
                 debug!(
                     node = %node.index(),
+                    name = %Sensitive(&graph[node].name().display_unquoted()),
+                    domain = domain_of(&graph[node]),
                     cols = ?index_on,
                     "materializing existing non-materialized node"
                 );
@@ -1166,53 +2731,102 @@ impl Materializations {
             // This is somewhat wasteful in some (fully materialized) cases, but it's a lot easier
             // to reason about if all the replay decisions happen in the planner.
             {
-                let span = info_span!("reconstructing node", node = %node.index());
+                let span = info_span!(
+                    "reconstructing node",
+                    node = %node.index(),
+                    name = %Sensitive(&graph[node].name().display_unquoted()),
+                    domain = domain_of(&graph[node]),
+                );
                 let _guard = span.enter();
-                self.setup(node, &mut index_on, &mut non_ready_nodes, graph, dmp)?;
+                if let Some((domain, node)) =
+                    self.setup(node, &mut index_on, &mut non_ready_nodes, graph, dmp)?
+                {
+                    dmp.add_message(domain, DomainRequest::QueryReplayDone { node })?;
+                }
             }
             index_on.clear();
         }
 
-        // then, we start prepping new nodes
-        for ni in &make {
-            let n = &graph[*ni];
-            let mut index_on = self
-                .added
-                .remove(ni)
-                .map(|idxs| -> ReadySetResult<_> {
-                    invariant!(!idxs.is_empty());
-                    Ok(idxs)
-                })
-                .transpose()?
-                .unwrap_or_default();
-
-            let start = ::std::time::Instant::now();
-            self.ready_one(*ni, &mut index_on, &mut non_ready_nodes, graph, dmp)?;
-            let reconstructed = index_on.is_empty();
-
-            // communicate to the domain in charge of a particular node that it should start
-            // delivering updates to a given new node. note that we wait for the domain to
-            // acknowledge the change. this is important so that we don't ready a child in a
-            // different domain before the parent has been readied. it's also important to avoid us
-            // returning before the graph is actually fully operational.
-            trace!(node = %ni.index(), "readying node");
-            dmp.add_message(
-                n.domain(),
-                DomainRequest::Ready {
-                    node: n.local_addr(),
-                    purge: n.purge,
-                    index: index_on,
-                },
-            )?;
-            trace!(node = %ni.index(), "node ready");
+        // then, we start prepping new nodes. nodes in the same group don't depend on each other,
+        // so we start all of their replays before waiting for any of them to finish
+        for group in self.replay_groups(graph, &make) {
+            let mut replays = vec![];
+            let mut prepped = Vec::with_capacity(group.len());
+            for ni in group {
+                let mut index_on = self
+                    .added
+                    .remove(&ni)
+                    .map(|idxs| -> ReadySetResult<_> {
+                        invariant!(!idxs.is_empty());
+                        Ok(idxs)
+                    })
+                    .transpose()?
+                    .unwrap_or_default();
 
-            if reconstructed {
-                debug!(
-                    ms = %start.elapsed().as_millis(),
-                    node = %ni.index(),
-                    "reconstruction completed"
-                );
+                let start = Instant::now();
+                replays.extend(self.ready_one(
+                    ni,
+                    &mut index_on,
+                    &mut non_ready_nodes,
+                    graph,
+                    dmp,
+                )?);
+                prepped.push((ni, index_on, start));
+            }
+
+            // wait for the last domain of each replay to receive all the records
+            for (domain, node) in replays {
+                dmp.add_message(domain, DomainRequest::QueryReplayDone { node })?;
+            }
+
+            for (ni, index_on, start) in prepped {
+                let n = &graph[ni];
+                let reconstructed = index_on.is_empty();
+
+                // communicate to the domain in charge of a particular node that it should start
+                // delivering updates to a given new node. note that we wait for the domain to
+                // acknowledge the change. this is important so that we don't ready a child in a
+                // different domain before the parent has been readied. it's also important to
+                // avoid us returning before the graph is actually fully operational.
+                trace!(node = %ni.index(), "readying node");
+                dmp.add_message(
+                    n.domain(),
+                    DomainRequest::Ready {
+                        node: n.local_addr(),
+                        purge: n.purge,
+                        index: index_on,
+                        eviction_granularity: self.eviction_granularity(n),
+                    },
+                )?;
+                trace!(node = %ni.index(), "node ready");
+
+                if reconstructed {
+                    debug!(
+                        ms = %start.elapsed().as_millis(),
+                        node = %ni.index(),
+                        name = %Sensitive(&n.name().display_unquoted()),
+                        domain = domain_of(n),
+                        "reconstruction completed"
+                    );
+                }
+            }
+        }
+
+        for &ni in &make {
+            if !graph[ni].is_reader() {
+                continue;
             }
+            let (new_sources, existing_sources): (Vec<_>, Vec<_>) = self
+                .replay_sources_for(ni)
+                .into_iter()
+                .partition(|source| new.contains(source));
+            info!(
+                reader = %ni.index(),
+                name = %Sensitive(&graph[ni].name().display_unquoted()),
+                existing_sources = ?existing_sources.iter().map(|n| n.index()).collect::<Vec<_>>(),
+                new_sources = ?new_sources.iter().map(|n| n.index()).collect::<Vec<_>>(),
+                "new reader sources replays from materialized nodes"
+            );
         }
 
         // Wait for each of the nodes to be ready which we didn't already (eg because we wanted to
@@ -1221,14 +2835,86 @@ impl Materializations {
             dmp.add_message(domain, DomainRequest::IsReady { node })?;
         }
 
+        for &ni in self.have.keys().chain(&make) {
+            if self.have.contains_key(&ni) || graph[ni].is_reader() {
+                self.access_counters.register(ni);
+            }
+        }
+
         self.added.clear();
         self.new_readers.clear();
-        self.had.extend(self.have.keys().copied());
-        Ok(())
+        self.obligations.clear();
+        self.record_newly_materialized();
+        self.assert_invariants(graph);
+        if let Some((purged, materialized)) = self.purge_inconsistency(graph) {
+            error!(
+                purged = %purged.index(),
+                materialized = %materialized.index(),
+                "node beyond the materialization frontier is upstream of a materialization which \
+                 isn't; evictions from it may be missed by the materialization below"
+            );
+        }
+        gauge!(recorded::CONTROLLER_REPLAY_PATHS).set(self.total_paths() as f64);
+        gauge!(recorded::CONTROLLER_INDICES).set(self.index_count() as f64);
+        Ok(scratch.into_messages())
+    }
+
+    /// Record every node in `have` that wasn't in `had` as newly materialized, then bring `had` up
+    /// to date with `have`
+    fn record_newly_materialized(&mut self) {
+        self.newly_materialized = self
+            .have
+            .keys()
+            .filter(|ni| !self.had.contains(ni))
+            .copied()
+            .collect();
+        self.had.extend(self.newly_materialized.iter().copied());
+    }
+
+    /// Split the new nodes in `make`, which must be in the order returned by [`commit_order`],
+    /// into groups whose replays can run at the same time.
+    ///
+    /// No node in a group is an ancestor of another node in the same group, and each group
+    /// contains at most [`replay_concurrency`] nodes which might need to be reconstructed. Groups
+    /// are returned in the order they must be processed in.
+    ///
+    /// [`commit_order`]: Materializations::commit_order
+    /// [`replay_concurrency`]: Config::replay_concurrency
+    fn replay_groups(&self, graph: &Graph, make: &[NodeIndex]) -> Vec<Vec<NodeIndex>> {
+        let limit = self.config.replay_concurrency;
+        if limit <= 1 {
+            return make.iter().map(|&ni| vec![ni]).collect();
+        }
+
+        let mut groups = vec![];
+        let mut group: Vec<NodeIndex> = vec![];
+        let mut replaying = 0;
+        for &ni in make {
+            let may_replay = self.added.contains_key(&ni) || graph[ni].is_reader();
+            let depends_on_group = !group.is_empty() && {
+                let ancestors = reachable(graph, ni, petgraph::EdgeDirection::Incoming);
+                group.iter().any(|n| ancestors.contains(n))
+            };
+            if depends_on_group || (may_replay && replaying == limit) {
+                groups.push(std::mem::take(&mut group));
+                replaying = 0;
+            }
+            if may_replay {
+                replaying += 1;
+            }
+            group.push(ni);
+        }
+        if !group.is_empty() {
+            groups.push(group);
+        }
+        groups
     }
 
     /// Perform all operations necessary to bring any materializations for the given node up, and
     /// then mark that node as ready to receive updates.
+    ///
+    /// If the node has to be reconstructed via replay, returns the domain and local address of the
+    /// node, for the caller to wait for the replay to complete.
     fn ready_one(
         &mut self,
         ni: NodeIndex,
@@ -1236,7 +2922,7 @@ impl Materializations {
         non_ready_nodes: &mut HashSet<(DomainIndex, LocalNodeIndex)>,
         graph: &Graph,
         dmp: &mut DomainMigrationPlan,
-    ) -> Result<(), ReadySetError> {
+    ) -> ReadySetResult<Option<(DomainIndex, LocalNodeIndex)>> {
         let n = &graph[ni];
         let mut has_state = !index_on.is_empty();
 
@@ -1254,7 +2940,7 @@ impl Materializations {
             // a new base must be empty, so we can materialize it immediately
             debug!(node = %ni.index(), "no need to replay empty new base");
             assert!(!self.partial.contains(&ni));
-            return Ok(());
+            return Ok(None);
         }
 
         // if this node doesn't need to be materialized, then we're done.
@@ -1267,25 +2953,34 @@ impl Materializations {
 
         if !has_state {
             debug!(node = %ni.index(), "no need to replay non-materialized view");
-            return Ok(());
+            return Ok(None);
         }
 
         // we have a parent that has data, so we need to replay and reconstruct
-        {
-            let span = info_span!("reconstructing node", node = %ni.index());
+        let replay = {
+            let span = info_span!(
+                "reconstructing node",
+                node = %ni.index(),
+                name = %Sensitive(&n.name().display_unquoted()),
+                domain = domain_of(n),
+            );
             let _guard = span.enter();
             debug!(node = %ni.index(), "beginning reconstruction");
-            self.setup(ni, index_on, non_ready_nodes, graph, dmp)?;
-        }
+            self.setup(ni, index_on, non_ready_nodes, graph, dmp)?
+        };
 
         // NOTE: the state has already been marked ready by the replay completing, but we want to
         // wait for the domain to finish replay, which the ready executed by the outer commit()
         // loop does.
         index_on.clear();
-        Ok(())
+        Ok(replay)
     }
 
     /// Reconstruct the materialized state required by the given (new) node through replay.
+    ///
+    /// This only starts the replays; if there are any, returns the domain and local address of
+    /// the node, for the caller to send a [`DomainRequest::QueryReplayDone`] to wait for them to
+    /// complete.
     fn setup(
         &mut self,
         ni: NodeIndex,
@@ -1293,7 +2988,7 @@ impl Materializations {
         non_ready_nodes: &mut HashSet<(DomainIndex, LocalNodeIndex)>,
         graph: &Graph,
         dmp: &mut DomainMigrationPlan,
-    ) -> Result<(), ReadySetError> {
+    ) -> ReadySetResult<Option<(DomainIndex, LocalNodeIndex)>> {
         if index_on.is_empty() {
             // we must be reconstructing a Reader.
             // figure out what key that Reader is using
@@ -1315,11 +3010,11 @@ impl Materializations {
             }
             plan.finalize()?
         };
-        // grr `HashMap` doesn't implement `IndexMut`
-        self.paths.entry(ni).or_default().extend(paths);
+        self.add_paths(ni, paths)?;
 
         if pending.is_empty() {
             trace!("No replays to do");
+            Ok(None)
         } else {
             trace!("all domains ready for replay");
             // prepare for, start, and wait for replays
@@ -1351,20 +3046,14 @@ impl Materializations {
                     },
                 )?;
             }
-            // and then wait for the last domain to receive all the records
+            // and then have the caller wait for the last domain to receive all the records
             let target = graph[ni].domain();
             debug!(
                domain = %target.index(),
                "waiting for done message from target"
             );
-            dmp.add_message(
-                target,
-                DomainRequest::QueryReplayDone {
-                    node: graph[ni].local_addr(),
-                },
-            )?;
+            Ok(Some((target, graph[ni].local_addr())))
         }
-        Ok(())
     }
 
     /// Returns a (`NodeIndex`, `Tag`) pair for each index in a partially materialized node.
@@ -1383,3 +3072,1039 @@ impl Materializations {
             .collect()
     }
 }
+
+/// Construct a graph with `len` base tables joined together in a left-deep chain, returning the
+/// graph and all of its nodes
+#[cfg(any(test, feature = "bench"))]
+pub(super) fn join_chain(len: usize) -> (Graph, Vec<NodeIndex>) {
+    let mut graph = Graph::new();
+    let mut nodes = Vec::with_capacity(len * 2);
+    let mut add_base = |graph: &mut Graph, i: usize| {
+        let ni = graph.add_node(dataflow::node::Node::new(
+            format!("base_{i}").as_str(),
+            dataflow::utils::make_columns(&["a", "b"]),
+            dataflow::node::special::Base::default(),
+        ));
+        nodes.push(ni);
+        ni
+    };
+
+    let mut left = add_base(&mut graph, 0);
+    let mut joins = Vec::with_capacity(len);
+    for i in 1..len {
+        let right = add_base(&mut graph, i);
+        let join = graph.add_node(dataflow::node::Node::new(
+            format!("join_{i}").as_str(),
+            dataflow::utils::make_columns(&["a", "b", "c"]),
+            dataflow::ops::NodeOperator::Join(dataflow::ops::join::Join::new(
+                left,
+                right,
+                dataflow::ops::join::JoinType::Inner,
+                vec![(1, 0)],
+                vec![
+                    (dataflow::ops::Side::Left, 0),
+                    (dataflow::ops::Side::Left, 1),
+                    (dataflow::ops::Side::Right, 1),
+                ],
+            )),
+        ));
+        graph.add_edge(left, join, ());
+        graph.add_edge(right, join, ());
+        joins.push(join);
+        left = join;
+    }
+
+    nodes.extend(joins);
+    (graph, nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use dataflow::utils::make_columns;
+    use dataflow::{node, ops, Expr};
+    use readyset_client::debug::info::NodeMaterializedSize;
+    use readyset_data::DfType;
+
+    use super::*;
+
+    /// Construct a graph with a base table with two columns, and a projection node below it which
+    /// projects the given columns of the base table
+    pub(super) fn projection(columns: &[usize]) -> (Graph, NodeIndex, NodeIndex) {
+        let mut g = petgraph::Graph::new();
+        let src = g.add_node(node::Node::new(
+            "source",
+            make_columns(&[""]),
+            node::special::Source,
+        ));
+        let base = g.add_node(node::Node::new(
+            "base",
+            make_columns(&["a", "b"]),
+            node::special::Base::default(),
+        ));
+        g.add_edge(src, base, ());
+        let project = g.add_node(node::Node::new(
+            "project",
+            make_columns(&vec![""; columns.len()]),
+            ops::NodeOperator::Project(ops::project::Project::new(
+                base,
+                columns
+                    .iter()
+                    .map(|&index| Expr::Column {
+                        index,
+                        ty: DfType::Unknown,
+                    })
+                    .collect(),
+            )),
+        ));
+        g.add_edge(base, project, ());
+        (g, base, project)
+    }
+
+    #[test]
+    fn map_lookup_indices_through_reordering_projection() {
+        let (g, base, project) = projection(&[1, 0]);
+        let res = map_lookup_indices(
+            &g,
+            project,
+            base,
+            &HashSet::from([
+                LookupIndex::Strict(Index::hash_map(vec![0])),
+                LookupIndex::Weak(Index::hash_map(vec![1, 0])),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            HashSet::from([
+                LookupIndex::Strict(Index::hash_map(vec![1])),
+                LookupIndex::Weak(Index::hash_map(vec![0, 1])),
+            ])
+        );
+    }
+
+    #[test]
+    fn map_lookup_indices_onto_dropped_column() {
+        let (mut g, base, project) = projection(&[1, 0]);
+        let base_node = g[base].get_base_mut().unwrap();
+        *base_node = node::special::Base::new().with_default_values(vec![DfValue::None; 2]);
+        base_node.drop_column(1).unwrap();
+
+        // column 1 of the projection is column 0 of the base table, which still exists
+        let lookup = |col| HashSet::from([LookupIndex::Strict(Index::hash_map(vec![col]))]);
+        map_lookup_indices(&g, project, base, &lookup(1)).unwrap();
+
+        // but column 0 of the projection is the dropped column 1 of the base table
+        let err = map_lookup_indices(&g, project, base, &lookup(0)).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ReadySetError::LookupOnDroppedColumn {
+                    node,
+                    column: 1,
+                    ..
+                } if node == project.index()
+            ),
+            "{err}"
+        );
+        assert!(err.to_string().contains("base table base"), "{err}");
+    }
+
+    #[test]
+    fn validate_purge_above_non_purge() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        g[base].purge = true;
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        m.assert_invariants(&g);
+
+        let err = m
+            .validate(&g, &HashSet::from([base, project]))
+            .err()
+            .unwrap();
+        assert!(err.is_materialization_frontier_violation());
+        assert!(matches!(
+            err,
+            ReadySetError::MaterializationFrontierViolation {
+                node,
+                is_reader: false,
+                ..
+            } if node == base.index()
+        ));
+    }
+
+    #[test]
+    fn purge_inconsistency_across_whole_graph() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        assert_eq!(m.purge_inconsistency(&g), None);
+
+        // a purged node left over from an earlier migration, above a materialization which isn't
+        // part of the current one
+        g[base].purge = true;
+        assert!(m.purge_violations(&g, &HashSet::new()).is_empty());
+        assert_eq!(m.purge_inconsistency(&g), Some((base, project)));
+
+        g[project].purge = true;
+        assert_eq!(m.purge_inconsistency(&g), None);
+    }
+
+    #[test]
+    fn partial_chain_depths() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let below = g.add_node(g[project].duplicate());
+        g.add_edge(project, below, ());
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(below, HashSet::from([Index::hash_map(vec![0])]));
+        m.partial.insert(below);
+        // the unmaterialized projection in between doesn't count towards the depth
+        assert_eq!(m.partial_chain_depths(&g), HashMap::from([(below, 1)]));
+
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        m.partial.insert(project);
+        assert_eq!(
+            m.partial_chain_depths(&g),
+            HashMap::from([(project, 1), (below, 2)])
+        );
+
+        // a full materialization in between ends the chain
+        m.partial.remove(&project);
+        assert_eq!(m.partial_chain_depths(&g), HashMap::from([(below, 1)]));
+    }
+
+    #[test]
+    fn pinned_nodes_stay_before_frontier() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let new = HashSet::from([base, project]);
+
+        let mut m = Materializations::new();
+        m.config.frontier_strategy = FrontierStrategy::AllPartial;
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        m.partial.insert(project);
+        m.assert_invariants(&g);
+
+        m.place_frontier(&mut g, &new).unwrap();
+        assert!(g[project].purge);
+        assert_eq!(m.shallow_nodes(&g), HashSet::from([project]));
+
+        g[project].purge = false;
+        m.config.pinned_nodes = HashSet::from([project]);
+        m.place_frontier(&mut g, &new).unwrap();
+        assert!(!g[project].purge);
+        assert!(m.shallow_nodes(&g).is_empty());
+    }
+
+    #[test]
+    fn strict_frontier_purged_node_without_state() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let new = HashSet::from([base, project]);
+        g[project].purge = true;
+
+        let mut m = Materializations::new();
+        m.place_frontier(&mut g, &new).unwrap();
+
+        m.config.strict_frontier = true;
+        let err = m.place_frontier(&mut g, &new).unwrap_err();
+        assert!(err.is_unsupported());
+    }
+
+    #[test]
+    fn weak_only_nodes_beyond_frontier() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let new = HashSet::from([base, project]);
+
+        // `project` is only materialized to serve weak lookups
+        let mut m = Materializations::new();
+        m.config.frontier_strategy = FrontierStrategy::AllPartial;
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        for node_indices in [&mut m.have, &mut m.added, &mut m.added_weak] {
+            node_indices.insert(project, HashSet::from([Index::hash_map(vec![1])]));
+        }
+        m.assert_invariants(&g);
+
+        // A weak-only node whose paired strict index is full can't have its state purged, since
+        // nothing could replay it back in
+        m.place_frontier(&mut g, &new).unwrap();
+        assert!(!g[project].purge);
+        assert!(!g[base].purge);
+
+        // But once the strict index is partial, it's placed beyond the frontier like any other
+        // partial materialization
+        m.partial.insert(project);
+        m.place_frontier(&mut g, &new).unwrap();
+        assert!(g[project].purge);
+        assert!(!g[base].purge);
+        assert_eq!(m.shallow_nodes(&g), HashSet::from([project]));
+    }
+
+    #[test]
+    fn simulate_frontier_without_changing_graph() {
+        let (g, base, project) = projection(&[0, 1]);
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        m.partial.insert(project);
+        let node_sizes = HashMap::from([
+            (
+                base,
+                NodeSize {
+                    bytes: NodeMaterializedSize(100),
+                    ..Default::default()
+                },
+            ),
+            (
+                project,
+                NodeSize {
+                    bytes: NodeMaterializedSize(42),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        assert_eq!(
+            m.simulate_frontier(&g, FrontierStrategy::None, &node_sizes),
+            FrontierSimulation::default()
+        );
+        assert_eq!(
+            m.simulate_frontier(&g, FrontierStrategy::Readers, &node_sizes),
+            FrontierSimulation::default()
+        );
+        assert_eq!(
+            m.simulate_frontier(&g, FrontierStrategy::AllPartial, &node_sizes),
+            FrontierSimulation {
+                purged_nodes: vec![project],
+                evictable_bytes: 42,
+            }
+        );
+        assert!(!g[project].purge);
+
+        m.config.pinned_nodes = HashSet::from([project]);
+        assert_eq!(
+            m.simulate_frontier(&g, FrontierStrategy::AllPartial, &node_sizes),
+            FrontierSimulation::default()
+        );
+    }
+
+    #[test]
+    fn force_full_by_name_prefix() {
+        let (g, base, project) = projection(&[0, 1]);
+
+        let mut m = Materializations::new();
+        m.config.force_full = HashSet::from(["proj".to_owned()]);
+        assert!(!m.forced_full(&g[project]));
+
+        m.config.allow_full_materialization = true;
+        assert!(m.forced_full(&g[project]));
+        assert!(!m.forced_full(&g[base]));
+    }
+
+    #[test]
+    fn previously_materialized_descendant() {
+        let (g, base, project) = projection(&[0]);
+        let mut m = Materializations::new();
+        assert_eq!(
+            m.previously_materialized_descendant(&g, base, &HashSet::new()),
+            None
+        );
+
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        assert_eq!(
+            m.previously_materialized_descendant(&g, base, &HashSet::new()),
+            Some(project)
+        );
+        assert_eq!(
+            m.previously_materialized_descendant(&g, base, &HashSet::from([project])),
+            None
+        );
+
+        // a node whose indices were all added in this migration is only now becoming
+        // materialized
+        m.added
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        assert_eq!(
+            m.previously_materialized_descendant(&g, base, &HashSet::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn eviction_granularity_by_name_prefix() {
+        let (mut g, base, project) = projection(&[0, 1]);
+
+        let mut m = Materializations::new();
+        assert_eq!(
+            m.eviction_granularity(&g[project]),
+            EvictionGranularity::Key
+        );
+
+        m.config.range_eviction = HashSet::from(["proj".to_owned()]);
+        assert_eq!(
+            m.eviction_granularity(&g[project]),
+            EvictionGranularity::Range
+        );
+        assert_eq!(m.eviction_granularity(&g[base]), EvictionGranularity::Key);
+
+        let hinted = g.add_node(node::Node::new(
+            "RANGE_EVICT_view",
+            make_columns(&["a"]),
+            node::special::Base::default(),
+        ));
+        assert_eq!(
+            m.eviction_granularity(&g[hinted]),
+            EvictionGranularity::Range
+        );
+    }
+
+    #[test]
+    fn replay_paths_for_node() {
+        let (a, b, c) = (NodeIndex::new(1), NodeIndex::new(2), NodeIndex::new(3));
+        let mut m = Materializations::new();
+        m.paths.insert(
+            c,
+            BiHashMap::from_iter([
+                (Tag::new(7), (Index::hash_map(vec![1]), vec![b, c])),
+                (Tag::new(2), (Index::hash_map(vec![0]), vec![a, b, c])),
+            ]),
+        );
+
+        assert_eq!(
+            m.replay_paths_for(c),
+            vec![
+                (Tag::new(2), Index::hash_map(vec![0]), vec![a, b, c]),
+                (Tag::new(7), Index::hash_map(vec![1]), vec![b, c]),
+            ]
+        );
+        assert!(m.replay_paths_for(a).is_empty());
+    }
+
+    #[test]
+    fn replay_sources_for_node() {
+        let (a, b, c) = (NodeIndex::new(1), NodeIndex::new(2), NodeIndex::new(3));
+        let mut m = Materializations::new();
+        m.paths.insert(
+            c,
+            BiHashMap::from_iter([
+                (Tag::new(7), (Index::hash_map(vec![1]), vec![b, c])),
+                (Tag::new(2), (Index::hash_map(vec![0]), vec![a, b, c])),
+                (Tag::new(3), (Index::hash_map(vec![2]), vec![a, c])),
+            ]),
+        );
+
+        assert_eq!(m.replay_sources_for(c), vec![a, b]);
+        assert!(m.replay_sources_for(a).is_empty());
+    }
+
+    #[test]
+    fn leftover_replay_obligations() {
+        let (g, base, project) = projection(&[0, 1]);
+        check_obligations_satisfied(&g, &HashMap::new()).unwrap();
+
+        let obligations = HashMap::from([
+            (project, HashSet::from([Index::hash_map(vec![1])])),
+            (
+                base,
+                HashSet::from([Index::hash_map(vec![0]), Index::btree_map(vec![1])]),
+            ),
+        ]);
+        let err = check_obligations_satisfied(&g, &obligations)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(&format!(
+                "{} / base: hash[0], btree[1]; {} / project: hash[1]",
+                base.index(),
+                project.index()
+            )),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn frontier_strategy_display_from_str_round_trip() {
+        for strategy in <FrontierStrategy as clap::ValueEnum>::value_variants() {
+            assert_eq!(
+                strategy.to_string().parse::<FrontierStrategy>().unwrap(),
+                *strategy
+            );
+        }
+        assert_eq!(
+            "All-Partial".parse::<FrontierStrategy>().unwrap(),
+            FrontierStrategy::AllPartial
+        );
+        assert!(matches!(
+            "everything".parse::<FrontierStrategy>(),
+            Err(ReadySetError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn partial_overlap_of_multi_column_indices() {
+        assert_eq!(
+            partial_overlap(&Index::hash_map(vec![0]), &Index::hash_map(vec![0, 1])),
+            Some(1)
+        );
+        assert_eq!(
+            partial_overlap(&Index::hash_map(vec![0, 2]), &Index::hash_map(vec![0, 1])),
+            Some(2)
+        );
+        // Same columns in a different order
+        assert_eq!(
+            partial_overlap(&Index::hash_map(vec![1, 0]), &Index::hash_map(vec![0, 1])),
+            None
+        );
+        // No shared columns
+        assert_eq!(
+            partial_overlap(&Index::hash_map(vec![2]), &Index::hash_map(vec![0, 1])),
+            None
+        );
+        // Different index types
+        assert_eq!(
+            partial_overlap(&Index::btree_map(vec![0]), &Index::hash_map(vec![0, 1])),
+            None
+        );
+    }
+
+    #[test]
+    fn overlapping_multi_column_partial_indices() {
+        // The projection's columns are the base table's columns in reverse, so the projection's
+        // index on [0, 1] traces to an index on [1, 0] in the base table
+        let (g, base, project) = projection(&[1, 0]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![1])]));
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0, 1])]));
+        // Bases can't really be partial, but this lets us construct the case where a partial
+        // parent is indexed on only some of the columns of its partial child's index
+        m.partial.extend([base, project]);
+
+        assert_eq!(
+            m.overlapping_partial_index_violations(&g, &m.have).unwrap(),
+            vec![MaterializationViolation::OverlappingPartialIndices {
+                parent: base,
+                parent_index: Index::hash_map(vec![1]),
+                child: project,
+                child_index: Index::hash_map(vec![1, 0]),
+            }]
+        );
+
+        // Once the parent also has an index on exactly the child's (traced) columns, the overlap
+        // is safe
+        m.have
+            .get_mut(&base)
+            .unwrap()
+            .insert(Index::hash_map(vec![1, 0]));
+        assert!(m
+            .overlapping_partial_index_violations(&g, &m.have)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reuse_tags_for_readded_indices() {
+        let (_, base, project) = projection(&[0, 1]);
+        let index = Index::hash_map(vec![0]);
+        let path = |index: &Index| {
+            RawReplayPath::from_replay(vec1::vec1![
+                IndexRef {
+                    node: base,
+                    index: Some(index.clone()),
+                },
+                IndexRef {
+                    node: project,
+                    index: Some(index.clone()),
+                },
+            ])
+        };
+
+        let mut m = Materializations::new();
+        m.have.insert(project, HashSet::from([index.clone()]));
+        let tag = m.tag_for_path(&index, &path(&index));
+        m.add_paths(project, [(tag, (index.clone(), vec![base, project]))])
+            .unwrap();
+
+        // Drop the index, then add it back again in a later migration
+        m.have.remove(&project);
+        m.migration_generator += 1;
+        m.have.insert(project, HashSet::from([index.clone()]));
+        let readded_tag = m.tag_for_path(&index, &path(&index));
+        assert_eq!(readded_tag, tag);
+        assert_eq!(m.tag_migration(readded_tag), Some(0));
+        m.add_paths(
+            project,
+            [(readded_tag, (index.clone(), vec![base, project]))],
+        )
+        .unwrap();
+        assert_eq!(m.total_paths(), 1);
+
+        // A different index gets a new tag
+        let other_index = Index::hash_map(vec![1]);
+        assert_ne!(m.tag_for_path(&other_index, &path(&other_index)), tag);
+
+        // Setting up the same path again under a different tag would leak the old tag
+        m.add_paths(project, [(Tag::new(999), (index, vec![base, project]))])
+            .unwrap_err();
+        assert_eq!(m.total_paths(), 1);
+    }
+
+    #[test]
+    fn total_paths() {
+        let (a, b, c) = (NodeIndex::new(1), NodeIndex::new(2), NodeIndex::new(3));
+        let mut m = Materializations::new();
+        assert_eq!(m.total_paths(), 0);
+
+        m.paths.insert(
+            c,
+            BiHashMap::from_iter([
+                (Tag::new(7), (Index::hash_map(vec![1]), vec![b, c])),
+                (Tag::new(2), (Index::hash_map(vec![0]), vec![a, b, c])),
+            ]),
+        );
+        m.paths.insert(
+            b,
+            BiHashMap::from_iter([(Tag::new(1), (Index::hash_map(vec![0]), vec![a, b]))]),
+        );
+        assert_eq!(m.total_paths(), 3);
+    }
+
+    #[test]
+    fn index_count() {
+        let (a, b) = (NodeIndex::new(1), NodeIndex::new(2));
+        let mut m = Materializations::new();
+        assert_eq!(m.index_count(), 0);
+
+        m.have.insert(
+            a,
+            HashSet::from([Index::hash_map(vec![0]), Index::hash_map(vec![1])]),
+        );
+        m.have.insert(b, HashSet::from([Index::btree_map(vec![0])]));
+        assert_eq!(m.index_count(), 3);
+    }
+
+    #[test]
+    fn remove_dropped_nodes() {
+        let (mut g, base, project) = projection(&[0]);
+        g[base].add_to(DomainIndex::from(0));
+        g[project].add_to(DomainIndex::from(1));
+        let duplicate = g[project].duplicate();
+        let duplicate = g.add_node(duplicate);
+
+        let mut m = Materializations::new();
+        m.paths.insert(
+            project,
+            BiHashMap::from_iter([
+                (Tag::new(2), (Index::hash_map(vec![0]), vec![base, project])),
+                (Tag::new(1), (Index::hash_map(vec![1]), vec![base, project])),
+            ]),
+        );
+        m.paths.insert(
+            base,
+            BiHashMap::from_iter([(Tag::new(3), (Index::hash_map(vec![0]), vec![base]))]),
+        );
+        m.redundant_partial.insert(project, duplicate);
+        m.record_kind(base, MaterializationKind::OwnState);
+        m.record_kind(project, MaterializationKind::Lookup);
+
+        // nothing has been dropped yet
+        assert!(m.remove_dropped(&g).is_empty());
+        assert_eq!(m.total_paths(), 3);
+        assert_eq!(m.get_kind(project), Some(MaterializationKind::Lookup));
+
+        g[project].remove();
+        let removed = m.remove_dropped(&g);
+        assert_eq!(
+            removed,
+            HashMap::from([
+                (DomainIndex::from(0), vec![Tag::new(1), Tag::new(2)]),
+                (DomainIndex::from(1), vec![Tag::new(1), Tag::new(2)]),
+            ])
+        );
+        assert!(!m.paths.contains_key(&project));
+        assert_eq!(m.total_paths(), 1);
+        assert!(m.redundant_partial.is_empty());
+        assert_eq!(m.get_kind(project), None);
+        assert_eq!(m.get_kind(base), Some(MaterializationKind::OwnState));
+    }
+
+    #[test]
+    fn obligations_recomputed_when_parents_change() {
+        let (mut g, left, _) = projection(&[0, 1]);
+        let mut add_base = |name| {
+            g.add_node(node::Node::new(
+                name,
+                make_columns(&["c", "d"]),
+                node::special::Base::default(),
+            ))
+        };
+        let right = add_base("right");
+        let other_right = add_base("other_right");
+        let join = g.add_node(node::Node::new(
+            "join",
+            make_columns(&["a", "b", "d"]),
+            ops::NodeOperator::Join(ops::join::Join::new(
+                left,
+                right,
+                ops::join::JoinType::Inner,
+                vec![(1, 0)],
+                vec![
+                    (ops::Side::Left, 0),
+                    (ops::Side::Left, 1),
+                    (ops::Side::Right, 1),
+                ],
+            )),
+        ));
+        g.add_edge(left, join, ());
+        let right_edge = g.add_edge(right, join, ());
+
+        let mut m = Materializations::new();
+        let targets = |obligations: Vec<(NodeIndex, IndexObligation)>| {
+            obligations
+                .into_iter()
+                .map(|(ni, _)| ni)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(
+            targets(m.obligations_for(&g, join)),
+            HashSet::from([left, right])
+        );
+        // cached, since nothing has changed
+        assert_eq!(
+            targets(m.obligations_for(&g, join)),
+            HashSet::from([left, right])
+        );
+
+        // reroute the join to a different parent, like the planning loop does when it finds a
+        // fully materialized node below a partial one
+        g[join].replace_sibling(right, other_right);
+        g.add_edge(other_right, join, ());
+        g.remove_edge(right_edge);
+        assert_eq!(
+            targets(m.obligations_for(&g, join)),
+            HashSet::from([left, other_right])
+        );
+    }
+
+    #[test]
+    fn next_tag_records_creating_migration() {
+        let mut m = Materializations::new();
+        let first = m.next_tag();
+        m.migration_generator = 7;
+        let second = m.next_tag();
+
+        // Tags stay a plain sequence, regardless of which migration created them
+        assert_eq!(first, Tag::new(1));
+        assert_eq!(second, Tag::new(2));
+        assert_eq!(m.tag_migration(first), Some(0));
+        assert_eq!(m.tag_migration(second), Some(7));
+        assert_eq!(m.tag_migration(Tag::new(3)), None);
+    }
+
+    #[test]
+    fn record_most_inherent_kind() {
+        let (a, b) = (NodeIndex::new(1), NodeIndex::new(2));
+        let mut m = Materializations::new();
+        assert_eq!(m.get_kind(a), None);
+
+        m.record_kind(a, MaterializationKind::Replay);
+        m.record_kind(a, MaterializationKind::Lookup);
+        assert_eq!(m.get_kind(a), Some(MaterializationKind::Lookup));
+
+        m.record_kind(b, MaterializationKind::OwnState);
+        m.record_kind(b, MaterializationKind::Replay);
+        assert_eq!(m.get_kind(b), Some(MaterializationKind::OwnState));
+    }
+
+    #[test]
+    fn fallback_base_index_prefers_unique_keys() {
+        let mut config = Config::default();
+        let base = |base: node::special::Base| {
+            node::Node::new("base", make_columns(&["a", "b", "c"]), base)
+        };
+
+        let no_keys = base(node::special::Base::default());
+        assert_eq!(
+            fallback_base_index(&no_keys, &config),
+            Index::hash_map(vec![0])
+        );
+
+        config.default_base_index = Some(Index::btree_map(vec![2]));
+        assert_eq!(
+            fallback_base_index(&no_keys, &config),
+            Index::btree_map(vec![2])
+        );
+
+        config.default_base_index = Some(Index::btree_map(vec![3]));
+        assert_eq!(
+            fallback_base_index(&no_keys, &config),
+            Index::hash_map(vec![0])
+        );
+
+        let unique = base(node::special::Base::default().with_unique_keys([[1, 2]]));
+        assert_eq!(
+            fallback_base_index(&unique, &config),
+            Index::hash_map(vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn record_newly_materialized_nodes() {
+        let (_, base, project) = projection(&[0]);
+        let index = Index::hash_map(vec![0]);
+        let mut m = Materializations::new();
+
+        m.have.insert(base, HashSet::from([index.clone()]));
+        m.record_newly_materialized();
+        assert_eq!(m.newly_materialized(), &HashSet::from([base]));
+
+        // Adding an index to an existing materialization doesn't make it newly materialized
+        m.have
+            .get_mut(&base)
+            .unwrap()
+            .insert(Index::hash_map(vec![1]));
+        m.have.insert(project, HashSet::from([index]));
+        m.record_newly_materialized();
+        assert_eq!(m.newly_materialized(), &HashSet::from([project]));
+
+        m.record_newly_materialized();
+        assert!(m.newly_materialized().is_empty());
+        assert_eq!(m.had, HashSet::from([base, project]));
+    }
+
+    #[test]
+    fn replay_groups_of_independent_nodes() {
+        let (mut g, base, project) = projection(&[0]);
+        let sibling = g.add_node(g[project].duplicate());
+        g.add_edge(base, sibling, ());
+        let other_sibling = g.add_node(g[project].duplicate());
+        g.add_edge(base, other_sibling, ());
+        let child = g.add_node(g[project].duplicate());
+        g.add_edge(project, child, ());
+        let index = HashSet::from([Index::hash_map(vec![0])]);
+
+        let mut m = Materializations::new();
+        for ni in [project, sibling, other_sibling, child] {
+            m.added.insert(ni, index.clone());
+        }
+        let make = [project, sibling, child, other_sibling];
+        assert_eq!(
+            m.replay_groups(&g, &make),
+            make.iter().map(|&ni| vec![ni]).collect::<Vec<_>>()
+        );
+
+        // the child has to wait for its parent's replay, and the limit caps each group
+        m.config.replay_concurrency = 2;
+        assert_eq!(
+            m.replay_groups(&g, &make),
+            vec![vec![project, sibling], vec![child, other_sibling]]
+        );
+        m.config.replay_concurrency = 4;
+        assert_eq!(
+            m.replay_groups(&g, &[project, sibling, other_sibling, child]),
+            vec![vec![project, sibling, other_sibling], vec![child]]
+        );
+    }
+
+    #[test]
+    fn processing_order() {
+        let (g, base, project) = projection(&[1, 0]);
+        assert_eq!(Materializations::extend_order(&g), vec![project, base]);
+
+        let mut m = Materializations::new();
+        assert_eq!(
+            m.commit_order(&g, &HashSet::from([project])),
+            (vec![project], vec![])
+        );
+        m.added
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        assert_eq!(
+            m.commit_order(&g, &HashSet::from([project])),
+            (vec![project], vec![base])
+        );
+    }
+
+    #[test]
+    fn processing_order_is_deterministic() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let add_project = |g: &mut Graph, name: &str| {
+            g.add_node(node::Node::new(
+                name,
+                make_columns(&["a"]),
+                ops::NodeOperator::Project(ops::project::Project::new(
+                    base,
+                    vec![Expr::Column {
+                        index: 0,
+                        ty: DfType::Unknown,
+                    }],
+                )),
+            ))
+        };
+        // `below` has a lower index than its parent `above`, so ordering purely by index would
+        // visit it too early
+        let below = add_project(&mut g, "below");
+        let above = add_project(&mut g, "above");
+        g.add_edge(base, above, ());
+        g.add_edge(above, below, ());
+
+        let new = HashSet::from([project, above, below]);
+        let m = Materializations::new();
+        let expected = (vec![project, above, below], vec![]);
+        for _ in 0..10 {
+            assert_eq!(m.commit_order(&g, &new), expected);
+            assert_eq!(
+                Materializations::extend_order(&g),
+                vec![below, above, project, base]
+            );
+        }
+    }
+
+    #[test]
+    fn unpaired_weak_indices() {
+        // the join looks up into weak indices on the join columns of both of its parents
+        let (g, nodes) = join_chain(2);
+        let [left, right, join] = nodes[..] else {
+            panic!("expected two bases and a join");
+        };
+        let nodes = HashSet::from([left, right, join]);
+
+        // nothing has been added since the last commit, so the weak indices have to be found from
+        // the committed materializations alone
+        let mut m = Materializations::new();
+        m.have.insert(
+            left,
+            HashSet::from([Index::hash_map(vec![0]), Index::hash_map(vec![1])]),
+        );
+        m.have
+            .insert(right, HashSet::from([Index::hash_map(vec![0])]));
+        assert!(m.added_weak.is_empty());
+        assert!(m
+            .unpaired_weak_index_violations(&g, &nodes)
+            .unwrap()
+            .is_empty());
+
+        // drop the strict index that the left weak index is paired with
+        m.have
+            .get_mut(&left)
+            .unwrap()
+            .remove(&Index::hash_map(vec![1]));
+        assert_eq!(
+            m.unpaired_weak_index_violations(&g, &nodes).unwrap(),
+            vec![MaterializationViolation::UnpairedWeakIndex {
+                node: left,
+                index: Index::hash_map(vec![1]),
+            }]
+        );
+
+        // only lookups by the given nodes are checked
+        assert!(m
+            .unpaired_weak_index_violations(&g, &HashSet::from([left, right]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn unreachable_materializations() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![1])]));
+        assert_eq!(m.unreachable(&g), vec![project]);
+
+        let reader = g.add_node(node::Node::new(
+            "reader",
+            make_columns(&["a", "b"]),
+            node::special::Reader::new(project, Default::default()),
+        ));
+        g.add_edge(project, reader, ());
+        assert!(m.unreachable(&g).is_empty());
+
+        g[reader].remove();
+        assert_eq!(m.unreachable(&g), vec![project]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "but isn't in `have`")]
+    fn added_index_missing_from_have() {
+        let (g, _, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        m.added
+            .insert(project, HashSet::from([Index::hash_map(vec![1])]));
+        m.assert_invariants(&g);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is partial, but isn't in `have`")]
+    fn partial_node_missing_from_have() {
+        let (g, _, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.partial.insert(project);
+        m.assert_invariants(&g);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn map_lookup_indices_to_missing_parent_column() {
+        let (g, base, project) = projection(&[1, 2]);
+        let res = map_lookup_indices(
+            &g,
+            project,
+            base,
+            &HashSet::from([LookupIndex::Strict(Index::hash_map(vec![1]))]),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn range_replay_through_hash_index() {
+        let (g, base, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(project, HashSet::from([Index::btree_map(vec![0])]));
+        m.partial.insert(project);
+
+        // the projection's range index replays from the base table, which only has a hash index on
+        // the same column
+        assert_eq!(
+            m.hash_index_in_range_replay_violations(&g, &m.have, [])
+                .unwrap(),
+            vec![MaterializationViolation::HashIndexInRangeReplay {
+                node: project,
+                index: Index::btree_map(vec![0]),
+                through: base,
+                through_index: Index::hash_map(vec![0]),
+            }]
+        );
+        assert_eq!(m.check(&g).unwrap().len(), 1);
+
+        // once the base table has a range index to replay from, the path is fine
+        m.have
+            .get_mut(&base)
+            .unwrap()
+            .insert(Index::btree_map(vec![0]));
+        assert!(m
+            .hash_index_in_range_replay_violations(&g, &m.have, [])
+            .unwrap()
+            .is_empty());
+        assert!(m.check(&g).unwrap().is_empty());
+    }
+}
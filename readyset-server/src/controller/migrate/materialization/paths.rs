@@ -0,0 +1,155 @@
+//! Memoization of replay path computation during migration planning.
+//!
+//! Computing the replay paths for an index with [`keys::replay_paths_for_nonstop`] walks the graph
+//! all the way up to the base tables, splitting the path at every node that generates columns, so
+//! it can get expensive on large graphs. Within a single planning iteration the same paths are
+//! asked for several times (when deciding whether nodes can be partial, and again when validating
+//! the result), and the graph doesn't change in between, so we cache them here.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dataflow::prelude::*;
+use parking_lot::Mutex;
+use readyset_errors::ReadySetResult;
+
+use crate::controller::keys::{self, RawReplayPath};
+
+/// The maximum number of sets of paths to keep in a [`ReplayPathCache`]. Once the cache is full,
+/// further paths are computed as normal but aren't cached, so that planning a very large migration
+/// can't use an unbounded amount of memory.
+const MAX_CACHED_PATHS: usize = 4096;
+
+/// Cache of the results of [`keys::replay_paths_for_nonstop`], keyed by its arguments.
+///
+/// The paths for a column depend on the structure of the graph, so the cache must be
+/// [cleared](ReplayPathCache::clear) whenever the graph might have changed - in practice, at the
+/// start of every call to [`Materializations::extend`](super::Materializations::extend).
+#[derive(Debug, Default)]
+pub(super) struct ReplayPathCache {
+    paths: Mutex<HashMap<(ColumnRef, IndexType), Vec<RawReplayPath>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Clone for ReplayPathCache {
+    /// The cache is only valid for the graph it was populated from, so clones start out empty
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl ReplayPathCache {
+    /// Return the replay paths for `colref`, as [`keys::replay_paths_for_nonstop`] would, computing
+    /// them only if they aren't already cached
+    pub(super) fn paths_for(
+        &self,
+        graph: &Graph,
+        colref: ColumnRef,
+        index_type: IndexType,
+    ) -> ReadySetResult<Vec<RawReplayPath>> {
+        let key = (colref, index_type);
+        if let Some(paths) = self.paths.lock().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(paths.clone());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let paths = keys::replay_paths_for_nonstop(graph, key.0.clone(), key.1)?;
+        let mut cache = self.paths.lock();
+        if cache.len() < MAX_CACHED_PATHS {
+            cache.insert(key, paths.clone());
+        }
+        Ok(paths)
+    }
+
+    /// Returns the number of lookups into the cache which were and weren't satisfied from it since
+    /// it was last cleared, as a pair of `(hits, misses)`
+    pub(super) fn hit_counts(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Remove all cached paths, and reset the hit counts
+    pub(super) fn clear(&self) {
+        self.paths.lock().clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::join_chain;
+    use super::super::tests::projection;
+    use super::*;
+
+    #[test]
+    fn cached_paths_match_uncached() {
+        let (g, _, project) = projection(&[1, 0]);
+        let cache = ReplayPathCache::default();
+        let colref = ColumnRef {
+            node: project,
+            columns: vec![0],
+        };
+
+        let expected =
+            keys::replay_paths_for_nonstop(&g, colref.clone(), IndexType::HashMap).unwrap();
+        for _ in 0..2 {
+            assert_eq!(
+                cache
+                    .paths_for(&g, colref.clone(), IndexType::HashMap)
+                    .unwrap(),
+                expected
+            );
+        }
+        assert_eq!(cache.hit_counts(), (1, 1));
+
+        cache.clear();
+        assert_eq!(cache.hit_counts(), (0, 0));
+        assert_eq!(
+            cache.paths_for(&g, colref, IndexType::HashMap).unwrap(),
+            expected
+        );
+        assert_eq!(cache.hit_counts(), (0, 1));
+    }
+
+    #[test]
+    fn cached_paths_match_uncached_through_generated_columns() {
+        let (g, nodes) = join_chain(8);
+        let cache = ReplayPathCache::default();
+        // Indexing a join on columns from both sides generates those columns, so the paths for
+        // them are broken off at the join
+        let colrefs = nodes
+            .iter()
+            .filter(|&&ni| g[ni].is_internal())
+            .flat_map(|&node| {
+                [vec![0], vec![2], vec![0, 2]]
+                    .into_iter()
+                    .map(move |columns| ColumnRef { node, columns })
+            })
+            .collect::<Vec<_>>();
+
+        for round in 0..2 {
+            for colref in &colrefs {
+                let expected =
+                    keys::replay_paths_for_nonstop(&g, colref.clone(), IndexType::HashMap).unwrap();
+                if colref.columns == [0, 2] {
+                    assert!(expected.iter().any(|path| path.broken()));
+                }
+                assert_eq!(
+                    cache
+                        .paths_for(&g, colref.clone(), IndexType::HashMap)
+                        .unwrap(),
+                    expected,
+                    "round {round}, {colref:?}"
+                );
+            }
+        }
+
+        let n = colrefs.len() as u64;
+        assert_eq!(cache.hit_counts(), (n, n));
+    }
+}
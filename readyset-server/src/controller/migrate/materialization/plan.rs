@@ -20,6 +20,60 @@ use crate::controller::keys::{self, IndexRef, RawReplayPath};
 use crate::controller::migrate::DomainMigrationPlan;
 use crate::controller::state::Graphviz;
 
+/// Description of how to avoid [`ReadySetError::UnsupportedStraddledJoin`], included in the error
+const STRADDLED_JOIN_REMEDIATION: &str =
+    "Rewrite the query so that all of its parameters are compared against columns from the same \
+     side of the join, or enable straddled joins with --feature-straddled-joins, at the cost of \
+     upqueries to both sides of the join for every cache miss";
+
+/// If any of the given replay paths pass through a straddled join, return a
+/// [`ReadySetError::UnsupportedStraddledJoin`] describing that join.
+fn straddled_join_error(
+    graph: &Graph,
+    paths: &[RawReplayPath],
+) -> ReadySetResult<Option<ReadySetError>> {
+    // "has extension" is currently a weak-ish proxy for straddled joins, but works since
+    // straddled joins are the only case where we make extended replay paths right now. The
+    // extension of each path starts at the join, and the target of the path is the index in the
+    // parent that the key is traced to.
+    let mut parent_columns = HashMap::new();
+    let mut join = None;
+    for path in paths {
+        let Some(join_ref) = path.segments_with_extension().get(path.target_index() + 1) else {
+            continue;
+        };
+        if *join.get_or_insert(join_ref.node) != join_ref.node {
+            continue;
+        }
+        let target = path.target();
+        parent_columns.insert(
+            target.node,
+            target
+                .index
+                .as_ref()
+                .map(|idx| idx.columns.clone())
+                .unwrap_or_default(),
+        );
+    }
+
+    let Some(join) = join else {
+        return Ok(None);
+    };
+    let (left_parent, right_parent) = match graph[join].ancestors()?[..] {
+        [left, right] => (left, right),
+        _ => internal!("Straddled join {} does not have two parents", join.index()),
+    };
+
+    Ok(Some(ReadySetError::UnsupportedStraddledJoin {
+        join: join.index(),
+        left_parent: left_parent.index(),
+        left_columns: parent_columns.remove(&left_parent).unwrap_or_default(),
+        right_parent: right_parent.index(),
+        right_columns: parent_columns.remove(&right_parent).unwrap_or_default(),
+        remediation: STRADDLED_JOIN_REMEDIATION.to_owned(),
+    }))
+}
+
 /// A struct representing all the information required to construct and maintain the
 /// materializations for a single node within a dataflow graph.
 ///
@@ -125,15 +179,10 @@ impl<'a> Plan<'a> {
         .into_iter()
         .collect::<Vec<_>>();
 
-        if !self.m.config.allow_straddled_joins
-            && paths.iter().any(|p| {
-                // "has extension" is currently a weak-ish proxy for straddled joins, but works
-                // since straddled joins are the only case where we make extended replay paths right
-                // now
-                p.has_extension()
-            })
-        {
-            unsupported!("Straddled joins are not supported");
+        if !self.m.config.allow_straddled_joins {
+            if let Some(err) = straddled_join_error(graph, &paths)? {
+                return Err(err);
+            }
         }
 
         // don't include paths that don't end at this node.
@@ -425,7 +474,12 @@ impl<'a> Plan<'a> {
 
             invariant!(!segments.is_empty());
 
-            debug!(%tag, "domain replay path is {:?}", segments);
+            debug!(
+                %tag,
+                migration = ?self.m.tag_migration(tag),
+                "domain replay path is {:?}",
+                segments
+            );
 
             // tell all the domains about their segment of this replay path
             let mut pending = None;
@@ -436,17 +490,7 @@ impl<'a> Plan<'a> {
                 //  a domain may appear multiple times in this list if a path crosses into the same
                 //  domain more than once. currently, that will cause a deadlock.
                 if seen.contains(&domain) {
-                    trace!(
-                        "{}",
-                        Graphviz {
-                            graph: self.graph,
-                            detailed: true,
-                            node_sizes: None,
-                            materializations: self.m,
-                            domain_nodes: None,
-                            reachable_from: None,
-                        }
-                    );
+                    trace!("{}", Graphviz::new(self.graph, self.m));
                     internal!("detected A-B-A domain replay path");
                 }
                 seen.insert(domain);
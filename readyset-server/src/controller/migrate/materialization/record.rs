@@ -0,0 +1,92 @@
+//! Recording the domain requests enqueued while committing materializations, so that the exact
+//! sequence of messages a migration produced can be replayed later without the original schema.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use dataflow::prelude::*;
+use dataflow::DomainRequest;
+
+use crate::controller::migrate::StoredDomainRequest;
+
+/// Encode each of `messages`, in order, to be appended to a file with [`append_messages`].
+///
+/// Each message is encoded as a bincode-encoded `(domain, shard, request)` triple, so successive
+/// migrations recorded to the same file can be read back with [`read_recorded_messages`].
+pub(super) fn encode_messages(messages: &[StoredDomainRequest]) -> ReadySetResult<Vec<u8>> {
+    let mut encoded = vec![];
+    for StoredDomainRequest { domain, shard, req } in messages {
+        bincode::serialize_into(&mut encoded, &(domain, shard, req))?;
+    }
+    Ok(encoded)
+}
+
+/// Append messages encoded with [`encode_messages`] to the file at `path`, creating it if it
+/// doesn't exist.
+pub(super) fn append_messages(path: &Path, encoded: &[u8]) -> ReadySetResult<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(encoded)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Read back all of the messages recorded by [`append_messages`] from `reader`, in the order they
+/// were enqueued, as `(domain, shard, request)` triples. A shard of `None` means the request was
+/// sent to all shards of the domain.
+pub fn read_recorded_messages<R: Read>(
+    reader: R,
+) -> ReadySetResult<Vec<(DomainIndex, Option<usize>, DomainRequest)>> {
+    let mut reader = io::BufReader::new(reader);
+    let mut messages = vec![];
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(message) => messages.push(message),
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                _ => return Err(e.into()),
+            },
+        }
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan");
+        let (a, b) = (DomainIndex::from(0), DomainIndex::from(1));
+        let message = |domain, shard, node| StoredDomainRequest {
+            domain,
+            shard,
+            req: DomainRequest::IsReady {
+                node: LocalNodeIndex::make(node),
+            },
+        };
+
+        let record = |messages: &[StoredDomainRequest]| {
+            append_messages(&path, &encode_messages(messages).unwrap())
+        };
+        record(&[message(a, None, 0), message(b, Some(1), 1)]).unwrap();
+        // a second migration appends to the same file
+        record(&[message(a, Some(0), 2)]).unwrap();
+
+        let recorded = read_recorded_messages(File::open(&path).unwrap()).unwrap();
+        assert_eq!(
+            recorded
+                .iter()
+                .map(|(domain, shard, req)| match req {
+                    DomainRequest::IsReady { node } => (*domain, *shard, node.id()),
+                    _ => panic!("unexpected request {req:?}"),
+                })
+                .collect::<Vec<_>>(),
+            vec![(a, None, 0), (b, Some(1), 1), (a, Some(0), 2)]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Detecting indices added to new nodes which duplicate an index in one of their ancestors.
+//!
+//! Lookups into a node which can be queried through can be answered by looking up into the node's
+//! nearest materialized ancestor instead, so if that ancestor already has an index on the same
+//! columns then materializing the node with that index only creates redundant state.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use dataflow::prelude::*;
+
+use super::Materializations;
+
+/// An index added to a new node during a migration, which is already covered by an index in one
+/// of that node's ancestors
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(in crate::controller) struct RedundantIndex {
+    /// The node the redundant index was added to
+    pub(in crate::controller) node: NodeIndex,
+    /// The redundant index
+    pub(in crate::controller) index: Index,
+    /// The materialized ancestor of `node` which covers `index`
+    pub(in crate::controller) ancestor: NodeIndex,
+    /// The index in `ancestor` which covers `index`
+    pub(in crate::controller) ancestor_index: Index,
+}
+
+impl Display for RedundantIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "index {} in node {} is covered by index {} in ancestor {}",
+            self.index,
+            self.node.index(),
+            self.ancestor_index,
+            self.ancestor.index()
+        )
+    }
+}
+
+/// Trace the columns of `index` in `ni` to the corresponding columns in its `parent`, returning
+/// `None` if any of them can't be traced (for example, because they're generated by `ni`)
+fn trace_index(graph: &Graph, ni: NodeIndex, parent: NodeIndex, index: &Index) -> Option<Index> {
+    let columns = index
+        .columns
+        .iter()
+        .map(|&col| {
+            graph[ni]
+                .parent_columns(col)
+                .into_iter()
+                .find(|&(anc, _)| anc == parent)
+                .and_then(|(_, col)| col)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Index::new(index.index_type, columns))
+}
+
+impl Materializations {
+    /// Find all indices which have been added to any of the nodes in `new` in the current
+    /// migration that are already covered by an index in a materialized ancestor of that node,
+    /// which lookups into the node could query through to instead.
+    pub(super) fn redundant_indices(
+        &self,
+        graph: &Graph,
+        new: &HashSet<NodeIndex>,
+    ) -> Vec<RedundantIndex> {
+        let mut redundant = vec![];
+        for (&ni, indices) in &self.added {
+            let n = &graph[ni];
+            if !new.contains(&ni) || !n.is_internal() || !n.can_query_through() {
+                continue;
+            }
+
+            for index in indices {
+                let (mut node, mut traced) = (ni, index.clone());
+                loop {
+                    // query-through nodes always have exactly one parent
+                    let mut parents =
+                        graph.neighbors_directed(node, petgraph::EdgeDirection::Incoming);
+                    let (Some(parent), None) = (parents.next(), parents.next()) else {
+                        break;
+                    };
+                    let Some(parent_index) = trace_index(graph, node, parent, &traced) else {
+                        break;
+                    };
+
+                    match self.have.get(&parent) {
+                        Some(parent_indices) => {
+                            if parent_indices.contains(&parent_index) {
+                                redundant.push(RedundantIndex {
+                                    node: ni,
+                                    index: index.clone(),
+                                    ancestor: parent,
+                                    ancestor_index: parent_index,
+                                });
+                            }
+                            break;
+                        }
+                        None if graph[parent].is_internal()
+                            && graph[parent].can_query_through() =>
+                        {
+                            node = parent;
+                            traced = parent_index;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        redundant.sort();
+        redundant
+    }
+
+    /// Stop materializing any of the given new, fully materialized nodes for which *all* indices
+    /// are covered by indices in fully materialized ancestors, since lookups into those nodes can
+    /// query through to their ancestors instead.
+    ///
+    /// Returns the nodes which are no longer materialized, in sorted order.
+    pub(super) fn suppress_redundant_indices(
+        &mut self,
+        redundant: &[RedundantIndex],
+        new: &HashSet<NodeIndex>,
+    ) -> Vec<NodeIndex> {
+        let mut covered: HashMap<NodeIndex, HashSet<&Index>> = HashMap::new();
+        for r in redundant {
+            if !self.partial.contains(&r.ancestor) {
+                covered.entry(r.node).or_default().insert(&r.index);
+            }
+        }
+
+        let mut suppressed = covered
+            .into_iter()
+            .filter(|(ni, covered)| {
+                new.contains(ni)
+                    && !self.had.contains(ni)
+                    && !self.partial.contains(ni)
+                    && self.have.get(ni).map_or(false, |indices| {
+                        indices.iter().all(|i| covered.contains(&i))
+                    })
+            })
+            .map(|(ni, _)| ni)
+            .collect::<Vec<_>>();
+        suppressed.sort();
+
+        for ni in &suppressed {
+            self.have.remove(ni);
+            self.added.remove(ni);
+            self.added_weak.remove(ni);
+            self.kinds.remove(ni);
+        }
+
+        suppressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::projection;
+    use super::*;
+
+    #[test]
+    fn detect_and_suppress_redundant_index() {
+        let (g, base, project) = projection(&[1, 0]);
+        let new = HashSet::from([project]);
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![1])]));
+        m.had.insert(base);
+        for node_indices in [&mut m.have, &mut m.added] {
+            node_indices.insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        }
+
+        m.assert_invariants(&g);
+
+        let redundant = m.redundant_indices(&g, &new);
+        assert_eq!(
+            redundant,
+            vec![RedundantIndex {
+                node: project,
+                index: Index::hash_map(vec![0]),
+                ancestor: base,
+                ancestor_index: Index::hash_map(vec![1]),
+            }]
+        );
+
+        // Suppressing the redundant index means we no longer need to materialize the projection at
+        // all, saving all the memory its state would have used
+        assert_eq!(
+            m.suppress_redundant_indices(&redundant, &new),
+            vec![project]
+        );
+        assert!(!m.have.contains_key(&project));
+        assert!(!m.added.contains_key(&project));
+        assert_eq!(m.have.len(), 1);
+        m.assert_invariants(&g);
+    }
+
+    #[test]
+    fn index_on_different_columns_is_not_redundant() {
+        let (g, base, project) = projection(&[1, 0]);
+        let new = HashSet::from([project]);
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        for node_indices in [&mut m.have, &mut m.added] {
+            node_indices.insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        }
+
+        assert!(m.redundant_indices(&g, &new).is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+//! Summarizing every materialization in the graph into a single [`MaterializationReport`].
+
+use std::collections::{BTreeMap, HashSet};
+
+use dataflow::prelude::*;
+use itertools::Itertools;
+use readyset_client::debug::info::{MaterializationReport, MaterializationViolation};
+
+use super::sizes::SizeEstimator;
+use super::Materializations;
+
+impl Materializations {
+    /// Build a [`MaterializationReport`] summarizing every materialization in `graph`, given the
+    /// estimated size of each node in `node_sizes`, listing the `top_n` largest materializations.
+    ///
+    /// This only reads the materializations, so it's safe to run against a live deployment.
+    pub(in crate::controller) fn report(
+        &self,
+        graph: &Graph,
+        node_sizes: &dyn SizeEstimator,
+        top_n: usize,
+    ) -> ReadySetResult<MaterializationReport> {
+        let nodes = graph
+            .node_indices()
+            .filter(|&ni| !graph[ni].is_source() && !graph[ni].is_dropped())
+            .collect::<HashSet<_>>();
+        let readers = nodes
+            .iter()
+            .filter(|&&ni| graph[ni].is_reader())
+            .copied()
+            .collect::<HashSet<_>>();
+        let sizes = node_sizes
+            .estimate_all(graph)
+            .into_iter()
+            .filter(|(ni, _)| nodes.contains(ni))
+            .map(|(ni, size)| (ni, size.bytes.0 as u64))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut memory_by_domain = BTreeMap::new();
+        for (&ni, &bytes) in &sizes {
+            if graph[ni].has_domain() {
+                *memory_by_domain.entry(graph[ni].domain()).or_default() += bytes;
+            }
+        }
+
+        let orphaned = self
+            .unreachable(graph)
+            .into_iter()
+            .map(|ni| (ni, sizes.get(&ni).copied().unwrap_or_default()))
+            .collect();
+
+        let unpaired_weak_indices = self
+            .unpaired_weak_index_violations(graph, &nodes)?
+            .into_iter()
+            .filter_map(|violation| match violation {
+                MaterializationViolation::UnpairedWeakIndex { node, index } => Some((node, index)),
+                _ => None,
+            })
+            .collect();
+
+        let largest = sizes
+            .iter()
+            .filter(|(ni, _)| self.have.contains_key(ni) || readers.contains(ni))
+            .sorted_by(|(a_ni, a), (b_ni, b)| b.cmp(a).then(a_ni.cmp(b_ni)))
+            .take(top_n)
+            .map(|(&ni, &bytes)| (ni, graph[ni].name().clone(), bytes))
+            .collect();
+
+        let partial = self
+            .have
+            .keys()
+            .filter(|ni| self.partial.contains(ni))
+            .count();
+        Ok(MaterializationReport {
+            total_nodes: nodes.len(),
+            partial,
+            full: self.have.len() - partial,
+            readers: readers.len(),
+            total_indices: self.index_count(),
+            total_replay_paths: self.total_paths(),
+            memory_by_domain,
+            orphaned,
+            unpaired_weak_indices,
+            largest,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use dataflow::utils::make_columns;
+    use dataflow::{node, ops, Expr};
+    use readyset_client::debug::info::{NodeMaterializedSize, NodeSize};
+    use readyset_data::DfType;
+
+    use super::super::tests::projection;
+    use super::*;
+
+    #[test]
+    fn report_summarizes_materializations() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let reader = g.add_node(node::Node::new(
+            "reader",
+            make_columns(&["a", "b"]),
+            node::special::Reader::new(project, Default::default()),
+        ));
+        g.add_edge(project, reader, ());
+        let orphan = g.add_node(node::Node::new(
+            "orphan",
+            make_columns(&["a", "b"]),
+            ops::NodeOperator::Project(ops::project::Project::new(
+                base,
+                vec![
+                    Expr::Column {
+                        index: 0,
+                        ty: DfType::Unknown,
+                    },
+                    Expr::Column {
+                        index: 1,
+                        ty: DfType::Unknown,
+                    },
+                ],
+            )),
+        ));
+        g.add_edge(base, orphan, ());
+        g[base].add_to(DomainIndex::from(0));
+        for ni in [project, reader, orphan] {
+            g[ni].add_to(DomainIndex::from(1));
+        }
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have.insert(
+            project,
+            HashSet::from([Index::hash_map(vec![0]), Index::hash_map(vec![1])]),
+        );
+        m.have
+            .insert(orphan, HashSet::from([Index::hash_map(vec![1])]));
+        m.partial.insert(project);
+
+        let node_size = |bytes| NodeSize {
+            bytes: NodeMaterializedSize(bytes),
+            ..Default::default()
+        };
+        let node_sizes = HashMap::from([
+            (base, node_size(1000)),
+            (project, node_size(200)),
+            (reader, node_size(50)),
+            (orphan, node_size(300)),
+        ]);
+
+        let report = m.report(&g, &node_sizes, 2).unwrap();
+        assert_eq!(report.total_nodes, 4);
+        assert_eq!((report.partial, report.full, report.readers), (1, 2, 1));
+        assert_eq!(report.total_indices, 4);
+        assert_eq!(report.total_replay_paths, 0);
+        assert_eq!(
+            report.memory_by_domain,
+            BTreeMap::from([(DomainIndex::from(0), 1000), (DomainIndex::from(1), 550)])
+        );
+        assert_eq!(report.orphaned, vec![(orphan, 300)]);
+        assert!(report.unpaired_weak_indices.is_empty());
+        assert_eq!(
+            report
+                .largest
+                .iter()
+                .map(|(ni, _, bytes)| (*ni, *bytes))
+                .collect::<Vec<_>>(),
+            vec![(base, 1000), (orphan, 300)]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Sourcing replays from an existing, compatible index on a full materialization, rather than
+//! adding a new index with exactly the requested columns and type.
+//!
+//! Point lookups into in-memory state fall back to a BTree index when there's no hash index on the
+//! looked-up columns, so a BTree index can serve any replay which would otherwise need a hash index
+//! on the same columns. Partial materializations are never reused this way, since replays into them
+//! must be sourced from an index on exactly the traced columns. Neither are base tables, nor full
+//! materializations kept on disk, since point lookups into persistent state need a hash index.
+
+use dataflow::prelude::*;
+
+use super::Materializations;
+
+impl Materializations {
+    /// If [`prefer_existing_indices`](super::Config::prefer_existing_indices) is set, return an
+    /// existing index on the fully materialized node `ni` which replays for `index` can be sourced
+    /// from instead of adding `index` to the node.
+    pub(super) fn compatible_existing_index(
+        &self,
+        graph: &Graph,
+        ni: NodeIndex,
+        index: &Index,
+    ) -> Option<&Index> {
+        if !self.config.prefer_existing_indices
+            || self.partial.contains(&ni)
+            || index.index_type != IndexType::HashMap
+        {
+            return None;
+        }
+        // persistent state only serves point lookups from a hash index
+        if graph[ni].is_base() || self.config.persistent_full_materializations {
+            return None;
+        }
+
+        self.have
+            .get(&ni)?
+            .get(&Index::btree_map(index.columns.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::super::tests::projection;
+    use super::*;
+
+    #[test]
+    fn reuse_btree_for_hash_index() {
+        let (g, _, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have.insert(
+            project,
+            HashSet::from([Index::btree_map(vec![0]), Index::hash_map(vec![1])]),
+        );
+
+        // without the flag, a new index is always added
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::hash_map(vec![0])),
+            None
+        );
+
+        m.config.prefer_existing_indices = true;
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::hash_map(vec![0])),
+            Some(&Index::btree_map(vec![0]))
+        );
+        // a hash index can't serve range replays
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::btree_map(vec![1])),
+            None
+        );
+        // the columns must match exactly
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::hash_map(vec![0, 1])),
+            None
+        );
+
+        // partial materializations need an index on exactly the traced columns
+        m.partial.insert(project);
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::hash_map(vec![0])),
+            None
+        );
+    }
+
+    #[test]
+    fn never_reuse_persistent_state() {
+        let (g, base, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.config.prefer_existing_indices = true;
+        for ni in [base, project] {
+            m.have
+                .insert(ni, HashSet::from([Index::btree_map(vec![0])]));
+        }
+
+        // base tables are always persistent, so they need an exact hash index
+        assert_eq!(
+            m.compatible_existing_index(&g, base, &Index::hash_map(vec![0])),
+            None
+        );
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::hash_map(vec![0])),
+            Some(&Index::btree_map(vec![0]))
+        );
+
+        // the same goes for full materializations kept on disk
+        m.config.persistent_full_materializations = true;
+        assert_eq!(
+            m.compatible_existing_index(&g, project, &Index::hash_map(vec![0])),
+            None
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Attributing the memory used by materialized nodes to their individual indices, and to the
+//! queries which depend on them.
+
+use std::collections::{HashMap, HashSet};
+
+use dataflow::prelude::*;
+use itertools::Itertools;
+use petgraph::Direction;
+use readyset_client::debug::info::NodeSize;
+
+use super::Materializations;
+use crate::controller::reachability::reachable;
+
+/// A source of estimates for the size of the state of materialized nodes.
+///
+/// Planning decisions which depend on how much memory materializations use (such as
+/// [`check_budget`](Materializations::check_budget)) only need an estimate of the size of each
+/// node, which doesn't have to come from the live sizes reported by the domains - it could just as
+/// well come from historical averages or a model of the cardinality of the node's columns, which
+/// also allows estimating the size of nodes that haven't been created yet. The default
+/// implementation is the map of [`NodeSize`]s reported by the domains.
+pub(in crate::controller) trait SizeEstimator {
+    /// Return the estimated size of the state of the node at `ni`, or `None` if there's no
+    /// estimate for that node
+    fn estimate(&self, ni: NodeIndex) -> Option<NodeSize>;
+
+    /// Return the estimated size of every node in `graph` which has an estimate
+    fn estimate_all(&self, graph: &Graph) -> HashMap<NodeIndex, NodeSize> {
+        graph
+            .node_indices()
+            .filter_map(|ni| Some((ni, self.estimate(ni)?)))
+            .collect()
+    }
+}
+
+impl SizeEstimator for HashMap<NodeIndex, NodeSize> {
+    fn estimate(&self, ni: NodeIndex) -> Option<NodeSize> {
+        self.get(&ni).copied()
+    }
+
+    fn estimate_all(&self, _graph: &Graph) -> HashMap<NodeIndex, NodeSize> {
+        self.clone()
+    }
+}
+
+impl Materializations {
+    /// Return the number of bytes used by each index of each materialized node, given the total
+    /// size of each node in `node_sizes` and the per-index sizes reported by the domains in
+    /// `reported`.
+    ///
+    /// Entries in `reported` for the same node (one per shard) are added together. Nodes whose
+    /// state can only report its total size, and which therefore don't appear in `reported`, have
+    /// that total split evenly between all of their indices. Readers only ever have a single index,
+    /// which is attributed the reader's entire size.
+    pub(in crate::controller) fn index_sizes(
+        &self,
+        graph: &Graph,
+        node_sizes: &HashMap<NodeIndex, NodeSize>,
+        reported: Vec<(NodeIndex, Vec<(Index, u64)>)>,
+    ) -> HashMap<(NodeIndex, Index), u64> {
+        let mut res = HashMap::new();
+        let mut have_reported = HashSet::new();
+        for (ni, sizes) in reported {
+            have_reported.insert(ni);
+            for (index, bytes) in sizes {
+                *res.entry((ni, index)).or_default() += bytes;
+            }
+        }
+
+        for (&ni, size) in node_sizes {
+            if have_reported.contains(&ni) {
+                continue;
+            }
+            let bytes = size.bytes.0 as u64;
+            if let Some(index) = graph[ni].as_reader().and_then(|r| r.index()) {
+                res.insert((ni, index.clone()), bytes);
+            } else if let Some(indices) = self.indexes_for(ni) {
+                if indices.is_empty() {
+                    continue;
+                }
+                let per_index = bytes / indices.len() as u64;
+                for index in indices {
+                    res.insert((ni, index.clone()), per_index);
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Estimate the number of bytes used on behalf of each query, keyed by the index of the
+    /// query's reader, given the total size of each materialized node in `node_sizes`.
+    ///
+    /// The size of every materialized node (including the readers themselves) is split evenly
+    /// between all of the readers it's an ancestor of, with any remainder going to the reader with
+    /// the lowest index so that the totals add up exactly. Base tables hold the data for every
+    /// query, so they aren't attributed to any of them, and neither are materializations which no
+    /// reader depends on (see [`unreachable`](Self::unreachable)).
+    pub(in crate::controller) fn memory_by_query(
+        &self,
+        graph: &Graph,
+        node_sizes: &HashMap<NodeIndex, NodeSize>,
+    ) -> HashMap<NodeIndex, u64> {
+        let readers = graph
+            .node_indices()
+            .filter(|&ni| graph[ni].is_reader() && !graph[ni].is_dropped())
+            .sorted()
+            .collect::<Vec<_>>();
+        let mut dependents: HashMap<NodeIndex, Vec<NodeIndex>> = HashMap::new();
+        for &reader in &readers {
+            for ni in reachable(graph, reader, Direction::Incoming) {
+                dependents.entry(ni).or_default().push(reader);
+            }
+        }
+
+        let mut res = readers
+            .iter()
+            .map(|&reader| (reader, 0))
+            .collect::<HashMap<_, _>>();
+        for (ni, size) in node_sizes {
+            if graph[*ni].is_base() || graph[*ni].is_dropped() {
+                continue;
+            }
+            let Some(readers) = dependents.get(ni) else {
+                continue;
+            };
+            let bytes = size.bytes.0 as u64;
+            let share = bytes / readers.len() as u64;
+            for reader in readers {
+                *res.entry(*reader).or_default() += share;
+            }
+            *res.entry(readers[0]).or_default() += bytes % readers.len() as u64;
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dataflow::node;
+    use dataflow::utils::make_columns;
+    use readyset_client::debug::info::NodeMaterializedSize;
+
+    use super::super::tests::projection;
+    use super::*;
+
+    #[test]
+    fn index_sizes_with_fallback() {
+        let (g, base, project) = projection(&[0, 1]);
+        let mut m = Materializations::new();
+        m.have.insert(
+            base,
+            HashSet::from([Index::hash_map(vec![0]), Index::hash_map(vec![1])]),
+        );
+        m.have.insert(
+            project,
+            HashSet::from([Index::hash_map(vec![0]), Index::hash_map(vec![1])]),
+        );
+        let node_size = |bytes| NodeSize {
+            bytes: NodeMaterializedSize(bytes),
+            ..Default::default()
+        };
+        let node_sizes = HashMap::from([(base, node_size(1000)), (project, node_size(300))]);
+        // the base table is sharded, and each shard reports the size of each of its indices
+        let reported = vec![
+            (
+                base,
+                vec![
+                    (Index::hash_map(vec![0]), 100),
+                    (Index::hash_map(vec![1]), 400),
+                ],
+            ),
+            (
+                base,
+                vec![
+                    (Index::hash_map(vec![0]), 200),
+                    (Index::hash_map(vec![1]), 300),
+                ],
+            ),
+        ];
+
+        assert_eq!(
+            m.index_sizes(&g, &node_sizes, reported),
+            HashMap::from([
+                ((base, Index::hash_map(vec![0])), 300),
+                ((base, Index::hash_map(vec![1])), 700),
+                // the projection didn't report per-index sizes, so its total is split evenly
+                ((project, Index::hash_map(vec![0])), 150),
+                ((project, Index::hash_map(vec![1])), 150),
+            ])
+        );
+    }
+
+    #[test]
+    fn memory_by_query_splits_shared_nodes() {
+        let (mut g, base, project) = projection(&[0, 1]);
+        let reader = |g: &mut Graph, name: &str| {
+            let ni = g.add_node(node::Node::new(
+                name,
+                make_columns(&["a", "b"]),
+                node::special::Reader::new(project, Default::default()),
+            ));
+            g.add_edge(project, ni, ());
+            ni
+        };
+        let (r1, r2) = (reader(&mut g, "r1"), reader(&mut g, "r2"));
+        let m = Materializations::new();
+        let node_size = |bytes| NodeSize {
+            bytes: NodeMaterializedSize(bytes),
+            ..Default::default()
+        };
+        let node_sizes = HashMap::from([
+            (base, node_size(1000)),
+            (project, node_size(301)),
+            (r1, node_size(50)),
+            (r2, node_size(20)),
+        ]);
+
+        assert_eq!(
+            m.memory_by_query(&g, &node_sizes),
+            // the projection is shared, with the odd byte going to the first reader; the base
+            // table isn't attributed to either query
+            HashMap::from([(r1, 151 + 50), (r2, 150 + 20)])
+        );
+
+        g[r2].remove();
+        assert_eq!(
+            m.memory_by_query(&g, &node_sizes),
+            HashMap::from([(r1, 301 + 50)])
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! Freezing the materialization decisions made for one query into a [`MaterializationTemplate`],
+//! and applying them to structurally identical queries created later.
+//!
+//! Deployments which repeatedly create caches for queries generated from the same template end up
+//! analyzing the same graph shape over and over again. Once a template has been added with
+//! [`Materializations::add_template`], [`extend`](Materializations::extend) checks whether the new
+//! nodes in each migration have the same shape as it, and if so, whether its decisions are
+//! consistent with the materializations those nodes will replay from and look up into. If both
+//! hold, the decisions are applied as-is rather than computed from scratch. On any mismatch,
+//! migrations fall back to the full analysis.
+//!
+//! The indices in a template already reflect any indices that were merged or suppressed when the
+//! query it was exported from was planned. The materialization frontier isn't part of a template,
+//! though, and is placed for the new nodes as usual once a template has been applied.
+
+use std::collections::{HashMap, HashSet};
+
+use dataflow::prelude::*;
+use dataflow::LookupIndex;
+use itertools::Itertools;
+use readyset_client::debug::info::{MaterializationTemplate, TemplateNode, TemplateParent};
+use readyset_client::internal::MaterializationKind;
+use tracing::debug;
+
+use super::{index_obligations, map_lookup_indices, topo_order, IndexObligation, Materializations};
+use crate::controller::reachability::{reachable, reachable_from_any};
+
+/// Returns the nodes which belong to the query whose reader is `reader`: the reader itself, and
+/// all of its ancestors which aren't base tables and which no other query's reader depends on
+pub(in crate::controller) fn query_nodes(graph: &Graph, reader: NodeIndex) -> HashSet<NodeIndex> {
+    let other_readers = graph
+        .node_indices()
+        .filter(|&ni| ni != reader && graph[ni].is_reader() && !graph[ni].is_dropped());
+    let shared = reachable_from_any(graph, other_readers, petgraph::EdgeDirection::Incoming);
+    reachable(graph, reader, petgraph::EdgeDirection::Incoming)
+        .into_iter()
+        .filter(|ni| !shared.contains(ni) && !graph[*ni].is_base() && !graph[*ni].is_source())
+        .collect()
+}
+
+/// Returns the nodes in `nodes`, in topological order
+fn template_order(graph: &Graph, nodes: &HashSet<NodeIndex>) -> Vec<NodeIndex> {
+    topo_order(graph)
+        .into_iter()
+        .filter(|ni| nodes.contains(ni))
+        .collect()
+}
+
+/// Returns the parents of the node at `ni`, in order of their node index, as their position in
+/// `positions`, or as themselves for parents which don't have one
+fn template_parents(
+    graph: &Graph,
+    ni: NodeIndex,
+    positions: &HashMap<NodeIndex, usize>,
+) -> Vec<TemplateParent> {
+    graph
+        .neighbors_directed(ni, petgraph::EdgeDirection::Incoming)
+        .filter(|&pi| !graph[pi].is_source())
+        .sorted()
+        .map(|pi| match positions.get(&pi) {
+            Some(&position) => TemplateParent::Template(position),
+            None => TemplateParent::External(pi),
+        })
+        .collect()
+}
+
+/// A lookup performed by one of the nodes a template is being applied to
+struct TemplateLookup {
+    /// The node performing the lookup
+    requester: NodeIndex,
+    /// The node the lookup is into
+    target: NodeIndex,
+    /// The materialization which answers the lookup, once it's been hoisted through any
+    /// query-through nodes
+    materialization: NodeIndex,
+    /// The indices the lookup needs on `materialization`
+    indices: HashSet<LookupIndex>,
+}
+
+/// Returns true if `template_node` has the same shape as the node at `ni`, given the `positions`
+/// of all the nodes the template is being matched against
+fn same_shape(
+    template_node: &TemplateNode,
+    graph: &Graph,
+    ni: NodeIndex,
+    positions: &HashMap<NodeIndex, usize>,
+) -> bool {
+    template_node.operator == graph[ni].description(false)
+        && template_node.columns == graph[ni].columns().len()
+        && template_node.parents == template_parents(graph, ni, positions)
+}
+
+impl Materializations {
+    /// Freeze the materialization decisions for the nodes in `nodes`, which should be all the
+    /// nodes belonging to a single query, into a [`MaterializationTemplate`]
+    pub(in crate::controller) fn export_template(
+        &self,
+        graph: &Graph,
+        nodes: &HashSet<NodeIndex>,
+    ) -> MaterializationTemplate {
+        let order = template_order(graph, nodes);
+        let positions = order
+            .iter()
+            .enumerate()
+            .map(|(i, &ni)| (ni, i))
+            .collect::<HashMap<_, _>>();
+        MaterializationTemplate {
+            nodes: order
+                .iter()
+                .map(|&ni| TemplateNode {
+                    operator: graph[ni].description(false),
+                    columns: graph[ni].columns().len(),
+                    parents: template_parents(graph, ni, &positions),
+                    materialized: self.have.contains_key(&ni),
+                    indices: self
+                        .have
+                        .get(&ni)
+                        .into_iter()
+                        .flatten()
+                        .cloned()
+                        .sorted()
+                        .collect(),
+                    partial: self.partial.contains(&ni),
+                    kind: self.get_kind(ni),
+                })
+                .collect(),
+        }
+    }
+
+    /// Add a template to apply to the new nodes of future migrations whenever they have the same
+    /// shape as it
+    pub(in crate::controller) fn add_template(&mut self, template: MaterializationTemplate) {
+        if !self.templates.contains(&template) {
+            self.templates.push(template);
+        }
+    }
+
+    /// If one of the templates added with [`add_template`](Self::add_template) has the same shape
+    /// as the nodes in `new`, and its decisions are consistent with the existing materializations,
+    /// apply those decisions to the nodes in `new` and return true. Otherwise, return false without
+    /// changing anything.
+    pub(super) fn apply_template(
+        &mut self,
+        graph: &mut Graph,
+        new: &HashSet<NodeIndex>,
+    ) -> ReadySetResult<bool> {
+        if self.templates.is_empty() {
+            return Ok(false);
+        }
+
+        let order = template_order(graph, new);
+        let positions = order
+            .iter()
+            .enumerate()
+            .map(|(i, &ni)| (ni, i))
+            .collect::<HashMap<_, _>>();
+        let Some(template) = self
+            .templates
+            .iter()
+            .find(|template| {
+                template.nodes.len() == order.len()
+                    && template
+                        .nodes
+                        .iter()
+                        .zip(&order)
+                        .all(|(node, &ni)| same_shape(node, graph, ni, &positions))
+            })
+            .cloned()
+        else {
+            return Ok(false);
+        };
+
+        let decisions = order
+            .iter()
+            .copied()
+            .zip(template.nodes)
+            .collect::<HashMap<_, _>>();
+        let lookups = match self.check_template(graph, &decisions)? {
+            Ok(lookups) => lookups,
+            Err(reason) => {
+                debug!(
+                    %reason,
+                    "materialization template doesn't apply; falling back to full analysis"
+                );
+                return Ok(false);
+            }
+        };
+
+        for (ni, decision) in decisions {
+            if decision.materialized {
+                let indices = decision.indices.into_iter().collect::<HashSet<_>>();
+                self.have.insert(ni, indices.clone());
+                if !indices.is_empty() {
+                    self.added.insert(ni, indices);
+                }
+            }
+            if decision.partial {
+                self.partial.insert(ni);
+            }
+            if let Some(kind) = decision.kind {
+                self.record_kind(ni, kind);
+            }
+            if graph[ni].as_reader().and_then(|r| r.index()).is_some() {
+                self.new_readers.insert(ni);
+            }
+        }
+
+        // like `extend`, add every lookup index to `added` (even on existing materializations,
+        // which will already have it), so that domains are told about any new weak indices
+        for lookup in lookups {
+            let mi = lookup.materialization;
+            if !positions.contains_key(&mi) {
+                let kind = if graph[mi].is_base() {
+                    MaterializationKind::Base
+                } else {
+                    MaterializationKind::Lookup
+                };
+                self.record_kind(mi, kind);
+            }
+            for index in lookup.indices {
+                if index.is_weak() {
+                    self.weak_lookups
+                        .entry(lookup.requester)
+                        .or_default()
+                        .insert(lookup.target);
+                    self.added_weak
+                        .entry(mi)
+                        .or_default()
+                        .insert(index.index().clone());
+                }
+                self.have
+                    .entry(mi)
+                    .or_default()
+                    .insert(index.index().clone());
+                self.added.entry(mi).or_default().insert(index.into_index());
+            }
+        }
+
+        self.place_frontier(graph, new)?;
+        debug!(nodes = order.len(), "applied materialization template");
+        Ok(true)
+    }
+
+    /// Check that applying the materialization `decisions` for each new node would satisfy all
+    /// of their lookup obligations and partial replay obligations, given the existing
+    /// materializations, and respect the current [`Config`](super::Config).
+    ///
+    /// Decisions are never applied if the new nodes would look up into, or have their replays
+    /// sourced from, an existing partial materialization, since those would need replay paths of
+    /// their own, which only the full analysis sets up.
+    ///
+    /// If the decisions can be applied, returns the lookups the new nodes perform. Otherwise,
+    /// returns the reason they can't be.
+    fn check_template(
+        &self,
+        graph: &Graph,
+        decisions: &HashMap<NodeIndex, TemplateNode>,
+    ) -> ReadySetResult<Result<Vec<TemplateLookup>, String>> {
+        let materialized = |ni: NodeIndex| {
+            self.have.contains_key(&ni) || decisions.get(&ni).map_or(false, |d| d.materialized)
+        };
+        let has_index = |ni: NodeIndex, index: &Index| {
+            self.have
+                .get(&ni)
+                .map_or(false, |have| have.contains(index))
+                || decisions
+                    .get(&ni)
+                    .map_or(false, |d| d.indices.contains(index))
+        };
+
+        let mut lookups = vec![];
+        for (&ni, decision) in decisions.iter().sorted_by_key(|(ni, _)| **ni) {
+            let node = &graph[ni];
+            if decision.partial && (!self.config.partial_enabled || self.forced_full(node)) {
+                return Ok(Err(format!("node {} can't be partial", ni.index())));
+            }
+            let full = decision.materialized && !decision.partial && !node.is_base();
+            if full && !self.config.allow_full_materialization {
+                return Ok(Err(format!("node {} can't be full", ni.index())));
+            }
+            if full && node.purge {
+                return Ok(Err(format!(
+                    "node {} is beyond the materialization frontier, so can't be full",
+                    ni.index()
+                )));
+            }
+
+            for (target, obligation) in index_obligations(node, ni, &self.config) {
+                let IndexObligation::Lookup(index) = obligation else {
+                    continue;
+                };
+
+                // follow the lookup up through any query-through nodes, the same way `extend`
+                // hoists lookup obligations
+                let mut mi = target;
+                let mut indices = HashSet::from([index]);
+                while !materialized(mi) && graph[mi].is_internal() && graph[mi].can_query_through()
+                {
+                    let Some(parent) = graph
+                        .neighbors_directed(mi, petgraph::EdgeDirection::Incoming)
+                        .next()
+                    else {
+                        break;
+                    };
+                    indices = map_lookup_indices(graph, mi, parent, &indices)?;
+                    mi = parent;
+                }
+                if let Some(index) = indices.iter().find(|i| !has_index(mi, i.index())) {
+                    return Ok(Err(format!(
+                        "lookup index {} on node {} is missing",
+                        index.index(),
+                        mi.index()
+                    )));
+                }
+                if !decisions.contains_key(&mi) && self.partial.contains(&mi) {
+                    return Ok(Err(format!(
+                        "node {} would look up into existing partial node {}",
+                        ni.index(),
+                        mi.index()
+                    )));
+                }
+                lookups.push(TemplateLookup {
+                    requester: ni,
+                    target,
+                    materialization: mi,
+                    indices,
+                });
+            }
+
+            if !decision.partial {
+                continue;
+            }
+            let replay_indices: Vec<Index> = match node.as_reader() {
+                Some(reader) => reader.index().into_iter().cloned().collect(),
+                None => decision.indices.clone(),
+            };
+            for index in replay_indices {
+                for path in self.path_cache.paths_for(
+                    graph,
+                    ColumnRef {
+                        node: ni,
+                        columns: index.columns.clone(),
+                    },
+                    index.index_type,
+                )? {
+                    let n_to_skip = usize::from(path.target().node == ni);
+                    let source = path
+                        .segments()
+                        .iter()
+                        .rev()
+                        .skip(n_to_skip)
+                        .find(|segment| segment.index.is_none() || materialized(segment.node));
+                    let Some(IndexRef {
+                        node: source,
+                        index: Some(source_index),
+                    }) = source
+                    else {
+                        return Ok(Err(format!(
+                            "node {} has no partial replay source for index {}",
+                            ni.index(),
+                            index
+                        )));
+                    };
+                    if !has_index(*source, source_index) {
+                        return Ok(Err(format!(
+                            "replay index {} on node {} is missing",
+                            source_index,
+                            source.index()
+                        )));
+                    }
+                    if !decisions.contains_key(source) && self.partial.contains(source) {
+                        return Ok(Err(format!(
+                            "node {} would replay from existing partial node {}",
+                            ni.index(),
+                            source.index()
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(Ok(lookups))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dataflow::utils::make_columns;
+    use dataflow::{node, ops};
+
+    use super::super::tests::projection;
+    use super::*;
+
+    #[test]
+    fn apply_template_to_same_shape() {
+        // two structurally identical projections of the same base table
+        let (mut g, base, project) = projection(&[0, 1]);
+        let other = g[project].clone();
+        let other = g.add_node(other);
+        g.add_edge(base, other, ());
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![0])]));
+        m.have
+            .insert(project, HashSet::from([Index::hash_map(vec![0])]));
+        m.partial.insert(project);
+        let template = m.export_template(&g, &HashSet::from([project]));
+        assert_eq!(template.nodes.len(), 1);
+        assert_eq!(
+            template.nodes[0].parents,
+            vec![TemplateParent::External(base)]
+        );
+
+        // without any templates, nothing is applied
+        let new = HashSet::from([other]);
+        assert!(!m.apply_template(&mut g, &new).unwrap());
+
+        m.add_template(template.clone());
+        assert!(m.apply_template(&mut g, &new).unwrap());
+        assert_eq!(m.have[&other], HashSet::from([Index::hash_map(vec![0])]));
+        assert!(m.partial.contains(&other));
+        assert_eq!(m.added[&other], m.have[&other]);
+
+        // the other projection can't be partial if the base table doesn't have an index to replay
+        // from, so the template doesn't apply
+        let mut m = Materializations::new();
+        m.have
+            .insert(base, HashSet::from([Index::hash_map(vec![1])]));
+        m.add_template(template);
+        assert!(!m.apply_template(&mut g, &new).unwrap());
+        assert!(!m.have.contains_key(&other));
+        assert!(!m.partial.contains(&other));
+    }
+
+    #[test]
+    fn apply_template_to_join() {
+        fn add_base(g: &mut Graph, name: &str) -> NodeIndex {
+            g.add_node(node::Node::new(
+                name,
+                make_columns(&["c", "d"]),
+                node::special::Base::default(),
+            ))
+        }
+        fn add_join(g: &mut Graph, left: NodeIndex, right: NodeIndex) -> NodeIndex {
+            let ni = g.add_node(node::Node::new(
+                "join",
+                make_columns(&["a", "b", "d"]),
+                ops::NodeOperator::Join(ops::join::Join::new(
+                    left,
+                    right,
+                    ops::join::JoinType::Inner,
+                    vec![(1, 0)],
+                    vec![
+                        (ops::Side::Left, 0),
+                        (ops::Side::Left, 1),
+                        (ops::Side::Right, 1),
+                    ],
+                )),
+            ));
+            g.add_edge(left, ni, ());
+            g.add_edge(right, ni, ());
+            ni
+        }
+
+        let (mut g, left, _) = projection(&[0, 1]);
+        let right = add_base(&mut g, "right");
+        let other_right = add_base(&mut g, "other_right");
+        let join = add_join(&mut g, left, right);
+        let same = add_join(&mut g, left, right);
+        let different = add_join(&mut g, left, other_right);
+
+        let mut m = Materializations::new();
+        m.have
+            .insert(left, HashSet::from([Index::hash_map(vec![1])]));
+        for ni in [right, other_right] {
+            m.have.insert(ni, HashSet::from([Index::hash_map(vec![0])]));
+        }
+        m.add_template(m.export_template(&g, &HashSet::from([join])));
+
+        // the join performs weak lookups into both of its parents, which need to be set up in
+        // their domains just like when the join is planned from scratch
+        assert!(m.apply_template(&mut g, &HashSet::from([same])).unwrap());
+        assert_eq!(m.weak_lookups[&same], HashSet::from([left, right]));
+        assert_eq!(
+            m.added_weak[&left],
+            HashSet::from([Index::hash_map(vec![1])])
+        );
+        assert_eq!(
+            m.added_weak[&right],
+            HashSet::from([Index::hash_map(vec![0])])
+        );
+        assert!(m.added[&right].contains(&Index::hash_map(vec![0])));
+
+        // a join with the same shape over a different base table doesn't match the template
+        assert!(!m
+            .apply_template(&mut g, &HashSet::from([different]))
+            .unwrap());
+        assert!(!m.weak_lookups.contains_key(&different));
+        assert!(!m.added_weak.contains_key(&other_right));
+    }
+}
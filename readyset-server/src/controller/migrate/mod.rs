@@ -38,8 +38,10 @@ use array2::Array2;
 use dataflow::node::Column;
 use dataflow::prelude::*;
 use dataflow::{node, DomainRequest, ReaderProcessing};
+use futures::future;
 use metrics::{counter, histogram};
 use nom_sql::Relation;
+use readyset_client::debug::info::NodeSize;
 use readyset_client::metrics::recorded;
 use readyset_client::{KeyColumnIdx, ViewPlaceholder};
 use readyset_data::{DfType, Dialect};
@@ -189,10 +191,10 @@ impl StoredDomainRequest {
                         );
                         spins = 0;
                     }
-                    std::thread::sleep(Duration::from_millis(200));
+                    sleep(Duration::from_millis(200)).await;
                 }
             }
-            DomainRequest::RemoveNodes { .. } => {
+            DomainRequest::RemoveNodes { .. } | DomainRequest::RemoveReplayPaths { .. } => {
                 match dom.send_to_healthy::<()>(self.req, &mainline.workers).await {
                     // The worker failing is an even more efficient way to remove nodes.
                     Ok(_) | Err(ReadySetError::WorkerFailed { .. }) => {}
@@ -358,6 +360,7 @@ impl<'df> MigrationPlan<'df> {
         debug!(
             new_domains = dmp.place.len(),
             messages = dmp.stored.len(),
+            touched_domains = ?dmp.touched_domains(),
             "applying migration plan",
         );
 
@@ -366,10 +369,12 @@ impl<'df> MigrationPlan<'df> {
         match dmp.apply(dataflow_state).await {
             Ok(_) => {
                 debug!(ms = %start.elapsed().as_millis(), "migration plan applied");
+                dataflow_state.materializations.finish_recording(true);
                 Ok(())
             }
             Err(e) => {
                 error!(error = %e, "migration plan apply failed");
+                dataflow_state.materializations.finish_recording(false);
                 Err(ReadySetError::MigrationApplyFailed {
                     source: Box::new(e),
                 })
@@ -491,6 +496,27 @@ impl DomainMigrationPlan {
         };
         let mut retry_strategy = create_exponential_backoff();
         while let Some(req) = stored.pop_front() {
+            if matches!(req.req, DomainRequest::QueryReplayDone { .. }) {
+                // Consecutive requests to wait for replays to finish are for replays which were
+                // all started before any of them were waited on, so wait for them all at once
+                let mut waits = vec![req];
+                while stored
+                    .front()
+                    .is_some_and(|req| matches!(req.req, DomainRequest::QueryReplayDone { .. }))
+                {
+                    waits.extend(stored.pop_front());
+                }
+                let mainline = &*mainline;
+                future::try_join_all(
+                    waits
+                        .into_iter()
+                        .map(|req| req.apply(mainline, &just_placed_shard_replicas)),
+                )
+                .await?;
+                retry_strategy = create_exponential_backoff();
+                continue;
+            }
+
             if let Some(req) = req.apply(mainline, &just_placed_shard_replicas).await? {
                 // Initializing base table nodes might take a lot of time, so we try to wait using
                 // an exponential backoff strategy.
@@ -561,6 +587,28 @@ impl DomainMigrationPlan {
         }
     }
 
+    /// Make a new, empty [`DomainMigrationPlan`] with the same mode and valid domains as this one.
+    ///
+    /// Messages can be enqueued into the resulting plan to inspect them (via
+    /// [`into_messages`](Self::into_messages)) before adding them to this plan.
+    pub fn scratch(&self) -> Self {
+        Self::new(self.mode, self.domains.clone())
+    }
+
+    /// Consume this plan, returning the ordered list of messages that would have been sent on
+    /// application
+    pub fn into_messages(self) -> Vec<StoredDomainRequest> {
+        self.stored.into()
+    }
+
+    /// Enqueue all of the given messages to be sent, in order, on plan application
+    pub fn add_messages<I>(&mut self, messages: I)
+    where
+        I: IntoIterator<Item = StoredDomainRequest>,
+    {
+        self.stored.extend(messages);
+    }
+
     /// Extend the [`DomainMigrationPlan`] with all the valid domains and messages enqueued in
     /// `other`.
     pub fn extend(&mut self, other: DomainMigrationPlan) {
@@ -569,6 +617,17 @@ impl DomainMigrationPlan {
         self.domains.extend(other.domains);
     }
 
+    /// Returns the set of domains which applying this plan would place or send messages to. These
+    /// are the domains which will be busy with the migration, for example setting up replay paths
+    /// or replaying to fill new materializations.
+    pub fn touched_domains(&self) -> BTreeSet<DomainIndex> {
+        self.place
+            .iter()
+            .map(|place| place.idx)
+            .chain(self.stored.iter().map(|stored| stored.domain))
+            .collect()
+    }
+
     /// Returns list of domains which could not be placed because no worker was available for them
     /// to run on
     pub fn failed_placement(&self) -> &[ReplicaAddress] {
@@ -948,13 +1007,28 @@ impl<'df> Migration<'df> {
     pub(super) async fn commit(self, dry_run: bool) -> ReadySetResult<()> {
         let start = self.start;
 
-        let plan = self
-            .plan()
-            .map_err(|e| ReadySetError::MigrationPlanFailed {
-                source: Box::new(e),
-            })?;
+        // Fetching the sizes of all nodes requires a round trip to every domain, so only do it if
+        // we need to check the materialization budget
+        let node_sizes = if self
+            .dataflow_state
+            .materializations
+            .config
+            .max_total_materialized_bytes
+            .is_some()
+        {
+            Some(self.dataflow_state.node_sizes().await?)
+        } else {
+            None
+        };
+
+        let mut plan =
+            self.plan(node_sizes.as_ref())
+                .map_err(|e| ReadySetError::MigrationPlanFailed {
+                    source: Box::new(e),
+                })?;
         // We skip the actual migration when we run in dry-run mode.
         if dry_run {
+            plan.dataflow_state.materializations.finish_recording(false);
             return Ok(());
         }
         plan.apply().await?;
@@ -973,8 +1047,14 @@ impl<'df> Migration<'df> {
     /// controller to make it happen.
     ///
     /// See the module-level docs for more information on what a migration entails.
+    ///
+    /// If `node_sizes` is provided, new nodes are checked against the materialization budget
+    /// using those as the current sizes of all existing nodes.
     #[allow(clippy::cognitive_complexity)]
-    pub(super) fn plan(self) -> ReadySetResult<MigrationPlan<'df>> {
+    pub(super) fn plan(
+        self,
+        node_sizes: Option<&HashMap<NodeIndex, NodeSize>>,
+    ) -> ReadySetResult<MigrationPlan<'df>> {
         let span = info_span!("plan");
         let _g = span.enter();
 
@@ -993,7 +1073,12 @@ impl<'df> Migration<'df> {
             match change {
                 NodeChanges::Add(new_nodes) => {
                     added += new_nodes.len();
-                    dmp.extend(plan_add_nodes(dataflow_state, new_nodes, &worker)?)
+                    dmp.extend(plan_add_nodes(
+                        dataflow_state,
+                        new_nodes,
+                        &worker,
+                        node_sizes,
+                    )?)
                 }
                 NodeChanges::Drop(drop_nodes) => {
                     dropped += drop_nodes.len();
@@ -1028,6 +1113,7 @@ fn plan_add_nodes(
     dataflow_state: &mut DfState,
     mut new_nodes: HashSet<NodeIndex>,
     worker: &Option<WorkerIdentifier>,
+    node_sizes: Option<&HashMap<NodeIndex, NodeSize>>,
 ) -> ReadySetResult<DomainMigrationPlan> {
     let mut topo = topo_order(dataflow_state, &new_nodes);
 
@@ -1366,6 +1452,14 @@ fn plan_add_nodes(
             let old_edge = dataflow_state.ingredients.find_edge(parent, child).unwrap();
             dataflow_state.ingredients.remove_edge(old_edge);
         } else {
+            if let Some(node_sizes) = node_sizes {
+                dataflow_state.materializations.check_budget(
+                    &dataflow_state.ingredients,
+                    &new_nodes,
+                    node_sizes,
+                )?;
+            }
+
             dataflow_state.domain_nodes = domain_nodes;
 
             // Add any new nodes to existing domains (they'll also ignore all updates for now)
@@ -1385,7 +1479,21 @@ fn plan_add_nodes(
                 &mut dataflow_state.ingredients,
                 &new_nodes,
                 &mut dmp,
+                node_sizes,
             )?;
+            let materialized_existing = dataflow_state
+                .materializations
+                .newly_materialized()
+                .iter()
+                .filter(|ni| !new_nodes.contains(*ni))
+                .map(|ni| ni.index())
+                .collect::<Vec<_>>();
+            if !materialized_existing.is_empty() {
+                info!(
+                    nodes = ?materialized_existing,
+                    "materialized existing non-materialized nodes"
+                );
+            }
 
             dataflow_state
                 .materializations
@@ -1427,6 +1535,24 @@ fn remove_nodes(
             .push(node.local_addr())
     }
 
+    // Tear down any replay paths targeting the removed nodes before removing the nodes themselves
+    let path_removals = dataflow_state
+        .materializations
+        .remove_dropped(&dataflow_state.ingredients);
+    for (domain, tags) in path_removals {
+        trace!(
+            domain_index = %domain.index(),
+            ?tags,
+            "Storing domain request for replay path removals",
+        );
+
+        dmp.stored.push_back(StoredDomainRequest {
+            domain,
+            shard: None,
+            req: DomainRequest::RemoveReplayPaths { tags },
+        });
+    }
+
     // Send messages to domains
     for (domain, nodes) in domain_removals {
         trace!(
@@ -1443,3 +1569,86 @@ fn remove_nodes(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scratch_plan_messages() {
+        let domain = DomainIndex::from(0);
+        let mut dmp = DomainMigrationPlan::new(
+            DomainMigrationMode::Extend,
+            HashMap::from([(
+                domain,
+                DomainSettings {
+                    num_shards: 1,
+                    num_replicas: 1,
+                },
+            )]),
+        );
+
+        let mut scratch = dmp.scratch();
+        for id in 0..2 {
+            scratch
+                .add_message(
+                    domain,
+                    DomainRequest::IsReady {
+                        node: LocalNodeIndex::make(id),
+                    },
+                )
+                .unwrap();
+        }
+        assert!(scratch
+            .add_message(
+                DomainIndex::from(1),
+                DomainRequest::IsReady {
+                    node: LocalNodeIndex::make(0)
+                }
+            )
+            .is_err());
+        assert!(dmp.stored.is_empty());
+
+        let messages = scratch.into_messages();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|m| match &m.req {
+                    DomainRequest::IsReady { node } => node.id(),
+                    _ => panic!("unexpected message: {:?}", m.req),
+                })
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+
+        dmp.add_messages(messages);
+        assert_eq!(dmp.stored.len(), 2);
+    }
+
+    #[test]
+    fn touched_domains() {
+        let settings = DomainSettings {
+            num_shards: 1,
+            num_replicas: 1,
+        };
+        let mut dmp = DomainMigrationPlan::new(
+            DomainMigrationMode::Extend,
+            (0..3).map(|i| (DomainIndex::from(i), settings)).collect(),
+        );
+        assert!(dmp.touched_domains().is_empty());
+
+        for domain in [2, 0, 2] {
+            dmp.add_message(
+                DomainIndex::from(domain),
+                DomainRequest::IsReady {
+                    node: LocalNodeIndex::make(0),
+                },
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            dmp.touched_domains(),
+            BTreeSet::from([DomainIndex::from(0), DomainIndex::from(2)])
+        );
+    }
+}
@@ -51,6 +51,7 @@ mod inner;
 mod keys;
 pub(crate) mod migrate; // crate viz for tests
 mod mir_to_flow;
+mod reachability;
 pub(crate) mod replication;
 pub(crate) mod schema;
 pub(crate) mod sql;
@@ -1874,6 +1875,75 @@ mod tests {
         shutdown_tx.shutdown().await;
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn check_materializations() {
+        let (mut noria, shutdown_tx) = start_simple("check_materializations").await;
+        noria
+            .extend_recipe(
+                ChangeList::from_str(
+                    "CREATE TABLE t1 (x int primary key, y int);
+                     CREATE CACHE q FROM SELECT x FROM t1 WHERE y = ?;",
+                    DataDialect::DEFAULT_MYSQL,
+                )
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let violations = noria.check_materializations().await.unwrap();
+        assert!(violations.is_empty(), "{violations:?}");
+
+        shutdown_tx.shutdown().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn materialization_access_stats() {
+        let (mut noria, shutdown_tx) = start_simple("materialization_access_stats").await;
+        noria
+            .extend_recipe(
+                ChangeList::from_str(
+                    "CREATE TABLE t1 (x int primary key, y int);
+                     CREATE CACHE q FROM SELECT x FROM t1 WHERE y = ?;",
+                    DataDialect::DEFAULT_MYSQL,
+                )
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let mut view = noria.view("q").await.unwrap().into_reader_handle().unwrap();
+        let reader = *view.node();
+
+        let stats = noria.materialization_access_stats().await.unwrap();
+        assert_eq!(stats.sample_rate, 1);
+        assert!(stats.lookups.contains_key(&reader));
+        assert!(stats.lookups.values().all(|n| *n == 0));
+
+        view.lookup(&[1.into()], true).await.unwrap();
+        view.lookup(&[2.into()], true).await.unwrap();
+        let stats = noria.materialization_access_stats().await.unwrap();
+        // A lookup which misses in the reader is retried once the upquery fills the hole, so each
+        // read might be counted more than once
+        assert!(stats.lookups[&reader] >= 2, "{stats:?}");
+        // The upqueries for the misses have to look up into the base table
+        assert!(
+            stats.lookups.iter().any(|(ni, n)| *ni != reader && *n > 0),
+            "{stats:?}"
+        );
+
+        noria.reset_materialization_access_stats().await.unwrap();
+        let stats = noria.materialization_access_stats().await.unwrap();
+        assert!(stats.lookups.values().all(|n| *n == 0), "{stats:?}");
+
+        view.lookup(&[1.into()], true).await.unwrap();
+        assert_eq!(
+            noria.materialization_access_stats().await.unwrap().lookups[&reader],
+            1
+        );
+
+        shutdown_tx.shutdown().await;
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn min_persisted_replication_offset() {
         let (mut noria, shutdown_tx) = start_simple("min_persisted_replication_offset").await;
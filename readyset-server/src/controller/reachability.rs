@@ -0,0 +1,110 @@
+//! Helpers for computing which nodes in a graph are reachable from a given node.
+
+use std::collections::HashSet;
+
+use petgraph::graph::NodeIndex;
+use petgraph::{Direction, Graph};
+
+/// Returns the set of all nodes reachable from `seed` by only following edges in `direction`,
+/// including `seed` itself.
+///
+/// With [`Direction::Incoming`] these are `seed` and all of its ancestors, and with
+/// [`Direction::Outgoing`] `seed` and all of its descendants.
+pub(crate) fn reachable<N, E>(
+    graph: &Graph<N, E>,
+    seed: NodeIndex,
+    direction: Direction,
+) -> HashSet<NodeIndex> {
+    reachable_from_any(graph, [seed], direction)
+}
+
+/// Returns the set of all nodes reachable from any of `seeds` by only following edges in
+/// `direction`, including the seeds themselves.
+///
+/// This visits each node at most once, no matter how many of the seeds it's reachable from.
+pub(crate) fn reachable_from_any<N, E, I>(
+    graph: &Graph<N, E>,
+    seeds: I,
+    direction: Direction,
+) -> HashSet<NodeIndex>
+where
+    I: IntoIterator<Item = NodeIndex>,
+{
+    let mut nodes = HashSet::new();
+    let mut stack = seeds.into_iter().collect::<Vec<_>>();
+    while let Some(node) = stack.pop() {
+        if nodes.insert(node) {
+            for next in graph.neighbors_directed(node, direction) {
+                if !nodes.contains(&next) {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+
+    nodes
+}
+
+/// Returns `seed` along with all of its ancestors and all of its descendants.
+///
+/// Note that this is *not* the same as the set of all nodes connected to `seed`, since it
+/// excludes (for example) siblings of `seed` which are only reachable by following edges in both
+/// directions.
+#[allow(dead_code)] // only used in tests for now
+pub(crate) fn reachable_bidirectional<N, E>(
+    graph: &Graph<N, E>,
+    seed: NodeIndex,
+) -> HashSet<NodeIndex> {
+    let mut nodes = reachable(graph, seed, Direction::Incoming);
+    nodes.extend(reachable(graph, seed, Direction::Outgoing));
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a -> b -> d, a -> c, e -> b
+    fn graph() -> (Graph<(), ()>, [NodeIndex; 5]) {
+        let mut g = Graph::new();
+        let [a, b, c, d, e] = [(); 5].map(|_| g.add_node(()));
+        for (from, to) in [(a, b), (b, d), (a, c), (e, b)] {
+            g.add_edge(from, to, ());
+        }
+        (g, [a, b, c, d, e])
+    }
+
+    #[test]
+    fn reachable_in_direction() {
+        let (g, [a, b, c, d, e]) = graph();
+        assert_eq!(
+            reachable(&g, b, Direction::Incoming),
+            HashSet::from([a, b, e])
+        );
+        assert_eq!(
+            reachable(&g, a, Direction::Outgoing),
+            HashSet::from([a, b, c, d])
+        );
+        assert_eq!(reachable(&g, d, Direction::Outgoing), HashSet::from([d]));
+    }
+
+    #[test]
+    fn reachable_from_any_seed() {
+        let (g, [a, b, c, d, e]) = graph();
+        assert_eq!(
+            reachable_from_any(&g, [c, d], Direction::Incoming),
+            HashSet::from([a, b, c, d, e])
+        );
+        assert_eq!(
+            reachable_from_any(&g, [b, e], Direction::Outgoing),
+            HashSet::from([b, d, e])
+        );
+        assert!(reachable_from_any(&g, [], Direction::Outgoing).is_empty());
+    }
+
+    #[test]
+    fn reachable_in_both_directions() {
+        let (g, [a, b, _c, d, e]) = graph();
+        assert_eq!(reachable_bidirectional(&g, b), HashSet::from([a, b, d, e]));
+    }
+}
@@ -39,22 +39,27 @@ use readyset_client::builders::{
     ReaderHandleBuilder, ReusedReaderHandleBuilder, TableBuilder, ViewBuilder,
 };
 use readyset_client::consensus::{Authority, AuthorityControl};
-use readyset_client::debug::info::{GraphInfo, MaterializationInfo, NodeSize};
+use readyset_client::debug::info::{
+    FrontierSimulation, GraphInfo, MaterializationAccessStats, MaterializationImpact,
+    MaterializationInfo, MaterializationReport, MaterializationTemplate, MaterializationViolation,
+    MigrationDryRunReport, NodeHealth, NodeSize,
+};
 use readyset_client::debug::stats::{DomainStats, GraphStats, NodeStats};
 #[cfg(feature = "failure_injection")]
 use readyset_client::failpoints;
-use readyset_client::internal::{MaterializationStatus, ReplicaAddress};
+use readyset_client::internal::{Index, MaterializationStatus, ReplicaAddress};
 use readyset_client::metrics::recorded;
 use readyset_client::recipe::changelist::{Change, ChangeList};
 use readyset_client::recipe::{CacheExpr, ExtendRecipeSpec};
 use readyset_client::{
-    PersistencePoint, SingleKeyEviction, TableReplicationStatus, TableStatus, ViewCreateRequest,
-    ViewFilter, ViewRequest, ViewSchema,
+    GraphvizOptions, PersistencePoint, SingleKeyEviction, TableReplicationStatus, TableStatus,
+    ViewCreateRequest, ViewFilter, ViewRequest, ViewSchema,
 };
 use readyset_data::{DfValue, Dialect};
 use readyset_errors::{
-    internal, internal_err, invariant_eq, NodeType, ReadySetError, ReadySetResult,
+    bad_request_err, internal, internal_err, invariant_eq, NodeType, ReadySetError, ReadySetResult,
 };
+use regex::Regex;
 use replication_offset::{ReplicationOffset, ReplicationOffsets};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -66,7 +71,9 @@ use super::migrate::DomainSettings;
 use super::replication::ReplicationStrategy;
 use super::sql::Recipe;
 use crate::controller::domain_handle::DomainHandle;
-use crate::controller::migrate::materialization::Materializations;
+use crate::controller::migrate::materialization::{
+    query_nodes, FrontierStrategy, Materializations, SizeEstimator,
+};
 use crate::controller::migrate::scheduling::Scheduler;
 use crate::controller::migrate::{routing, DomainMigrationMode, DomainMigrationPlan, Migration};
 use crate::controller::sql::{RecipeExpr, Schema};
@@ -80,7 +87,7 @@ use crate::worker::WorkerRequestKind;
 
 mod graphviz;
 
-pub(in crate::controller) use self::graphviz::Graphviz;
+pub(in crate::controller) use self::graphviz::{dot_to_svg, Graphviz};
 
 /// Number of concurrent requests to make when making multiple simultaneous requests to domains (eg
 /// for replication offsets)
@@ -666,7 +673,9 @@ impl DfState {
         }))
     }
 
-    /// Get statistics about the time spent processing different parts of the graph.
+    /// Get statistics about the time spent processing different parts of the graph, about the
+    /// materializations which are no longer used by any reader, and about how much memory is used
+    /// on behalf of each query.
     pub(super) async fn get_statistics(&self) -> ReadySetResult<GraphStats> {
         trace!("asked to get statistics");
         let workers = &self.workers;
@@ -690,7 +699,22 @@ impl DfState {
             );
         }
 
-        Ok(GraphStats { domains })
+        let node_sizes = self.node_sizes().await?;
+        let unreachable_materializations = self
+            .materializations
+            .unreachable(&self.ingredients)
+            .into_iter()
+            .map(|ni| (ni, node_sizes.get(&ni).copied().unwrap_or_default()))
+            .collect();
+        let memory_by_query = self
+            .materializations
+            .memory_by_query(&self.ingredients, &node_sizes);
+
+        Ok(GraphStats {
+            domains,
+            unreachable_materializations,
+            memory_by_query,
+        })
     }
 
     pub(super) fn get_instances(&self) -> Vec<(WorkerIdentifier, bool)> {
@@ -700,27 +724,54 @@ impl DfState {
             .collect()
     }
 
-    pub(super) fn graphviz(
+    fn graphviz_with_options(
         &self,
-        detailed: bool,
+        options: &GraphvizOptions,
         node_sizes: Option<HashMap<NodeIndex, NodeSize>>,
-    ) -> String {
-        Graphviz {
-            graph: &self.ingredients,
-            detailed,
-            node_sizes,
-            materializations: &self.materializations,
+        node_health: Option<HashMap<NodeIndex, NodeHealth>>,
+    ) -> ReadySetResult<Graphviz<'_>> {
+        let name_filter = options
+            .name_filter
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| bad_request_err(format!("Invalid node name filter: {e}")))?;
+
+        Ok(Graphviz {
+            detailed: options.detailed,
+            node_sizes: node_sizes.map(|sizes| Box::new(sizes) as Box<dyn SizeEstimator>),
             domain_nodes: Some(&self.domain_nodes),
-            reachable_from: None,
-        }
-        .to_string()
+            collapse_query_through: options.collapse_query_through,
+            node_url: options.node_url.as_deref(),
+            show_redundant_partial: options.show_redundant_partial,
+            name_filter,
+            node_shape: options.node_shape,
+            show_description_hash: options.show_description_hash,
+            anonymize_names: options.anonymize_names,
+            resolve_index_columns: options.resolve_index_columns,
+            only_domain: options.only_domain,
+            node_health,
+            ..Graphviz::new(&self.ingredients, &self.materializations)
+        })
+    }
+
+    pub(super) fn graphviz(
+        &self,
+        options: &GraphvizOptions,
+        node_sizes: Option<HashMap<NodeIndex, NodeSize>>,
+        node_health: Option<HashMap<NodeIndex, NodeHealth>>,
+    ) -> ReadySetResult<String> {
+        Ok(self
+            .graphviz_with_options(options, node_sizes, node_health)?
+            .to_string())
     }
 
     pub(super) fn graphviz_for_query(
         &self,
         query: &Relation,
-        detailed: bool,
+        options: &GraphvizOptions,
         node_sizes: Option<HashMap<NodeIndex, NodeSize>>,
+        node_health: Option<HashMap<NodeIndex, NodeHealth>>,
     ) -> ReadySetResult<String> {
         let ni = self
             .recipe
@@ -733,12 +784,8 @@ impl DfState {
             })?;
 
         Ok(Graphviz {
-            graph: &self.ingredients,
-            detailed,
-            node_sizes,
-            materializations: &self.materializations,
-            domain_nodes: Some(&self.domain_nodes),
             reachable_from: Some((ni, Direction::Incoming)),
+            ..self.graphviz_with_options(options, node_sizes, node_health)?
         }
         .to_string())
     }
@@ -806,12 +853,115 @@ impl DfState {
                     node_description,
                     size: sizes.get(&node_index).cloned().unwrap_or_default(),
                     partial: self.materializations.is_partial(node_index),
+                    kind: self.materializations.get_kind(node_index),
                     indexes,
                 },
             )
             .collect())
     }
 
+    /// Check all materializations in the graph for invariant violations, returning a list of any
+    /// violations that were found
+    pub(super) fn check_materializations(&self) -> ReadySetResult<Vec<MaterializationViolation>> {
+        self.materializations.check(&self.ingredients)
+    }
+
+    /// Summarize every materialization in the graph, along with the current size of each one,
+    /// listing the `top_n` largest materializations. This doesn't change the graph.
+    pub(super) async fn materialization_report(
+        &self,
+        top_n: usize,
+    ) -> ReadySetResult<MaterializationReport> {
+        let node_sizes = self.node_sizes().await?;
+        self.materializations
+            .report(&self.ingredients, &node_sizes, top_n)
+    }
+
+    /// Freeze the materialization decisions made for the query named `query` into a
+    /// [`MaterializationTemplate`], which can be added with
+    /// [`add_materialization_template`](Self::add_materialization_template) to apply the same
+    /// decisions to structurally identical queries created later.
+    ///
+    /// The template covers the query's reader and all of its ancestors which aren't base tables and
+    /// don't belong to any other query.
+    pub(super) fn materialization_template(
+        &self,
+        query: &Relation,
+    ) -> ReadySetResult<MaterializationTemplate> {
+        let reader = self
+            .recipe
+            .node_addr_for(query)
+            .ok()
+            .or_else(|| self.views().get(query).copied())
+            .and_then(|leaf| self.find_reader_for(leaf, query, &Default::default()))
+            .ok_or_else(|| ReadySetError::QueryNotFound {
+                name: query.display_unquoted().to_string(),
+            })?;
+        let nodes = query_nodes(&self.ingredients, reader);
+        Ok(self
+            .materializations
+            .export_template(&self.ingredients, &nodes))
+    }
+
+    /// Apply the materialization decisions in `template` to the new nodes of all future
+    /// migrations which have the same shape as it, instead of analyzing them from scratch. If a
+    /// template's decisions turn out to be inconsistent with the materializations that already
+    /// exist, the migration falls back to the full analysis.
+    pub(super) fn add_materialization_template(&mut self, template: MaterializationTemplate) {
+        self.materializations.add_template(template)
+    }
+
+    /// Return the number of lookups into each materialized node since it was materialized or since
+    /// the last call to [`reset_materialization_access_stats`], along with the rate at which
+    /// lookups are sampled
+    ///
+    /// [`reset_materialization_access_stats`]: DfState::reset_materialization_access_stats
+    pub(super) async fn materialization_access_stats(
+        &self,
+    ) -> ReadySetResult<MaterializationAccessStats> {
+        let node_lookups = self.node_lookups().await?;
+        Ok(self
+            .materializations
+            .access_stats(&node_lookups, self.domain_config.lookup_sample_rate))
+    }
+
+    /// Return the set of nodes which are currently placed beyond the materialization frontier
+    pub(super) fn shallow_nodes(&self) -> HashSet<NodeIndex> {
+        self.materializations.shallow_nodes(&self.ingredients)
+    }
+
+    /// Compute which materialized nodes would be evictable if the materialization frontier were
+    /// placed with the frontier strategy named by `strategy`, and how many bytes of state they
+    /// currently hold, without changing the graph
+    pub(super) async fn simulate_frontier(
+        &self,
+        strategy: &str,
+    ) -> ReadySetResult<FrontierSimulation> {
+        let strategy = strategy.parse::<FrontierStrategy>()?;
+        let node_sizes = self.node_sizes().await?;
+        Ok(self
+            .materializations
+            .simulate_frontier(&self.ingredients, strategy, &node_sizes))
+    }
+
+    /// Return the replay paths which fill the indices of the given node, sorted by tag
+    pub(super) fn replay_paths_for(&self, node: NodeIndex) -> Vec<(Tag, Index, Vec<NodeIndex>)> {
+        self.materializations.replay_paths_for(node)
+    }
+
+    /// Return the materialized nodes which the replay paths for the given node are sourced from,
+    /// in sorted order
+    pub(super) fn replay_sources_for(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        self.materializations.replay_sources_for(node)
+    }
+
+    /// Reset the lookup counts for all materialized nodes to zero
+    pub(super) async fn reset_materialization_access_stats(&self) -> ReadySetResult<()> {
+        let node_lookups = self.node_lookups().await?;
+        self.materializations.reset_access_stats(&node_lookups);
+        Ok(())
+    }
+
     /// Issue all of `requests` to their corresponding domains asynchronously, and return a stream
     /// of the results, consisting of shard, then replica, then result (potentially in a different
     /// order).
@@ -1147,6 +1297,95 @@ impl DfState {
         Ok(res)
     }
 
+    /// Return a map of node indices to the total number of lookups performed into their
+    /// materializations, summed across all shards and replicas.
+    pub(super) async fn node_lookups(&self) -> ReadySetResult<HashMap<NodeIndex, u64>> {
+        let requests = self
+            .domains
+            .keys()
+            .map(|di| (*di, DomainRequest::RequestNodeLookups))
+            .collect::<Vec<_>>();
+        let lookups_per_domain: Vec<Array2<Option<Vec<(NodeIndex, u64)>>>> = stream::iter(requests)
+            .map(move |(domain, request)| {
+                self.domains[&domain]
+                    .send_to_healthy::<Vec<(NodeIndex, u64)>>(request, &self.workers)
+            })
+            .buffer_unordered(CONCURRENT_REQUESTS)
+            .try_collect()
+            .await?;
+
+        let mut res = HashMap::new();
+        for (node_index, lookups) in lookups_per_domain
+            .into_iter()
+            .flat_map(|per_shard| per_shard.into_cells().into_iter().flatten().flatten())
+        {
+            *res.entry(node_index).or_default() += lookups;
+        }
+        Ok(res)
+    }
+
+    /// Return a map of (node index, index) pairs to the approximate size in bytes of that index.
+    ///
+    /// Nodes whose state can't report the size of each of its indices have their total size split
+    /// evenly between their indices - see [`Materializations::index_sizes`].
+    pub(super) async fn index_sizes(&self) -> ReadySetResult<HashMap<(NodeIndex, Index), u64>> {
+        let node_sizes = self.node_sizes().await?;
+        let requests = self
+            .domains
+            .keys()
+            .map(|di| (*di, DomainRequest::RequestIndexSizes))
+            .collect::<Vec<_>>();
+        let sizes_per_domain: Vec<Array2<Option<Vec<(NodeIndex, Vec<(Index, u64)>)>>>> =
+            stream::iter(requests)
+                .map(move |(domain, request)| {
+                    self.domains[&domain].send_to_healthy::<Vec<(NodeIndex, Vec<(Index, u64)>)>>(
+                        request,
+                        &self.workers,
+                    )
+                })
+                .buffer_unordered(CONCURRENT_REQUESTS)
+                .try_collect()
+                .await?;
+
+        let reported = sizes_per_domain
+            .into_iter()
+            .flat_map(|per_shard| per_shard.into_cells().into_iter().flatten().flatten())
+            .collect();
+        Ok(self
+            .materializations
+            .index_sizes(&self.ingredients, &node_sizes, reported))
+    }
+
+    /// Query every domain for the [`NodeHealth`] of each of its nodes. For nodes in sharded
+    /// domains, the least healthy state reported by any shard is returned.
+    pub(super) async fn node_health(&self) -> ReadySetResult<HashMap<NodeIndex, NodeHealth>> {
+        let requests = self
+            .domains
+            .keys()
+            .map(|di| (*di, DomainRequest::RequestNodeHealth))
+            .collect::<Vec<_>>();
+        let health_per_domain: Vec<Array2<Option<Vec<(NodeIndex, NodeHealth)>>>> =
+            stream::iter(requests)
+                .map(move |(domain, request)| {
+                    self.domains[&domain]
+                        .send_to_healthy::<Vec<(NodeIndex, NodeHealth)>>(request, &self.workers)
+                })
+                .buffer_unordered(CONCURRENT_REQUESTS)
+                .try_collect()
+                .await?;
+
+        let mut res = HashMap::new();
+        for (ni, health) in health_per_domain
+            .into_iter()
+            .flat_map(|per_shard| per_shard.into_cells().into_iter().flatten().flatten())
+        {
+            res.entry(ni)
+                .and_modify(|h: &mut NodeHealth| *h = (*h).max(health))
+                .or_insert(health);
+        }
+        Ok(res)
+    }
+
     // ** Modify operations **
 
     /// Perform a new query schema migration.
@@ -1359,10 +1598,23 @@ impl DfState {
                 .push(node.local_addr())
         }
 
+        // Tear down any replay paths targeting the removed nodes before removing the nodes
+        // themselves
+        let path_removals = self.materializations.remove_dropped(&self.ingredients);
+        let removals = path_removals
+            .into_iter()
+            .map(|(domain, tags)| (domain, DomainRequest::RemoveReplayPaths { tags }))
+            .chain(
+                domain_removals
+                    .into_iter()
+                    .map(|(domain, nodes)| (domain, DomainRequest::RemoveNodes { nodes })),
+            );
+
         // Send messages to domains
-        for (domain, nodes) in domain_removals {
+        for (domain, req) in removals {
             trace!(
                 domain_index = %domain.index(),
+                ?req,
                 "Notifying domain of node removals",
             );
 
@@ -1374,7 +1626,7 @@ impl DfState {
                     shard: 0,
                     replica: 0,
                 })?
-                .send_to_healthy::<()>(DomainRequest::RemoveNodes { nodes }, &self.workers)
+                .send_to_healthy::<()>(req, &self.workers)
                 .await
             {
                 // The worker failing is an even more efficient way to remove nodes.
@@ -1592,6 +1844,43 @@ impl DfState {
         }
     }
 
+    /// Perform a dry run of the given recipe changes, returning the effect they would have on the
+    /// materializations of nodes which already exist in the graph.
+    ///
+    /// This updates the graph and materializations of `self` as though the migration had been
+    /// applied (without telling any domains about it), so it must only be called on a copy of the
+    /// live dataflow state.
+    pub(super) async fn migration_impact(
+        &mut self,
+        recipe_spec: ExtendRecipeSpec<'_>,
+    ) -> ReadySetResult<MaterializationImpact> {
+        let materializations = self.materializations.clone();
+        let graph = self.ingredients.clone();
+        self.extend_recipe(recipe_spec, true).await?;
+        Ok(materializations.impact_on_existing(&graph, &self.materializations, &self.ingredients))
+    }
+
+    /// Perform a dry run of the given recipe changes, returning the full materialization plan the
+    /// migration would commit, along with an estimate of how much memory it would use.
+    ///
+    /// Like [`Self::migration_impact`], this must only be called on a copy of the live dataflow
+    /// state.
+    pub(super) async fn dry_run_report(
+        &mut self,
+        recipe_spec: ExtendRecipeSpec<'_>,
+    ) -> ReadySetResult<MigrationDryRunReport> {
+        let node_sizes = self.node_sizes().await?;
+        let materializations = self.materializations.clone();
+        let graph = self.ingredients.clone();
+        self.extend_recipe(recipe_spec, true).await?;
+        materializations.dry_run_report(
+            &graph,
+            &self.materializations,
+            &self.ingredients,
+            &node_sizes,
+        )
+    }
+
     /// Return 1 if one or more expressions were removed, else return 0.
     /// Someday we may want to return # expressions (and aliases?) dropped.
     pub(super) async fn remove_query(&mut self, query_name: &Relation) -> ReadySetResult<u64> {
@@ -1790,7 +2079,7 @@ impl DfState {
             .extend(&mut self.ingredients, &new, &dmp)?;
 
         self.materializations
-            .commit(&mut self.ingredients, &new, &mut dmp)?;
+            .commit(&mut self.ingredients, &new, &mut dmp, None)?;
 
         Ok(dmp)
     }
@@ -1817,6 +2106,10 @@ impl DfState {
             .redundant_partial
             .clone_from(&self.materializations.redundant_partial);
         new_materializations.tag_generator = self.materializations.tag_generator;
+        new_materializations.migration_generator = self.materializations.migration_generator;
+        new_materializations
+            .tag_migrations
+            .clone_from(&self.materializations.tag_migrations);
         new_materializations.config = self.materializations.config.clone();
 
         self.materializations = new_materializations;
@@ -1977,6 +2270,15 @@ impl DfStateHandle {
         .map_err(|_| internal_err!("Unable to update state"))?;
 
         let mut state_guard = self.reader.write().await;
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            let diff = state_guard
+                .state
+                .materializations
+                .diff(&new_state.materializations);
+            if !diff.is_empty() {
+                debug!(?diff, "committing changes to materializations");
+            }
+        }
         state_guard.replace(new_state.clone());
         Ok(())
     }
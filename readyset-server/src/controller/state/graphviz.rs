@@ -1,15 +1,28 @@
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Display};
 
-use dataflow::prelude::{Graph, NodeIndex};
+use dataflow::prelude::{Graph, MaterializationStatus, Node, NodeIndex};
 use dataflow::{DomainIndex, NodeMap};
+#[cfg(feature = "graphviz_svg")]
+use layout::backends::svg::SVGWriter;
+#[cfg(feature = "graphviz_svg")]
+use layout::gv::{DotParser, GraphBuilder};
 use lazy_static::lazy_static;
 use petgraph::Direction;
-use readyset_client::debug::info::NodeSize;
+use readyset_client::debug::info::NodeHealth;
+use readyset_client::GraphvizNodeShape;
+#[cfg(feature = "graphviz_svg")]
+use readyset_errors::internal_err;
+#[cfg(not(feature = "graphviz_svg"))]
+use readyset_errors::unsupported;
+use readyset_errors::ReadySetResult;
 use regex::Regex;
+use sha1::{Digest, Sha1};
 
-use crate::controller::migrate::materialization::Materializations;
+use crate::controller::keys;
+use crate::controller::migrate::materialization::{Materializations, SizeEstimator};
+use crate::controller::reachability::reachable;
 
 #[allow(clippy::unwrap_used)] // regex is hardcoded and valid
 fn sanitize(s: &str) -> Cow<str> {
@@ -19,13 +32,270 @@ fn sanitize(s: &str) -> Cow<str> {
     SANITIZE_RE.replace_all(s, "\\$1")
 }
 
+/// Render `dot`, the [`Display`] output of a [`Graphviz`], to SVG bytes.
+///
+/// The layout is done in-process, so this doesn't need graphviz to be installed on the server. It
+/// is CPU-bound and can take a while for large graphs though, so callers on the async runtime
+/// should run it via [`tokio::task::spawn_blocking`], without holding any locks.
+#[cfg(feature = "graphviz_svg")]
+pub(in crate::controller) fn dot_to_svg(dot: &str) -> ReadySetResult<Vec<u8>> {
+    let graph = DotParser::new(dot)
+        .process()
+        .map_err(|e| internal_err!("Could not parse graphviz output: {e}"))?;
+    let mut builder = GraphBuilder::new();
+    builder.visit_graph(&graph);
+    let mut svg = SVGWriter::new();
+    builder.get().do_it(false, false, false, &mut svg);
+    Ok(svg.finalize().into_bytes())
+}
+
+/// Render `dot`, the [`Display`] output of a [`Graphviz`], to SVG bytes.
+///
+/// Always returns an error, since ReadySet was built without the `graphviz_svg` feature.
+#[cfg(not(feature = "graphviz_svg"))]
+pub(in crate::controller) fn dot_to_svg(_dot: &str) -> ReadySetResult<Vec<u8>> {
+    unsupported!(
+        "Rendering the graph to SVG requires ReadySet to be built with the `graphviz_svg` feature"
+    )
+}
+
+/// Returns a short hash of the description of a node, which is the same every time for the same
+/// description
+fn description_hash(description: &str) -> String {
+    Sha1::digest(description.as_bytes())[..4]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Returns an anonymized stand-in for the name of `node`, eg `table_0123456789abcdef` for a base
+/// table. This is derived from a hash of the name salted with `salt`, so it's the same for the same
+/// name within one render of the graph (which shares a salt), but can't be matched against the
+/// hashes of guessed names, or correlated across renders.
+fn anonymized_name(node: &Node, salt: &[u8]) -> String {
+    let kind = if node.is_base() {
+        "table"
+    } else if node.is_reader() {
+        "query"
+    } else {
+        "view"
+    };
+    let hash = Sha1::new()
+        .chain_update(salt)
+        .chain_update(node.name().display_unquoted().to_string().as_bytes())
+        .finalize();
+    format!(
+        "{kind}_{}",
+        hash[..8]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()
+    )
+}
+
+/// Build the URL for the node with the given index from a `node_url` template, by replacing
+/// every occurrence of `{index}` with the index of the node, and escape the result so that it can
+/// be placed inside a double-quoted dot string
+fn node_url(template: &str, index: NodeIndex) -> String {
+    template
+        .replace("{index}", &index.index().to_string())
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+/// Returns the name of the base table column which column `col` of `ni` originates from, as
+/// `table.column`, or `None` if it doesn't originate from a base table column (for example because
+/// it's computed by a projection or an aggregate). If the column originates from more than one
+/// base table column (for example the key of a join), the first one found is used.
+///
+/// If `anonymization_salt` is given, the table is named by its [`anonymized_name`] and the column
+/// by its position in the table, eg `table_0123456789abcdef.col2`.
+fn source_column_name(
+    graph: &Graph,
+    ni: NodeIndex,
+    col: usize,
+    anonymization_salt: Option<&[u8]>,
+) -> Option<String> {
+    keys::provenance_of(graph, ni, &[col])
+        .ok()?
+        .into_iter()
+        .find_map(|path| {
+            // the column originates at the last node on the path which still has it
+            let (source, cols) = path.iter().rfind(|(_, cols)| cols[0].is_some())?;
+            let node = &graph[*source];
+            if !node.is_base() {
+                return None;
+            }
+            let col = cols[0]?;
+            let column = node.columns().get(col)?;
+            Some(match anonymization_salt {
+                Some(salt) => format!("{}.col{col}", anonymized_name(node, salt)),
+                None => format!("{}.{}", node.name().name, column.name()),
+            })
+        })
+}
+
+/// Returns the color to outline a node with the given health in
+fn health_color(health: NodeHealth) -> &'static str {
+    match health {
+        NodeHealth::Ready => "#2E7D32",
+        NodeHealth::Replaying => "#F9A825",
+        NodeHealth::NotReady => "#C62828",
+    }
+}
+
 pub(in crate::controller) struct Graphviz<'a> {
     pub graph: &'a Graph,
     pub detailed: bool,
-    pub node_sizes: Option<HashMap<NodeIndex, NodeSize>>,
+    /// If set, the source of the sizes to label materialized nodes with
+    pub node_sizes: Option<Box<dyn SizeEstimator + 'a>>,
     pub materializations: &'a Materializations,
     pub domain_nodes: Option<&'a HashMap<DomainIndex, NodeMap<NodeIndex>>>,
     pub reachable_from: Option<(NodeIndex, Direction)>,
+    /// Render chains of consecutive non-materialized query-through nodes as a single labeled edge
+    /// between the surrounding nodes
+    pub collapse_query_through: bool,
+    /// If set, a template for a URL to attach to each node, for use by interactive viewers of the
+    /// rendered graph. Every occurrence of `{index}` in the template is replaced with the index of
+    /// the node
+    pub node_url: Option<&'a str>,
+    /// Pair each full node created as a duplicate of a partial node during rerouting with the
+    /// partial node it duplicates, via a dashed "redundant of" edge, and annotate the duplicate as
+    /// a rerouting artifact
+    pub show_redundant_partial: bool,
+    /// If set, only render nodes whose name matches this regex, along with their direct neighbors
+    /// so that the edges to and from the matching nodes make sense. If `reachable_from` is also
+    /// set, only nodes which are both reachable and match (or neighbor a match) are rendered.
+    pub name_filter: Option<Regex>,
+    /// The shape to draw nodes with in detailed mode
+    pub node_shape: GraphvizNodeShape,
+    /// Label each node with a short hash of its description, which changes if and only if the
+    /// description does (for example because the node gained an index)
+    pub show_description_hash: bool,
+    /// Replace the name of every node with an anonymized token derived from a salted hash of that
+    /// name (see [`anonymized_name`]), name columns by their position, and describe operators only
+    /// by their kind, so that the structure of the graph can be shared without revealing the
+    /// schema, the names of queries, or any literals in them.
+    pub anonymize_names: bool,
+    /// If set, only render the nodes in this domain (according to `domain_nodes`), along with
+    /// stubs for their direct neighbors in other domains so that the edges into and out of the
+    /// domain make sense. Combined with `reachable_from` or `name_filter`, only nodes which also
+    /// pass those filters are rendered.
+    pub only_domain: Option<DomainIndex>,
+    /// If set, outline each node in a color indicating its [`NodeHealth`] (see [`health_color`]).
+    /// Nodes with no entry are rendered as usual.
+    pub node_health: Option<HashMap<NodeIndex, NodeHealth>>,
+    /// Describe the columns of each index on a materialized node by the base table column they
+    /// originate from (see [`source_column_name`]), falling back to the name of the column in the
+    /// node if it can't be traced back to one
+    pub resolve_index_columns: bool,
+    /// The salt for the hashes of anonymized names, which is random for each [`Graphviz`]
+    pub anonymization_salt: [u8; 16],
+}
+
+impl<'a> Graphviz<'a> {
+    /// Construct a detailed [`Graphviz`] of the entire graph, with no node sizes or domains, and
+    /// all other options disabled
+    pub(in crate::controller) fn new(
+        graph: &'a Graph,
+        materializations: &'a Materializations,
+    ) -> Self {
+        Self {
+            graph,
+            detailed: true,
+            node_sizes: None,
+            materializations,
+            domain_nodes: None,
+            reachable_from: None,
+            collapse_query_through: false,
+            node_url: None,
+            show_redundant_partial: false,
+            name_filter: None,
+            node_shape: GraphvizNodeShape::default(),
+            show_description_hash: false,
+            anonymize_names: false,
+            only_domain: None,
+            node_health: None,
+            resolve_index_columns: false,
+            anonymization_salt: rand::random(),
+        }
+    }
+
+    /// Returns the salt to anonymize names with, if `anonymize_names` is set
+    fn anonymization_salt(&self) -> Option<&[u8]> {
+        self.anonymize_names
+            .then_some(self.anonymization_salt.as_slice())
+    }
+
+    /// Returns the nodes in `nodes` whose name matches `filter`, along with any of their direct
+    /// neighbors which are also in `nodes`
+    fn matching_with_neighbors(
+        &self,
+        filter: &Regex,
+        nodes: &HashSet<NodeIndex>,
+    ) -> HashSet<NodeIndex> {
+        let mut matching = HashSet::new();
+        for &ni in nodes {
+            if filter.is_match(&self.graph[ni].name().display_unquoted().to_string()) {
+                matching.insert(ni);
+                matching.extend(
+                    self.graph
+                        .neighbors_undirected(ni)
+                        .filter(|neighbor| nodes.contains(neighbor)),
+                );
+            }
+        }
+        matching
+    }
+
+    /// Returns the nodes in `nodes` which are in `domain`, along with the set of their direct
+    /// neighbors in `nodes` which are in other domains, to render as stubs
+    fn in_domain_with_neighbors(
+        &self,
+        domain: DomainIndex,
+        nodes: &HashSet<NodeIndex>,
+        domain_for_node: &HashMap<NodeIndex, DomainIndex>,
+    ) -> (HashSet<NodeIndex>, HashSet<NodeIndex>) {
+        let in_domain = nodes
+            .iter()
+            .copied()
+            .filter(|ni| domain_for_node.get(ni) == Some(&domain))
+            .collect::<HashSet<_>>();
+        let stubs = in_domain
+            .iter()
+            .flat_map(|&ni| self.graph.neighbors_undirected(ni))
+            .filter(|neighbor| nodes.contains(neighbor) && !in_domain.contains(neighbor))
+            .collect::<HashSet<_>>();
+        (in_domain, stubs)
+    }
+
+    /// Can the given node be collapsed into an edge when `collapse_query_through` is enabled?
+    ///
+    /// This is the case for non-materialized query-through nodes with exactly one parent and
+    /// exactly one child.
+    fn is_collapsed(&self, ni: NodeIndex) -> bool {
+        if !self.collapse_query_through {
+            return false;
+        }
+
+        let node = &self.graph[ni];
+        node.is_internal()
+            && node.can_query_through()
+            && matches!(
+                self.materializations.get_status(ni, node),
+                MaterializationStatus::Not
+            )
+            && self
+                .graph
+                .neighbors_directed(ni, Direction::Incoming)
+                .count()
+                == 1
+            && self
+                .graph
+                .neighbors_directed(ni, Direction::Outgoing)
+                .count()
+                == 1
+    }
 }
 
 /// Builds a graphviz [dot][] representation of the graph
@@ -36,7 +306,21 @@ pub(in crate::controller) struct Graphviz<'a> {
 impl Display for Graphviz<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let indentln = |f: &mut fmt::Formatter<'_>| f.write_str("    ");
-        let node_sizes = self.node_sizes.clone().unwrap_or_default();
+        let node_sizes = self
+            .node_sizes
+            .as_ref()
+            .map(|estimator| estimator.estimate_all(self.graph))
+            .unwrap_or_default();
+        // map from full duplicates to the partial nodes they duplicate
+        let redundant_of = if self.show_redundant_partial {
+            self.materializations
+                .redundant_partial
+                .iter()
+                .map(|(original, duplicate)| (*duplicate, *original))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
 
         // header.
         writeln!(f, "digraph {{")?;
@@ -46,7 +330,7 @@ impl Display for Graphviz<'_> {
         write!(f, "fontsize=10")?;
         indentln(f)?;
         if self.detailed {
-            writeln!(f, "node [shape=record, fontsize=10]")?;
+            writeln!(f, "node [shape={}, fontsize=10]", self.node_shape)?;
         } else {
             writeln!(
                 f,
@@ -60,22 +344,14 @@ impl Display for Graphviz<'_> {
         }
 
         let nodes = if let Some((ni, dir)) = self.reachable_from {
-            let mut nodes = HashSet::new();
-            let mut stack = vec![ni];
-            while let Some(node) = stack.pop() {
-                if nodes.insert(node) {
-                    for next in self.graph.neighbors_directed(node, dir) {
-                        if !nodes.contains(&next) {
-                            stack.push(next);
-                        }
-                    }
-                }
-            }
-
-            nodes
+            reachable(self.graph, ni, dir)
         } else {
             self.graph.node_indices().collect()
         };
+        let nodes = match &self.name_filter {
+            Some(filter) => self.matching_with_neighbors(filter, &nodes),
+            None => nodes,
+        };
 
         let domain_for_node = self
             .domain_nodes
@@ -83,14 +359,34 @@ impl Display for Graphviz<'_> {
             .flat_map(|m| m.iter())
             .flat_map(|(di, nodes)| nodes.iter().map(|(_, ni)| (*ni, *di)))
             .collect::<HashMap<_, _>>();
-        let mut domains_to_nodes = HashMap::new();
+        let (nodes, stubs) = match self.only_domain {
+            Some(domain) => {
+                let (mut nodes, stubs) =
+                    self.in_domain_with_neighbors(domain, &nodes, &domain_for_node);
+                nodes.extend(stubs.iter().copied());
+                (nodes, stubs)
+            }
+            None => (nodes, HashSet::new()),
+        };
+
+        // emit domains, and the nodes within them, in sorted order so that the output is the same
+        // every time for the same graph
+        let mut domains_to_nodes = BTreeMap::new();
         for ni in &nodes {
-            let domain = domain_for_node.get(ni).copied();
+            // stubs for nodes outside the domain being rendered are drawn outside of any cluster
+            let domain = if stubs.contains(ni) {
+                None
+            } else {
+                domain_for_node.get(ni).copied()
+            };
             domains_to_nodes
                 .entry(domain)
                 .or_insert_with(Vec::new)
                 .push(*ni);
         }
+        for nodes in domains_to_nodes.values_mut() {
+            nodes.sort();
+        }
 
         // node descriptions.
         for (domain, nodes) in domains_to_nodes {
@@ -105,21 +401,96 @@ impl Display for Graphviz<'_> {
                 )?;
             }
             for index in nodes {
+                if stubs.contains(&index) {
+                    indentln(f)?;
+                    write!(
+                        f,
+                        "n{} [shape=plaintext, fontcolor=\"#999999\", label=\"{}",
+                        index.index(),
+                        index.index()
+                    )?;
+                    if let Some(domain) = domain_for_node.get(&index) {
+                        write!(f, " (domain {})", domain.index())?;
+                    }
+                    writeln!(f, "\"]")?;
+                    continue;
+                }
+                if self.is_collapsed(index) {
+                    continue;
+                }
                 let node = &self.graph[index];
                 let materialization_status = self.materializations.get_status(index, node);
+                let indices = self.materializations.indexes_for(index);
+                let column_labels = match indices {
+                    Some(indices) if self.resolve_index_columns => indices
+                        .iter()
+                        .flat_map(|idx| idx.columns.iter().copied())
+                        .filter_map(|col| {
+                            source_column_name(self.graph, index, col, self.anonymization_salt())
+                                .map(|name| (col, name))
+                        })
+                        .collect(),
+                    _ => HashMap::new(),
+                };
+                let description = node.describe(
+                    index,
+                    self.detailed,
+                    &node_sizes,
+                    materialization_status,
+                    indices,
+                    &column_labels,
+                    self.anonymization_salt()
+                        .map(|salt| anonymized_name(node, salt))
+                        .as_deref(),
+                );
                 indentln(f)?;
                 write!(f, "n{}", index.index())?;
-                write!(
-                    f,
-                    "{}",
-                    sanitize(&node.describe(
-                        index,
-                        self.detailed,
-                        &node_sizes,
-                        materialization_status
-                    ))
-                    .as_ref(),
-                )?;
+                write!(f, "{}", sanitize(&description).as_ref())?;
+                if let Some(kind) = self.materializations.get_kind(index) {
+                    indentln(f)?;
+                    writeln!(
+                        f,
+                        "n{} [tooltip=\"materialized for {}\"]",
+                        index.index(),
+                        kind
+                    )?;
+                }
+                if let Some(template) = self.node_url {
+                    indentln(f)?;
+                    writeln!(
+                        f,
+                        "n{} [URL=\"{}\"]",
+                        index.index(),
+                        node_url(template, index)
+                    )?;
+                }
+                let mut xlabel = vec![];
+                if let Some(health) = self.node_health.as_ref().and_then(|h| h.get(&index)) {
+                    indentln(f)?;
+                    writeln!(
+                        f,
+                        "n{} [color=\"{}\", penwidth=3]",
+                        index.index(),
+                        health_color(*health)
+                    )?;
+                    if *health != NodeHealth::Ready {
+                        xlabel.push(health.to_string());
+                    }
+                }
+                if let Some(original) = redundant_of.get(&index) {
+                    xlabel.push(format!(
+                        "rerouting artifact: full duplicate of partial node {} for a downstream \
+                         full materialization",
+                        original.index()
+                    ));
+                }
+                if self.show_description_hash {
+                    xlabel.push(format!("#{}", description_hash(&description)));
+                }
+                if !xlabel.is_empty() {
+                    indentln(f)?;
+                    writeln!(f, "n{} [xlabel=\"{}\"]", index.index(), xlabel.join("\\n"))?;
+                }
             }
             if domain.is_some() {
                 write!(f, "\n    }}\n")?;
@@ -131,25 +502,593 @@ impl Display for Graphviz<'_> {
             if !(nodes.contains(&edge.source()) && nodes.contains(&edge.target())) {
                 continue;
             }
+            if self.is_collapsed(edge.source()) {
+                // rendered as part of the edge into the start of the collapsed chain
+                continue;
+            }
+
+            // follow any chain of collapsed nodes down to the next rendered node
+            let mut target = edge.target();
+            let mut collapsed = 0;
+            while self.is_collapsed(target) {
+                collapsed += 1;
+                #[allow(clippy::unwrap_used)] // collapsed nodes have exactly one child
+                let next = self
+                    .graph
+                    .neighbors_directed(target, Direction::Outgoing)
+                    .next()
+                    .unwrap();
+                if !nodes.contains(&next) {
+                    break;
+                }
+                target = next;
+            }
+            if self.is_collapsed(target) {
+                // the chain leaves the set of nodes we're rendering
+                continue;
+            }
+
+            // edges between nodes in different domains are where replays (and writes) have to
+            // cross a domain boundary, so call them out explicitly
+            let crossed_domains = match (
+                domain_for_node.get(&edge.source()),
+                domain_for_node.get(&target),
+            ) {
+                (Some(from), Some(to)) if from != to => Some((from, to)),
+                _ => None,
+            };
+
+            let mut attributes = if collapsed > 0 {
+                format!("label=\"through: {collapsed} nodes\", style=dashed")
+            } else if let Some((from, to)) = crossed_domains {
+                format!(
+                    "label=\"domain {from} -> {to}\", color=\"#D9822B\", penwidth=2, \
+                         style=dashed"
+                )
+            } else if self.graph[edge.source()].is_egress() {
+                "color=\"#CCCCCC\"".to_owned()
+            } else if self.graph[edge.source()].is_source() {
+                "style=invis".to_owned()
+            } else {
+                "".to_owned()
+            };
+            // lookups into weak indices are forbidden during replays, which makes them easy to get
+            // wrong, so mark the parent end of the edge
+            if self.materializations.looks_up_weakly(target, edge.source()) {
+                if !attributes.is_empty() {
+                    attributes.push_str(", ");
+                }
+                attributes.push_str("dir=both, arrowtail=odot, taillabel=\"weak\"");
+            }
 
             indentln(f)?;
-            write!(
+            writeln!(
                 f,
-                "n{} -> n{} [ {} ]",
+                "n{} -> n{} [ {attributes} ]",
                 edge.source().index(),
-                edge.target().index(),
-                if self.graph[edge.source()].is_egress() {
-                    "color=\"#CCCCCC\""
-                } else if self.graph[edge.source()].is_source() {
-                    "style=invis"
-                } else {
-                    ""
-                }
+                target.index(),
+            )?;
+        }
+
+        // redundant duplicates of partial nodes.
+        for (duplicate, original) in &redundant_of {
+            if !(nodes.contains(duplicate) && nodes.contains(original)) {
+                continue;
+            }
+            indentln(f)?;
+            writeln!(
+                f,
+                "n{} -> n{} [ label=\"redundant of\", style=dashed, color=\"#999999\", \
+                 constraint=false ]",
+                duplicate.index(),
+                original.index()
             )?;
-            writeln!(f)?;
         }
 
         // footer.
         write!(f, "}}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use dataflow::prelude::{Index, LocalNodeIndex};
+    use dataflow::utils::make_columns;
+    use dataflow::{node, ops, Expr};
+    use readyset_data::{DfType, DfValue};
+
+    use super::*;
+
+    /// Construct a graph with a base table and two identical projections below it
+    fn two_projections() -> (Graph, NodeIndex, NodeIndex) {
+        let mut g = petgraph::Graph::new();
+        let src = g.add_node(node::Node::new(
+            "source",
+            make_columns(&[""]),
+            node::special::Source,
+        ));
+        let base = g.add_node(node::Node::new(
+            "base",
+            make_columns(&["a"]),
+            node::special::Base::default(),
+        ));
+        g.add_edge(src, base, ());
+        let mut project = || {
+            let ni = g.add_node(node::Node::new(
+                "project",
+                make_columns(&["a"]),
+                ops::NodeOperator::Project(ops::project::Project::new(
+                    base,
+                    vec![Expr::Column {
+                        index: 0,
+                        ty: DfType::Unknown,
+                    }],
+                )),
+            ));
+            g.add_edge(base, ni, ());
+            ni
+        };
+        let (original, duplicate) = (project(), project());
+        (g, original, duplicate)
+    }
+
+    #[test]
+    fn redundant_partial_edges() {
+        let (g, original, duplicate) = two_projections();
+        let mut materializations = Materializations::new();
+        materializations
+            .redundant_partial
+            .insert(original, duplicate);
+
+        let edge = format!(
+            "n{} -> n{} [ label=\"redundant of\"",
+            duplicate.index(),
+            original.index()
+        );
+        let graphviz = Graphviz {
+            detailed: false,
+            ..Graphviz::new(&g, &materializations)
+        };
+        assert!(!graphviz.to_string().contains(&edge));
+
+        let rendered = Graphviz {
+            show_redundant_partial: true,
+            ..graphviz
+        }
+        .to_string();
+        assert!(rendered.contains(&edge));
+        assert!(rendered.contains(&format!(
+            "n{} [xlabel=\"rerouting artifact: full duplicate of partial node {} ",
+            duplicate.index(),
+            original.index()
+        )));
+    }
+
+    #[cfg(feature = "graphviz_svg")]
+    #[test]
+    fn renders_svg() {
+        let (g, _, _) = two_projections();
+        let materializations = Materializations::new();
+        let dot = Graphviz::new(&g, &materializations).to_string();
+        let svg = String::from_utf8(dot_to_svg(&dot).unwrap()).unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(svg.contains("base"));
+    }
+
+    #[test]
+    fn description_hash_labels() {
+        let (g, original, duplicate) = two_projections();
+        let mut materializations = Materializations::new();
+        let render = |materializations: &Materializations| {
+            Graphviz {
+                show_description_hash: true,
+                ..Graphviz::new(&g, materializations)
+            }
+            .to_string()
+        };
+        let hash_label = |rendered: &str, ni: NodeIndex| {
+            let prefix = format!("n{} [xlabel=\"#", ni.index());
+            let start = rendered.find(&prefix).unwrap() + prefix.len();
+            rendered[start..start + 8].to_owned()
+        };
+
+        let before = render(&materializations);
+        assert!(!Graphviz::new(&g, &materializations)
+            .to_string()
+            .contains("xlabel"));
+        assert_eq!(before, render(&materializations));
+
+        materializations
+            .have
+            .insert(original, HashSet::from([Index::hash_map(vec![0])]));
+        let after = render(&materializations);
+        assert_ne!(hash_label(&before, original), hash_label(&after, original));
+        assert_eq!(
+            hash_label(&before, duplicate),
+            hash_label(&after, duplicate)
+        );
+    }
+
+    #[test]
+    fn anonymized_names() {
+        let (g, original, duplicate) = two_projections();
+        let materializations = Materializations::new();
+        let base = NodeIndex::new(1);
+        for detailed in [true, false] {
+            let graphviz = Graphviz {
+                detailed,
+                anonymize_names: true,
+                ..Graphviz::new(&g, &materializations)
+            };
+            let salt = graphviz.anonymization_salt().unwrap();
+            let rendered = graphviz.to_string();
+            assert!(!rendered.contains("base"), "{rendered}");
+            assert!(!rendered.contains("project"), "{rendered}");
+            assert!(anonymized_name(&g[base], salt).starts_with("table_"));
+            assert!(rendered.contains(&anonymized_name(&g[base], salt)));
+            // nodes with the same name get the same token within a render
+            assert!(anonymized_name(&g[original], salt).starts_with("view_"));
+            assert_eq!(
+                anonymized_name(&g[original], salt),
+                anonymized_name(&g[duplicate], salt)
+            );
+            assert!(rendered.contains(&anonymized_name(&g[original], salt)));
+            // the same render is reproducible, but a new one uses a new salt
+            assert_eq!(rendered, graphviz.to_string());
+            assert_ne!(
+                rendered,
+                Graphviz {
+                    detailed,
+                    anonymize_names: true,
+                    ..Graphviz::new(&g, &materializations)
+                }
+                .to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn anonymized_detail_hides_columns_and_expressions() {
+        let mut g = petgraph::Graph::new();
+        let src = g.add_node(node::Node::new(
+            "source",
+            make_columns(&[""]),
+            node::special::Source,
+        ));
+        let base = g.add_node(node::Node::new(
+            "base",
+            make_columns(&["secret_column"]),
+            node::special::Base::default(),
+        ));
+        g.add_edge(src, base, ());
+        let filter = g.add_node(node::Node::new(
+            "filter",
+            make_columns(&["secret_column"]),
+            ops::NodeOperator::Filter(ops::filter::Filter::new(
+                base,
+                Expr::Literal {
+                    val: DfValue::from("secret literal"),
+                    ty: DfType::DEFAULT_TEXT,
+                },
+            )),
+        ));
+        g.add_edge(base, filter, ());
+        let mut materializations = Materializations::new();
+        materializations
+            .have
+            .insert(filter, HashSet::from([Index::hash_map(vec![0])]));
+
+        let rendered = Graphviz::new(&g, &materializations).to_string();
+        assert!(rendered.contains("secret_column"), "{rendered}");
+        assert!(rendered.contains("secret literal"), "{rendered}");
+
+        let rendered = Graphviz {
+            anonymize_names: true,
+            resolve_index_columns: true,
+            ..Graphviz::new(&g, &materializations)
+        }
+        .to_string();
+        assert!(!rendered.contains("secret"), "{rendered}");
+        assert!(rendered.contains("[0] col0"), "{rendered}");
+        assert!(rendered.contains("| Filter"), "{rendered}");
+        assert!(rendered.contains(".col0]"), "{rendered}");
+    }
+
+    #[test]
+    fn deterministic_node_order() {
+        let (g, original, duplicate) = two_projections();
+        let materializations = Materializations::new();
+        let domain_nodes = HashMap::from([
+            (
+                DomainIndex::from(1),
+                NodeMap::from_iter([(LocalNodeIndex::make(0), duplicate)]),
+            ),
+            (
+                DomainIndex::from(0),
+                NodeMap::from_iter([(LocalNodeIndex::make(0), original)]),
+            ),
+        ]);
+        let graphviz = Graphviz {
+            detailed: false,
+            domain_nodes: Some(&domain_nodes),
+            ..Graphviz::new(&g, &materializations)
+        };
+
+        let rendered = graphviz.to_string();
+        assert_eq!(rendered, graphviz.to_string());
+
+        let position = |s: &str| rendered.find(s).unwrap();
+        let node = |ni: NodeIndex| position(&format!("n{}[", ni.index()));
+        // nodes outside of any domain come first, in order of their index, followed by each domain
+        // in order of its index
+        assert!(node(NodeIndex::new(0)) < node(NodeIndex::new(1)));
+        assert!(node(NodeIndex::new(1)) < position("cluster_d0"));
+        assert!(position("cluster_d0") < node(original));
+        assert!(node(original) < position("cluster_d1"));
+        assert!(position("cluster_d1") < node(duplicate));
+    }
+
+    #[test]
+    fn detailed_node_shape() {
+        let (g, _, _) = two_projections();
+        let materializations = Materializations::new();
+        let graphviz = Graphviz::new(&g, &materializations);
+        assert!(graphviz
+            .to_string()
+            .contains("node [shape=record, fontsize=10]"));
+
+        let rendered = Graphviz {
+            node_shape: GraphvizNodeShape::Mrecord,
+            ..graphviz
+        }
+        .to_string();
+        assert!(rendered.contains("node [shape=Mrecord, fontsize=10]"));
+    }
+
+    #[test]
+    fn cross_domain_edges() {
+        let (g, original, duplicate) = two_projections();
+        let base = NodeIndex::new(1);
+        let materializations = Materializations::new();
+        let domain_nodes = HashMap::from([
+            (
+                DomainIndex::from(0),
+                NodeMap::from_iter([
+                    (LocalNodeIndex::make(0), base),
+                    (LocalNodeIndex::make(1), duplicate),
+                ]),
+            ),
+            (
+                DomainIndex::from(1),
+                NodeMap::from_iter([(LocalNodeIndex::make(0), original)]),
+            ),
+        ]);
+        let rendered = Graphviz {
+            detailed: false,
+            domain_nodes: Some(&domain_nodes),
+            ..Graphviz::new(&g, &materializations)
+        }
+        .to_string();
+
+        assert!(rendered.contains(&format!(
+            "n{} -> n{} [ label=\"domain 0 -> 1\"",
+            base.index(),
+            original.index()
+        )));
+        assert!(rendered.contains(&format!("n{} -> n{} [  ]", base.index(), duplicate.index())));
+    }
+
+    #[test]
+    fn weak_lookup_edges() {
+        let (g, original, duplicate) = two_projections();
+        let base = NodeIndex::new(1);
+        let mut materializations = Materializations::new();
+        materializations
+            .weak_lookups
+            .insert(original, HashSet::from([base]));
+
+        let rendered = Graphviz {
+            detailed: false,
+            ..Graphviz::new(&g, &materializations)
+        }
+        .to_string();
+        assert!(rendered.contains(&format!(
+            "n{} -> n{} [ dir=both, arrowtail=odot, taillabel=\"weak\" ]",
+            base.index(),
+            original.index()
+        )));
+        assert!(rendered.contains(&format!("n{} -> n{} [  ]", base.index(), duplicate.index())));
+    }
+
+    #[test]
+    fn only_domain_renders_neighbors_as_stubs() {
+        let (g, original, duplicate) = two_projections();
+        let base = NodeIndex::new(1);
+        let materializations = Materializations::new();
+        let domain_nodes = HashMap::from([
+            (
+                DomainIndex::from(0),
+                NodeMap::from_iter([(LocalNodeIndex::make(0), base)]),
+            ),
+            (
+                DomainIndex::from(1),
+                NodeMap::from_iter([(LocalNodeIndex::make(0), original)]),
+            ),
+            (
+                DomainIndex::from(2),
+                NodeMap::from_iter([(LocalNodeIndex::make(0), duplicate)]),
+            ),
+        ]);
+        let rendered = Graphviz {
+            domain_nodes: Some(&domain_nodes),
+            only_domain: Some(DomainIndex::from(1)),
+            ..Graphviz::new(&g, &materializations)
+        }
+        .to_string();
+
+        assert!(rendered.contains("subgraph cluster_d1 {"));
+        assert!(!rendered.contains("cluster_d0"));
+        assert!(rendered.contains(&format!(
+            "n{} [shape=plaintext, fontcolor=\"#999999\", label=\"{} (domain 0)\"]",
+            base.index(),
+            base.index()
+        )));
+        assert!(rendered.contains(&format!("n{} -> n{}", base.index(), original.index())));
+        // nodes which aren't neighbors of the domain aren't rendered at all
+        assert!(!rendered.contains(&format!("n{} [", duplicate.index())));
+    }
+
+    #[test]
+    fn node_health_overlay() {
+        let (g, original, duplicate) = two_projections();
+        let base = NodeIndex::new(1);
+        let materializations = Materializations::new();
+        let rendered = Graphviz {
+            node_health: Some(HashMap::from([
+                (base, NodeHealth::Ready),
+                (original, NodeHealth::Replaying),
+            ])),
+            ..Graphviz::new(&g, &materializations)
+        }
+        .to_string();
+
+        assert!(rendered.contains(&format!(
+            "n{} [color=\"#2E7D32\", penwidth=3]",
+            base.index()
+        )));
+        assert!(rendered.contains(&format!(
+            "n{} [color=\"#F9A825\", penwidth=3]",
+            original.index()
+        )));
+        assert!(rendered.contains(&format!("n{} [xlabel=\"replaying\"]", original.index())));
+        // nodes with no health entry render neutrally
+        assert!(!rendered.contains(&format!("n{} [color=", duplicate.index())));
+        assert!(!rendered.contains(&format!("n{} [xlabel=", base.index())));
+    }
+
+    #[test]
+    fn name_filter_includes_neighbors() {
+        let (g, original, duplicate) = two_projections();
+        let materializations = Materializations::new();
+        let rendered = |graphviz: Graphviz| {
+            let rendered = graphviz.to_string();
+            g.node_indices()
+                .filter(|ni| rendered.contains(&format!("n{}[", ni.index())))
+                .collect::<Vec<_>>()
+        };
+        let (source, base) = (NodeIndex::new(0), NodeIndex::new(1));
+
+        assert_eq!(
+            rendered(Graphviz {
+                detailed: false,
+                name_filter: Some(Regex::new("^sour").unwrap()),
+                ..Graphviz::new(&g, &materializations)
+            }),
+            vec![source, base]
+        );
+
+        // combined with `reachable_from`, only nodes that are both reachable and match (or
+        // neighbor a match) are included
+        assert_eq!(
+            rendered(Graphviz {
+                detailed: false,
+                reachable_from: Some((original, Direction::Incoming)),
+                name_filter: Some(Regex::new("^project$").unwrap()),
+                ..Graphviz::new(&g, &materializations)
+            }),
+            vec![base, original]
+        );
+        assert_eq!(
+            rendered(Graphviz {
+                detailed: false,
+                name_filter: Some(Regex::new("^project$").unwrap()),
+                ..Graphviz::new(&g, &materializations)
+            }),
+            vec![base, original, duplicate]
+        );
+    }
+
+    #[test]
+    fn detailed_label_includes_index_types_and_columns() {
+        let (g, original, _) = two_projections();
+        let mut materializations = Materializations::new();
+        materializations.have.insert(
+            original,
+            HashSet::from([Index::btree_map(vec![0]), Index::hash_map(vec![0])]),
+        );
+
+        let rendered = Graphviz::new(&g, &materializations).to_string();
+        assert!(rendered.contains("| ● hash[a], btree[a]"), "{rendered}");
+    }
+
+    #[test]
+    fn resolve_index_columns_to_base_columns() {
+        let (mut g, original, _) = two_projections();
+        let base = g
+            .neighbors_directed(original, Direction::Incoming)
+            .next()
+            .unwrap();
+        // a column computed by the projection doesn't come from any base table column
+        let computed = g.add_node(node::Node::new(
+            "computed",
+            make_columns(&["x"]),
+            ops::NodeOperator::Project(ops::project::Project::new(
+                base,
+                vec![Expr::Literal {
+                    val: DfValue::Int(42),
+                    ty: DfType::Int,
+                }],
+            )),
+        ));
+        g.add_edge(base, computed, ());
+
+        let mut materializations = Materializations::new();
+        materializations
+            .have
+            .insert(original, HashSet::from([Index::hash_map(vec![0])]));
+        materializations
+            .have
+            .insert(computed, HashSet::from([Index::hash_map(vec![0])]));
+
+        let rendered = Graphviz::new(&g, &materializations).to_string();
+        assert!(rendered.contains("| ● hash[a]"), "{rendered}");
+        assert!(!rendered.contains("base.a"), "{rendered}");
+
+        let rendered = Graphviz {
+            resolve_index_columns: true,
+            ..Graphviz::new(&g, &materializations)
+        }
+        .to_string();
+        assert!(rendered.contains("| ● hash[base.a]"), "{rendered}");
+        assert!(rendered.contains("| ● hash[x]"), "{rendered}");
+
+        let graphviz = Graphviz {
+            resolve_index_columns: true,
+            anonymize_names: true,
+            ..Graphviz::new(&g, &materializations)
+        };
+        let table = anonymized_name(&g[base], graphviz.anonymization_salt().unwrap());
+        let rendered = graphviz.to_string();
+        assert!(
+            rendered.contains(&format!("hash[{table}.col0]")),
+            "{rendered}"
+        );
+        assert!(rendered.contains("hash[col0]"), "{rendered}");
+    }
+
+    #[test]
+    fn node_url_substitutes_index() {
+        assert_eq!(
+            node_url("/nodes/{index}?from={index}", NodeIndex::new(7)),
+            "/nodes/7?from=7"
+        );
+    }
+
+    #[test]
+    fn node_url_escapes_quotes_and_backslashes() {
+        assert_eq!(
+            node_url(r#"/n/{index}?q="a\b""#, NodeIndex::new(1)),
+            r#"/n/1?q=\"a\\b\""#
+        );
+    }
+}
@@ -2943,7 +2943,7 @@ async fn pkey_then_full_table_with_bogokey() {
 async fn materialization_frontier() {
     // set up graph
     let (mut g, shutdown_tx) = start_simple_unsharded("materialization_frontier").await;
-    let (article, vote) = g
+    let (article, vote, vc) = g
         .migrate(|mig| {
             // migrate
 
@@ -2979,10 +2979,12 @@ async fn materialization_frontier() {
 
             let ri = mig.maintain_anonymous(end, &Index::hash_map(vec![0]));
             mig.mark_shallow(ri);
-            (article, vote)
+            (article, vote, vc)
         })
         .await;
 
+    assert!(g.shallow_nodes().await.unwrap().contains(&vc));
+
     let mut a = g.table_by_index(article).await.unwrap();
     let mut v = g.table_by_index(vote).await.unwrap();
     let mut r = g.view("awvc").await.unwrap().into_reader_handle().unwrap();
@@ -8354,6 +8356,49 @@ async fn forbid_full_materialization() {
     shutdown_tx.shutdown().await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn forbid_straddled_joins() {
+    let (mut g, shutdown_tx) = {
+        let mut builder = Builder::for_tests();
+        builder.set_straddled_joins(false);
+        builder.set_sharding(None);
+        builder.set_persistence(get_persistence_params("forbid_straddled_joins"));
+        builder
+            .start_local_custom(Arc::new(Authority::from(LocalAuthority::new_with_store(
+                Arc::new(LocalAuthorityStore::new()),
+            ))))
+            .await
+            .unwrap()
+    };
+    g.extend_recipe(
+        ChangeList::from_str(
+            "CREATE TABLE a (a1 int, a2 int);
+             CREATE TABLE b (b1 int, b2 int);",
+            Dialect::DEFAULT_MYSQL,
+        )
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+    let res = g
+        .extend_recipe(
+            ChangeList::from_str(
+                "CREATE CACHE straddle FROM
+                 SELECT * FROM a INNER JOIN b ON a.a2 = b.b1 WHERE a.a1 = ? AND b.b2 = ?;",
+                Dialect::DEFAULT_MYSQL,
+            )
+            .unwrap(),
+        )
+        .await;
+    let err = res.err().unwrap();
+    assert!(err.caused_by_unsupported());
+    let msg = err.to_string();
+    assert!(msg.contains("straddled join"), "{msg}");
+    assert!(msg.contains("--feature-straddled-joins"), "{msg}");
+
+    shutdown_tx.shutdown().await;
+}
+
 // This test replicates the `extend_recipe` path used when we need to resnapshot. The
 // snapshotted DDL may vary slightly from the DDL propagated through the replicator but
 // should not cause the extend_recipe to fail.
@@ -8771,6 +8816,37 @@ async fn simple_dry_run() {
     shutdown_tx.shutdown().await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_does_not_record_plan() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("plan");
+    let authority = Arc::new(Authority::from(LocalAuthority::new_with_store(Arc::new(
+        LocalAuthorityStore::new(),
+    ))));
+    let mut builder = Builder::for_tests();
+    builder.set_persistence(get_persistence_params("dry_run_does_not_record_plan"));
+    builder.set_sharding(None);
+    builder.set_record_plan_path(path.clone());
+    let (mut g, shutdown_tx) = builder.start_local_custom(authority).await.unwrap();
+
+    let query = "
+        CREATE TABLE table_1 (column_1 INT);
+        CREATE CACHE t1 FROM SELECT * FROM table_1;
+    ";
+    g.dry_run(ChangeList::from_str(query, Dialect::DEFAULT_MYSQL).unwrap())
+        .await
+        .unwrap();
+    assert!(!path.exists());
+
+    g.extend_recipe(ChangeList::from_str(query, Dialect::DEFAULT_MYSQL).unwrap())
+        .await
+        .unwrap();
+    let recorded = crate::read_recorded_messages(std::fs::File::open(&path).unwrap()).unwrap();
+    assert!(!recorded.is_empty());
+
+    shutdown_tx.shutdown().await;
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn simple_dry_run_unsupported() {
     let (mut g, shutdown_tx) = start_simple_unsharded("simple_dry_run").await;
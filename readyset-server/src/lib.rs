@@ -401,7 +401,9 @@ pub enum ReuseConfigType {
 }
 
 use controller::migrate::materialization;
-pub use controller::migrate::materialization::FrontierStrategy;
+#[cfg(feature = "bench")]
+pub use controller::migrate::materialization::bench;
+pub use controller::migrate::materialization::{read_recorded_messages, FrontierStrategy};
 pub use controller::replication::{ReplicationOptions, ReplicationStrategy};
 use controller::sql;
 use database_utils::UpstreamConfig;
@@ -493,6 +495,7 @@ impl Default for Config {
                 eviction_kind: dataflow::EvictionKind::default(),
                 verbose_metrics: false,
                 materialization_persistence: false,
+                lookup_sample_rate: 1,
             },
             persistence: Default::default(),
             min_workers: 1,
@@ -593,6 +596,66 @@ pub struct WorkerOptions {
     #[arg(long, env = "MATERIALIZATION_FRONTIER", default_value_t = FrontierStrategy::None, hide = true)]
     pub materialization_frontier: FrontierStrategy,
 
+    /// Fail migrations which place a node beyond the materialization frontier if neither that node
+    /// nor any of its parents have state which could be placed beyond the frontier, rather than
+    /// ignoring that node
+    #[arg(long, env = "STRICT_MATERIALIZATION_FRONTIER", hide = true)]
+    pub strict_materialization_frontier: bool,
+
+    /// Comma-separated list of name prefixes for nodes which should always be fully materialized,
+    /// even if they could be partially materialized. Useful for views where the cost of replays
+    /// outweighs the memory saved by partial materialization. Ignored unless full materialization
+    /// is enabled.
+    #[arg(
+        long,
+        env = "FORCE_FULL_MATERIALIZATION",
+        value_delimiter = ',',
+        hide = true
+    )]
+    pub force_full_materialization: Vec<String>,
+
+    /// Only count one in every N lookups into materializations when tracking how often each
+    /// materialization is accessed, scaling the counts accordingly. Higher values reduce the
+    /// overhead of access tracking on very hot readers, at the cost of accuracy.
+    #[arg(
+        long,
+        default_value = "1",
+        env = "MATERIALIZATION_ACCESS_SAMPLE_RATE",
+        hide = true
+    )]
+    pub materialization_access_sample_rate: u64,
+
+    /// Comma-separated list of name prefixes for views whose in-memory state should evict runs of
+    /// adjacent keys from a BTree index, rather than individual keys chosen at random. This suits
+    /// views which are mostly looked up by range. Views whose names start with `RANGE_EVICT_`
+    /// always evict this way.
+    #[arg(long, env = "RANGE_EVICTION", value_delimiter = ',', hide = true)]
+    pub range_eviction: Vec<String>,
+
+    /// Maximum number of new materializations to populate via full replay at once during a
+    /// migration. Replays are only run at the same time for materializations which don't depend
+    /// on each other.
+    #[arg(long, default_value = "1", env = "REPLAY_CONCURRENCY", hide = true)]
+    pub replay_concurrency: usize,
+
+    /// Log a warning, rather than failing the migration, when a shard merger is added below a
+    /// subgraph that's sharded by a column which another column aliases. The check behind that
+    /// failure is conservative, so only set this once the rejected queries have been confirmed to
+    /// be false positives.
+    #[arg(long, env = "ALLOW_ALIASED_SHARDING", hide = true)]
+    pub allow_aliased_sharding: bool,
+
+    /// Append every domain request sent to set up new materializations to the given file, in
+    /// order, so that a migration can be reproduced without the original schema.
+    #[arg(long, env = "RECORD_MIGRATION_PLAN", hide = true)]
+    pub record_migration_plan: Option<PathBuf>,
+
+    /// Log a warning when a migration adds a partially materialized view which replays would have
+    /// to reach through more than this many partial materializations to fill. Such views are slow
+    /// to fill on a miss, and may benefit from fully materializing a view in between.
+    #[arg(long, env = "MAX_PARTIAL_CHAIN_DEPTH", hide = true)]
+    pub max_partial_chain_depth: Option<usize>,
+
     /// Enable packet filters in egresses before readers
     #[arg(long, hide = true)]
     pub enable_packet_filters: bool,
@@ -3,7 +3,7 @@
 use std::str::FromStr;
 
 use anyhow::anyhow;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use readyset_client::consensus::AuthorityType;
 use readyset_client::ReadySetHandle;
 
@@ -20,15 +20,43 @@ struct ControllerRequest {
     deployment: String,
 
     /// The name of the endpoint to issue a controller request to.
-    /// This currently only supports endpoints without parameters.
+    /// This currently only supports endpoints without parameters, and `/materialization_report`.
+    /// Required unless a command is given.
     #[arg(short, long)]
-    endpoint: Request,
+    endpoint: Option<Request>,
+
+    /// The number of largest materializations to list in the output of
+    /// `/materialization_report`.
+    #[arg(long, default_value_t = 10)]
+    top_n: usize,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Admin commands, which can be given instead of an `--endpoint`
+#[derive(Subcommand)]
+enum Command {
+    /// Check the running graph for invariant violations, without changing it
+    Check {
+        #[command(subcommand)]
+        check: Check,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Subcommand)]
+enum Check {
+    /// Check that the materializations in the graph satisfy all the invariants which are checked
+    /// when migrating. Equivalent to `--endpoint /check_materializations`
+    Materializations,
 }
 
 #[derive(Clone, Copy, Debug)]
 enum Request {
     HealthyWorkers,
     ControllerUri,
+    CheckMaterializations,
+    MaterializationReport,
 }
 
 impl FromStr for Request {
@@ -37,13 +65,19 @@ impl FromStr for Request {
         match s {
             "/healthy_workers" => Ok(Request::HealthyWorkers),
             "/controller_uri" => Ok(Request::ControllerUri),
+            "/check_materializations" => Ok(Request::CheckMaterializations),
+            "/materialization_report" => Ok(Request::MaterializationReport),
             _ => Err(anyhow!("Unsupported request")),
         }
     }
 }
 
 impl Request {
-    async fn issue_and_print(&self, mut handle: ReadySetHandle) -> anyhow::Result<()> {
+    async fn issue_and_print(
+        &self,
+        mut handle: ReadySetHandle,
+        top_n: usize,
+    ) -> anyhow::Result<()> {
         match self {
             Request::HealthyWorkers => {
                 let res = handle.healthy_workers().await?;
@@ -53,6 +87,19 @@ impl Request {
                 let res = handle.controller_uri().await?;
                 println!("{:?}", res);
             }
+            Request::CheckMaterializations => {
+                let violations = handle.check_materializations().await?;
+                if violations.is_empty() {
+                    println!("No materialization invariant violations found");
+                }
+                for violation in violations {
+                    println!("{}", violation);
+                }
+            }
+            Request::MaterializationReport => {
+                let report = handle.materialization_report(top_n).await?;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
         }
 
         Ok(())
@@ -65,10 +112,19 @@ impl ControllerRequest {
             .authority
             .to_authority(&self.authority_address, &self.deployment);
 
+        let request = match self.command {
+            Some(Command::Check {
+                check: Check::Materializations,
+            }) => Request::CheckMaterializations,
+            None => self
+                .endpoint
+                .ok_or_else(|| anyhow!("Either an endpoint or a command is required"))?,
+        };
+
         let mut handle: ReadySetHandle = ReadySetHandle::new(authority).await;
         handle.ready().await.unwrap();
 
-        self.endpoint.issue_and_print(handle).await?;
+        request.issue_and_print(handle, self.top_n).await?;
 
         Ok(())
     }
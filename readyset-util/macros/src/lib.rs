@@ -0,0 +1,100 @@
+//! Procedural macros for `readyset-util`. These are re-exported from `readyset-util` itself, and
+//! shouldn't be depended on directly.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index};
+
+/// Derive [`Debug`] for a struct, wrapping every field annotated with `#[sensitive]` in
+/// `readyset_util::redacted::Sensitive` so that it's redacted if the `redact_sensitive` feature of
+/// `readyset-util` is enabled. Fields without the annotation are formatted as they would be by
+/// `#[derive(Debug)]`.
+///
+/// Example:
+///
+/// ```ignore
+/// use readyset_util::redacted::SensitiveDebug;
+///
+/// #[derive(SensitiveDebug)]
+/// struct Connection {
+///     host: String,
+///     #[sensitive]
+///     password: String,
+/// }
+/// ```
+///
+/// is formatted as `Connection { host: "localhost", password: <redacted> }` with
+/// `redact_sensitive` enabled.
+#[proc_macro_derive(SensitiveDebug, attributes(sensitive))]
+pub fn derive_sensitive_debug(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match sensitive_debug(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn sensitive_debug(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "SensitiveDebug can only be derived for structs",
+        ));
+    };
+
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let field_value = |field: &syn::Field, accessor: TokenStream| {
+        if field
+            .attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("sensitive"))
+        {
+            quote!(&::readyset_util::redacted::Sensitive(&self.#accessor))
+        } else {
+            quote!(&self.#accessor)
+        }
+    };
+    let body = match &data.fields {
+        Fields::Named(fields) => {
+            let fields = fields.named.iter().map(|field| {
+                #[allow(clippy::unwrap_used)] // named fields always have an ident
+                let ident = field.ident.as_ref().unwrap();
+                let value = field_value(field, ident.to_token_stream());
+                let ident_str = ident.to_string();
+                quote!(.field(#ident_str, #value))
+            });
+            quote!(f.debug_struct(#name_str) #(#fields)* .finish())
+        }
+        Fields::Unnamed(fields) => {
+            let fields = fields.unnamed.iter().enumerate().map(|(i, field)| {
+                let value = field_value(field, Index::from(i).to_token_stream());
+                quote!(.field(#value))
+            });
+            quote!(f.debug_tuple(#name_str) #(#fields)* .finish())
+        }
+        Fields::Unit => quote!(f.write_str(#name_str)),
+    };
+
+    // Like `#[derive(Debug)]`, require every type parameter to implement `Debug`
+    let type_params = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::std::fmt::Debug));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                #body
+            }
+        }
+    })
+}
@@ -1,6 +1,10 @@
 //! This crate provides miscellaneous utilities and extensions to the Rust standard library, for use
 //! in all crates in this workspace.
 #![deny(missing_docs, rustdoc::missing_crate_level_docs)]
+// Allow macros in this crate to refer to it as `readyset_util`, so that code generated by them works
+// both here and in other crates
+extern crate self as readyset_util;
+
 use std::borrow::Borrow;
 use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
@@ -2,10 +2,12 @@
 //! Display. These wrappers are intended to be used to hide user PII in logs or errors.
 
 use std::convert::Infallible;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
+use std::net::SocketAddr;
 use std::ops::Deref;
 use std::str::FromStr;
 
+pub use readyset_util_macros::SensitiveDebug;
 use serde::{Deserialize, Serialize};
 
 /// Wraps a type that implements Display and Debug, overriding both implementations if the
@@ -40,6 +42,72 @@ where
     }
 }
 
+impl<'a, T> Sensitive<'a, T>
+where
+    T: ?Sized + PartiallySensitive,
+{
+    /// Display the wrapped value with only its sensitive parts masked if the `redact_sensitive`
+    /// feature is enabled, rather than masking the entire value.
+    ///
+    /// This is useful for values such as connection endpoints, where the host and port are
+    /// required to diagnose connectivity issues but any credentials must not be logged.
+    pub fn partial(&self) -> PartiallyRedacted<'a, T> {
+        PartiallyRedacted(self.0)
+    }
+}
+
+/// Types which contain both sensitive information (such as credentials) and information which is
+/// safe to log (such as the host and port of a network endpoint), and so can be partially
+/// redacted. See [`Sensitive::partial`].
+pub trait PartiallySensitive {
+    /// Write the full representation of `self`, including any sensitive information
+    fn fmt_unredacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Write a representation of `self` with all sensitive information masked
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// A socket address identifies an endpoint but carries no credentials, so the whole address is
+/// preserved even when partially redacted
+impl PartiallySensitive for SocketAddr {
+    fn fmt_unredacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+/// Wraps a type that implements [`PartiallySensitive`], masking the sensitive parts of it when
+/// printed with Display or Debug if the `redact_sensitive` feature is enabled.
+///
+/// Constructed with [`Sensitive::partial`].
+pub struct PartiallyRedacted<'a, T: ?Sized>(&'a T);
+
+impl<T> Display for PartiallyRedacted<'_, T>
+where
+    T: ?Sized + PartiallySensitive,
+{
+    #[cfg(not(feature = "redact_sensitive"))]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt_unredacted(f)
+    }
+    #[cfg(feature = "redact_sensitive")]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt_redacted(f)
+    }
+}
+
+impl<T> Debug for PartiallyRedacted<'_, T>
+where
+    T: ?Sized + PartiallySensitive,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
 /// Wraps a given string, replacing its contents with "<redacted>" when debug
 /// printed if the `redact_sensitive` feature is enabled.
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -93,3 +161,73 @@ impl From<RedactedString> for String {
         s.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Credentials;
+
+    impl PartiallySensitive for Credentials {
+        fn fmt_unredacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "user:password@host")
+        }
+
+        fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "user:<redacted>@host")
+        }
+    }
+
+    #[test]
+    fn partially_redacted() {
+        let res = Sensitive(&Credentials).partial().to_string();
+        if cfg!(feature = "redact_sensitive") {
+            assert_eq!(res, "user:<redacted>@host");
+        } else {
+            assert_eq!(res, "user:password@host");
+        }
+    }
+
+    #[derive(SensitiveDebug)]
+    struct Login<T> {
+        user: &'static str,
+        #[sensitive]
+        password: &'static str,
+        attempts: T,
+    }
+
+    #[derive(SensitiveDebug)]
+    struct Secret(u32, #[sensitive] &'static str);
+
+    #[test]
+    fn sensitive_debug_redacts_annotated_fields() {
+        let login = format!(
+            "{:?}",
+            Login {
+                user: "alice",
+                password: "hunter2",
+                attempts: 3,
+            }
+        );
+        let secret = format!("{:?}", Secret(1, "hunter2"));
+        if cfg!(feature = "redact_sensitive") {
+            assert_eq!(
+                login,
+                r#"Login { user: "alice", password: <redacted>, attempts: 3 }"#
+            );
+            assert_eq!(secret, "Secret(1, <redacted>)");
+        } else {
+            assert_eq!(
+                login,
+                r#"Login { user: "alice", password: "hunter2", attempts: 3 }"#
+            );
+            assert_eq!(secret, r#"Secret(1, "hunter2")"#);
+        }
+    }
+
+    #[test]
+    fn partially_redacted_socket_addr() {
+        let addr: SocketAddr = "127.0.0.1:3306".parse().unwrap();
+        assert_eq!(Sensitive(&addr).partial().to_string(), "127.0.0.1:3306");
+    }
+}